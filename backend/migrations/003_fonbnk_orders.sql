@@ -0,0 +1,18 @@
+-- On-ramp orders created through the Fonbnk hosted widget
+CREATE TABLE IF NOT EXISTS fonbnk_orders (
+    id TEXT PRIMARY KEY, -- NovaPay order reference passed to Fonbnk as orderParams
+    user_id TEXT NOT NULL,
+    wallet_address TEXT NOT NULL,
+    amount REAL NOT NULL,
+    currency TEXT NOT NULL,
+    network TEXT NOT NULL,
+    asset TEXT NOT NULL,
+    widget_url TEXT NOT NULL,
+    fonbnk_order_id TEXT,
+    status TEXT NOT NULL DEFAULT 'created',
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_fonbnk_orders_user_id ON fonbnk_orders (user_id);
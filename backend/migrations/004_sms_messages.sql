@@ -0,0 +1,16 @@
+-- Outbound SMS with provider receipts and delivery status
+CREATE TABLE IF NOT EXISTS sms_messages (
+    id TEXT PRIMARY KEY,
+    provider TEXT NOT NULL,
+    provider_message_id TEXT,
+    phone_number TEXT NOT NULL,
+    message TEXT NOT NULL,
+    cost TEXT,
+    status TEXT NOT NULL DEFAULT 'sent', -- sent, delivered, failed
+    failure_reason TEXT,
+    created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    delivered_at DATETIME
+);
+
+CREATE INDEX IF NOT EXISTS idx_sms_messages_provider_message_id ON sms_messages (provider, provider_message_id);
@@ -0,0 +1,2 @@
+-- Notification language preference (en, sw, lg)
+ALTER TABLE users ADD COLUMN preferred_language TEXT NOT NULL DEFAULT 'en';
@@ -0,0 +1,29 @@
+-- Per-user notification channels and quiet hours
+CREATE TABLE IF NOT EXISTS notification_preferences (
+    user_id TEXT PRIMARY KEY,
+    sms_enabled BOOLEAN NOT NULL DEFAULT 1,
+    email_enabled BOOLEAN NOT NULL DEFAULT 1,
+    push_enabled BOOLEAN NOT NULL DEFAULT 0,
+    webhook_enabled BOOLEAN NOT NULL DEFAULT 0,
+    push_token TEXT,
+    webhook_url TEXT,
+    webhook_secret TEXT,
+    quiet_hours_start TEXT, -- HH:MM in the user's local time
+    quiet_hours_end TEXT,
+    utc_offset_minutes INTEGER NOT NULL DEFAULT 180, -- East Africa Time
+    updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+-- Devices a user has signed in from, used to detect new-device logins
+CREATE TABLE IF NOT EXISTS user_devices (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    fingerprint TEXT NOT NULL,
+    user_agent TEXT,
+    last_ip TEXT,
+    first_seen_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    last_seen_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (user_id) REFERENCES users (id),
+    UNIQUE (user_id, fingerprint)
+);
@@ -0,0 +1,19 @@
+-- Refresh tokens. Every rotation adds a row to the same family; a family is
+-- one signed-in device and is what the access token's `sid` refers to.
+CREATE TABLE IF NOT EXISTS sessions (
+    id TEXT PRIMARY KEY,
+    family_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    refresh_token_hash TEXT NOT NULL UNIQUE, -- SHA-256 hex
+    user_agent TEXT,
+    ip_address TEXT,
+    created_at DATETIME NOT NULL,
+    expires_at DATETIME NOT NULL,
+    rotated_at DATETIME, -- set once the token has been exchanged
+    revoked_at DATETIME,
+    revoked_reason TEXT, -- logout, user_revoked, token_reuse
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_sessions_family ON sessions (family_id);
+CREATE INDEX IF NOT EXISTS idx_sessions_user ON sessions (user_id);
@@ -0,0 +1,12 @@
+-- Ed25519 keys used to sign access tokens. Exactly one key is 'active' and
+-- signs new tokens; 'verify' keys were rotated out and are still accepted
+-- (and published in the JWKS) until retire_at.
+CREATE TABLE IF NOT EXISTS jwt_keys (
+    kid TEXT PRIMARY KEY,
+    algorithm TEXT NOT NULL DEFAULT 'EdDSA',
+    private_key TEXT NOT NULL, -- hex Ed25519 seed
+    public_key TEXT NOT NULL, -- base64url, as published in the JWKS "x"
+    status TEXT NOT NULL DEFAULT 'active', -- active, verify
+    created_at DATETIME NOT NULL,
+    retire_at DATETIME
+);
@@ -0,0 +1,18 @@
+-- Phone verification and one-time passcodes
+ALTER TABLE users ADD COLUMN phone_verified_at DATETIME;
+
+CREATE TABLE IF NOT EXISTS otp_codes (
+    id TEXT PRIMARY KEY,
+    phone_number TEXT NOT NULL, -- E.164
+    purpose TEXT NOT NULL, -- verify_phone, login
+    user_id TEXT,
+    code_hash TEXT NOT NULL, -- HMAC-SHA256 of phone, purpose and code
+    attempts INTEGER NOT NULL DEFAULT 0,
+    max_attempts INTEGER NOT NULL DEFAULT 5,
+    expires_at DATETIME NOT NULL,
+    consumed_at DATETIME,
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_otp_codes_phone_purpose ON otp_codes (phone_number, purpose, created_at);
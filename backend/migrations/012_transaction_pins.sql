@@ -0,0 +1,10 @@
+-- Payment confirmation PIN, separate from the login password
+CREATE TABLE IF NOT EXISTS transaction_pins (
+    user_id TEXT PRIMARY KEY,
+    pin_hash TEXT NOT NULL, -- argon2
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    locked_until DATETIME,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
@@ -0,0 +1,9 @@
+-- Failed password logins per account, for progressive lockout
+CREATE TABLE IF NOT EXISTS login_lockouts (
+    user_id TEXT PRIMARY KEY,
+    failed_attempts INTEGER NOT NULL DEFAULT 0,
+    lockouts INTEGER NOT NULL DEFAULT 0, -- how many times the account has locked since the last success
+    locked_until DATETIME,
+    last_failed_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
@@ -0,0 +1,22 @@
+-- Staff roles, account freezes and the audit trail of back-office actions
+ALTER TABLE users ADD COLUMN role TEXT NOT NULL DEFAULT 'customer'; -- customer, support, finance, admin
+ALTER TABLE users ADD COLUMN frozen_at DATETIME;
+ALTER TABLE users ADD COLUMN frozen_reason TEXT;
+
+ALTER TABLE transactions ADD COLUMN reversed_by TEXT; -- id of the compensating transaction
+
+CREATE TABLE IF NOT EXISTS audit_events (
+    id TEXT PRIMARY KEY,
+    actor_id TEXT NOT NULL,
+    actor_role TEXT NOT NULL,
+    action TEXT NOT NULL, -- e.g. user.freeze, transaction.reverse
+    target_type TEXT NOT NULL,
+    target_id TEXT,
+    details TEXT NOT NULL DEFAULT '{}', -- JSON
+    ip_address TEXT,
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (actor_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_events_target ON audit_events (target_type, target_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_audit_events_actor ON audit_events (actor_id, created_at);
@@ -0,0 +1,27 @@
+-- Business accounts and the API keys they use for server-to-server calls
+CREATE TABLE IF NOT EXISTS merchants (
+    id TEXT PRIMARY KEY,
+    owner_user_id TEXT NOT NULL UNIQUE, -- wallet that payouts are paid from
+    business_name TEXT NOT NULL,
+    website TEXT,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    FOREIGN KEY (owner_user_id) REFERENCES users (id)
+);
+
+CREATE TABLE IF NOT EXISTS api_keys (
+    id TEXT PRIMARY KEY, -- npk_..., sent in X-NovaPay-Key; the secret is derived from it
+    merchant_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    scopes TEXT NOT NULL, -- comma-separated, e.g. balance:read,payouts:create
+    ip_allowlist TEXT, -- comma-separated IPs or CIDR ranges; NULL allows any
+    expires_at DATETIME, -- set when the key is rotated out
+    revoked_at DATETIME,
+    rotated_to TEXT, -- id of the replacement key
+    last_used_at DATETIME,
+    last_used_ip TEXT,
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (merchant_id) REFERENCES merchants (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_keys_merchant ON api_keys (merchant_id, created_at);
@@ -0,0 +1,37 @@
+-- Hash-chain the audit trail and record request context and state changes
+ALTER TABLE audit_events ADD COLUMN user_agent TEXT;
+ALTER TABLE audit_events ADD COLUMN before_state TEXT; -- JSON, for changes
+ALTER TABLE audit_events ADD COLUMN after_state TEXT; -- JSON, for changes
+ALTER TABLE audit_events ADD COLUMN seq INTEGER; -- position in the chain, assigned when sealed
+ALTER TABLE audit_events ADD COLUMN prev_hash TEXT;
+ALTER TABLE audit_events ADD COLUMN hash TEXT; -- SHA-256 over prev_hash and the row
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_audit_events_seq ON audit_events (seq);
+CREATE INDEX IF NOT EXISTS idx_audit_events_unsealed ON audit_events (created_at) WHERE hash IS NULL;
+
+-- Append-only: rows can't be deleted, and once sealed can't be changed. Before
+-- sealing only the chain columns may be filled in.
+CREATE TRIGGER IF NOT EXISTS audit_events_no_delete
+BEFORE DELETE ON audit_events
+BEGIN
+    SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
+
+CREATE TRIGGER IF NOT EXISTS audit_events_no_update
+BEFORE UPDATE ON audit_events
+WHEN OLD.hash IS NOT NULL
+    OR NEW.id IS NOT OLD.id
+    OR NEW.actor_id IS NOT OLD.actor_id
+    OR NEW.actor_role IS NOT OLD.actor_role
+    OR NEW.action IS NOT OLD.action
+    OR NEW.target_type IS NOT OLD.target_type
+    OR NEW.target_id IS NOT OLD.target_id
+    OR NEW.details IS NOT OLD.details
+    OR NEW.before_state IS NOT OLD.before_state
+    OR NEW.after_state IS NOT OLD.after_state
+    OR NEW.ip_address IS NOT OLD.ip_address
+    OR NEW.user_agent IS NOT OLD.user_agent
+    OR NEW.created_at IS NOT OLD.created_at
+BEGIN
+    SELECT RAISE(ABORT, 'audit_events is append-only');
+END;
@@ -0,0 +1,14 @@
+-- Stellar accounts created through /sdk/wallet, held by the server for their owner
+CREATE TABLE IF NOT EXISTS custodial_wallets (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    public_key TEXT NOT NULL UNIQUE,
+    encrypted_secret TEXT NOT NULL, -- base64(nonce || AES-256-GCM ciphertext), bound to id
+    label TEXT,
+    exportable BOOLEAN NOT NULL DEFAULT FALSE, -- owner opted in to taking the key elsewhere
+    exported_at DATETIME,
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_custodial_wallets_user ON custodial_wallets (user_id, created_at);
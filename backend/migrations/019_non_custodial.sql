@@ -0,0 +1,31 @@
+-- Stellar accounts whose keys stay with the user; the server builds
+-- transactions for them and the user signs on their device
+CREATE TABLE IF NOT EXISTS external_accounts (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    public_key TEXT NOT NULL,
+    label TEXT,
+    created_at DATETIME NOT NULL,
+    UNIQUE (user_id, public_key),
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE TABLE IF NOT EXISTS stellar_transactions (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    source_public_key TEXT NOT NULL,
+    kind TEXT NOT NULL, -- payment | trustline | path_payment
+    details TEXT NOT NULL, -- JSON of the request that built it
+    unsigned_xdr TEXT NOT NULL, -- base64 TransactionEnvelope with no signatures
+    tx_hash TEXT NOT NULL, -- hex hash the client signs
+    network_passphrase TEXT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending', -- pending | submitted | failed
+    error TEXT, -- Horizon result codes when the network rejected it
+    transaction_id TEXT, -- row in transactions once a payment is submitted
+    expires_at DATETIME NOT NULL, -- the envelope's max time bound
+    created_at DATETIME NOT NULL,
+    submitted_at DATETIME,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_stellar_transactions_user ON stellar_transactions (user_id, created_at);
@@ -0,0 +1,10 @@
+-- SEP-10 challenges handed out, so each one can be redeemed only once
+CREATE TABLE IF NOT EXISTS sep10_challenges (
+    nonce TEXT PRIMARY KEY, -- the base64 ManageData value of the challenge
+    account TEXT NOT NULL,
+    expires_at DATETIME NOT NULL,
+    consumed_at DATETIME,
+    created_at DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_sep10_challenges_expires ON sep10_challenges (expires_at);
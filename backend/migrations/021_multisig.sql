@@ -0,0 +1,35 @@
+-- Transactions on multisig accounts that collect co-signatures through the
+-- API and are submitted once the signers' weight reaches the threshold
+CREATE TABLE IF NOT EXISTS multisig_approvals (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL, -- who proposed it
+    source_public_key TEXT NOT NULL,
+    kind TEXT NOT NULL, -- payment | trustline | path_payment | set_options
+    details TEXT NOT NULL, -- JSON of the request that built it
+    unsigned_xdr TEXT NOT NULL,
+    tx_hash TEXT NOT NULL, -- hex hash every signer signs
+    network_passphrase TEXT NOT NULL,
+    threshold INTEGER NOT NULL, -- signer weight needed to submit
+    status TEXT NOT NULL DEFAULT 'pending', -- pending | submitting | submitted | failed | expired
+    error TEXT,
+    transaction_id TEXT,
+    expires_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL,
+    submitted_at DATETIME,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_multisig_approvals_source ON multisig_approvals (source_public_key, status);
+
+-- The account's signers when the transaction was proposed, and the
+-- signatures collected from them so far
+CREATE TABLE IF NOT EXISTS multisig_approval_signers (
+    approval_id TEXT NOT NULL,
+    public_key TEXT NOT NULL,
+    weight INTEGER NOT NULL,
+    signature TEXT, -- base64 ed25519 signature over tx_hash
+    signed_by TEXT, -- the user who submitted it
+    signed_at DATETIME,
+    PRIMARY KEY (approval_id, public_key),
+    FOREIGN KEY (approval_id) REFERENCES multisig_approvals (id)
+);
@@ -0,0 +1,38 @@
+-- Bulk payouts from a custodial wallet, e.g. payroll. Every row is checked
+-- when the batch is uploaded; payments then go out in transactions of up to
+-- 100 operations and each row keeps its own outcome
+CREATE TABLE IF NOT EXISTS payout_batches (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    wallet_id TEXT NOT NULL,
+    source_public_key TEXT NOT NULL,
+    asset TEXT NOT NULL, -- XLM or CODE:ISSUER
+    memo TEXT,
+    reference TEXT,
+    status TEXT NOT NULL DEFAULT 'queued', -- queued | processing | completed | partially_failed | failed
+    row_count INTEGER NOT NULL,
+    total_amount REAL NOT NULL,
+    created_at DATETIME NOT NULL,
+    completed_at DATETIME,
+    FOREIGN KEY (user_id) REFERENCES users (id),
+    FOREIGN KEY (wallet_id) REFERENCES custodial_wallets (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_payout_batches_user ON payout_batches (user_id, created_at);
+
+CREATE TABLE IF NOT EXISTS payout_batch_rows (
+    batch_id TEXT NOT NULL,
+    line INTEGER NOT NULL, -- CSV line, or position in the JSON list from 1
+    recipient TEXT NOT NULL, -- as uploaded: G... address, email or phone number
+    destination TEXT NOT NULL, -- the Stellar account it resolved to
+    amount TEXT NOT NULL,
+    reference TEXT,
+    status TEXT NOT NULL DEFAULT 'pending', -- pending | paid | failed
+    error TEXT,
+    tx_hash TEXT,
+    transaction_id TEXT,
+    attempts INTEGER NOT NULL DEFAULT 0,
+    updated_at DATETIME,
+    PRIMARY KEY (batch_id, line),
+    FOREIGN KEY (batch_id) REFERENCES payout_batches (id)
+);
@@ -0,0 +1,44 @@
+-- Transfers that repeat on a rule, e.g. the same amount home every month.
+-- The scheduler turns each due occurrence into a run, and each run into a
+-- normal transaction
+CREATE TABLE IF NOT EXISTS scheduled_transfers (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    recipient_email TEXT NOT NULL,
+    amount REAL NOT NULL,
+    currency TEXT NOT NULL,
+    target_currency TEXT NOT NULL,
+    frequency TEXT NOT NULL, -- monthly | cron
+    day_of_month INTEGER, -- monthly: 1-31, the last day in shorter months
+    time_of_day TEXT NOT NULL, -- monthly: HH:MM
+    cron TEXT, -- cron: minute hour day-of-month month day-of-week
+    timezone TEXT NOT NULL, -- IANA name, e.g. Europe/London
+    start_date DATE NOT NULL,
+    end_date DATE,
+    status TEXT NOT NULL DEFAULT 'active', -- active | paused | completed | cancelled
+    next_run_at DATETIME,
+    last_run_at DATETIME,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_transfers_user ON scheduled_transfers (user_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_scheduled_transfers_due ON scheduled_transfers (status, next_run_at);
+
+CREATE TABLE IF NOT EXISTS scheduled_transfer_runs (
+    id TEXT PRIMARY KEY,
+    schedule_id TEXT NOT NULL,
+    due_at DATETIME NOT NULL,
+    status TEXT NOT NULL DEFAULT 'pending', -- pending | retrying | completed | failed | skipped
+    attempts INTEGER NOT NULL DEFAULT 0,
+    next_attempt_at DATETIME, -- retrying: when funds are checked again
+    transaction_id TEXT,
+    error TEXT,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    UNIQUE (schedule_id, due_at),
+    FOREIGN KEY (schedule_id) REFERENCES scheduled_transfers (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_scheduled_transfer_runs_retry ON scheduled_transfer_runs (status, next_attempt_at);
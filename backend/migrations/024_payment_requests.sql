@@ -0,0 +1,24 @@
+-- Requests for money, addressed to one user or shared as an open link. Each
+-- is payable in the app or from any Stellar wallet through its SEP-7 URI
+CREATE TABLE IF NOT EXISTS payment_requests (
+    id TEXT PRIMARY KEY,
+    requester_id TEXT NOT NULL,
+    payer_id TEXT, -- NULL for an open link anyone can pay
+    payer_contact TEXT, -- the email or phone number the request was sent to
+    amount REAL NOT NULL,
+    currency TEXT NOT NULL, -- XLM or CODE:ISSUER
+    memo TEXT,
+    destination TEXT NOT NULL, -- the requester's Stellar account
+    status TEXT NOT NULL DEFAULT 'open', -- open | processing | paid | declined | cancelled | expired
+    expires_at DATETIME NOT NULL,
+    paid_by TEXT,
+    transaction_id TEXT,
+    tx_hash TEXT,
+    created_at DATETIME NOT NULL,
+    settled_at DATETIME,
+    FOREIGN KEY (requester_id) REFERENCES users (id),
+    FOREIGN KEY (payer_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_payment_requests_requester ON payment_requests (requester_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_payment_requests_payer ON payment_requests (payer_id, created_at);
@@ -0,0 +1,63 @@
+-- Merchant invoices, paid from a NovaPay wallet or on-chain to the invoice's
+-- muxed address or with its memo. Status changes are posted to the
+-- merchant's webhook
+ALTER TABLE merchants ADD COLUMN webhook_url TEXT;
+ALTER TABLE merchants ADD COLUMN webhook_secret TEXT; -- whsec_..., signs webhook bodies
+
+CREATE TABLE IF NOT EXISTS invoices (
+    id TEXT PRIMARY KEY,
+    merchant_id TEXT NOT NULL,
+    reference TEXT, -- the merchant's own order number
+    description TEXT,
+    currency TEXT NOT NULL, -- XLM or CODE:ISSUER
+    amount_due REAL NOT NULL, -- sum of the line items
+    amount_paid REAL NOT NULL DEFAULT 0,
+    status TEXT NOT NULL DEFAULT 'open', -- open | underpaid | paid | overpaid | expired
+    destination TEXT NOT NULL, -- the merchant owner's Stellar account
+    muxed_id INTEGER NOT NULL UNIQUE, -- id in the invoice's M... address
+    memo TEXT NOT NULL UNIQUE, -- text memo that identifies a payment to the plain address
+    expires_at DATETIME NOT NULL,
+    paid_at DATETIME,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    FOREIGN KEY (merchant_id) REFERENCES merchants (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoices_merchant ON invoices (merchant_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_invoices_status ON invoices (status, expires_at);
+
+CREATE TABLE IF NOT EXISTS invoice_items (
+    id TEXT PRIMARY KEY,
+    invoice_id TEXT NOT NULL,
+    position INTEGER NOT NULL,
+    description TEXT NOT NULL,
+    quantity INTEGER NOT NULL,
+    unit_price REAL NOT NULL,
+    amount REAL NOT NULL,
+    FOREIGN KEY (invoice_id) REFERENCES invoices (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoice_items_invoice ON invoice_items (invoice_id, position);
+
+CREATE TABLE IF NOT EXISTS invoice_payments (
+    id TEXT PRIMARY KEY,
+    invoice_id TEXT NOT NULL,
+    source TEXT NOT NULL, -- wallet | stellar
+    payer_id TEXT, -- the NovaPay user, for wallet payments
+    from_account TEXT, -- the paying Stellar account, for on-chain payments
+    amount REAL NOT NULL,
+    transaction_id TEXT,
+    tx_hash TEXT,
+    paging_token TEXT UNIQUE, -- Horizon's id for the payment, so it is counted once
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (invoice_id) REFERENCES invoices (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_invoice_payments_invoice ON invoice_payments (invoice_id, created_at);
+
+-- How far the payment watcher has read each receiving account's history
+CREATE TABLE IF NOT EXISTS payment_watch_cursors (
+    account TEXT PRIMARY KEY,
+    cursor TEXT NOT NULL,
+    updated_at DATETIME NOT NULL
+);
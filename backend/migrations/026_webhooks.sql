@@ -0,0 +1,39 @@
+-- Partner webhook endpoints and the log of every event posted to them
+CREATE TABLE IF NOT EXISTS webhook_endpoints (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    merchant_id TEXT, -- set for endpoints that receive the merchant's invoice events
+    url TEXT NOT NULL,
+    secret TEXT NOT NULL, -- whsec_..., signs delivery bodies
+    event_types TEXT NOT NULL DEFAULT '', -- comma-separated; empty means every event
+    description TEXT,
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    FOREIGN KEY (user_id) REFERENCES users (id),
+    FOREIGN KEY (merchant_id) REFERENCES merchants (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_endpoints_user ON webhook_endpoints (user_id, merchant_id);
+CREATE INDEX IF NOT EXISTS idx_webhook_endpoints_merchant ON webhook_endpoints (merchant_id);
+
+CREATE TABLE IF NOT EXISTS webhook_deliveries (
+    id TEXT PRIMARY KEY,
+    endpoint_id TEXT NOT NULL,
+    event_id TEXT NOT NULL, -- evt_..., the same for every delivery of one event
+    event_type TEXT NOT NULL,
+    payload TEXT NOT NULL, -- the JSON body, posted as is on every attempt
+    status TEXT NOT NULL DEFAULT 'pending', -- pending | delivered | failed
+    attempts INTEGER NOT NULL DEFAULT 0,
+    response_status INTEGER, -- HTTP status of the last attempt
+    last_error TEXT,
+    replay_of TEXT, -- the delivery this one replays
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    delivered_at DATETIME,
+    FOREIGN KEY (endpoint_id) REFERENCES webhook_endpoints (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_webhook_deliveries_endpoint ON webhook_deliveries (endpoint_id, created_at);
+-- An event is published to an endpoint once; replays are extra rows
+CREATE UNIQUE INDEX IF NOT EXISTS idx_webhook_deliveries_event
+    ON webhook_deliveries (endpoint_id, event_id) WHERE replay_of IS NULL;
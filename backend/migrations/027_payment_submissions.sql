@@ -0,0 +1,6 @@
+-- A payment is signed once before it is sent and the envelope kept, so a
+-- retry submits the same Stellar transaction (same sequence number) or finds
+-- it on Horizon, and can't pay twice
+ALTER TABLE transactions ADD COLUMN submission_hash TEXT;
+ALTER TABLE transactions ADD COLUMN submission_envelope TEXT;
+ALTER TABLE transactions ADD COLUMN submitted_at DATETIME;
@@ -0,0 +1,4 @@
+-- Wrong second-factor codes in a row, per user, across login challenges,
+-- step-up and disabling TOTP; enough of them lock the codes out for a while
+ALTER TABLE user_totp ADD COLUMN failed_attempts INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE user_totp ADD COLUMN locked_until DATETIME;
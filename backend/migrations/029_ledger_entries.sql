@@ -0,0 +1,13 @@
+-- Wallet balance changes, each booked against the transaction that caused
+-- it, so a reversal undoes exactly what was applied
+CREATE TABLE IF NOT EXISTS ledger_entries (
+    id TEXT PRIMARY KEY,
+    transaction_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    amount REAL NOT NULL, -- positive credits, negative debits
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (transaction_id) REFERENCES transactions (id),
+    FOREIGN KEY (user_id) REFERENCES users (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_ledger_entries_transaction ON ledger_entries (transaction_id);
@@ -0,0 +1,15 @@
+-- API keys get a random secret of their own, sealed under API_KEY_SECRET.
+-- Keys issued before this have none and stop verifying until rotated
+ALTER TABLE api_keys ADD COLUMN secret_sealed TEXT;
+
+-- Idempotency keys of API payouts, so a retried request pays only once
+CREATE TABLE IF NOT EXISTS merchant_payouts (
+    merchant_id TEXT NOT NULL,
+    idempotency_key TEXT NOT NULL, -- the caller's Idempotency-Key header
+    request_hash TEXT NOT NULL, -- SHA-256 of the payout request, to refuse reuse for another payout
+    response TEXT, -- JSON answered to the caller; NULL while the payout is in flight
+    created_at DATETIME NOT NULL,
+    completed_at DATETIME,
+    PRIMARY KEY (merchant_id, idempotency_key),
+    FOREIGN KEY (merchant_id) REFERENCES merchants (id)
+);
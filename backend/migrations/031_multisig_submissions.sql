@@ -0,0 +1,4 @@
+-- The signed envelope of an approval that reached its threshold, kept so a
+-- submission that crashed or timed out is retried with the same transaction
+-- instead of staying `submitting`
+ALTER TABLE multisig_approvals ADD COLUMN submission_envelope TEXT;
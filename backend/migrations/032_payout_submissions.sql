@@ -0,0 +1,13 @@
+-- Payout transactions are signed and stored before they are sent, and their
+-- rows move to `sending` with its hash, so a crash or a timeout is settled by
+-- resubmitting the same envelope instead of paying the rows again.
+-- payout_batch_rows.status: pending | sending | paid | failed
+CREATE TABLE IF NOT EXISTS payout_submissions (
+    tx_hash TEXT PRIMARY KEY,
+    batch_id TEXT NOT NULL,
+    envelope TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (batch_id) REFERENCES payout_batches (id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_payout_batch_rows_status ON payout_batch_rows (batch_id, status);
@@ -0,0 +1,5 @@
+-- Each payment request's SEP-7 URI pays a muxed address of the requester's
+-- account, so payments made from other Stellar wallets can be matched to it
+ALTER TABLE payment_requests ADD COLUMN muxed_id INTEGER;
+
+CREATE INDEX IF NOT EXISTS idx_payment_requests_muxed ON payment_requests (destination, muxed_id);
@@ -0,0 +1,5 @@
+-- An invoice being paid from a wallet is held `processing` with the
+-- transaction that pays it, so it can't be paid twice and a payment that
+-- went out but wasn't recorded is settled by a job.
+-- invoices.status: open | processing | underpaid | paid | overpaid | expired
+ALTER TABLE invoices ADD COLUMN processing_transaction_id TEXT;
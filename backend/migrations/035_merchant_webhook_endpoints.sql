@@ -0,0 +1,29 @@
+-- The merchant's own webhook_url becomes one of its webhook endpoints, so
+-- invoice events are posted once, by the endpoint deliveries
+INSERT INTO webhook_endpoints (id, user_id, merchant_id, url, secret, event_types, description, created_at, updated_at)
+SELECT lower(hex(randomblob(16))), owner_user_id, id, webhook_url, webhook_secret, '', 'Merchant webhook',
+       CURRENT_TIMESTAMP, CURRENT_TIMESTAMP
+FROM merchants
+WHERE webhook_url IS NOT NULL AND webhook_secret IS NOT NULL;
+
+-- Events still waiting for the old merchant_webhook job go out as
+-- deliveries to that endpoint instead
+INSERT OR IGNORE INTO webhook_deliveries (id, endpoint_id, event_id, event_type, payload, created_at, updated_at)
+SELECT j.id, e.id, json_extract(j.payload, '$.body.id'), json_extract(j.payload, '$.body.type'),
+       json_extract(j.payload, '$.body'), CURRENT_TIMESTAMP, CURRENT_TIMESTAMP
+FROM jobs j
+JOIN webhook_endpoints e
+    ON e.merchant_id = json_extract(j.payload, '$.merchant_id') AND e.description = 'Merchant webhook'
+WHERE j.kind = 'merchant_webhook' AND j.status IN ('pending', 'running');
+
+INSERT INTO jobs (id, kind, payload, run_at)
+SELECT lower(hex(randomblob(16))), 'webhook_delivery', json_object('kind', 'webhook_delivery', 'delivery_id', d.id),
+       CURRENT_TIMESTAMP
+FROM webhook_deliveries d
+JOIN jobs j ON j.id = d.id AND j.kind = 'merchant_webhook';
+
+UPDATE jobs SET status = 'cancelled', updated_at = CURRENT_TIMESTAMP
+WHERE kind = 'merchant_webhook' AND status IN ('pending', 'running', 'dead');
+
+ALTER TABLE merchants DROP COLUMN webhook_url;
+ALTER TABLE merchants DROP COLUMN webhook_secret;
@@ -0,0 +1,351 @@
+use axum::{
+    extract::{ConnectInfo, Extension, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use std::net::SocketAddr;
+use validator::Validate;
+
+use crate::handlers::auth::find_user;
+use crate::middleware::{request_actor, SessionId};
+use crate::models::{
+    ChangePasswordRequest, ForgotPasswordRequest, ResetPasswordRequest, Role, User, UserResponse, VerifyEmailRequest,
+};
+use crate::services::{
+    AccountTokenError, AccountTokenPurpose, AccountTokenService, Actor, AuditLog, AuthService, PasswordPolicy,
+    SessionService,
+};
+
+/// Always answers the same way so the endpoint cannot be used to find out
+/// which addresses have accounts. The email is sent in the background.
+pub async fn forgot_password(
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<ForgotPasswordRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    tokio::spawn(async move {
+        let user = match sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = ?")
+            .bind(&payload.email)
+            .fetch_optional(&pool)
+            .await
+        {
+            Ok(Some(user)) => user,
+            Ok(None) => return,
+            Err(e) => return println!("Failed to look up password reset request: {}", e),
+        };
+        if let Err(e) = AccountTokenService::new()
+            .send(&pool, &user, AccountTokenPurpose::PasswordReset)
+            .await
+        {
+            println!("Failed to send password reset email: {}", e);
+        }
+    });
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "If an account exists for that address, a reset link has been sent"
+    })))
+}
+
+/// Sets a new password from an emailed link and signs the user out
+/// everywhere.
+pub async fn reset_password(
+    State(pool): State<SqlitePool>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<ResetPasswordRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let tokens = AccountTokenService::new();
+    let (user_id, email) = tokens
+        .check(&pool, AccountTokenPurpose::PasswordReset, &payload.token)
+        .await
+        .map_err(account_token_status)?;
+    let user = find_user(&pool, &user_id).await?;
+    if user.email != email {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    PasswordPolicy::check(&payload.new_password, &[&user.email, &user.full_name]).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let password_hash = AuthService::hash_password(&payload.new_password).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tokens
+        .consume(&pool, AccountTokenPurpose::PasswordReset, &payload.token)
+        .await
+        .map_err(account_token_status)?;
+
+    // Following the link proves the user controls the mailbox
+    let now = chrono::Utc::now();
+    sqlx::query(
+        "UPDATE users SET password_hash = ?, email_verified_at = COALESCE(email_verified_at, ?), updated_at = ? WHERE id = ?",
+    )
+    .bind(&password_hash)
+    .bind(now)
+    .bind(now)
+    .bind(&user_id)
+    .execute(&pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let revoked = SessionService::revoke_all(&pool, &user_id, None, "password_reset")
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let actor = request_actor(user.id.clone(), Role::from_str_or_customer(&user.role), &headers, Some(peer));
+    AuditLog::record_after(
+        &pool,
+        &actor,
+        "auth.password_reset",
+        "user",
+        Some(&user.id),
+        json!({ "sessions_revoked": revoked }),
+        None,
+    )
+    .await;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Password reset; sign in with your new password"
+    })))
+}
+
+/// Changes the password of a signed-in user and revokes every other session.
+pub async fn change_password(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Extension(SessionId(session_id)): Extension<SessionId>,
+    Json(payload): Json<ChangePasswordRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let user_id = actor.user_id.clone();
+    let user = find_user(&pool, &user_id).await?;
+    let is_valid = AuthService::verify_password(&payload.current_password, &user.password_hash)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !is_valid {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    if payload.new_password == payload.current_password {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    PasswordPolicy::check(&payload.new_password, &[&user.email, &user.full_name]).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let password_hash = AuthService::hash_password(&payload.new_password).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    sqlx::query("UPDATE users SET password_hash = ?, updated_at = ? WHERE id = ?")
+        .bind(&password_hash)
+        .bind(chrono::Utc::now())
+        .bind(&user_id)
+        .execute(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let revoked = SessionService::revoke_all(&pool, &user_id, Some(&session_id), "password_changed")
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    AuditLog::record_after(
+        &pool,
+        &actor,
+        "auth.password_change",
+        "user",
+        Some(&user_id),
+        json!({ "sessions_revoked": revoked }),
+        None,
+    )
+    .await;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Password changed",
+        "sessions_revoked": revoked
+    })))
+}
+
+pub async fn verify_email(
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<VerifyEmailRequest>,
+) -> Result<Json<UserResponse>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let (user_id, email) = AccountTokenService::new()
+        .consume(&pool, AccountTokenPurpose::VerifyEmail, &payload.token)
+        .await
+        .map_err(account_token_status)?;
+
+    // The address may have changed since the link was sent
+    let user = sqlx::query_as::<_, User>(
+        r#"
+        UPDATE users SET email_verified_at = COALESCE(email_verified_at, ?), updated_at = ?
+        WHERE id = ? AND email = ?
+        RETURNING *
+        "#,
+    )
+    .bind(chrono::Utc::now())
+    .bind(chrono::Utc::now())
+    .bind(&user_id)
+    .bind(&email)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::BAD_REQUEST)?;
+
+    Ok(Json(UserResponse::from(user)))
+}
+
+/// Sends a new verification link to the user's email address.
+pub async fn resend_email_verification(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let user = find_user(&pool, &user_id).await?;
+    if user.email_verified_at.is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    AccountTokenService::new()
+        .send(&pool, &user, AccountTokenPurpose::VerifyEmail)
+        .await
+        .map_err(account_token_status)?;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Verification email sent"
+    })))
+}
+
+/// Best-effort: the user can always ask for another link later.
+pub(crate) async fn send_email_verification(pool: &SqlitePool, user: &User) -> bool {
+    match AccountTokenService::new().send(pool, user, AccountTokenPurpose::VerifyEmail).await {
+        Ok(()) => true,
+        Err(e) => {
+            println!("Failed to send email verification: {}", e);
+            false
+        }
+    }
+}
+
+fn account_token_status(error: AccountTokenError) -> StatusCode {
+    match error {
+        AccountTokenError::Invalid | AccountTokenError::Expired | AccountTokenError::Used => StatusCode::BAD_REQUEST,
+        AccountTokenError::Cooldown(_) => StatusCode::TOO_MANY_REQUESTS,
+        AccountTokenError::Mail(_) => StatusCode::BAD_GATEWAY,
+        AccountTokenError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, jwt_keys, memory_pool};
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    /// Account tokens read their secret and mailer from the environment; every
+    /// test sets the same values and tells its mail apart by address.
+    fn mail_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("novapay-account-mail-{}", std::process::id()));
+        std::env::set_var("ACCOUNT_TOKEN_SECRET", "account-token-test-secret-0123456789abcdef");
+        std::env::set_var("MAIL_FILE_DIR", &dir);
+        dir
+    }
+
+    /// The token from the newest link mailed to `email`, waiting for it to
+    /// arrive if it is sent in the background.
+    async fn mailed_token(email: &str) -> String {
+        for _ in 0..100 {
+            let mut mails: Vec<PathBuf> = std::fs::read_dir(mail_dir())
+                .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.path()).collect())
+                .unwrap_or_default();
+            mails.retain(|path| path.to_string_lossy().ends_with(&format!("_{}.eml", email)));
+            mails.sort();
+            if let Some(path) = mails.last() {
+                let body = std::fs::read_to_string(path).unwrap();
+                let start = body.find("token=").unwrap() + "token=".len();
+                let token: String = body[start..].chars().take_while(|c| c.is_ascii_hexdigit() || *c == '.').collect();
+                return token.trim_end_matches('.').to_string();
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("no mail for {}", email);
+    }
+
+    #[tokio::test]
+    async fn test_password_reset_revokes_sessions() {
+        mail_dir();
+        let pool = memory_pool().await;
+        let _keys = jwt_keys(&pool).await;
+        insert_user(&pool, "user-1", "reset@example.com").await;
+        let phone = SessionService::create(&pool, "user-1", Some("phone"), None).await.unwrap();
+        let laptop = SessionService::create(&pool, "user-1", Some("laptop"), None).await.unwrap();
+
+        let forgot = forgot_password(State(pool.clone()), Json(ForgotPasswordRequest { email: "reset@example.com".to_string() }))
+            .await
+            .unwrap();
+        assert_eq!(forgot.0["success"], true);
+        let token = mailed_token("reset@example.com").await;
+
+        let reset = |token: String| {
+            reset_password(
+                State(pool.clone()),
+                ConnectInfo("127.0.0.1:4000".parse().unwrap()),
+                HeaderMap::new(),
+                Json(ResetPasswordRequest { token, new_password: "Correct-Horse-Battery-9".to_string() }),
+            )
+        };
+
+        // A tampered signature is refused and doesn't use the link up
+        let (id, signature) = token.split_once('.').unwrap();
+        let forged = format!("{}.{}", id, signature.chars().rev().collect::<String>());
+        assert_eq!(reset(forged).await.unwrap_err(), StatusCode::BAD_REQUEST);
+
+        assert_eq!(reset(token.clone()).await.unwrap().0["success"], true);
+        let user = find_user(&pool, "user-1").await.unwrap();
+        assert!(AuthService::verify_password("Correct-Horse-Battery-9", &user.password_hash).unwrap());
+        assert!(user.email_verified_at.is_some());
+        assert!(!SessionService::is_active(&pool, &phone.session_id).await.unwrap());
+        assert!(!SessionService::is_active(&pool, &laptop.session_id).await.unwrap());
+
+        // Each link works once
+        assert_eq!(reset(token).await.unwrap_err(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_verify_email() {
+        mail_dir();
+        let pool = memory_pool().await;
+        insert_user(&pool, "user-2", "verify@example.com").await;
+        let user = find_user(&pool, "user-2").await.unwrap();
+        assert!(user.email_verified_at.is_none());
+
+        assert!(send_email_verification(&pool, &user).await);
+        let token = mailed_token("verify@example.com").await;
+        // A reset link can't verify an address, nor the other way round
+        let tokens = AccountTokenService::new();
+        assert!(matches!(
+            tokens.check(&pool, AccountTokenPurpose::PasswordReset, &token).await,
+            Err(AccountTokenError::Invalid)
+        ));
+
+        let verified = verify_email(State(pool.clone()), Json(VerifyEmailRequest { token: token.clone() }))
+            .await
+            .unwrap();
+        assert_eq!(verified.0.email, "verify@example.com");
+        assert!(find_user(&pool, "user-2").await.unwrap().email_verified_at.is_some());
+        assert_eq!(
+            verify_email(State(pool), Json(VerifyEmailRequest { token })).await.unwrap_err(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+}
@@ -0,0 +1,365 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::handlers::auth::find_user;
+use crate::middleware::{perm, PermissionMarker, RequirePermission};
+use crate::models::{
+    AdminUserView, AuditEvent, AuditExportFormat, AuditExportQuery, AuditQuery, AuditVerification, FreezeAccountRequest,
+    Job, JobListQuery, JwtKey, ReverseTransactionRequest, Transaction, UpdateRoleRequest, UserSearchQuery, Wallet,
+};
+use crate::services::{
+    AdminError, AdminService, AuditLog, JobQueue, KeyManager, LoginLockout, RateLimitKey, RateLimiter,
+};
+
+#[derive(Debug, Deserialize)]
+pub struct LimitQuery {
+    pub limit: Option<i64>,
+}
+
+pub async fn list_jobs(
+    _: RequirePermission<perm::ManageJobs>,
+    State(pool): State<SqlitePool>,
+    Query(query): Query<JobListQuery>,
+) -> Result<Json<Vec<Job>>, StatusCode> {
+    JobQueue::list(&pool, &query)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn get_job(
+    _: RequirePermission<perm::ManageJobs>,
+    State(pool): State<SqlitePool>,
+    Path(job_id): Path<String>,
+) -> Result<Json<Job>, StatusCode> {
+    match JobQueue::get(&pool, &job_id).await {
+        Ok(Some(job)) => Ok(Json(job)),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn retry_job(
+    permission: RequirePermission<perm::ManageJobs>,
+    State(pool): State<SqlitePool>,
+    Path(job_id): Path<String>,
+) -> Result<Json<Job>, StatusCode> {
+    match JobQueue::retry(&pool, &job_id).await {
+        Ok(Some(job)) => {
+            audit(&pool, &permission, "job.retry", "job", Some(&job.id), json!({ "kind": job.kind })).await?;
+            Ok(Json(job))
+        }
+        // Unknown, or not in a retryable state
+        Ok(None) => Err(StatusCode::CONFLICT),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn cancel_job(
+    permission: RequirePermission<perm::ManageJobs>,
+    State(pool): State<SqlitePool>,
+    Path(job_id): Path<String>,
+) -> Result<Json<Job>, StatusCode> {
+    match JobQueue::cancel(&pool, &job_id).await {
+        Ok(Some(job)) => {
+            audit(&pool, &permission, "job.cancel", "job", Some(&job.id), json!({ "kind": job.kind })).await?;
+            Ok(Json(job))
+        }
+        Ok(None) => Err(StatusCode::CONFLICT),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn list_jwt_keys(
+    _: RequirePermission<perm::ManageKeys>,
+    State(pool): State<SqlitePool>,
+) -> Result<Json<Vec<JwtKey>>, StatusCode> {
+    KeyManager::load(&pool)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn rotate_jwt_key(
+    permission: RequirePermission<perm::ManageKeys>,
+    State(pool): State<SqlitePool>,
+) -> Result<Json<JwtKey>, StatusCode> {
+    let key = KeyManager::rotate(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    audit(&pool, &permission, "jwt_key.rotate", "jwt_key", Some(&key.kid), json!({})).await?;
+    Ok(Json(key))
+}
+
+/// Lifts a login lockout early, e.g. after the owner proved their identity
+/// to support. Also forgets the rate-limit buckets for the account's email.
+pub async fn unlock_user(
+    permission: RequirePermission<perm::UnlockAccounts>,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let user = find_user(&pool, &user_id).await?;
+    let was_locked = LoginLockout::unlock(&pool, &user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    RateLimiter::reset(RateLimitKey::Email, &user.email.to_lowercase());
+    audit(&pool, &permission, "user.unlock", "user", Some(&user.id), json!({ "was_locked": was_locked })).await?;
+
+    println!("🔓 Login lockout cleared for user {}", user.id);
+    Ok(Json(json!({
+        "success": true,
+        "user_id": user.id,
+        "was_locked": was_locked
+    })))
+}
+
+pub async fn search_users(
+    permission: RequirePermission<perm::ViewUsers>,
+    State(pool): State<SqlitePool>,
+    Query(query): Query<UserSearchQuery>,
+) -> Result<Json<Vec<AdminUserView>>, StatusCode> {
+    let users = AdminService::search_users(&pool, &query)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    audit(
+        &pool,
+        &permission,
+        "user.search",
+        "user",
+        None,
+        json!({ "q": query.q, "role": query.role, "frozen": query.frozen, "results": users.len() }),
+    )
+    .await?;
+    Ok(Json(users.into_iter().map(AdminUserView::from).collect()))
+}
+
+pub async fn get_user_admin(
+    permission: RequirePermission<perm::ViewUsers>,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+) -> Result<Json<AdminUserView>, StatusCode> {
+    let user = find_user(&pool, &user_id).await?;
+    audit(&pool, &permission, "user.view", "user", Some(&user.id), json!({})).await?;
+    Ok(Json(AdminUserView::from(user)))
+}
+
+pub async fn get_user_wallet(
+    permission: RequirePermission<perm::ViewWallets>,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let wallet: Wallet = AdminService::wallet(&pool, &user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    audit(&pool, &permission, "wallet.view", "user", Some(&user_id), json!({ "wallet_id": wallet.id })).await?;
+
+    // Never expose the secret key, even to staff
+    Ok(Json(json!({
+        "id": wallet.id,
+        "user_id": wallet.user_id,
+        "stellar_public_key": wallet.stellar_public_key,
+        "balance": wallet.balance,
+        "created_at": wallet.created_at
+    })))
+}
+
+pub async fn get_user_transactions(
+    permission: RequirePermission<perm::ViewTransactions>,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+    Query(query): Query<LimitQuery>,
+) -> Result<Json<Vec<Transaction>>, StatusCode> {
+    let transactions = AdminService::transactions(&pool, &user_id, query.limit.unwrap_or(100))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    audit(&pool, &permission, "transaction.list", "user", Some(&user_id), json!({})).await?;
+    Ok(Json(transactions))
+}
+
+pub async fn freeze_user(
+    permission: RequirePermission<perm::FreezeAccounts>,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+    Json(payload): Json<FreezeAccountRequest>,
+) -> Result<Json<AdminUserView>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    AdminService::set_frozen(&pool, &permission.actor, &user_id, Some(&payload.reason))
+        .await
+        .map(|user| Json(AdminUserView::from(user)))
+        .map_err(admin_status)
+}
+
+pub async fn unfreeze_user(
+    permission: RequirePermission<perm::FreezeAccounts>,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+) -> Result<Json<AdminUserView>, StatusCode> {
+    AdminService::set_frozen(&pool, &permission.actor, &user_id, None)
+        .await
+        .map(|user| Json(AdminUserView::from(user)))
+        .map_err(admin_status)
+}
+
+pub async fn update_user_role(
+    permission: RequirePermission<perm::ManageRoles>,
+    State(pool): State<SqlitePool>,
+    Path(user_id): Path<String>,
+    Json(payload): Json<UpdateRoleRequest>,
+) -> Result<Json<AdminUserView>, StatusCode> {
+    AdminService::set_role(&pool, &permission.actor, &user_id, payload.role)
+        .await
+        .map(|user| Json(AdminUserView::from(user)))
+        .map_err(admin_status)
+}
+
+pub async fn reverse_transaction(
+    permission: RequirePermission<perm::ReverseTransactions>,
+    State(pool): State<SqlitePool>,
+    Path(transaction_id): Path<String>,
+    Json(payload): Json<ReverseTransactionRequest>,
+) -> Result<Json<Transaction>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    AdminService::reverse_transaction(&pool, &permission.actor, &transaction_id, &payload.reason)
+        .await
+        .map(Json)
+        .map_err(admin_status)
+}
+
+pub async fn list_audit_events(
+    _: RequirePermission<perm::ViewAudit>,
+    State(pool): State<SqlitePool>,
+    Query(query): Query<AuditQuery>,
+) -> Result<Json<Vec<AuditEvent>>, StatusCode> {
+    AuditLog::list(&pool, &query)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Walks the audit hash chain; see `AuditLog::verify`.
+pub async fn verify_audit_chain(
+    _: RequirePermission<perm::ViewAudit>,
+    State(pool): State<SqlitePool>,
+) -> Result<Json<AuditVerification>, StatusCode> {
+    AuditLog::seal(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    AuditLog::verify(&pool)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Sealed audit events as JSON Lines or CSV, with `seq`, `prev_hash` and
+/// `hash` so auditors can re-check the chain themselves.
+pub async fn export_audit_events(
+    permission: RequirePermission<perm::ViewAudit>,
+    State(pool): State<SqlitePool>,
+    Query(query): Query<AuditExportQuery>,
+) -> Result<Response, StatusCode> {
+    AuditLog::seal(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let events = AuditLog::export(&pool, &query)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    audit(
+        &pool,
+        &permission,
+        "audit.export",
+        "audit_log",
+        None,
+        json!({ "from": query.from, "to": query.to, "events": events.len() }),
+    )
+    .await?;
+
+    let (content_type, extension, body) = match query.format {
+        AuditExportFormat::Jsonl => {
+            let lines: Vec<String> = events
+                .iter()
+                .map(|event| serde_json::to_string(event).unwrap_or_default())
+                .collect();
+            ("application/x-ndjson", "jsonl", lines.join("\n"))
+        }
+        AuditExportFormat::Csv => {
+            let mut csv = String::from(
+                "seq,id,created_at,actor_id,actor_role,action,target_type,target_id,ip_address,user_agent,details,before_state,after_state,prev_hash,hash\n",
+            );
+            for event in &events {
+                let fields = [
+                    event.seq.map(|seq| seq.to_string()),
+                    Some(event.id.clone()),
+                    Some(event.created_at.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)),
+                    Some(event.actor_id.clone()),
+                    Some(event.actor_role.clone()),
+                    Some(event.action.clone()),
+                    Some(event.target_type.clone()),
+                    event.target_id.clone(),
+                    event.ip_address.clone(),
+                    event.user_agent.clone(),
+                    Some(event.details.clone()),
+                    event.before_state.clone(),
+                    event.after_state.clone(),
+                    event.prev_hash.clone(),
+                    event.hash.clone(),
+                ];
+                let row: Vec<String> = fields.iter().map(|field| csv_field(field.as_deref().unwrap_or_default())).collect();
+                csv.push_str(&row.join(","));
+                csv.push('\n');
+            }
+            ("text/csv", "csv", csv)
+        }
+    };
+
+    let disposition = format!("attachment; filename=\"audit-events.{}\"", extension);
+    Ok((
+        [(header::CONTENT_TYPE, content_type.to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        body,
+    )
+        .into_response())
+}
+
+pub(crate) fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Records a staff action that has no transaction of its own to share.
+async fn audit<P: PermissionMarker>(
+    pool: &SqlitePool,
+    permission: &RequirePermission<P>,
+    action: &str,
+    target_type: &str,
+    target_id: Option<&str>,
+    details: Value,
+) -> Result<(), StatusCode> {
+    AuditLog::record(pool, &permission.actor, action, target_type, target_id, details)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn admin_status(error: AdminError) -> StatusCode {
+    match error {
+        AdminError::NotFound => StatusCode::NOT_FOUND,
+        AdminError::Conflict(_) | AdminError::InsufficientBalance => StatusCode::CONFLICT,
+        AdminError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -1,42 +1,62 @@
 use axum::{
-    extract::{Extension, State},
-    http::StatusCode,
+    extract::{ConnectInfo, Extension, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
     Json,
 };
 use serde_json::{json, Value};
 use sqlx::SqlitePool;
+use std::net::SocketAddr;
 use uuid::Uuid;
 use validator::Validate;
 
-use crate::models::{CreateUser, LoginUser, User, UserResponse};
-use crate::services::{AuthService, StellarService};
+use crate::handlers::account::send_email_verification;
+use crate::middleware::{request_actor, SessionId};
+use crate::models::{
+    CreateUser, LoginUser, OtpLoginRequest, OtpLoginVerify, RefreshTokenRequest, SessionResponse, User, UserResponse,
+    Role, VerifyPhoneRequest,
+};
+use crate::services::notification_templates::Locale;
+use crate::services::{
+    normalize_phone, AuditLog, AuthService, DeviceService, DomainEvent, JobPayload, KeyManager, LoginLockout, MfaService, OtpError,
+    OtpPurpose, OtpService, Outbox, PasswordPolicy, SessionError, SessionService, StellarService, TokenPair,
+};
 
 pub async fn register(
     State(pool): State<SqlitePool>,
+    headers: HeaderMap,
     Json(payload): Json<CreateUser>,
 ) -> Result<Json<Value>, StatusCode> {
     if let Err(_) = payload.validate() {
         return Err(StatusCode::BAD_REQUEST);
     }
+    PasswordPolicy::check(&payload.password, &[&payload.email, &payload.full_name]).map_err(|_| StatusCode::BAD_REQUEST)?;
 
     let password_hash = AuthService::hash_password(&payload.password)
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let user_id = Uuid::new_v4().to_string();
+    let phone_number = payload
+        .phone_number
+        .as_deref()
+        .map(|phone| normalize_phone(phone).ok_or(StatusCode::BAD_REQUEST))
+        .transpose()?;
+    let preferred_language = Locale::from_preference(payload.preferred_language.as_deref());
     let stellar_account = StellarService::generate_keypair();
 
     let result = sqlx::query(
         r#"
-        INSERT INTO users (id, email, password_hash, full_name, phone_number, stellar_public_key)
-        VALUES (?, ?, ?, ?, ?, ?)
+        INSERT INTO users (id, email, password_hash, full_name, phone_number, stellar_public_key, preferred_language)
+        VALUES (?, ?, ?, ?, ?, ?, ?)
         "#,
     )
     .bind(&user_id)
     .bind(&payload.email)
     .bind(&password_hash)
     .bind(&payload.full_name)
-    .bind(&payload.phone_number)
+    .bind(&phone_number)
     .bind(&stellar_account.public_key)
+    .bind(preferred_language.code())
     .execute(&pool)
     .await;
 
@@ -57,17 +77,29 @@ pub async fn register(
             .execute(&pool)
             .await;
 
-            let token = AuthService::generate_jwt(&user_id)
+            let tokens = SessionService::create(&pool, &user_id, user_agent(&headers), client_ip(&headers).as_deref())
+                .await
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+            let phone_verification_sent = match &phone_number {
+                Some(phone) => send_verification_code(&pool, &user_id, phone, preferred_language).await,
+                None => false,
+            };
+            let email_verification_sent = send_email_verification(&pool, &find_user(&pool, &user_id).await?).await;
+
             Ok(Json(json!({
-                "token": token,
+                "token": tokens.access_token,
+                "refresh_token": tokens.refresh_token,
+                "expires_in": tokens.expires_in,
+                "phone_verification_sent": phone_verification_sent,
+                "email_verification_sent": email_verification_sent,
                 "user": {
                     "id": user_id,
                     "email": payload.email,
                     "full_name": payload.full_name,
-                    "phone_number": payload.phone_number,
-                    "stellar_public_key": stellar_account.public_key
+                    "phone_number": phone_number,
+                    "stellar_public_key": stellar_account.public_key,
+                    "preferred_language": preferred_language.code()
                 }
             })))
         }
@@ -77,45 +109,75 @@ pub async fn register(
 
 pub async fn login(
     State(pool): State<SqlitePool>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
     Json(payload): Json<LoginUser>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, Response> {
     if let Err(_) = payload.validate() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(StatusCode::BAD_REQUEST.into_response());
     }
 
     let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE email = ?")
         .bind(&payload.email)
         .fetch_optional(&pool)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
 
     match user {
         Some(user) => {
+            // Checked before the password so a locked account gives nothing away
+            let locked_until = LoginLockout::locked_until(&pool, &user.id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+            if let Some(until) = locked_until {
+                audit_login_failure(&pool, &user, &headers, peer, json!({ "reason": "locked", "locked_until": until })).await;
+                return Err(locked_response(until));
+            }
+
             let is_valid = AuthService::verify_password(&payload.password, &user.password_hash)
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
 
             if is_valid {
-                let token = AuthService::generate_jwt(&user.id)
-                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-                Ok(Json(json!({
-                    "token": token,
-                    "user": UserResponse {
-                        id: user.id,
-                        email: user.email,
-                        full_name: user.full_name,
-                        phone_number: user.phone_number,
-                        stellar_public_key: user.stellar_public_key,
-                    }
-                })))
+                LoginLockout::record_success(&pool, &user.id)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+                complete_first_factor(&pool, user, &headers, peer, "password")
+                    .await
+                    .map_err(IntoResponse::into_response)
             } else {
-                Err(StatusCode::UNAUTHORIZED)
+                let result = LoginLockout::record_failure(&pool, &user.id).await;
+                let locked_until = result.as_ref().ok().copied().flatten();
+                audit_login_failure(
+                    &pool,
+                    &user,
+                    &headers,
+                    peer,
+                    json!({ "reason": "bad_password", "locked_until": locked_until }),
+                )
+                .await;
+                match result {
+                    Ok(Some(until)) => Err(locked_response(until)),
+                    Ok(None) => Err(StatusCode::UNAUTHORIZED.into_response()),
+                    Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+                }
             }
         }
-        None => Err(StatusCode::UNAUTHORIZED),
+        None => Err(StatusCode::UNAUTHORIZED.into_response()),
     }
 }
 
+/// Failed sign-ins are recorded against the account that was tried.
+async fn audit_login_failure(pool: &SqlitePool, user: &User, headers: &HeaderMap, peer: SocketAddr, details: Value) {
+    let actor = request_actor(user.id.clone(), Role::from_str_or_customer(&user.role), headers, Some(peer));
+    AuditLog::record_after(pool, &actor, "auth.login_failed", "user", Some(&user.id), details, None).await;
+}
+
+/// `423 Locked` with a `Retry-After` header counting down to `until`.
+fn locked_response(until: chrono::DateTime<chrono::Utc>) -> Response {
+    let retry_after = (until - chrono::Utc::now()).num_seconds().max(1);
+    (StatusCode::LOCKED, [(header::RETRY_AFTER, retry_after.to_string())]).into_response()
+}
+
 pub async fn me(
     State(pool): State<SqlitePool>,
     Extension(user_id): Extension<String>,
@@ -127,13 +189,461 @@ pub async fn me(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     match user {
-        Some(user) => Ok(Json(UserResponse {
-            id: user.id,
-            email: user.email,
-            full_name: user.full_name,
-            phone_number: user.phone_number,
-            stellar_public_key: user.stellar_public_key,
-        })),
+        Some(user) => Ok(Json(UserResponse::from(user))),
         None => Err(StatusCode::NOT_FOUND),
     }
-}
\ No newline at end of file
+}
+pub async fn refresh(
+    State(pool): State<SqlitePool>,
+    headers: HeaderMap,
+    Json(payload): Json<RefreshTokenRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let result = SessionService::refresh(&pool, &payload.refresh_token, user_agent(&headers), client_ip(&headers).as_deref()).await;
+
+    match result {
+        Ok(tokens) => Ok(token_response(tokens)),
+        Err(SessionError::InvalidToken | SessionError::Expired | SessionError::Reused) => Err(StatusCode::UNAUTHORIZED),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub async fn logout(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Extension(SessionId(session_id)): Extension<SessionId>,
+) -> Result<Json<Value>, StatusCode> {
+    SessionService::revoke(&pool, &user_id, &session_id, "logout")
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Logged out"
+    })))
+}
+
+pub async fn list_sessions(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Extension(SessionId(session_id)): Extension<SessionId>,
+) -> Result<Json<Vec<SessionResponse>>, StatusCode> {
+    SessionService::list(&pool, &user_id, &session_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn revoke_session(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Path(session_id): Path<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let revoked = SessionService::revoke(&pool, &user_id, &session_id, "user_revoked")
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if !revoked {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Session revoked"
+    })))
+}
+
+/// Sends a new verification code to the user's phone number.
+pub async fn send_phone_verification(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let user = find_user(&pool, &user_id).await?;
+    let phone_number = user.phone_number.ok_or(StatusCode::BAD_REQUEST)?;
+    if user.phone_verified_at.is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    OtpService::new()
+        .send(
+            &pool,
+            &phone_number,
+            OtpPurpose::VerifyPhone,
+            Some(&user_id),
+            Locale::from_preference(Some(&user.preferred_language)),
+        )
+        .await
+        .map_err(otp_status)?;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Verification code sent"
+    })))
+}
+
+pub async fn verify_phone(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<VerifyPhoneRequest>,
+) -> Result<Json<UserResponse>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let user = find_user(&pool, &user_id).await?;
+    let phone_number = user.phone_number.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let issued_to = OtpService::new()
+        .verify(&pool, &phone_number, OtpPurpose::VerifyPhone, &payload.code)
+        .await
+        .map_err(otp_status)?;
+    if issued_to.as_deref() != Some(user_id.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // A number can only be verified on one account, since it is used to
+    // sign in and to route transfers
+    let taken: Option<String> = sqlx::query_scalar(
+        "SELECT id FROM users WHERE phone_number = ? AND phone_verified_at IS NOT NULL AND id != ?",
+    )
+    .bind(&phone_number)
+    .bind(&user_id)
+    .fetch_optional(&pool)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if taken.is_some() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    sqlx::query("UPDATE users SET phone_verified_at = ?, updated_at = CURRENT_TIMESTAMP WHERE id = ?")
+        .bind(chrono::Utc::now())
+        .bind(&user_id)
+        .execute(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(UserResponse::from(find_user(&pool, &user_id).await?)))
+}
+
+/// Starts a passwordless sign-in. Always answers the same way so it cannot be
+/// used to find out which numbers have accounts.
+pub async fn request_login_otp(
+    State(pool): State<SqlitePool>,
+    Json(payload): Json<OtpLoginRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let phone_number = normalize_phone(&payload.phone_number).ok_or(StatusCode::BAD_REQUEST)?;
+
+    if let Some(user) = find_user_by_verified_phone(&pool, &phone_number).await? {
+        let locale = Locale::from_preference(Some(&user.preferred_language));
+        let result = OtpService::new()
+            .send(&pool, &phone_number, OtpPurpose::Login, Some(&user.id), locale)
+            .await;
+        match result {
+            Ok(()) | Err(OtpError::Cooldown(_)) => {}
+            Err(e) => println!("Failed to send login code: {}", e),
+        }
+    }
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "If this number is registered, a sign-in code has been sent"
+    })))
+}
+
+pub async fn otp_login(
+    State(pool): State<SqlitePool>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<OtpLoginVerify>,
+) -> Result<Json<Value>, Response> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST.into_response());
+    }
+    let phone_number = normalize_phone(&payload.phone_number).ok_or(StatusCode::BAD_REQUEST.into_response())?;
+
+    let user = find_user_by_verified_phone(&pool, &phone_number)
+        .await
+        .map_err(IntoResponse::into_response)?
+        .ok_or(StatusCode::UNAUTHORIZED.into_response())?;
+
+    // Shares the password login's lockout, checked before the code
+    let locked_until = LoginLockout::locked_until(&pool, &user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+    if let Some(until) = locked_until {
+        audit_login_failure(&pool, &user, &headers, peer, json!({ "reason": "locked", "locked_until": until })).await;
+        return Err(locked_response(until));
+    }
+
+    let result = OtpService::new()
+        .verify(&pool, &phone_number, OtpPurpose::Login, &payload.code)
+        .await;
+    let failure = match result {
+        Ok(Some(issued_to)) if issued_to == user.id => None,
+        Ok(_) | Err(OtpError::NotFound | OtpError::InvalidCode) => Some(StatusCode::UNAUTHORIZED),
+        Err(OtpError::TooManyAttempts) => Some(StatusCode::TOO_MANY_REQUESTS),
+        Err(e) => return Err(otp_status(e).into_response()),
+    };
+
+    match failure {
+        None => {
+            LoginLockout::record_success(&pool, &user.id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+            complete_first_factor(&pool, user, &headers, peer, "sms_otp")
+                .await
+                .map_err(IntoResponse::into_response)
+        }
+        Some(status) => {
+            let result = LoginLockout::record_failure(&pool, &user.id).await;
+            let locked_until = result.as_ref().ok().copied().flatten();
+            audit_login_failure(
+                &pool,
+                &user,
+                &headers,
+                peer,
+                json!({ "reason": "bad_code", "locked_until": locked_until }),
+            )
+            .await;
+            match result {
+                Ok(Some(until)) => Err(locked_response(until)),
+                Ok(None) => Err(status.into_response()),
+                Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR.into_response()),
+            }
+        }
+    }
+}
+
+/// After a password or SMS code was accepted: users with two-factor
+/// authentication get an MFA challenge, everyone else a session.
+pub(crate) async fn complete_first_factor(
+    pool: &SqlitePool,
+    user: User,
+    headers: &HeaderMap,
+    peer: SocketAddr,
+    method: &str,
+) -> Result<Json<Value>, StatusCode> {
+    let mfa_enabled = MfaService::is_enabled(pool, &user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !mfa_enabled {
+        return start_session(pool, user, headers, peer, method).await;
+    }
+
+    let mfa_token = MfaService::create_challenge(pool, &user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(json!({
+        "mfa_required": true,
+        "mfa_token": mfa_token,
+        "methods": ["totp", "recovery_code"]
+    })))
+}
+
+pub(crate) async fn start_session(
+    pool: &SqlitePool,
+    user: User,
+    headers: &HeaderMap,
+    peer: SocketAddr,
+    method: &str,
+) -> Result<Json<Value>, StatusCode> {
+    notify_if_new_device(pool, &user.id, headers).await;
+
+    let tokens = SessionService::create(pool, &user.id, user_agent(headers), client_ip(headers).as_deref())
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let actor = request_actor(user.id.clone(), Role::from_str_or_customer(&user.role), headers, Some(peer));
+    AuditLog::record_after(
+        pool,
+        &actor,
+        "auth.login",
+        "session",
+        Some(&tokens.session_id),
+        json!({ "method": method }),
+        None,
+    )
+    .await;
+
+    Ok(Json(json!({
+        "token": tokens.access_token,
+        "refresh_token": tokens.refresh_token,
+        "expires_in": tokens.expires_in,
+        "user": UserResponse::from(user)
+    })))
+}
+
+/// Best-effort: the user can always ask for another code later.
+pub(crate) async fn send_verification_code(pool: &SqlitePool, user_id: &str, phone_number: &str, locale: Locale) -> bool {
+    let result = OtpService::new()
+        .send(pool, phone_number, OtpPurpose::VerifyPhone, Some(user_id), locale)
+        .await;
+    match result {
+        Ok(()) => true,
+        Err(e) => {
+            println!("Failed to send phone verification code: {}", e);
+            false
+        }
+    }
+}
+
+pub(crate) fn otp_status(error: OtpError) -> StatusCode {
+    match error {
+        OtpError::NotFound | OtpError::InvalidCode => StatusCode::BAD_REQUEST,
+        OtpError::TooManyAttempts | OtpError::Cooldown(_) => StatusCode::TOO_MANY_REQUESTS,
+        OtpError::Sms(_) => StatusCode::BAD_GATEWAY,
+        OtpError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+pub(crate) async fn find_user(pool: &SqlitePool, user_id: &str) -> Result<User, StatusCode> {
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn find_user_by_verified_phone(pool: &SqlitePool, phone_number: &str) -> Result<Option<User>, StatusCode> {
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE phone_number = ? AND phone_verified_at IS NOT NULL")
+        .bind(phone_number)
+        .fetch_optional(pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Public signing keys so other services can verify access tokens.
+pub async fn jwks() -> Json<Value> {
+    Json(KeyManager::jwks())
+}
+
+fn token_response(tokens: TokenPair) -> Json<Value> {
+    Json(json!({
+        "token": tokens.access_token,
+        "refresh_token": tokens.refresh_token,
+        "expires_in": tokens.expires_in,
+        "session_id": tokens.session_id
+    }))
+}
+
+fn user_agent(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::USER_AGENT).and_then(|v| v.to_str().ok())
+}
+
+fn client_ip(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("X-Forwarded-For")
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.split(',').next())
+        .or_else(|| headers.get("X-Real-IP").and_then(|v| v.to_str().ok()))
+        .map(|ip| ip.trim().to_string())
+}
+
+async fn notify_if_new_device(pool: &SqlitePool, user_id: &str, headers: &HeaderMap) {
+    let user_agent = user_agent(headers).unwrap_or("unknown device");
+    let ip_address = client_ip(headers);
+
+    match DeviceService::record_login(pool, user_id, user_agent, ip_address.as_deref()).await {
+        Ok(true) => {
+            let event = DomainEvent::LoginFromNewDevice {
+                user_id: user_id.to_string(),
+                device: user_agent.to_string(),
+                ip_address,
+            };
+            if let Err(e) = Outbox::enqueue(pool, &JobPayload::Notify { event }).await {
+                println!("Failed to queue notifications: {}", e);
+            }
+        }
+        Ok(false) => {}
+        Err(e) => println!("Failed to record login device: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, jwt_keys, memory_pool};
+
+    fn new_user(email: &str, phone_number: Option<&str>) -> Json<CreateUser> {
+        Json(CreateUser {
+            email: email.to_string(),
+            password: "Correct-Horse-Battery-9".to_string(),
+            full_name: "Test User".to_string(),
+            phone_number: phone_number.map(str::to_string),
+            preferred_language: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_register_normalizes_or_refuses_the_phone_number() {
+        std::env::set_var("OTP_SECRET", "otp-test-secret-0123456789abcdef0123");
+        std::env::set_var("ACCOUNT_TOKEN_SECRET", "account-token-test-secret-0123456789abcdef");
+        std::env::set_var("MAIL_FILE_DIR", std::env::temp_dir().join(format!("novapay-auth-mail-{}", std::process::id())));
+        let pool = memory_pool().await;
+        let _keys = jwt_keys(&pool).await;
+
+        for phone in ["0712 345", "+1 415 555 0100", ""] {
+            let refused = register(State(pool.clone()), HeaderMap::new(), new_user("bad@example.com", Some(phone))).await;
+            assert_eq!(refused.unwrap_err(), StatusCode::BAD_REQUEST, "{:?}", phone);
+        }
+        let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users").fetch_one(&pool).await.unwrap();
+        assert_eq!(users, 0);
+
+        let registered = register(State(pool.clone()), HeaderMap::new(), new_user("ok@example.com", Some("0712 345 678")))
+            .await
+            .unwrap();
+        assert_eq!(registered.0["user"]["phone_number"], "+254712345678");
+        let stored: Option<String> = sqlx::query_scalar("SELECT phone_number FROM users WHERE email = 'ok@example.com'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(stored.as_deref(), Some("+254712345678"));
+    }
+
+    #[tokio::test]
+    async fn test_wrong_sign_in_codes_lock_the_account() {
+        std::env::set_var("OTP_SECRET", "otp-test-secret-0123456789abcdef0123");
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+        sqlx::query("UPDATE users SET phone_number = '+254712345678', phone_verified_at = CURRENT_TIMESTAMP")
+            .execute(&pool)
+            .await
+            .unwrap();
+        OtpService::new()
+            .send(&pool, "+254712345678", OtpPurpose::Login, Some("u1"), Locale::DEFAULT)
+            .await
+            .unwrap();
+
+        let attempt = || {
+            otp_login(
+                State(pool.clone()),
+                ConnectInfo("127.0.0.1:4000".parse().unwrap()),
+                HeaderMap::new(),
+                Json(OtpLoginVerify { phone_number: "0712 345 678".to_string(), code: "000000".to_string() }),
+            )
+        };
+        // A code could only be guessed right one time in a million here
+        for _ in 0..4 {
+            assert_eq!(attempt().await.unwrap_err().status(), StatusCode::UNAUTHORIZED);
+        }
+        let locked = attempt().await.unwrap_err();
+        assert_eq!(locked.status(), StatusCode::LOCKED);
+        assert!(locked.headers().contains_key(header::RETRY_AFTER));
+        assert!(LoginLockout::locked_until(&pool, "u1").await.unwrap().is_some());
+
+        // Locked, it's refused before any code is checked
+        assert_eq!(attempt().await.unwrap_err().status(), StatusCode::LOCKED);
+
+        let reasons: Vec<String> = sqlx::query_scalar(
+            "SELECT json_extract(details, '$.reason') FROM audit_events WHERE action = 'auth.login_failed' AND target_id = 'u1' ORDER BY created_at",
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        assert_eq!(reasons, ["bad_code", "bad_code", "bad_code", "bad_code", "bad_code", "locked"]);
+    }
+}
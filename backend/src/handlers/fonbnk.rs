@@ -1,10 +1,15 @@
-use axum::{extract::State, http::StatusCode, Json};
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    Json,
+};
 use serde::{Deserialize, Serialize};
 use sqlx::SqlitePool;
 use uuid::Uuid;
+use validator::Validate;
 
-use crate::models::Wallet;
-use crate::services::FonbnkService;
+use crate::models::{CreateFonbnkWidgetUrl, FonbnkWidgetUrlResponse, Wallet};
+use crate::services::{FonbnkService, Ledger};
 
 #[derive(Debug, Deserialize)]
 pub struct FonbnkDepositRequest {
@@ -76,22 +81,10 @@ async fn add_xlm_to_wallet(
     xlm_amount: f64,
     fonbnk_tx_id: &str,
 ) -> Result<f64, Box<dyn std::error::Error>> {
-    // Get user's wallet
-    let wallet = sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE user_id = ?")
-        .bind(user_id)
-        .fetch_one(pool)
-        .await?;
-
-    // Update wallet balance
-    let new_balance = wallet.balance + xlm_amount;
-    sqlx::query("UPDATE wallets SET balance = ? WHERE user_id = ?")
-        .bind(new_balance)
-        .bind(user_id)
-        .execute(pool)
-        .await?;
-
-    // Record the deposit transaction
+    // Record the deposit and credit the wallet together, so the credit is
+    // on the ledger against this transaction
     let deposit_id = Uuid::new_v4().to_string();
+    let mut db_tx = pool.begin().await?;
     sqlx::query(
         "INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, stellar_tx_hash, status) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
     )
@@ -103,8 +96,12 @@ async fn add_xlm_to_wallet(
     .bind("XLM")
     .bind(fonbnk_tx_id)
     .bind("completed")
-    .execute(pool)
+    .execute(&mut *db_tx)
     .await?;
+    let new_balance = Ledger::post(&mut db_tx, &deposit_id, user_id, xlm_amount)
+        .await?
+        .ok_or("Wallet not found")?;
+    db_tx.commit().await?;
 
     println!("💰 Fonbnk Deposit: {} XLM added to wallet (Fonbnk TX: {})", xlm_amount, fonbnk_tx_id);
     Ok(new_balance)
@@ -136,4 +133,39 @@ pub async fn get_fonbnk_rate(
             Err(StatusCode::SERVICE_UNAVAILABLE)
         }
     }
-}
\ No newline at end of file
+}
+pub async fn create_widget_url(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<CreateFonbnkWidgetUrl>,
+) -> Result<Json<FonbnkWidgetUrlResponse>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let wallet = sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE user_id = ?")
+        .bind(&user_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let fonbnk_service = FonbnkService::new();
+
+    match fonbnk_service
+        .create_widget_order(&pool, &user_id, &wallet.stellar_public_key, payload.amount, &payload.currency)
+        .await
+    {
+        Ok(order) => Ok(Json(FonbnkWidgetUrlResponse {
+            order_reference: order.id,
+            url: order.widget_url,
+            wallet_address: order.wallet_address,
+            amount: order.amount,
+            currency: order.currency,
+        })),
+        Err(e) => {
+            println!("Failed to create Fonbnk widget URL: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -4,6 +4,7 @@ use sqlx::SqlitePool;
 use uuid::Uuid;
 
 use crate::models::Wallet;
+use crate::services::Ledger;
 
 #[derive(Debug, Deserialize)]
 pub struct FonbnkDepositRequest {
@@ -72,46 +73,32 @@ async fn add_xlm_to_wallet(
     user_id: &str,
     xlm_amount: f64,
 ) -> Result<f64, Box<dyn std::error::Error>> {
-    // Get user's wallet or create demo wallet
-    let wallet = match sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE user_id = ?")
+    // Create a demo wallet if the user has none
+    let wallet = sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE user_id = ?")
         .bind(user_id)
         .fetch_optional(pool)
-        .await? {
-        Some(wallet) => wallet,
-        None => {
-            // Create demo wallet
-            let wallet_id = Uuid::new_v4().to_string();
-            let demo_public_key = format!("GDEMO{}", &wallet_id.replace("-", "")[..20]);
-            let demo_secret_key = format!("SDEMO{}", &wallet_id.replace("-", "")[..20]);
-            
-            sqlx::query(
-                "INSERT INTO wallets (id, user_id, stellar_public_key, stellar_secret_key, balance) VALUES (?, ?, ?, ?, ?)"
-            )
-            .bind(&wallet_id)
-            .bind(user_id)
-            .bind(&demo_public_key)
-            .bind(&demo_secret_key)
-            .bind(1000.0) // Starting balance
-            .execute(pool)
-            .await?;
-            
-            sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE user_id = ?")
-                .bind(user_id)
-                .fetch_one(pool)
-                .await?
-        }
-    };
+        .await?;
+    if wallet.is_none() {
+        let wallet_id = Uuid::new_v4().to_string();
+        let demo_public_key = format!("GDEMO{}", &wallet_id.replace("-", "")[..20]);
+        let demo_secret_key = format!("SDEMO{}", &wallet_id.replace("-", "")[..20]);
 
-    // Update wallet balance
-    let new_balance = wallet.balance + xlm_amount;
-    sqlx::query("UPDATE wallets SET balance = ? WHERE user_id = ?")
-        .bind(new_balance)
+        sqlx::query(
+            "INSERT INTO wallets (id, user_id, stellar_public_key, stellar_secret_key, balance) VALUES (?, ?, ?, ?, ?)"
+        )
+        .bind(&wallet_id)
         .bind(user_id)
+        .bind(&demo_public_key)
+        .bind(&demo_secret_key)
+        .bind(1000.0) // Starting balance
         .execute(pool)
         .await?;
+    }
 
-    // Record transaction
+    // Record the deposit and credit the wallet together, so the credit is
+    // on the ledger against this transaction
     let deposit_id = Uuid::new_v4().to_string();
+    let mut db_tx = pool.begin().await?;
     sqlx::query(
         "INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, stellar_tx_hash, status) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
     )
@@ -123,8 +110,12 @@ async fn add_xlm_to_wallet(
     .bind("XLM")
     .bind(&format!("fonbnk_tx_{}", deposit_id))
     .bind("completed")
-    .execute(pool)
+    .execute(&mut *db_tx)
     .await?;
+    let new_balance = Ledger::post(&mut db_tx, &deposit_id, user_id, xlm_amount)
+        .await?
+        .ok_or("Wallet not found")?;
+    db_tx.commit().await?;
 
     Ok(new_balance)
 }
@@ -0,0 +1,185 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde_json::json;
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::handlers::merchant::own_merchant;
+use crate::handlers::mfa::require_step_up;
+use crate::handlers::pin::require_pin;
+use crate::middleware::{scope, RequireScope};
+use crate::models::{
+    CheckoutView, CreateInvoiceRequest, InvoiceListQuery, InvoiceView, Merchant, PayInvoiceRequest, StepUpAction,
+    StepUpKind,
+};
+use crate::services::invoices::remaining;
+use crate::services::non_custodial::asset_code;
+use crate::services::{Actor, AuditLog, InvoiceError, InvoiceService};
+
+pub async fn create_invoice(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<CreateInvoiceRequest>,
+) -> Result<Json<InvoiceView>, StatusCode> {
+    let merchant = own_merchant(&pool, &actor.user_id).await?;
+    create(&pool, &actor, &merchant, &payload).await.map(Json)
+}
+
+pub async fn list_invoices(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Query(query): Query<InvoiceListQuery>,
+) -> Result<Json<Vec<InvoiceView>>, StatusCode> {
+    let merchant = own_merchant(&pool, &user_id).await?;
+    InvoiceService::list(&pool, &merchant.id, query.status.as_deref())
+        .await
+        .map(Json)
+        .map_err(invoice_status)
+}
+
+pub async fn get_invoice(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Path(id): Path<String>,
+) -> Result<Json<InvoiceView>, StatusCode> {
+    let merchant = own_merchant(&pool, &user_id).await?;
+    InvoiceService::find(&pool, &merchant.id, &id)
+        .await
+        .map(Json)
+        .map_err(invoice_status)
+}
+
+pub async fn merchant_create_invoice(
+    State(pool): State<SqlitePool>,
+    key: RequireScope<scope::CreateInvoices>,
+    actor: Actor,
+    Json(payload): Json<CreateInvoiceRequest>,
+) -> Result<Json<InvoiceView>, StatusCode> {
+    let merchant = own_merchant(&pool, &key.context.owner_user_id).await?;
+    create(&pool, &actor, &merchant, &payload).await.map(Json)
+}
+
+pub async fn merchant_list_invoices(
+    State(pool): State<SqlitePool>,
+    key: RequireScope<scope::ReadInvoices>,
+    Query(query): Query<InvoiceListQuery>,
+) -> Result<Json<Vec<InvoiceView>>, StatusCode> {
+    InvoiceService::list(&pool, &key.context.merchant_id, query.status.as_deref())
+        .await
+        .map(Json)
+        .map_err(invoice_status)
+}
+
+pub async fn merchant_get_invoice(
+    State(pool): State<SqlitePool>,
+    key: RequireScope<scope::ReadInvoices>,
+    Path(id): Path<String>,
+) -> Result<Json<InvoiceView>, StatusCode> {
+    InvoiceService::find(&pool, &key.context.merchant_id, &id)
+        .await
+        .map(Json)
+        .map_err(invoice_status)
+}
+
+/// Public checkout page: what is owed and how to pay it.
+pub async fn get_checkout(
+    State(pool): State<SqlitePool>,
+    Path(id): Path<String>,
+) -> Result<Json<CheckoutView>, StatusCode> {
+    InvoiceService::checkout(&pool, &id)
+        .await
+        .map(Json)
+        .map_err(invoice_status)
+}
+
+/// Pays what is left on the invoice from the user's wallet. Needs the PIN
+/// and, above the step-up threshold, a `transfer` proof for that amount to
+/// the invoice's destination.
+pub async fn pay_checkout(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(payload): Json<PayInvoiceRequest>,
+) -> Result<Json<CheckoutView>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let invoice = InvoiceService::payable(&pool, &actor.user_id, &id)
+        .await
+        .map_err(invoice_status)?;
+    require_pin(&pool, &actor.user_id, &payload.pin).await?;
+    let action = StepUpAction {
+        action: StepUpKind::Transfer,
+        amount: Some(remaining(&invoice)),
+        currency: Some(asset_code(&invoice.currency)),
+        destination: Some(invoice.destination.clone()),
+    };
+    require_step_up(&pool, &actor.user_id, &headers, &action).await?;
+
+    let paid = InvoiceService::pay(&pool, &actor.user_id, &id)
+        .await
+        .map_err(invoice_status)?;
+    AuditLog::record(
+        &pool,
+        &actor,
+        "invoice.pay",
+        "invoice",
+        Some(&paid.id),
+        json!({
+            "amount": remaining(&invoice),
+            "currency": paid.currency,
+            "status": paid.status,
+        }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(paid))
+}
+
+async fn create(
+    pool: &SqlitePool,
+    actor: &Actor,
+    merchant: &Merchant,
+    payload: &CreateInvoiceRequest,
+) -> Result<InvoiceView, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let created = InvoiceService::create(pool, merchant, payload)
+        .await
+        .map_err(invoice_status)?;
+    AuditLog::record(
+        pool,
+        actor,
+        "invoice.create",
+        "invoice",
+        Some(&created.invoice.id),
+        json!({
+            "merchant_id": merchant.id,
+            "reference": created.invoice.reference,
+            "amount_due": created.invoice.amount_due,
+            "currency": created.invoice.currency,
+        }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(created)
+}
+
+fn invoice_status(error: InvoiceError) -> StatusCode {
+    match error {
+        InvoiceError::NotFound => StatusCode::NOT_FOUND,
+        InvoiceError::InvalidCurrency(_) | InvoiceError::OwnInvoice => StatusCode::BAD_REQUEST,
+        InvoiceError::NoReceivingAccount => StatusCode::UNPROCESSABLE_ENTITY,
+        InvoiceError::NotPayable(_) | InvoiceError::InsufficientFunds => StatusCode::CONFLICT,
+        InvoiceError::Expired => StatusCode::GONE,
+        InvoiceError::Payment(_) => StatusCode::BAD_GATEWAY,
+        InvoiceError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
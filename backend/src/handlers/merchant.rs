@@ -0,0 +1,304 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::handlers::pin::require_pin;
+use crate::handlers::wallet::{audit_balance_change, WalletBalance};
+use crate::middleware::{scope, RequireScope};
+use crate::models::{
+    ApiKeyResponse, ApiKeyWithSecret, ApiScope, CreateApiKeyRequest, CreateMerchantRequest, Merchant,
+    MerchantPayoutRequest, RotateApiKeyRequest, UpdateMerchantRequest,
+};
+use crate::services::signing::sha256_hex;
+use crate::services::{Actor, AuditLog, MerchantError, MerchantService, WalletService};
+
+pub async fn create_merchant(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<CreateMerchantRequest>,
+) -> Result<Json<Merchant>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let merchant = MerchantService::create(&pool, &actor.user_id, &payload)
+        .await
+        .map_err(merchant_status)?;
+    audit(&pool, &actor, "merchant.create", "merchant", &merchant.id, json!({ "business_name": merchant.business_name })).await?;
+    Ok(Json(merchant))
+}
+
+pub async fn get_merchant(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Merchant>, StatusCode> {
+    own_merchant(&pool, &user_id).await.map(Json)
+}
+
+/// Updates the merchant's profile.
+pub async fn update_merchant(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<UpdateMerchantRequest>,
+) -> Result<Json<Merchant>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let merchant = own_merchant(&pool, &actor.user_id).await?;
+    let updated = MerchantService::update(&pool, &merchant.id, &payload)
+        .await
+        .map_err(merchant_status)?;
+    let details = json!({
+        "business_name": updated.business_name,
+        "website": updated.website,
+    });
+    audit(&pool, &actor, "merchant.update", "merchant", &updated.id, details).await?;
+    Ok(Json(updated))
+}
+
+pub async fn list_api_keys(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Vec<ApiKeyResponse>>, StatusCode> {
+    let merchant = own_merchant(&pool, &user_id).await?;
+    let keys = MerchantService::list_keys(&pool, &merchant.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(keys.into_iter().map(ApiKeyResponse::from).collect()))
+}
+
+pub async fn create_api_key(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<CreateApiKeyRequest>,
+) -> Result<Json<ApiKeyWithSecret>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let merchant = own_merchant(&pool, &actor.user_id).await?;
+    // A key that can pay out moves money without the PIN, so issuing one needs it
+    if payload.scopes.contains(&ApiScope::CreatePayouts) {
+        require_pin(&pool, &actor.user_id, payload.pin.as_deref().unwrap_or_default()).await?;
+    }
+
+    let (key, secret) = MerchantService::create_key(&pool, &merchant.id, &payload)
+        .await
+        .map_err(merchant_status)?;
+    audit(
+        &pool,
+        &actor,
+        "api_key.create",
+        "api_key",
+        &key.id,
+        json!({ "merchant_id": merchant.id, "scopes": key.scopes, "ip_allowlist": key.ip_allowlist }),
+    )
+    .await?;
+
+    println!("🔑 API key {} issued for merchant {}", key.id, merchant.id);
+    Ok(Json(ApiKeyWithSecret {
+        key: ApiKeyResponse::from(key),
+        secret,
+    }))
+}
+
+pub async fn rotate_api_key(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(key_id): Path<String>,
+    payload: Option<Json<RotateApiKeyRequest>>,
+) -> Result<Json<ApiKeyWithSecret>, StatusCode> {
+    let Json(payload) = payload.unwrap_or_default();
+    let merchant = own_merchant(&pool, &actor.user_id).await?;
+
+    let current = MerchantService::list_keys(&pool, &merchant.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .find(|key| key.id == key_id)
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if ApiScope::parse_list(&current.scopes).contains(&ApiScope::CreatePayouts) {
+        require_pin(&pool, &actor.user_id, payload.pin.as_deref().unwrap_or_default()).await?;
+    }
+
+    let (key, secret) = MerchantService::rotate_key(&pool, &merchant.id, &key_id)
+        .await
+        .map_err(merchant_status)?;
+    audit(&pool, &actor, "api_key.rotate", "api_key", &key_id, json!({ "merchant_id": merchant.id, "rotated_to": key.id })).await?;
+
+    Ok(Json(ApiKeyWithSecret {
+        key: ApiKeyResponse::from(key),
+        secret,
+    }))
+}
+
+pub async fn revoke_api_key(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(key_id): Path<String>,
+) -> Result<Json<ApiKeyResponse>, StatusCode> {
+    let merchant = own_merchant(&pool, &actor.user_id).await?;
+    let key = MerchantService::revoke_key(&pool, &merchant.id, &key_id)
+        .await
+        .map_err(merchant_status)?;
+    audit(&pool, &actor, "api_key.revoke", "api_key", &key.id, json!({ "merchant_id": merchant.id })).await?;
+
+    println!("🗑️ API key {} revoked", key.id);
+    Ok(Json(ApiKeyResponse::from(key)))
+}
+
+/// Balance of the merchant's wallet, for API-key callers.
+pub async fn merchant_balance(
+    State(pool): State<SqlitePool>,
+    key: RequireScope<scope::ReadBalance>,
+) -> Result<Json<WalletBalance>, StatusCode> {
+    let wallet_service = WalletService::new();
+    let user_id = &key.context.owner_user_id;
+
+    let xlm_balance = wallet_service
+        .get_wallet_balance(&pool, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let wallet_id: String = sqlx::query_scalar("SELECT stellar_public_key FROM wallets WHERE user_id = ?")
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .unwrap_or_default();
+
+    Ok(Json(WalletBalance {
+        xlm_balance,
+        kes_equivalent: wallet_service.convert_xlm_to_kes(xlm_balance),
+        wallet_id,
+    }))
+}
+
+/// Pays out from the merchant's wallet. The signed request stands in for the
+/// PIN and step-up proof a signed-in user would need. An `Idempotency-Key`
+/// header is required; a retry with the same key and body gets the first
+/// answer back instead of paying again.
+pub async fn merchant_payout(
+    State(pool): State<SqlitePool>,
+    key: RequireScope<scope::CreatePayouts>,
+    actor: Actor,
+    headers: HeaderMap,
+    Json(payload): Json<MerchantPayoutRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let idempotency_key = headers
+        .get("Idempotency-Key")
+        .and_then(|v| v.to_str().ok())
+        .map(str::trim)
+        .filter(|v| (1..=255).contains(&v.len()))
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let merchant_id = &key.context.merchant_id;
+    let request_hash = sha256_hex(
+        json!({
+            "xlm_amount": payload.xlm_amount,
+            "to_wallet_id": payload.to_wallet_id,
+            "reference": payload.reference,
+        })
+        .to_string()
+        .as_bytes(),
+    );
+    if let Some(response) = MerchantService::begin_payout(&pool, merchant_id, idempotency_key, &request_hash)
+        .await
+        .map_err(merchant_status)?
+    {
+        println!("🔁 Merchant {} payout replayed for key {}", merchant_id, idempotency_key);
+        return Ok(Json(response));
+    }
+
+    let wallet_service = WalletService::new();
+    let user_id = &key.context.owner_user_id;
+
+    let balance = wallet_service.get_wallet_balance(&pool, user_id).await.map_err(|e| e.to_string());
+    let refused = match balance {
+        Ok(balance) if balance < payload.xlm_amount => Some(StatusCode::CONFLICT),
+        Err(_) => Some(StatusCode::INTERNAL_SERVER_ERROR),
+        Ok(_) => None,
+    };
+    if let Some(status) = refused {
+        // Nothing was sent, so the key is freed for a retry
+        let _ = MerchantService::release_payout(&pool, merchant_id, idempotency_key).await;
+        return Err(status);
+    }
+    let balance = balance.unwrap_or_default();
+
+    // On an error the payout may or may not have gone out, so the key stays
+    // claimed and a retry answers 409 rather than risking a second payment
+    let tx_hash = wallet_service
+        .transfer_to_wallet(&pool, user_id, &payload.to_wallet_id, payload.xlm_amount)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    println!(
+        "🏪 Merchant {} payout of {} XLM via key {}",
+        merchant_id, payload.xlm_amount, key.context.key_id
+    );
+    let details = json!({
+        "merchant_id": merchant_id,
+        "key_id": key.context.key_id,
+        "idempotency_key": idempotency_key,
+        "xlm_amount": payload.xlm_amount,
+        "to_wallet_id": payload.to_wallet_id,
+        "reference": payload.reference,
+        "tx_hash": tx_hash,
+    });
+    audit_balance_change(&pool, &actor, "merchant.payout", details, balance).await;
+
+    let response = json!({
+        "success": true,
+        "xlm_amount": payload.xlm_amount,
+        "to_wallet_id": payload.to_wallet_id,
+        "reference": payload.reference,
+        "tx_hash": tx_hash
+    });
+    MerchantService::finish_payout(&pool, merchant_id, idempotency_key, &response)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(response))
+}
+
+pub(crate) async fn own_merchant(pool: &SqlitePool, user_id: &str) -> Result<Merchant, StatusCode> {
+    MerchantService::for_user(pool, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn audit(
+    pool: &SqlitePool,
+    actor: &Actor,
+    action: &str,
+    target_type: &str,
+    target_id: &str,
+    details: Value,
+) -> Result<(), StatusCode> {
+    AuditLog::record(pool, actor, action, target_type, Some(target_id), details)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn merchant_status(error: MerchantError) -> StatusCode {
+    match error {
+        MerchantError::NotFound => StatusCode::NOT_FOUND,
+        MerchantError::AlreadyExists | MerchantError::KeyInactive | MerchantError::IdempotencyInProgress => {
+            StatusCode::CONFLICT
+        }
+        MerchantError::IdempotencyMismatch => StatusCode::UNPROCESSABLE_ENTITY,
+        MerchantError::InvalidAllowlist(_) => StatusCode::BAD_REQUEST,
+        MerchantError::BadSignature | MerchantError::StaleTimestamp => StatusCode::UNAUTHORIZED,
+        MerchantError::IpNotAllowed(_) => StatusCode::FORBIDDEN,
+        MerchantError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,217 @@
+use axum::{
+    extract::{ConnectInfo, Extension, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use std::net::SocketAddr;
+use validator::Validate;
+
+use crate::handlers::auth::{find_user, otp_status, start_session};
+use crate::models::{MfaChallengeRequest, StepUpAction, StepUpMethod, StepUpProofRequest, TotpCodeRequest};
+use crate::services::notification_templates::Locale;
+use crate::services::{Actor, AuditLog, MfaError, MfaService, OtpPurpose, OtpService, StepUpService};
+
+pub async fn enroll_totp(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let user = find_user(&pool, &user_id).await?;
+
+    let enrolment = MfaService::enroll_totp(&pool, &user_id, &user.email)
+        .await
+        .map_err(mfa_status)?;
+
+    Ok(Json(json!({
+        "secret": enrolment.secret,
+        "provisioning_uri": enrolment.provisioning_uri
+    })))
+}
+
+pub async fn confirm_totp(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let recovery_codes = MfaService::confirm_totp(&pool, &actor.user_id, &payload.code)
+        .await
+        .map_err(mfa_status)?;
+    AuditLog::record_after(
+        &pool,
+        &actor,
+        "auth.mfa_enable",
+        "user",
+        Some(&actor.user_id),
+        json!({ "method": "totp" }),
+        None,
+    )
+    .await;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Two-factor authentication enabled. Store these recovery codes somewhere safe; they are shown only once.",
+        "recovery_codes": recovery_codes
+    })))
+}
+
+pub async fn disable_totp(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<TotpCodeRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    MfaService::disable_totp(&pool, &actor.user_id, &payload.code)
+        .await
+        .map_err(mfa_status)?;
+    AuditLog::record_after(
+        &pool,
+        &actor,
+        "auth.mfa_disable",
+        "user",
+        Some(&actor.user_id),
+        json!({ "method": "totp" }),
+        None,
+    )
+    .await;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Two-factor authentication disabled"
+    })))
+}
+
+/// Second step of a login for users with two-factor authentication.
+pub async fn verify_mfa_challenge(
+    State(pool): State<SqlitePool>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<MfaChallengeRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let user_id = match MfaService::complete_challenge(&pool, &payload.mfa_token, &payload.code).await {
+        Ok(user_id) => user_id,
+        Err(MfaError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(MfaError::Locked(_)) => return Err(StatusCode::LOCKED),
+        Err(_) => return Err(StatusCode::UNAUTHORIZED),
+    };
+
+    let user = find_user(&pool, &user_id).await?;
+    start_session(&pool, user, &headers, peer, "mfa").await
+}
+
+/// Sends an SMS code for step-up to the user's verified phone number.
+pub async fn request_step_up_otp(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let user = find_user(&pool, &user_id).await?;
+    let phone_number = user
+        .phone_number
+        .filter(|_| user.phone_verified_at.is_some())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    OtpService::new()
+        .send(
+            &pool,
+            &phone_number,
+            OtpPurpose::StepUp,
+            Some(&user_id),
+            Locale::from_preference(Some(&user.preferred_language)),
+        )
+        .await
+        .map_err(otp_status)?;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Verification code sent"
+    })))
+}
+
+/// Exchanges a fresh TOTP or SMS code for a single-use token that authorises
+/// exactly the described operation.
+pub async fn create_step_up_proof(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<StepUpProofRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let verified = match payload.method {
+        StepUpMethod::Totp => match MfaService::verify_code_limited(&pool, &user_id, &payload.code).await {
+            Ok(verified) => verified,
+            Err(e @ (MfaError::NotEnrolled | MfaError::Locked(_))) => return Err(mfa_status(e)),
+            Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        },
+        StepUpMethod::Otp => {
+            let user = find_user(&pool, &user_id).await?;
+            let phone_number = user
+                .phone_number
+                .filter(|_| user.phone_verified_at.is_some())
+                .ok_or(StatusCode::BAD_REQUEST)?;
+            OtpService::new()
+                .verify(&pool, &phone_number, OtpPurpose::StepUp, &payload.code)
+                .await
+                .map_err(otp_status)?
+                .as_deref()
+                == Some(user_id.as_str())
+        }
+    };
+    if !verified {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    let step_up_token = StepUpService::issue(&pool, &user_id, &payload.action)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(json!({
+        "step_up_token": step_up_token,
+        "expires_in": 300
+    })))
+}
+
+/// Rejects a high-value operation with 403 unless the request carries an
+/// `X-Step-Up-Token` issued for exactly this operation.
+pub(crate) async fn require_step_up(
+    pool: &SqlitePool,
+    user_id: &str,
+    headers: &HeaderMap,
+    action: &StepUpAction,
+) -> Result<(), StatusCode> {
+    if !StepUpService::required(action) {
+        return Ok(());
+    }
+
+    let token = headers
+        .get("X-Step-Up-Token")
+        .and_then(|v| v.to_str().ok())
+        .ok_or(StatusCode::FORBIDDEN)?;
+
+    let valid = StepUpService::consume(pool, user_id, token, action)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if valid {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn mfa_status(error: MfaError) -> StatusCode {
+    match error {
+        MfaError::NotEnrolled | MfaError::InvalidCode => StatusCode::BAD_REQUEST,
+        MfaError::AlreadyEnabled => StatusCode::CONFLICT,
+        MfaError::ChallengeExpired => StatusCode::UNAUTHORIZED,
+        MfaError::Locked(_) => StatusCode::LOCKED,
+        MfaError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -1,16 +1,50 @@
+pub mod account;
+pub mod admin;
 pub mod auth;
 pub mod fonbnk;
 pub mod fonbnk_simple;
+pub mod invoice;
+pub mod merchant;
+pub mod mfa;
+pub mod multisig;
+pub mod non_custodial;
+pub mod notification;
+pub mod payment_request;
+pub mod payout;
+pub mod pin;
+pub mod scheduled_transfer;
+pub mod sep10;
+pub mod sms;
 pub mod stellar;
 pub mod transaction;
 pub mod user;
 pub mod wallet;
 pub mod wallet_sdk;
+pub mod webhook;
 
+pub use account::*;
+pub use admin::*;
 pub use auth::*;
+pub use fonbnk::create_widget_url as create_fonbnk_widget_url;
 pub use fonbnk_simple::{deposit_via_fonbnk, get_fonbnk_rate};
+pub use invoice::*;
+pub use merchant::*;
+pub use mfa::*;
+pub use multisig::*;
+pub use non_custodial::*;
+pub use notification::*;
+pub use payment_request::*;
+pub use payout::*;
+pub use pin::*;
+pub use scheduled_transfer::*;
+pub use sep10::*;
+pub use sms::*;
 pub use stellar::*;
 pub use transaction::*;
 pub use user::*;
 pub use wallet::*;
-pub use wallet_sdk::{create_wallet as create_wallet_sdk, get_wallet_balance_sdk, send_payment_sdk, fund_testnet_sdk, create_trustline_sdk};
\ No newline at end of file
+pub use webhook::*;
+pub use wallet_sdk::{
+    create_trustline_sdk, create_wallet as create_wallet_sdk, export_wallet_key_sdk, fund_testnet_sdk,
+    get_wallet_balance_sdk, list_wallets_sdk, restore_wallet_sdk, send_payment_sdk,
+};
\ No newline at end of file
@@ -0,0 +1,127 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::models::{MultisigApprovalView, PrepareStellarTransactionRequest, SubmitStellarTransactionRequest};
+use crate::services::stellar_tx::StellarTxError;
+use crate::services::{Actor, AuditLog, MultisigError, MultisigService};
+
+/// Builds a transaction on a multisig account for its co-signers to sign.
+pub async fn propose_multisig_approval(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<PrepareStellarTransactionRequest>,
+) -> Result<Json<MultisigApprovalView>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let proposed = MultisigService::propose(&pool, &actor.user_id, &payload)
+        .await
+        .map_err(multisig_status)?;
+    AuditLog::record(
+        &pool,
+        &actor,
+        "stellar.approval_propose",
+        "multisig_approval",
+        Some(&proposed.approval.id),
+        json!({
+            "kind": proposed.approval.kind,
+            "source": proposed.approval.source_public_key,
+            "threshold": proposed.approval.threshold,
+        }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(proposed))
+}
+
+pub async fn list_multisig_approvals(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Vec<MultisigApprovalView>>, StatusCode> {
+    MultisigService::list(&pool, &user_id)
+        .await
+        .map(Json)
+        .map_err(multisig_status)
+}
+
+pub async fn get_multisig_approval(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Path(id): Path<String>,
+) -> Result<Json<MultisigApprovalView>, StatusCode> {
+    MultisigService::find(&pool, &user_id, &id)
+        .await
+        .map(Json)
+        .map_err(multisig_status)
+}
+
+/// Adds the caller's signatures; the response says whether that was enough
+/// to submit the transaction.
+pub async fn sign_multisig_approval(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(id): Path<String>,
+    Json(payload): Json<SubmitStellarTransactionRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let signed = MultisigService::sign(&pool, &actor.user_id, &id, &payload.signed_xdr)
+        .await
+        .map_err(multisig_status)?;
+
+    let submitted = signed.approval.status == "submitted";
+    let details = json!({
+        "source": signed.approval.source_public_key,
+        "weight": signed.weight,
+        "threshold": signed.approval.threshold,
+        "submitted": submitted,
+    });
+    AuditLog::record_after(
+        &pool,
+        &actor,
+        "stellar.approval_sign",
+        "multisig_approval",
+        Some(&signed.approval.id),
+        details,
+        None,
+    )
+    .await;
+
+    Ok(Json(json!({
+        "success": true,
+        "submitted": submitted,
+        "approval": signed
+    })))
+}
+
+fn multisig_status(error: MultisigError) -> StatusCode {
+    match error {
+        MultisigError::NotFound => StatusCode::NOT_FOUND,
+        MultisigError::NotPending => StatusCode::CONFLICT,
+        MultisigError::AccountNotRegistered | MultisigError::NotASigner => StatusCode::FORBIDDEN,
+        MultisigError::Expired => StatusCode::GONE,
+        MultisigError::ThresholdUnreachable | MultisigError::EnvelopeMismatch | MultisigError::BadSignature => {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        MultisigError::Stellar(e) => match e {
+            StellarTxError::InvalidAddress(_)
+            | StellarTxError::InvalidAsset(_)
+            | StellarTxError::InvalidAmount(_)
+            | StellarTxError::InvalidMemo
+            | StellarTxError::InvalidOperation(_)
+            | StellarTxError::MalformedXdr => StatusCode::BAD_REQUEST,
+            StellarTxError::AccountNotFound(_) | StellarTxError::Rejected(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            StellarTxError::Horizon(message) => {
+                println!("⚠️ Horizon error: {}", message);
+                StatusCode::BAD_GATEWAY
+            }
+        },
+        MultisigError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
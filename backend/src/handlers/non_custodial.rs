@@ -0,0 +1,146 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::models::{
+    ExternalAccount, PrepareStellarTransactionRequest, RegisterExternalAccountRequest, StellarTransaction,
+    SubmitStellarTransactionRequest,
+};
+use crate::services::stellar_tx::StellarTxError;
+use crate::services::{Actor, AuditLog, NonCustodialError, NonCustodialService, Sep10Error};
+
+pub async fn register_external_account(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<RegisterExternalAccountRequest>,
+) -> Result<Json<ExternalAccount>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let account = NonCustodialService::register_account(&pool, &actor.user_id, &payload)
+        .await
+        .map_err(non_custodial_status)?;
+    AuditLog::record(
+        &pool,
+        &actor,
+        "stellar.account_register",
+        "external_account",
+        Some(&account.id),
+        json!({ "public_key": account.public_key }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(account))
+}
+
+pub async fn list_external_accounts(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Vec<ExternalAccount>>, StatusCode> {
+    NonCustodialService::list_accounts(&pool, &user_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Returns the unsigned envelope and the hash the client has to sign.
+pub async fn prepare_stellar_transaction(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<PrepareStellarTransactionRequest>,
+) -> Result<Json<StellarTransaction>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    NonCustodialService::prepare(&pool, &user_id, &payload)
+        .await
+        .map(Json)
+        .map_err(non_custodial_status)
+}
+
+pub async fn get_stellar_transaction(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Path(id): Path<String>,
+) -> Result<Json<StellarTransaction>, StatusCode> {
+    NonCustodialService::find(&pool, &user_id, &id)
+        .await
+        .map(Json)
+        .map_err(non_custodial_status)
+}
+
+/// Takes the envelope back with the source account's signature and submits
+/// it. The signature stands in for the PIN and step-up a server-held key
+/// would need.
+pub async fn submit_stellar_transaction(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(id): Path<String>,
+    Json(payload): Json<SubmitStellarTransactionRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    let submitted = NonCustodialService::submit(&pool, &actor.user_id, &id, &payload.signed_xdr)
+        .await
+        .map_err(non_custodial_status)?;
+
+    let details = json!({
+        "kind": submitted.kind,
+        "source": submitted.source_public_key,
+        "tx_hash": submitted.tx_hash,
+        "transaction_id": submitted.transaction_id,
+    });
+    AuditLog::record_after(
+        &pool,
+        &actor,
+        "stellar.submit",
+        "stellar_transaction",
+        Some(&submitted.id),
+        details,
+        None,
+    )
+    .await;
+
+    Ok(Json(json!({
+        "success": true,
+        "transaction": submitted
+    })))
+}
+
+fn non_custodial_status(error: NonCustodialError) -> StatusCode {
+    match error {
+        NonCustodialError::NotFound => StatusCode::NOT_FOUND,
+        NonCustodialError::AlreadyRegistered | NonCustodialError::NotPending => StatusCode::CONFLICT,
+        NonCustodialError::AccountNotRegistered => StatusCode::FORBIDDEN,
+        NonCustodialError::Expired => StatusCode::GONE,
+        NonCustodialError::EnvelopeMismatch | NonCustodialError::BadSignature => StatusCode::UNPROCESSABLE_ENTITY,
+        NonCustodialError::Stellar(e) => match e {
+            StellarTxError::InvalidAddress(_)
+            | StellarTxError::InvalidAsset(_)
+            | StellarTxError::InvalidAmount(_)
+            | StellarTxError::InvalidMemo
+            | StellarTxError::InvalidOperation(_)
+            | StellarTxError::MalformedXdr => StatusCode::BAD_REQUEST,
+            StellarTxError::AccountNotFound(_) | StellarTxError::Rejected(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            StellarTxError::Horizon(message) => {
+                println!("⚠️ Horizon error: {}", message);
+                StatusCode::BAD_GATEWAY
+            }
+        },
+        NonCustodialError::Unproven(e) => match e {
+            Sep10Error::Stellar(StellarTxError::Horizon(message)) => {
+                println!("⚠️ Horizon error: {}", message);
+                StatusCode::BAD_GATEWAY
+            }
+            Sep10Error::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            _ => StatusCode::FORBIDDEN,
+        },
+        NonCustodialError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
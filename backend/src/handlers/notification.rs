@@ -0,0 +1,39 @@
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    Json,
+};
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::models::{NotificationPreferences, UpdateNotificationPreferences};
+use crate::services::outbound::{allowed_hosts, check_url};
+use crate::services::NotificationDispatcher;
+
+pub async fn get_notification_preferences(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<NotificationPreferences>, StatusCode> {
+    NotificationDispatcher::preferences(&pool, &user_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub async fn update_notification_preferences(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<UpdateNotificationPreferences>,
+) -> Result<Json<NotificationPreferences>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if let Some(url) = &payload.webhook_url {
+        check_url(url, &allowed_hosts()).await.map_err(|_| StatusCode::BAD_REQUEST)?;
+    }
+
+    NotificationDispatcher::update_preferences(&pool, &user_id, &payload)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
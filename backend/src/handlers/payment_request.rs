@@ -0,0 +1,197 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::handlers::mfa::require_step_up;
+use crate::handlers::pin::require_pin;
+use crate::middleware::{scope, RequireScope};
+use crate::models::{
+    CreatePaymentRequest, PayPaymentRequest, PaymentRequestListQuery, PaymentRequestQrQuery, PaymentRequestView,
+    StepUpAction, StepUpKind,
+};
+use crate::services::non_custodial::asset_code;
+use crate::services::{Actor, AuditLog, PaymentRequestError, PaymentRequestService};
+
+/// Asks someone for money: the user given as `payer`, or anyone with the link.
+pub async fn create_payment_request(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<CreatePaymentRequest>,
+) -> Result<Json<PaymentRequestView>, StatusCode> {
+    create(&pool, &actor, &actor.user_id, &payload).await.map(Json)
+}
+
+/// Same as `POST /payment-requests`, for the merchant's owner.
+pub async fn merchant_payment_request(
+    State(pool): State<SqlitePool>,
+    key: RequireScope<scope::CreatePaymentRequests>,
+    actor: Actor,
+    Json(payload): Json<CreatePaymentRequest>,
+) -> Result<Json<PaymentRequestView>, StatusCode> {
+    create(&pool, &actor, &key.context.owner_user_id, &payload).await.map(Json)
+}
+
+pub async fn list_payment_requests(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Query(query): Query<PaymentRequestListQuery>,
+) -> Result<Json<Vec<PaymentRequestView>>, StatusCode> {
+    let received = match query.direction.as_deref() {
+        None | Some("sent") => false,
+        Some("received") => true,
+        Some(_) => return Err(StatusCode::BAD_REQUEST),
+    };
+    PaymentRequestService::list(&pool, &user_id, received)
+        .await
+        .map(Json)
+        .map_err(payment_request_status)
+}
+
+pub async fn get_payment_request(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Path(id): Path<String>,
+) -> Result<Json<PaymentRequestView>, StatusCode> {
+    PaymentRequestService::find(&pool, &user_id, &id)
+        .await
+        .map(Json)
+        .map_err(payment_request_status)
+}
+
+/// The request's SEP-7 URI as a QR code, `?format=png` (default) or `svg`.
+pub async fn payment_request_qr(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Path(id): Path<String>,
+    Query(query): Query<PaymentRequestQrQuery>,
+) -> Result<Response, StatusCode> {
+    let format = query.format.unwrap_or_else(|| "png".to_string());
+    let content_type = match format.as_str() {
+        "png" => "image/png",
+        "svg" => "image/svg+xml",
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let view = PaymentRequestService::find(&pool, &user_id, &id)
+        .await
+        .map_err(payment_request_status)?;
+    let image = PaymentRequestService::qr_code(&view, &format).map_err(payment_request_status)?;
+
+    Ok(([(header::CONTENT_TYPE, content_type)], image).into_response())
+}
+
+/// Pays the request from the user's wallet. Needs the PIN and, above the
+/// step-up threshold, a `transfer` proof for the amount to the requester.
+pub async fn pay_payment_request(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(payload): Json<PayPaymentRequest>,
+) -> Result<Json<PaymentRequestView>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let request = PaymentRequestService::find_request(&pool, &actor.user_id, &id)
+        .await
+        .map_err(payment_request_status)?;
+    if request.requester_id == actor.user_id {
+        return Err(payment_request_status(PaymentRequestError::OwnRequest));
+    }
+    require_pin(&pool, &actor.user_id, &payload.pin).await?;
+    let action = StepUpAction {
+        action: StepUpKind::Transfer,
+        amount: Some(request.amount),
+        currency: Some(asset_code(&request.currency)),
+        destination: Some(request.destination.clone()),
+    };
+    require_step_up(&pool, &actor.user_id, &headers, &action).await?;
+
+    let paid = PaymentRequestService::pay(&pool, &actor.user_id, &id)
+        .await
+        .map_err(payment_request_status)?;
+    record(&pool, &actor, "payment_request.pay", &paid).await?;
+    Ok(Json(paid))
+}
+
+pub async fn decline_payment_request(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Result<Json<PaymentRequestView>, StatusCode> {
+    let declined = PaymentRequestService::decline(&pool, &actor.user_id, &id)
+        .await
+        .map_err(payment_request_status)?;
+    record(&pool, &actor, "payment_request.decline", &declined).await?;
+    Ok(Json(declined))
+}
+
+pub async fn cancel_payment_request(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Result<Json<PaymentRequestView>, StatusCode> {
+    let cancelled = PaymentRequestService::cancel(&pool, &actor.user_id, &id)
+        .await
+        .map_err(payment_request_status)?;
+    record(&pool, &actor, "payment_request.cancel", &cancelled).await?;
+    Ok(Json(cancelled))
+}
+
+async fn create(
+    pool: &SqlitePool,
+    actor: &Actor,
+    requester_id: &str,
+    payload: &CreatePaymentRequest,
+) -> Result<PaymentRequestView, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let created = PaymentRequestService::create(pool, requester_id, payload)
+        .await
+        .map_err(payment_request_status)?;
+    record(pool, actor, "payment_request.create", &created).await?;
+    Ok(created)
+}
+
+async fn record(pool: &SqlitePool, actor: &Actor, action: &str, view: &PaymentRequestView) -> Result<(), StatusCode> {
+    AuditLog::record(
+        pool,
+        actor,
+        action,
+        "payment_request",
+        Some(&view.request.id),
+        json!({
+            "amount": view.request.amount,
+            "currency": view.request.currency,
+            "payer": view.request.payer_contact,
+            "status": view.request.status,
+            "transaction_id": view.request.transaction_id,
+        }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn payment_request_status(error: PaymentRequestError) -> StatusCode {
+    match error {
+        PaymentRequestError::NotFound => StatusCode::NOT_FOUND,
+        PaymentRequestError::InvalidCurrency(_) | PaymentRequestError::OwnRequest => StatusCode::BAD_REQUEST,
+        PaymentRequestError::UnknownPayer | PaymentRequestError::NoReceivingAccount => {
+            StatusCode::UNPROCESSABLE_ENTITY
+        }
+        PaymentRequestError::NotPayer => StatusCode::FORBIDDEN,
+        PaymentRequestError::NotOpen(_) | PaymentRequestError::InsufficientFunds => StatusCode::CONFLICT,
+        PaymentRequestError::Expired => StatusCode::GONE,
+        PaymentRequestError::Payment(_) => StatusCode::BAD_GATEWAY,
+        PaymentRequestError::Qr(_) | PaymentRequestError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
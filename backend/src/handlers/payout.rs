@@ -0,0 +1,186 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::json;
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::handlers::admin::csv_field;
+use crate::handlers::mfa::require_step_up;
+use crate::handlers::pin::require_pin;
+use crate::models::{CreatePayoutBatchRequest, PayoutBatchView, RetryPayoutBatchRequest, StepUpAction, StepUpKind};
+use crate::services::non_custodial::asset_code;
+use crate::services::{Actor, AuditLog, CustodialWalletService, PayoutError, PayoutService};
+
+/// Checks every row, then queues the batch for payment from the given SDK
+/// wallet. Needs the PIN and, above the step-up threshold, a `transfer` proof
+/// for the batch total with no destination. Rows that can't be paid are
+/// listed in a `422` response and nothing is stored.
+pub async fn create_payout_batch(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    headers: HeaderMap,
+    Json(payload): Json<CreatePayoutBatchRequest>,
+) -> Result<Json<PayoutBatchView>, Response> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST.into_response());
+    }
+
+    let wallet = CustodialWalletService::find_owned(&pool, &actor.user_id, &payload.wallet_id)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND.into_response())?;
+    require_pin(&pool, &actor.user_id, &payload.pin)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let payouts = PayoutService::validate(&pool, &payload).await.map_err(payout_response)?;
+    let total = PayoutService::total(&payouts);
+    let asset = PayoutService::asset(&payload);
+    let action = StepUpAction {
+        action: StepUpKind::Transfer,
+        amount: Some(total),
+        currency: Some(asset_code(&asset)),
+        destination: None,
+    };
+    require_step_up(&pool, &actor.user_id, &headers, &action)
+        .await
+        .map_err(IntoResponse::into_response)?;
+
+    let batch = PayoutService::create(&pool, &actor.user_id, &wallet, &payload, payouts)
+        .await
+        .map_err(payout_response)?;
+    AuditLog::record(
+        &pool,
+        &actor,
+        "payout.batch_create",
+        "payout_batch",
+        Some(&batch.batch.id),
+        json!({
+            "wallet_id": wallet.id,
+            "asset": asset,
+            "payments": batch.batch.row_count,
+            "total": total,
+        }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR.into_response())?;
+
+    Ok(Json(batch))
+}
+
+pub async fn list_payout_batches(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Vec<PayoutBatchView>>, StatusCode> {
+    PayoutService::list(&pool, &user_id).await.map(Json).map_err(payout_status)
+}
+
+pub async fn get_payout_batch(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Path(id): Path<String>,
+) -> Result<Json<PayoutBatchView>, StatusCode> {
+    PayoutService::find(&pool, &user_id, &id).await.map(Json).map_err(payout_status)
+}
+
+/// Every row with its outcome, as CSV.
+pub async fn payout_batch_report(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Path(id): Path<String>,
+) -> Result<Response, StatusCode> {
+    let batch = PayoutService::find_batch(&pool, &user_id, &id).await.map_err(payout_status)?;
+    let rows = PayoutService::rows(&pool, &batch.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut csv = String::from("line,recipient,destination,amount,asset,reference,status,error,tx_hash,attempts\n");
+    for row in &rows {
+        let fields = [
+            row.line.to_string(),
+            row.recipient.clone(),
+            row.destination.clone(),
+            row.amount.clone(),
+            batch.asset.clone(),
+            row.reference.clone().unwrap_or_default(),
+            row.status.clone(),
+            row.error.clone().unwrap_or_default(),
+            row.tx_hash.clone().unwrap_or_default(),
+            row.attempts.to_string(),
+        ];
+        let line: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&line.join(","));
+        csv.push('\n');
+    }
+
+    let disposition = format!("attachment; filename=\"payout-batch-{}.csv\"", batch.id);
+    Ok((
+        [(header::CONTENT_TYPE, "text/csv".to_string()), (header::CONTENT_DISPOSITION, disposition)],
+        csv,
+    )
+        .into_response())
+}
+
+/// Queues the failed rows of a finished batch again. Needs the PIN and, above
+/// the threshold, a `transfer` proof for the failed total.
+pub async fn retry_payout_batch(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    headers: HeaderMap,
+    Path(id): Path<String>,
+    Json(payload): Json<RetryPayoutBatchRequest>,
+) -> Result<Json<PayoutBatchView>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let batch = PayoutService::find_batch(&pool, &actor.user_id, &id).await.map_err(payout_status)?;
+    let failed_total = PayoutService::failed_total(&pool, &batch).await.map_err(payout_status)?;
+    require_pin(&pool, &actor.user_id, &payload.pin).await?;
+    let action = StepUpAction {
+        action: StepUpKind::Transfer,
+        amount: Some(failed_total),
+        currency: Some(asset_code(&batch.asset)),
+        destination: None,
+    };
+    require_step_up(&pool, &actor.user_id, &headers, &action).await?;
+
+    let retried = PayoutService::retry(&pool, &batch).await.map_err(payout_status)?;
+    AuditLog::record(
+        &pool,
+        &actor,
+        "payout.batch_retry",
+        "payout_batch",
+        Some(&batch.id),
+        json!({ "payments": retried.pending, "total": failed_total }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(retried))
+}
+
+fn payout_response(error: PayoutError) -> Response {
+    match error {
+        PayoutError::InvalidRows(rows) => (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": "Some payments can't be made", "rows": rows })),
+        )
+            .into_response(),
+        e => payout_status(e).into_response(),
+    }
+}
+
+fn payout_status(error: PayoutError) -> StatusCode {
+    match error {
+        PayoutError::NotFound => StatusCode::NOT_FOUND,
+        PayoutError::NoPayments | PayoutError::InvalidCsv(_) | PayoutError::InvalidAsset(_) => StatusCode::BAD_REQUEST,
+        PayoutError::TooManyPayments(_) => StatusCode::PAYLOAD_TOO_LARGE,
+        PayoutError::InvalidRows(_) => StatusCode::UNPROCESSABLE_ENTITY,
+        PayoutError::NotRetryable => StatusCode::CONFLICT,
+        PayoutError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,125 @@
+use axum::{
+    extract::{Extension, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::handlers::auth::{find_user, otp_status};
+use crate::models::{ChangePinRequest, ResetPinRequest, SetPinRequest};
+use crate::services::notification_templates::Locale;
+use crate::services::{OtpPurpose, OtpService, PinError, PinService};
+
+pub async fn set_pin(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<SetPinRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    PinService::set(&pool, &user_id, &payload.pin).await.map_err(pin_status)?;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Transaction PIN set"
+    })))
+}
+
+pub async fn change_pin(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<ChangePinRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    PinService::change(&pool, &user_id, &payload.current_pin, &payload.new_pin)
+        .await
+        .map_err(pin_status)?;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Transaction PIN changed"
+    })))
+}
+
+/// Sends the OTP needed to reset a forgotten PIN.
+pub async fn request_pin_reset(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Value>, StatusCode> {
+    let user = find_user(&pool, &user_id).await?;
+    let phone_number = user
+        .phone_number
+        .filter(|_| user.phone_verified_at.is_some())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    OtpService::new()
+        .send(
+            &pool,
+            &phone_number,
+            OtpPurpose::ResetPin,
+            Some(&user_id),
+            Locale::from_preference(Some(&user.preferred_language)),
+        )
+        .await
+        .map_err(otp_status)?;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Verification code sent"
+    })))
+}
+
+pub async fn reset_pin(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<ResetPinRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    PinService::validate(&payload.new_pin).map_err(pin_status)?;
+
+    let user = find_user(&pool, &user_id).await?;
+    let phone_number = user
+        .phone_number
+        .filter(|_| user.phone_verified_at.is_some())
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let issued_to = OtpService::new()
+        .verify(&pool, &phone_number, OtpPurpose::ResetPin, &payload.code)
+        .await
+        .map_err(otp_status)?;
+    if issued_to.as_deref() != Some(user_id.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    PinService::reset(&pool, &user_id, &payload.new_pin).await.map_err(pin_status)?;
+
+    Ok(Json(json!({
+        "success": true,
+        "message": "Transaction PIN reset"
+    })))
+}
+
+/// Verifies the PIN sent with a payment request.
+pub(crate) async fn require_pin(pool: &SqlitePool, user_id: &str, pin: &str) -> Result<(), StatusCode> {
+    PinService::verify(pool, user_id, pin).await.map_err(pin_status)
+}
+
+fn pin_status(error: PinError) -> StatusCode {
+    match error {
+        PinError::NotSet => StatusCode::PRECONDITION_REQUIRED,
+        PinError::AlreadySet => StatusCode::CONFLICT,
+        PinError::Weak => StatusCode::BAD_REQUEST,
+        PinError::Incorrect => StatusCode::FORBIDDEN,
+        PinError::Locked(_) => StatusCode::LOCKED,
+        PinError::Hash | PinError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,158 @@
+use axum::{
+    extract::{Extension, Path, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde_json::json;
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::handlers::mfa::require_step_up;
+use crate::handlers::pin::require_pin;
+use crate::models::{
+    CreateScheduledTransferRequest, ResumeScheduledTransferRequest, ScheduledTransferView, StepUpAction, StepUpKind,
+};
+use crate::services::{Actor, AuditLog, ScheduledTransferError, ScheduledTransferService};
+
+/// Sets up a repeating transfer. Needs the PIN and, above the step-up
+/// threshold, a `transfer` proof for one payment to the recipient.
+pub async fn create_scheduled_transfer(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    headers: HeaderMap,
+    Json(payload): Json<CreateScheduledTransferRequest>,
+) -> Result<Json<ScheduledTransferView>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    require_pin(&pool, &actor.user_id, &payload.pin).await?;
+    let action = StepUpAction {
+        action: StepUpKind::Transfer,
+        amount: Some(payload.amount),
+        currency: Some(payload.currency.clone().unwrap_or_else(|| "USD".to_string())),
+        destination: Some(payload.recipient_email.clone()),
+    };
+    require_step_up(&pool, &actor.user_id, &headers, &action).await?;
+
+    let created = ScheduledTransferService::create(&pool, &actor.user_id, &payload)
+        .await
+        .map_err(scheduled_transfer_status)?;
+    AuditLog::record(
+        &pool,
+        &actor,
+        "scheduled_transfer.create",
+        "scheduled_transfer",
+        Some(&created.schedule.id),
+        json!({
+            "recipient_email": created.schedule.recipient_email,
+            "amount": created.schedule.amount,
+            "currency": created.schedule.currency,
+            "frequency": created.schedule.frequency,
+            "next_run_at": created.schedule.next_run_at,
+        }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(created))
+}
+
+pub async fn list_scheduled_transfers(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Vec<ScheduledTransferView>>, StatusCode> {
+    ScheduledTransferService::list(&pool, &user_id)
+        .await
+        .map(Json)
+        .map_err(scheduled_transfer_status)
+}
+
+pub async fn get_scheduled_transfer(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Path(id): Path<String>,
+) -> Result<Json<ScheduledTransferView>, StatusCode> {
+    ScheduledTransferService::find(&pool, &user_id, &id)
+        .await
+        .map(Json)
+        .map_err(scheduled_transfer_status)
+}
+
+pub async fn pause_scheduled_transfer(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Result<Json<ScheduledTransferView>, StatusCode> {
+    let paused = ScheduledTransferService::pause(&pool, &actor.user_id, &id)
+        .await
+        .map_err(scheduled_transfer_status)?;
+    record(&pool, &actor, "scheduled_transfer.pause", &paused).await?;
+    Ok(Json(paused))
+}
+
+/// Restarting payments needs the PIN, like setting them up.
+pub async fn resume_scheduled_transfer(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(id): Path<String>,
+    Json(payload): Json<ResumeScheduledTransferRequest>,
+) -> Result<Json<ScheduledTransferView>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    require_pin(&pool, &actor.user_id, &payload.pin).await?;
+    let resumed = ScheduledTransferService::resume(&pool, &actor.user_id, &id)
+        .await
+        .map_err(scheduled_transfer_status)?;
+    record(&pool, &actor, "scheduled_transfer.resume", &resumed).await?;
+    Ok(Json(resumed))
+}
+
+pub async fn skip_scheduled_transfer(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Result<Json<ScheduledTransferView>, StatusCode> {
+    let skipped = ScheduledTransferService::skip_next(&pool, &actor.user_id, &id)
+        .await
+        .map_err(scheduled_transfer_status)?;
+    record(&pool, &actor, "scheduled_transfer.skip", &skipped).await?;
+    Ok(Json(skipped))
+}
+
+pub async fn cancel_scheduled_transfer(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Result<Json<ScheduledTransferView>, StatusCode> {
+    let cancelled = ScheduledTransferService::cancel(&pool, &actor.user_id, &id)
+        .await
+        .map_err(scheduled_transfer_status)?;
+    record(&pool, &actor, "scheduled_transfer.cancel", &cancelled).await?;
+    Ok(Json(cancelled))
+}
+
+async fn record(pool: &SqlitePool, actor: &Actor, action: &str, view: &ScheduledTransferView) -> Result<(), StatusCode> {
+    AuditLog::record(
+        pool,
+        actor,
+        action,
+        "scheduled_transfer",
+        Some(&view.schedule.id),
+        json!({ "status": view.schedule.status, "next_run_at": view.schedule.next_run_at }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn scheduled_transfer_status(error: ScheduledTransferError) -> StatusCode {
+    match error {
+        ScheduledTransferError::NotFound => StatusCode::NOT_FOUND,
+        ScheduledTransferError::InvalidRule(_) => StatusCode::BAD_REQUEST,
+        ScheduledTransferError::RecipientUnpayable => StatusCode::UNPROCESSABLE_ENTITY,
+        ScheduledTransferError::InvalidStatus(_) => StatusCode::CONFLICT,
+        ScheduledTransferError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,72 @@
+use axum::{
+    extract::{ConnectInfo, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::IntoResponse,
+    Json,
+};
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::net::SocketAddr;
+use validator::Validate;
+
+use crate::handlers::auth::complete_first_factor;
+use crate::models::{Sep10ChallengeQuery, Sep10ChallengeResponse, Sep10TokenRequest};
+use crate::services::stellar_tx::{StellarNetwork, StellarTxError};
+use crate::services::{Sep10Error, Sep10Service};
+
+pub async fn sep10_challenge(
+    State(pool): State<SqlitePool>,
+    Query(query): Query<Sep10ChallengeQuery>,
+) -> Result<Json<Sep10ChallengeResponse>, StatusCode> {
+    if query.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Sep10Service::challenge(&pool, &query.account, query.home_domain.as_deref())
+        .await
+        .map(Json)
+        .map_err(sep10_status)
+}
+
+/// Signs in the user who registered the account, like a password login:
+/// users with two-factor authentication get an MFA challenge instead.
+pub async fn sep10_token(
+    State(pool): State<SqlitePool>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(payload): Json<Sep10TokenRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let user = Sep10Service::verify(&pool, &payload.transaction).await.map_err(sep10_status)?;
+    complete_first_factor(&pool, user, &headers, peer, "sep10").await
+}
+
+/// SEP-1 discovery file pointing wallets at the SEP-10 endpoint and key.
+pub async fn stellar_toml() -> impl IntoResponse {
+    let body = format!(
+        "NETWORK_PASSPHRASE=\"{}\"\nWEB_AUTH_ENDPOINT=\"{}\"\nSIGNING_KEY=\"{}\"\n",
+        StellarNetwork::from_env().passphrase,
+        Sep10Service::web_auth_endpoint(),
+        Sep10Service::signing_public_key(),
+    );
+    ([(header::CONTENT_TYPE, "text/plain; charset=utf-8")], body)
+}
+
+fn sep10_status(error: Sep10Error) -> StatusCode {
+    match error {
+        Sep10Error::WrongHomeDomain | Sep10Error::InvalidChallenge(_) | Sep10Error::Expired | Sep10Error::AlreadyUsed => {
+            StatusCode::BAD_REQUEST
+        }
+        Sep10Error::NotAuthorized | Sep10Error::UnknownAccount => StatusCode::UNAUTHORIZED,
+        Sep10Error::AmbiguousAccount => StatusCode::CONFLICT,
+        Sep10Error::Stellar(StellarTxError::Horizon(message)) => {
+            println!("⚠️ Horizon error: {}", message);
+            StatusCode::BAD_GATEWAY
+        }
+        Sep10Error::Stellar(_) => StatusCode::BAD_REQUEST,
+        Sep10Error::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,85 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Form,
+};
+use serde::Deserialize;
+use sqlx::SqlitePool;
+use std::collections::BTreeMap;
+use std::env;
+
+use crate::services::sms::{africas_talking, twilio};
+use crate::services::SmsService;
+
+#[derive(Debug, Deserialize)]
+pub struct AfricasTalkingDeliveryReport {
+    pub id: String,
+    pub status: String,
+    #[serde(rename = "failureReason")]
+    pub failure_reason: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DeliveryReportQuery {
+    pub secret: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwilioStatusCallback {
+    #[serde(rename = "MessageSid")]
+    pub message_sid: String,
+    #[serde(rename = "MessageStatus")]
+    pub message_status: String,
+    #[serde(rename = "ErrorCode")]
+    pub error_code: Option<String>,
+}
+
+/// Needs the `secret` query parameter to match `AT_CALLBACK_SECRET`.
+pub async fn africastalking_delivery_report(
+    State(pool): State<SqlitePool>,
+    Query(query): Query<DeliveryReportQuery>,
+    Form(report): Form<AfricasTalkingDeliveryReport>,
+) -> StatusCode {
+    let expected = env::var("AT_CALLBACK_SECRET").unwrap_or_default();
+    if !africas_talking::verify_callback_secret(&expected, query.secret.as_deref().unwrap_or_default()) {
+        return StatusCode::FORBIDDEN;
+    }
+
+    let status = africas_talking::normalize_delivery_status(&report.status);
+
+    match SmsService::update_delivery_status(&pool, "africastalking", &report.id, status, report.failure_reason.as_deref()).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
+
+/// Needs a valid `X-Twilio-Signature` for `TWILIO_STATUS_CALLBACK_URL`.
+pub async fn twilio_delivery_report(
+    State(pool): State<SqlitePool>,
+    headers: HeaderMap,
+    Form(params): Form<BTreeMap<String, String>>,
+) -> StatusCode {
+    let (Ok(auth_token), Ok(callback_url)) = (env::var("TWILIO_AUTH_TOKEN"), env::var("TWILIO_STATUS_CALLBACK_URL")) else {
+        return StatusCode::FORBIDDEN;
+    };
+    let signature = headers
+        .get("X-Twilio-Signature")
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    if !twilio::verify_signature(&auth_token, &callback_url, &params, signature) {
+        return StatusCode::FORBIDDEN;
+    }
+    let Ok(report) = serde_json::to_value(&params).and_then(serde_json::from_value::<TwilioStatusCallback>) else {
+        return StatusCode::BAD_REQUEST;
+    };
+
+    let status = twilio::normalize_delivery_status(&report.message_status);
+    let failure_reason = report.error_code.map(|code| format!("Twilio error {}", code));
+
+    match SmsService::update_delivery_status(&pool, "twilio", &report.message_sid, status, failure_reason.as_deref()).await {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -6,15 +6,13 @@ use axum::{
 use serde_json::{json, Value};
 use sqlx::SqlitePool;
 
-use crate::services::StellarService;
+use crate::services::{JobPayload, Outbox, StellarService};
 use crate::models::User;
 
 pub async fn fund_test_account(
     State(pool): State<SqlitePool>,
     Extension(user_id): Extension<String>,
 ) -> Result<Json<Value>, StatusCode> {
-    let stellar_service = StellarService::new();
-
     // Get user's stellar public key
     let user = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
         .bind(&user_id)
@@ -25,19 +23,15 @@ pub async fn fund_test_account(
     match user {
         Some(user) => {
             if let Some(public_key) = user.stellar_public_key {
-                let success = stellar_service
-                    .fund_test_account(&public_key)
+                let job_id = Outbox::enqueue(&pool, &JobPayload::FriendbotFund { public_key })
                     .await
                     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-                if success {
-                    Ok(Json(json!({
-                        "success": true,
-                        "message": "Test account funded successfully"
-                    })))
-                } else {
-                    Err(StatusCode::BAD_REQUEST)
-                }
+                Ok(Json(json!({
+                    "success": true,
+                    "message": "Test account funding queued",
+                    "job_id": job_id
+                })))
             } else {
                 Err(StatusCode::NOT_FOUND)
             }
@@ -1,61 +1,97 @@
 use axum::{
     extract::{Extension, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     Json,
 };
 use serde_json::{json, Value};
 use sqlx::SqlitePool;
 use validator::Validate;
 
-use crate::models::{CreateTransaction, TransactionResponse};
-use crate::services::TransactionService;
+use crate::handlers::mfa::require_step_up;
+use crate::handlers::pin::require_pin;
+use crate::handlers::wallet::{audit_balance_change, wallet_balance};
+use crate::models::{CreateTransaction, StepUpAction, StepUpKind, TransactionResponse};
+use crate::services::{Actor, PaymentOutcome, TransactionService, WalletPaymentError};
 
 pub async fn send_money(
     State(pool): State<SqlitePool>,
-    Extension(user_id): Extension<String>,
+    actor: Actor,
+    headers: HeaderMap,
     Json(payload): Json<CreateTransaction>,
 ) -> Result<Json<Value>, StatusCode> {
     if let Err(_) = payload.validate() {
         return Err(StatusCode::BAD_REQUEST);
     }
 
+    let user_id = actor.user_id.clone();
+    require_pin(&pool, &user_id, &payload.pin).await?;
+
+    let action = StepUpAction {
+        action: StepUpKind::Transfer,
+        amount: Some(payload.amount),
+        currency: Some(payload.currency.clone().unwrap_or_else(|| "USD".to_string())),
+        destination: Some(payload.recipient_email.clone()),
+    };
+    require_step_up(&pool, &user_id, &headers, &action).await?;
+
     let tx_service = TransactionService::new();
-    
+    let destination = tx_service
+        .recipient_account(&pool, &payload.recipient_email)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNPROCESSABLE_ENTITY)?;
+    tx_service
+        .ensure_funds(&pool, &user_id, payload.amount)
+        .await
+        .map_err(wallet_payment_status)?;
+    let balance_before = wallet_balance(&pool, &user_id).await?;
+
     let transaction = tx_service
         .create_transaction(&pool, &user_id, payload)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    // Get user's wallet for payment processing
-    let wallet = sqlx::query_as::<_, crate::models::Wallet>("SELECT * FROM wallets WHERE user_id = ?")
-        .bind(&user_id)
-        .fetch_optional(&pool)
+    // A payment that definitely didn't go out fails the transaction; one
+    // whose outcome is unknown stays `submitting`
+    let (status, tx_hash) = match tx_service
+        .pay_from_wallet(&pool, &transaction.id, &destination)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    {
+        PaymentOutcome::Completed(tx_hash) => ("completed", Some(tx_hash)),
+        PaymentOutcome::Unknown(_) => ("submitting", None),
+        PaymentOutcome::Failed(_) => return Err(StatusCode::BAD_GATEWAY),
+    };
 
-    if let Some(wallet) = wallet {
-        // Mock recipient public key (in real app, look up by email)
-        let recipient_public_key = "GXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXXX";
-        
-        let tx_hash = tx_service
-            .process_payment(&pool, &transaction.id, &wallet.stellar_secret_key, recipient_public_key)
-            .await
-            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let details = json!({
+        "transaction_id": transaction.id,
+        "recipient_email": transaction.recipient_email,
+        "amount": transaction.amount,
+        "currency": transaction.currency,
+        "status": status,
+        "tx_hash": tx_hash,
+    });
+    audit_balance_change(&pool, &actor, "transaction.send", details, balance_before).await;
+
+    Ok(Json(json!({
+        "transaction": TransactionResponse {
+            id: transaction.id,
+            recipient_email: transaction.recipient_email,
+            amount: transaction.amount,
+            currency: transaction.currency,
+            target_currency: transaction.target_currency,
+            status: status.to_string(),
+            created_at: transaction.created_at,
+            stellar_tx_hash: tx_hash,
+        }
+    })))
+}
 
-        Ok(Json(json!({
-            "transaction": TransactionResponse {
-                id: transaction.id,
-                recipient_email: transaction.recipient_email,
-                amount: transaction.amount,
-                currency: transaction.currency,
-                target_currency: transaction.target_currency,
-                status: "completed".to_string(),
-                created_at: transaction.created_at,
-                stellar_tx_hash: Some(tx_hash),
-            }
-        })))
-    } else {
-        Err(StatusCode::NOT_FOUND)
+pub(crate) fn wallet_payment_status(error: WalletPaymentError) -> StatusCode {
+    match error {
+        WalletPaymentError::InsufficientFunds => StatusCode::CONFLICT,
+        WalletPaymentError::Failed(_) | WalletPaymentError::Unavailable(_) => StatusCode::BAD_GATEWAY,
+        WalletPaymentError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
     }
 }
 
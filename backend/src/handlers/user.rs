@@ -3,10 +3,14 @@ use axum::{
     http::StatusCode,
     Json,
 };
+use serde_json::json;
 use sqlx::SqlitePool;
 use validator::Validate;
 
+use crate::handlers::auth::send_verification_code;
 use crate::models::{User, UserResponse, UpdateUserProfile};
+use crate::services::{normalize_phone, Actor, AuditLog, Change};
+use crate::services::notification_templates::Locale;
 
 pub async fn get_profile(
     State(pool): State<SqlitePool>,
@@ -19,38 +23,56 @@ pub async fn get_profile(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     match user {
-        Some(user) => Ok(Json(UserResponse {
-            id: user.id,
-            email: user.email,
-            full_name: user.full_name,
-            phone_number: user.phone_number,
-            stellar_public_key: user.stellar_public_key,
-        })),
+        Some(user) => Ok(Json(UserResponse::from(user))),
         None => Err(StatusCode::NOT_FOUND),
     }
 }
 
 pub async fn update_profile(
     State(pool): State<SqlitePool>,
-    Extension(user_id): Extension<String>,
+    actor: Actor,
     Json(payload): Json<UpdateUserProfile>,
 ) -> Result<Json<UserResponse>, StatusCode> {
     if let Err(_) = payload.validate() {
         return Err(StatusCode::BAD_REQUEST);
     }
+    let user_id = actor.user_id.clone();
+
+    let preferred_language = payload
+        .preferred_language
+        .as_deref()
+        .and_then(Locale::from_code)
+        .map(|locale| locale.code());
+    let phone_number = payload
+        .phone_number
+        .as_deref()
+        .map(|phone| normalize_phone(phone).ok_or(StatusCode::BAD_REQUEST))
+        .transpose()?;
+
+    let before = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+        .bind(&user_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let phone_changed = phone_number.is_some() && phone_number != before.phone_number;
 
-    // Update user profile
+    // Update user profile; a new number has to be verified again
     let result = sqlx::query(
         r#"
         UPDATE users 
         SET full_name = COALESCE(?, full_name),
             phone_number = COALESCE(?, phone_number),
+            phone_verified_at = CASE WHEN ? THEN NULL ELSE phone_verified_at END,
+            preferred_language = COALESCE(?, preferred_language),
             updated_at = CURRENT_TIMESTAMP
         WHERE id = ?
         "#,
     )
     .bind(&payload.full_name)
-    .bind(&payload.phone_number)
+    .bind(&phone_number)
+    .bind(phone_changed)
+    .bind(preferred_language)
     .bind(&user_id)
     .execute(&pool)
     .await;
@@ -65,16 +87,39 @@ pub async fn update_profile(
                 .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
             match user {
-                Some(user) => Ok(Json(UserResponse {
-                    id: user.id,
-                    email: user.email,
-                    full_name: user.full_name,
-                    phone_number: user.phone_number,
-                    stellar_public_key: user.stellar_public_key,
-                })),
+                Some(user) => {
+                    let change = Change {
+                        before: profile_state(&before),
+                        after: profile_state(&user),
+                    };
+                    AuditLog::record_after(
+                        &pool,
+                        &actor,
+                        "user.profile_update",
+                        "user",
+                        Some(&user.id),
+                        json!({}),
+                        Some(change),
+                    )
+                    .await;
+
+                    if let (true, Some(phone)) = (phone_changed, &user.phone_number) {
+                        let locale = Locale::from_preference(Some(&user.preferred_language));
+                        send_verification_code(&pool, &user.id, phone, locale).await;
+                    }
+                    Ok(Json(UserResponse::from(user)))
+                }
                 None => Err(StatusCode::NOT_FOUND),
             }
         }
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
-}
\ No newline at end of file
+}
+
+fn profile_state(user: &User) -> serde_json::Value {
+    json!({
+        "full_name": user.full_name,
+        "phone_number": user.phone_number,
+        "preferred_language": user.preferred_language,
+    })
+}
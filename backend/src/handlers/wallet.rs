@@ -1,6 +1,6 @@
 use axum::{
     extract::{Extension, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     Json,
 };
 use serde::{Deserialize, Serialize};
@@ -8,8 +8,11 @@ use serde_json::{json, Value};
 use sqlx::SqlitePool;
 use validator::Validate;
 
-use crate::services::{WalletService, StellarService};
-use crate::models::Wallet;
+use crate::handlers::mfa::require_step_up;
+use crate::handlers::pin::require_pin;
+use crate::handlers::transaction::wallet_payment_status;
+use crate::services::{Actor, AuditLog, Change, TransactionService, WalletService, StellarService};
+use crate::models::{StepUpAction, StepUpKind, Wallet};
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct DepositRequest {
@@ -23,6 +26,8 @@ pub struct WithdrawRequest {
     #[validate(range(min = 0.01))]
     pub xlm_amount: f64,
     pub mpesa_number: String,
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -30,6 +35,8 @@ pub struct TransferRequest {
     #[validate(range(min = 0.01))]
     pub xlm_amount: f64,
     pub to_wallet_id: String,
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
 }
 
 #[derive(Debug, Serialize)]
@@ -70,14 +77,16 @@ pub async fn get_wallet_balance(
 
 pub async fn deposit_from_mpesa(
     State(pool): State<SqlitePool>,
-    Extension(user_id): Extension<String>,
+    actor: Actor,
     Json(payload): Json<DepositRequest>,
 ) -> Result<Json<Value>, StatusCode> {
     if let Err(_) = payload.validate() {
         return Err(StatusCode::BAD_REQUEST);
     }
 
+    let user_id = actor.user_id.clone();
     let wallet_service = WalletService::new();
+    let balance_before = wallet_balance(&pool, &user_id).await?;
     
     let tx_hash = wallet_service
         .deposit_from_mpesa(&pool, &user_id, payload.kes_amount, &payload.mpesa_ref)
@@ -85,6 +94,13 @@ pub async fn deposit_from_mpesa(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let xlm_amount = wallet_service.convert_kes_to_xlm(payload.kes_amount);
+    let details = json!({
+        "kes_amount": payload.kes_amount,
+        "xlm_amount": xlm_amount,
+        "mpesa_ref": payload.mpesa_ref,
+        "tx_hash": tx_hash,
+    });
+    audit_balance_change(&pool, &actor, "wallet.deposit", details, balance_before).await;
 
     Ok(Json(json!({
         "success": true,
@@ -97,47 +113,96 @@ pub async fn deposit_from_mpesa(
 
 pub async fn withdraw_to_mpesa(
     State(pool): State<SqlitePool>,
-    Extension(user_id): Extension<String>,
+    actor: Actor,
+    headers: HeaderMap,
     Json(payload): Json<WithdrawRequest>,
 ) -> Result<Json<Value>, StatusCode> {
     if let Err(_) = payload.validate() {
         return Err(StatusCode::BAD_REQUEST);
     }
 
+    let user_id = actor.user_id.clone();
+    require_pin(&pool, &user_id, &payload.pin).await?;
+
+    let action = StepUpAction {
+        action: StepUpKind::Withdrawal,
+        amount: Some(payload.xlm_amount),
+        currency: Some("XLM".to_string()),
+        destination: Some(payload.mpesa_number.clone()),
+    };
+    require_step_up(&pool, &user_id, &headers, &action).await?;
+
+    if WalletService::withdrawal_account().is_none() {
+        return Err(StatusCode::SERVICE_UNAVAILABLE);
+    }
+    TransactionService::new()
+        .ensure_funds(&pool, &user_id, payload.xlm_amount)
+        .await
+        .map_err(wallet_payment_status)?;
+
     let wallet_service = WalletService::new();
-    
-    let tx_hash = wallet_service
+    let balance_before = wallet_balance(&pool, &user_id).await?;
+
+    let withdrawal_id = wallet_service
         .withdraw_to_mpesa(&pool, &user_id, payload.xlm_amount, &payload.mpesa_number)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let kes_amount = wallet_service.convert_xlm_to_kes(payload.xlm_amount);
+    let details = json!({
+        "withdrawal_id": withdrawal_id,
+        "xlm_amount": payload.xlm_amount,
+        "kes_amount": kes_amount,
+        "mpesa_number": payload.mpesa_number,
+    });
+    audit_balance_change(&pool, &actor, "wallet.withdraw", details, balance_before).await;
 
     Ok(Json(json!({
         "success": true,
-        "message": "Withdrawal successful",
+        "message": "Withdrawal submitted",
+        "status": "pending",
+        "withdrawal_id": withdrawal_id,
         "xlm_amount": payload.xlm_amount,
-        "kes_amount": kes_amount,
-        "tx_hash": tx_hash
+        "kes_amount": kes_amount
     })))
 }
 
 pub async fn transfer_to_wallet(
     State(pool): State<SqlitePool>,
-    Extension(user_id): Extension<String>,
+    actor: Actor,
+    headers: HeaderMap,
     Json(payload): Json<TransferRequest>,
 ) -> Result<Json<Value>, StatusCode> {
     if let Err(_) = payload.validate() {
         return Err(StatusCode::BAD_REQUEST);
     }
 
+    let user_id = actor.user_id.clone();
+    require_pin(&pool, &user_id, &payload.pin).await?;
+
+    let action = StepUpAction {
+        action: StepUpKind::Transfer,
+        amount: Some(payload.xlm_amount),
+        currency: Some("XLM".to_string()),
+        destination: Some(payload.to_wallet_id.clone()),
+    };
+    require_step_up(&pool, &user_id, &headers, &action).await?;
+
     let wallet_service = WalletService::new();
+    let balance_before = wallet_balance(&pool, &user_id).await?;
     
     let tx_hash = wallet_service
         .transfer_to_wallet(&pool, &user_id, &payload.to_wallet_id, payload.xlm_amount)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let details = json!({
+        "xlm_amount": payload.xlm_amount,
+        "to_wallet_id": payload.to_wallet_id,
+        "tx_hash": tx_hash,
+    });
+    audit_balance_change(&pool, &actor, "wallet.transfer", details, balance_before).await;
+
     Ok(Json(json!({
         "success": true,
         "message": "Transfer successful",
@@ -186,4 +251,28 @@ pub async fn create_wallet(
         "wallet_id": wallet_id,
         "public_key": stellar_account.public_key
     })))
-}
\ No newline at end of file
+}
+
+pub(crate) async fn wallet_balance(pool: &SqlitePool, user_id: &str) -> Result<f64, StatusCode> {
+    WalletService::new()
+        .get_wallet_balance(pool, user_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// Audits money that has already moved, with the actor's wallet balance
+/// before and after.
+pub(crate) async fn audit_balance_change(
+    pool: &SqlitePool,
+    actor: &Actor,
+    action: &str,
+    details: Value,
+    balance_before: f64,
+) {
+    let balance_after = WalletService::new().get_wallet_balance(pool, &actor.user_id).await.ok();
+    let change = Change {
+        before: json!({ "balance": balance_before }),
+        after: json!({ "balance": balance_after }),
+    };
+    AuditLog::record_after(pool, actor, action, "wallet", Some(&actor.user_id), details, Some(change)).await;
+}
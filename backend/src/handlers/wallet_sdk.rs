@@ -1,40 +1,145 @@
 use axum::{
     extract::{Extension, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
     Json,
 };
 use serde_json::{json, Value};
 use sqlx::SqlitePool;
+use validator::Validate;
 
+use crate::handlers::mfa::require_step_up;
+use crate::handlers::pin::require_pin;
+use crate::models::{
+    CreateCustodialWalletRequest, CreatedCustodialWallet, CustodialPaymentRequest, CustodialTrustlineRequest,
+    CustodialWallet, CustodialWalletRef, ExportWalletKeyRequest, RestoreCustodialWalletRequest, StepUpAction,
+    StepUpKind,
+};
+use crate::services::{Actor, AuditLog, CustodialWalletError, CustodialWalletService};
+use crate::wallet_sdk::{NovaPayWallet, WalletError};
 use crate::wallet_sdk_service::{WalletSDKService, SendPaymentRequest};
 
 pub async fn create_wallet(
-    State(_pool): State<SqlitePool>,
-    Extension(_user_id): Extension<String>,
-) -> Result<Json<Value>, StatusCode> {
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    headers: HeaderMap,
+    payload: Option<Json<CreateCustodialWalletRequest>>,
+) -> Result<Json<CreatedCustodialWallet>, StatusCode> {
+    let payload = payload.map(|Json(payload)| payload).unwrap_or_default();
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // A backup phrase reveals the key, so it is gated like an export
+    if payload.backup_phrase {
+        if !payload.exportable {
+            return Err(custodial_wallet_status(CustodialWalletError::NotExportable));
+        }
+        require_pin(&pool, &actor.user_id, payload.pin.as_deref().unwrap_or_default()).await?;
+        let action = StepUpAction {
+            action: StepUpKind::SecretExport,
+            amount: None,
+            currency: None,
+            destination: None,
+        };
+        require_step_up(&pool, &actor.user_id, &headers, &action).await?;
+    }
+
     let service = WalletSDKService::new();
-    let response = service.create_wallet();
-    
-    Ok(Json(json!({
-        "public_key": response.public_key,
-        "secret_key": response.secret_key,
-        "wallet_id": response.wallet_id
-    })))
+    let (keypair, backup_phrase) = if payload.backup_phrase {
+        let (keypair, mnemonic) = service
+            .create_wallet_with_mnemonic()
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        (keypair, Some(mnemonic))
+    } else {
+        (service.create_wallet(), None)
+    };
+
+    let wallet = CustodialWalletService::create(&pool, &actor.user_id, keypair, &payload)
+        .await
+        .map_err(custodial_wallet_status)?;
+    AuditLog::record(
+        &pool,
+        &actor,
+        "wallet.create",
+        "custodial_wallet",
+        Some(&wallet.id),
+        json!({
+            "public_key": wallet.public_key,
+            "exportable": wallet.exportable,
+            "backup_phrase": backup_phrase.is_some(),
+        }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(CreatedCustodialWallet { wallet, backup_phrase }))
+}
+
+/// Recreates a wallet from its backup phrase, e.g. after losing the phone it
+/// was set up on. A key that is already held here returns `409`.
+pub async fn restore_wallet_sdk(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<RestoreCustodialWalletRequest>,
+) -> Result<Json<CustodialWallet>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    NovaPayWallet::validate_mnemonic(&payload.mnemonic).map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    let keypair = WalletSDKService::new()
+        .restore_wallet(&payload.mnemonic, payload.passphrase.as_deref().unwrap_or(""), payload.account_index)
+        .map_err(|e| match e {
+            // The account index is past the hardened range
+            WalletError::InvalidKeypair(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        })?;
+    let request = CreateCustodialWalletRequest {
+        label: payload.label,
+        exportable: payload.exportable,
+        backup_phrase: false,
+        pin: None,
+    };
+
+    let wallet = CustodialWalletService::create(&pool, &actor.user_id, keypair, &request)
+        .await
+        .map_err(custodial_wallet_status)?;
+    AuditLog::record(
+        &pool,
+        &actor,
+        "wallet.restore",
+        "custodial_wallet",
+        Some(&wallet.id),
+        json!({ "public_key": wallet.public_key, "account_index": payload.account_index }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Json(wallet))
+}
+
+pub async fn list_wallets_sdk(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Vec<CustodialWallet>>, StatusCode> {
+    CustodialWalletService::list(&pool, &user_id)
+        .await
+        .map(Json)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
 
 pub async fn get_wallet_balance_sdk(
-    State(_pool): State<SqlitePool>,
-    Extension(_user_id): Extension<String>,
-    Json(payload): Json<Value>,
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<CustodialWalletRef>,
 ) -> Result<Json<Value>, StatusCode> {
-    let secret_key = payload["secret_key"]
-        .as_str()
-        .ok_or(StatusCode::BAD_REQUEST)?;
+    let (_, secret_key) = owned_wallet(&pool, &user_id, &payload.wallet_id).await?;
 
     let service = WalletSDKService::new();
-    
-    match service.get_wallet_balance(secret_key).await {
+
+    match service.get_wallet_balance(&secret_key).await {
         Ok(response) => Ok(Json(json!({
+            "wallet_id": payload.wallet_id,
             "balances": response.balances,
             "public_key": response.public_key
         }))),
@@ -43,56 +148,66 @@ pub async fn get_wallet_balance_sdk(
 }
 
 pub async fn send_payment_sdk(
-    State(_pool): State<SqlitePool>,
-    Extension(_user_id): Extension<String>,
-    Json(payload): Json<Value>,
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<CustodialPaymentRequest>,
 ) -> Result<Json<Value>, StatusCode> {
-    let secret_key = payload["secret_key"]
-        .as_str()
-        .ok_or(StatusCode::BAD_REQUEST)?;
-    
-    let destination = payload["destination"]
-        .as_str()
-        .ok_or(StatusCode::BAD_REQUEST)?;
-    
-    let amount = payload["amount"]
-        .as_str()
-        .ok_or(StatusCode::BAD_REQUEST)?;
-
-    let asset_code = payload["asset_code"].as_str();
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let (wallet, secret_key) = owned_wallet(&pool, &actor.user_id, &payload.wallet_id).await?;
+    require_pin(&pool, &actor.user_id, &payload.pin).await?;
 
     let request = SendPaymentRequest {
-        destination: destination.to_string(),
-        amount: amount.to_string(),
-        asset_code: asset_code.map(|s| s.to_string()),
-        memo: payload["memo"].as_str().map(|s| s.to_string()),
+        destination: payload.destination.clone(),
+        amount: payload.amount.clone(),
+        asset_code: payload.asset_code.clone(),
+        memo: payload.memo.clone(),
     };
 
     let service = WalletSDKService::new();
-    
-    match service.send_payment(secret_key, request).await {
-        Ok(result) => Ok(Json(json!({
-            "transaction_hash": result.transaction_hash,
-            "amount": result.amount,
-            "asset_code": result.asset_code,
-            "destination": result.destination
-        }))),
+
+    match service.send_payment(&secret_key, request).await {
+        Ok(result) => {
+            let details = json!({
+                "wallet_id": wallet.id,
+                "destination": result.destination,
+                "amount": result.amount,
+                "asset_code": result.asset_code,
+                "tx_hash": result.transaction_hash,
+            });
+            AuditLog::record_after(
+                &pool,
+                &actor,
+                "stellar.payment",
+                "stellar_account",
+                Some(&wallet.public_key),
+                details,
+                None,
+            )
+            .await;
+            Ok(Json(json!({
+                "transaction_hash": result.transaction_hash,
+                "amount": result.amount,
+                "asset_code": result.asset_code,
+                "destination": result.destination
+            })))
+        }
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
 }
 
 pub async fn fund_testnet_sdk(
-    State(_pool): State<SqlitePool>,
-    Extension(_user_id): Extension<String>,
-    Json(payload): Json<Value>,
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<CustodialWalletRef>,
 ) -> Result<Json<Value>, StatusCode> {
-    let secret_key = payload["secret_key"]
-        .as_str()
-        .ok_or(StatusCode::BAD_REQUEST)?;
+    let (_, secret_key) = owned_wallet(&pool, &user_id, &payload.wallet_id).await?;
 
     let service = WalletSDKService::new();
-    
-    match service.fund_testnet_account(secret_key).await {
+
+    match service.fund_testnet_account(&secret_key).await {
         Ok(success) => Ok(Json(json!({
             "success": success,
             "message": if success { "Account funded successfully" } else { "Failed to fund account" }
@@ -102,32 +217,101 @@ pub async fn fund_testnet_sdk(
 }
 
 pub async fn create_trustline_sdk(
-    State(_pool): State<SqlitePool>,
-    Extension(_user_id): Extension<String>,
-    Json(payload): Json<Value>,
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Json(payload): Json<CustodialTrustlineRequest>,
 ) -> Result<Json<Value>, StatusCode> {
-    let secret_key = payload["secret_key"]
-        .as_str()
-        .ok_or(StatusCode::BAD_REQUEST)?;
-    
-    let asset_code = payload["asset_code"]
-        .as_str()
-        .ok_or(StatusCode::BAD_REQUEST)?;
-    
-    let issuer = payload["issuer"]
-        .as_str()
-        .ok_or(StatusCode::BAD_REQUEST)?;
-
-    let limit = payload["limit"].as_str();
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let (_, secret_key) = owned_wallet(&pool, &user_id, &payload.wallet_id).await?;
 
     let service = WalletSDKService::new();
-    
-    match service.create_trustline(secret_key, asset_code, issuer, limit).await {
+
+    match service
+        .create_trustline(&secret_key, &payload.asset_code, &payload.issuer, payload.limit.as_deref())
+        .await
+    {
         Ok(tx_hash) => Ok(Json(json!({
             "transaction_hash": tx_hash,
-            "asset_code": asset_code,
-            "issuer": issuer
+            "asset_code": payload.asset_code,
+            "issuer": payload.issuer
         }))),
         Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
     }
-}
\ No newline at end of file
+}
+
+/// Returns a wallet's secret key to its owner. The wallet must have been
+/// created with `exportable: true`, and the request needs the PIN and a
+/// `secret_export` step-up proof for this wallet id.
+pub async fn export_wallet_key_sdk(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    headers: HeaderMap,
+    Json(payload): Json<ExportWalletKeyRequest>,
+) -> Result<Json<Value>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let wallet = CustodialWalletService::find_owned(&pool, &actor.user_id, &payload.wallet_id)
+        .await
+        .map_err(custodial_wallet_status)?;
+    if !wallet.exportable {
+        return Err(custodial_wallet_status(CustodialWalletError::NotExportable));
+    }
+
+    require_pin(&pool, &actor.user_id, &payload.pin).await?;
+
+    let action = StepUpAction {
+        action: StepUpKind::SecretExport,
+        amount: None,
+        currency: None,
+        destination: Some(wallet.id.clone()),
+    };
+    require_step_up(&pool, &actor.user_id, &headers, &action).await?;
+
+    // Recorded before the secret is released, not after
+    AuditLog::record(
+        &pool,
+        &actor,
+        "key.export",
+        "custodial_wallet",
+        Some(&wallet.id),
+        json!({ "public_key": wallet.public_key, "previously_exported_at": wallet.exported_at }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let secret_key = CustodialWalletService::export(&pool, &wallet)
+        .await
+        .map_err(custodial_wallet_status)?;
+
+    Ok(Json(json!({
+        "wallet_id": wallet.id,
+        "public_key": wallet.public_key,
+        "secret_key": secret_key
+    })))
+}
+
+async fn owned_wallet(pool: &SqlitePool, user_id: &str, wallet_id: &str) -> Result<(CustodialWallet, String), StatusCode> {
+    let wallet = CustodialWalletService::find_owned(pool, user_id, wallet_id)
+        .await
+        .map_err(custodial_wallet_status)?;
+    let secret_key = CustodialWalletService::secret(&wallet).map_err(custodial_wallet_status)?;
+    Ok((wallet, secret_key))
+}
+
+fn custodial_wallet_status(error: CustodialWalletError) -> StatusCode {
+    match error {
+        CustodialWalletError::NotFound => StatusCode::NOT_FOUND,
+        CustodialWalletError::NotExportable => StatusCode::FORBIDDEN,
+        CustodialWalletError::AlreadyExists => StatusCode::CONFLICT,
+        CustodialWalletError::Key(e) => {
+            println!("🚨 Custodial wallet key unusable: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+        CustodialWalletError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
@@ -0,0 +1,129 @@
+use axum::{
+    extract::{Extension, Path, Query, State},
+    http::StatusCode,
+    Json,
+};
+use serde_json::json;
+use sqlx::SqlitePool;
+use validator::Validate;
+
+use crate::models::{
+    CreateWebhookEndpointRequest, WebhookDelivery, WebhookDeliveryListQuery, WebhookEndpoint, WebhookEndpointWithSecret,
+};
+use crate::services::{Actor, AuditLog, MerchantService, WebhookError, WebhookService};
+
+/// Registers a URL for the user's events, or with `merchant: true` for their
+/// merchant's invoice events. The signing secret is returned only here.
+pub async fn create_webhook_endpoint(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Json(payload): Json<CreateWebhookEndpointRequest>,
+) -> Result<Json<WebhookEndpointWithSecret>, StatusCode> {
+    if payload.validate().is_err() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let merchant_id = if payload.merchant {
+        let merchant = MerchantService::for_user(&pool, &actor.user_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or_else(|| webhook_status(WebhookError::NoMerchant))?;
+        Some(merchant.id)
+    } else {
+        None
+    };
+
+    let created = WebhookService::register(&pool, &actor.user_id, merchant_id.as_deref(), &payload)
+        .await
+        .map_err(webhook_status)?;
+    record(&pool, &actor, "webhook_endpoint.create", &created.endpoint).await?;
+    Ok(Json(created))
+}
+
+pub async fn list_webhook_endpoints(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+) -> Result<Json<Vec<WebhookEndpoint>>, StatusCode> {
+    WebhookService::list(&pool, &user_id)
+        .await
+        .map(Json)
+        .map_err(webhook_status)
+}
+
+pub async fn delete_webhook_endpoint(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Result<Json<WebhookEndpoint>, StatusCode> {
+    let deleted = WebhookService::delete(&pool, &actor.user_id, &id)
+        .await
+        .map_err(webhook_status)?;
+    record(&pool, &actor, "webhook_endpoint.delete", &deleted).await?;
+    Ok(Json(deleted))
+}
+
+/// The endpoint's delivery log, newest first, optionally by `status`.
+pub async fn list_webhook_deliveries(
+    State(pool): State<SqlitePool>,
+    Extension(user_id): Extension<String>,
+    Path(id): Path<String>,
+    Query(query): Query<WebhookDeliveryListQuery>,
+) -> Result<Json<Vec<WebhookDelivery>>, StatusCode> {
+    WebhookService::deliveries(&pool, &user_id, &id, &query)
+        .await
+        .map(Json)
+        .map_err(webhook_status)
+}
+
+/// Queues a delivery's event to be sent again.
+pub async fn replay_webhook_delivery(
+    State(pool): State<SqlitePool>,
+    actor: Actor,
+    Path(id): Path<String>,
+) -> Result<Json<WebhookDelivery>, StatusCode> {
+    let replay = WebhookService::replay(&pool, &actor.user_id, &id)
+        .await
+        .map_err(webhook_status)?;
+    AuditLog::record(
+        &pool,
+        &actor,
+        "webhook_delivery.replay",
+        "webhook_delivery",
+        Some(&replay.id),
+        json!({
+            "endpoint_id": replay.endpoint_id,
+            "event_id": replay.event_id,
+            "replay_of": replay.replay_of,
+        }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(Json(replay))
+}
+
+async fn record(pool: &SqlitePool, actor: &Actor, action: &str, endpoint: &WebhookEndpoint) -> Result<(), StatusCode> {
+    AuditLog::record(
+        pool,
+        actor,
+        action,
+        "webhook_endpoint",
+        Some(&endpoint.id),
+        json!({
+            "url": endpoint.url,
+            "merchant_id": endpoint.merchant_id,
+            "event_types": endpoint.event_types,
+        }),
+    )
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+fn webhook_status(error: WebhookError) -> StatusCode {
+    match error {
+        WebhookError::NotFound => StatusCode::NOT_FOUND,
+        WebhookError::UnknownEventType(_) | WebhookError::UnsafeUrl(_) => StatusCode::BAD_REQUEST,
+        WebhookError::NoMerchant => StatusCode::UNPROCESSABLE_ENTITY,
+        WebhookError::TooManyEndpoints(_) => StatusCode::CONFLICT,
+        WebhookError::Database(_) => StatusCode::INTERNAL_SERVER_ERROR,
+    }
+}
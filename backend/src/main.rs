@@ -2,17 +2,20 @@ mod handlers;
 mod middleware;
 mod models;
 mod services;
+#[cfg(test)]
+mod test_support;
 mod wallet_sdk;
 mod wallet_sdk_service;
 
 use axum::{
-    middleware::from_fn,
-    routing::{get, post},
+    middleware::{from_fn, from_fn_with_state},
+    routing::{delete, get, post, put},
     Router,
 };
 use dotenvy::dotenv;
 use sqlx::sqlite::SqlitePool;
 use std::env;
+use std::net::SocketAddr;
 use tower_http::cors::CorsLayer;
 use tracing_subscriber::fmt;
 
@@ -26,41 +29,216 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     // Run migrations
     sqlx::migrate!("./migrations").run(&pool).await?;
-    
+
+    // `novapay-backend verify-audit` checks the audit hash chain and exits
+    if env::args().nth(1).as_deref() == Some("verify-audit") {
+        services::AuditLog::seal(&pool).await?;
+        let report = services::AuditLog::verify(&pool).await?;
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        std::process::exit(if report.valid { 0 } else { 1 });
+    }
+
+    // Secrets that keep codes, tokens and keys unguessable; there are no
+    // fallbacks, so a deployment missing one stops here
+    services::signing::required_secret("OTP_SECRET")?;
+    services::signing::required_secret("ACCOUNT_TOKEN_SECRET")?;
+    services::signing::required_secret("API_KEY_SECRET")?;
+    services::key_vault::KeyVault::master_key()?;
+    services::Sep10Service::signing_key()?;
+
     // Initialize wallet balances for existing wallets
     sqlx::query("UPDATE wallets SET balance = 1000.0 WHERE balance = 0.0 OR balance IS NULL")
         .execute(&pool)
         .await?;
 
+    // Load JWT signing keys (creates the first one on a fresh database)
+    services::KeyManager::init(&pool).await?;
+
+    // Promote the operators listed in ADMIN_USER_IDS
+    services::AdminService::bootstrap_admins(&pool).await?;
+
+    // Background workers for queued side effects
+    services::JobWorker::spawn_pool(pool.clone());
+    services::RateLimiter::spawn_pruner();
+    services::AuditLog::spawn_sealer(pool.clone());
+    services::ScheduledTransferService::spawn_scheduler(pool.clone());
+    services::InvoiceService::spawn_watcher(pool.clone());
+
+    // Endpoints that move money get a tighter per-user limit on top of the
+    // general API one
+    let money_routes = Router::new()
+        .route("/transactions/send", post(handlers::send_money))
+        .route("/wallet/deposit", post(handlers::deposit_from_mpesa))
+        .route("/wallet/withdraw", post(handlers::withdraw_to_mpesa))
+        .route("/wallet/transfer", post(handlers::transfer_to_wallet))
+        .route("/sdk/wallet/send", post(handlers::send_payment_sdk))
+        .route("/sdk/wallet/export", post(handlers::export_wallet_key_sdk))
+        .route("/stellar/transactions/:id/submit", post(handlers::submit_stellar_transaction))
+        .route("/stellar/approvals/:id/sign", post(handlers::sign_multisig_approval))
+        .route("/payment-requests/:id/pay", post(handlers::pay_payment_request))
+        .route("/checkout/:id/pay", post(handlers::pay_checkout))
+        .route("/payouts/batches", post(handlers::create_payout_batch))
+        .route("/payouts/batches/:id/retry", post(handlers::retry_payout_batch))
+        .route("/scheduled-transfers", post(handlers::create_scheduled_transfer))
+        .route("/scheduled-transfers/:id/resume", post(handlers::resume_scheduled_transfer))
+        .layer(from_fn_with_state(pool.clone(), middleware::reject_frozen))
+        .layer(from_fn_with_state(services::RateLimitPolicy::money(), middleware::rate_limit));
+
     // Protected routes
     let protected_routes = Router::new()
         .route("/auth/me", get(handlers::me))
+        .route("/auth/logout", post(handlers::logout))
+        .route("/auth/sessions", get(handlers::list_sessions))
+        .route("/auth/sessions/:id", delete(handlers::revoke_session))
+        .route("/auth/password/change", post(handlers::change_password))
+        .route("/auth/email/resend", post(handlers::resend_email_verification))
+        .route("/auth/phone/send-code", post(handlers::send_phone_verification))
+        .route("/auth/phone/verify", post(handlers::verify_phone))
+        .route("/auth/mfa/totp/enroll", post(handlers::enroll_totp))
+        .route("/auth/mfa/totp/confirm", post(handlers::confirm_totp))
+        .route("/auth/mfa/totp/disable", post(handlers::disable_totp))
+        .route("/auth/step-up/otp", post(handlers::request_step_up_otp))
+        .route("/auth/step-up", post(handlers::create_step_up_proof))
         .route("/user/profile", get(handlers::get_profile))
         .route("/user/profile", post(handlers::update_profile).put(handlers::update_profile))
-        .route("/transactions/send", post(handlers::send_money))
+        .route("/user/pin", post(handlers::set_pin).put(handlers::change_pin))
+        .route("/user/pin/reset-code", post(handlers::request_pin_reset))
+        .route("/user/pin/reset", post(handlers::reset_pin))
+        .route("/notifications/preferences", get(handlers::get_notification_preferences).put(handlers::update_notification_preferences))
         .route("/transactions/history", get(handlers::get_transaction_history))
         .route("/stellar/fund-test-account", post(handlers::fund_test_account))
         .route("/stellar/get-balance", get(handlers::get_balance))
+        .route("/stellar/accounts", get(handlers::list_external_accounts).post(handlers::register_external_account))
+        .route("/stellar/transactions", post(handlers::prepare_stellar_transaction))
+        .route("/stellar/transactions/:id", get(handlers::get_stellar_transaction))
+        .route("/stellar/approvals", get(handlers::list_multisig_approvals).post(handlers::propose_multisig_approval))
+        .route("/stellar/approvals/:id", get(handlers::get_multisig_approval))
         .route("/wallet/balance", get(handlers::get_wallet_balance))
-        .route("/wallet/deposit", post(handlers::deposit_from_mpesa))
-        .route("/wallet/withdraw", post(handlers::withdraw_to_mpesa))
-        .route("/wallet/transfer", post(handlers::transfer_to_wallet))
+        .route("/sdk/wallet", get(handlers::list_wallets_sdk))
         .route("/sdk/wallet/create", post(handlers::create_wallet_sdk))
+        .route("/sdk/wallet/restore", post(handlers::restore_wallet_sdk))
         .route("/sdk/wallet/balance", post(handlers::get_wallet_balance_sdk))
-        .route("/sdk/wallet/send", post(handlers::send_payment_sdk))
         .route("/sdk/wallet/fund-testnet", post(handlers::fund_testnet_sdk))
         .route("/sdk/wallet/trustline", post(handlers::create_trustline_sdk))
-        .layer(from_fn(middleware::auth_middleware));
+        .route(
+            "/payment-requests",
+            get(handlers::list_payment_requests).post(handlers::create_payment_request),
+        )
+        .route(
+            "/payment-requests/:id",
+            get(handlers::get_payment_request).delete(handlers::cancel_payment_request),
+        )
+        .route("/payment-requests/:id/qr", get(handlers::payment_request_qr))
+        .route("/payment-requests/:id/decline", post(handlers::decline_payment_request))
+        .route("/payouts/batches", get(handlers::list_payout_batches))
+        .route("/payouts/batches/:id", get(handlers::get_payout_batch))
+        .route("/payouts/batches/:id/report", get(handlers::payout_batch_report))
+        .route("/scheduled-transfers", get(handlers::list_scheduled_transfers))
+        .route(
+            "/scheduled-transfers/:id",
+            get(handlers::get_scheduled_transfer).delete(handlers::cancel_scheduled_transfer),
+        )
+        .route("/scheduled-transfers/:id/pause", post(handlers::pause_scheduled_transfer))
+        .route("/scheduled-transfers/:id/skip", post(handlers::skip_scheduled_transfer))
+        .route("/fonbnk/widget-url", post(handlers::create_fonbnk_widget_url))
+        .route("/merchants", post(handlers::create_merchant))
+        .route("/merchants/me", get(handlers::get_merchant).patch(handlers::update_merchant))
+        .route("/merchants/me/api-keys", get(handlers::list_api_keys).post(handlers::create_api_key))
+        .route("/merchants/me/api-keys/:id", delete(handlers::revoke_api_key))
+        .route("/merchants/me/api-keys/:id/rotate", post(handlers::rotate_api_key))
+        .route("/merchants/me/invoices", get(handlers::list_invoices).post(handlers::create_invoice))
+        .route("/merchants/me/invoices/:id", get(handlers::get_invoice))
+        .route(
+            "/webhooks/endpoints",
+            get(handlers::list_webhook_endpoints).post(handlers::create_webhook_endpoint),
+        )
+        .route("/webhooks/endpoints/:id", delete(handlers::delete_webhook_endpoint))
+        .route("/webhooks/endpoints/:id/deliveries", get(handlers::list_webhook_deliveries))
+        .route("/webhooks/deliveries/:id/replay", post(handlers::replay_webhook_delivery))
+        .merge(money_routes)
+        .layer(from_fn_with_state(services::RateLimitPolicy::api(), middleware::rate_limit))
+        .layer(from_fn_with_state(pool.clone(), middleware::auth_middleware));
 
-    let app = Router::new()
-        // Public routes
+    // Operator routes
+    let admin_routes = Router::new()
+        .route("/admin/jobs", get(handlers::list_jobs))
+        .route("/admin/jobs/:id", get(handlers::get_job))
+        .route("/admin/jobs/:id/retry", post(handlers::retry_job))
+        .route("/admin/jobs/:id/cancel", post(handlers::cancel_job))
+        .route("/admin/jwt-keys", get(handlers::list_jwt_keys))
+        .route("/admin/jwt-keys/rotate", post(handlers::rotate_jwt_key))
+        .route("/admin/users", get(handlers::search_users))
+        .route("/admin/users/:id", get(handlers::get_user_admin))
+        .route("/admin/users/:id/wallet", get(handlers::get_user_wallet))
+        .route("/admin/users/:id/transactions", get(handlers::get_user_transactions))
+        .route("/admin/users/:id/unlock", post(handlers::unlock_user))
+        .route("/admin/users/:id/freeze", post(handlers::freeze_user))
+        .route("/admin/users/:id/unfreeze", post(handlers::unfreeze_user))
+        .route("/admin/users/:id/role", put(handlers::update_user_role))
+        .route("/admin/transactions/:id/reverse", post(handlers::reverse_transaction))
+        .route("/admin/audit", get(handlers::list_audit_events))
+        .route("/admin/audit/verify", get(handlers::verify_audit_chain))
+        .route("/admin/audit/export", get(handlers::export_audit_events))
+        .layer(from_fn(middleware::require_staff))
+        .layer(from_fn_with_state(pool.clone(), middleware::auth_middleware));
+
+    // Server-to-server merchant API, authenticated by signed API-key requests
+    let merchant_payout_routes = Router::new()
+        .route("/merchant/v1/payouts", post(handlers::merchant_payout))
+        .layer(from_fn_with_state(pool.clone(), middleware::reject_frozen))
+        .layer(from_fn_with_state(services::RateLimitPolicy::money(), middleware::rate_limit));
+
+    let merchant_api_routes = Router::new()
+        .route("/merchant/v1/balance", get(handlers::merchant_balance))
+        .route("/merchant/v1/payment-requests", post(handlers::merchant_payment_request))
+        .route(
+            "/merchant/v1/invoices",
+            get(handlers::merchant_list_invoices).post(handlers::merchant_create_invoice),
+        )
+        .route("/merchant/v1/invoices/:id", get(handlers::merchant_get_invoice))
+        .merge(merchant_payout_routes)
+        .layer(from_fn_with_state(services::RateLimitPolicy::api(), middleware::rate_limit))
+        .layer(from_fn_with_state(pool.clone(), middleware::api_key_middleware));
+
+    // Unauthenticated credential flows, limited per IP and per account email
+    let auth_routes = Router::new()
         .route("/auth/register", post(handlers::register))
         .route("/auth/login", post(handlers::login))
-        // Fonbnk routes (public for testing)
+        .route("/auth/refresh", post(handlers::refresh))
+        .route("/auth/password/forgot", post(handlers::forgot_password))
+        .route("/auth/password/reset", post(handlers::reset_password))
+        .route("/auth/email/verify", post(handlers::verify_email))
+        .route("/auth/otp/request", post(handlers::request_login_otp))
+        .route("/auth/otp/login", post(handlers::otp_login))
+        .route("/auth/mfa/verify", post(handlers::verify_mfa_challenge))
+        .route("/auth/sep10", get(handlers::sep10_challenge).post(handlers::sep10_token))
+        .layer(from_fn_with_state(services::RateLimitPolicy::auth(), middleware::rate_limit));
+
+    // Fonbnk routes (public for testing)
+    let fonbnk_routes = Router::new()
         .route("/fonbnk/deposit", post(handlers::deposit_via_fonbnk))
         .route("/fonbnk/rate", post(handlers::get_fonbnk_rate))
+        .layer(from_fn_with_state(services::RateLimitPolicy::public(), middleware::rate_limit));
+
+    // Checkout pages, open to anyone with the invoice id
+    let checkout_routes = Router::new()
+        .route("/checkout/:id", get(handlers::get_checkout))
+        .layer(from_fn_with_state(services::RateLimitPolicy::public(), middleware::rate_limit));
+
+    let app = Router::new()
+        // Public routes
+        .route("/.well-known/jwks.json", get(handlers::jwks))
+        .route("/.well-known/stellar.toml", get(handlers::stellar_toml))
+        // SMS provider delivery reports
+        .route("/sms/delivery-reports/africastalking", post(handlers::africastalking_delivery_report))
+        .route("/sms/delivery-reports/twilio", post(handlers::twilio_delivery_report))
+        .merge(auth_routes)
+        .merge(fonbnk_routes)
+        .merge(checkout_routes)
         // Merge protected routes
         .merge(protected_routes)
+        .merge(admin_routes)
+        .merge(merchant_api_routes)
         .layer(CorsLayer::permissive())
         .with_state(pool);
 
@@ -68,7 +246,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port)).await?;
     
     println!("🚀 NovaPay Backend running on port {} with wallet balances initialized", port);
-    axum::serve(listener, app).await?;
+    // Peer addresses are needed to rate-limit by IP
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>()).await?;
 
     Ok(())
 }
\ No newline at end of file
@@ -0,0 +1,134 @@
+use axum::{
+    async_trait,
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use sqlx::SqlitePool;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+
+use crate::middleware::rate_limit::client_ip;
+use crate::models::ApiScope;
+use crate::services::{ApiKeyContext, MerchantError, MerchantService, SignedRequest};
+
+/// Largest request body accepted on API-key routes; it is buffered to check
+/// the signature.
+const MAX_SIGNED_BODY: usize = 256 * 1024;
+
+/// Second authentication scheme, for server-to-server calls from merchants.
+/// Requests carry `X-NovaPay-Key`, `X-NovaPay-Timestamp` (unix seconds) and
+/// `X-NovaPay-Signature` (hex HMAC-SHA256 of the canonical request under the
+/// key's secret). Inserts the `ApiKeyContext` and the merchant owner's user id,
+/// so layers written for `auth_middleware` (rate limits, freezes) apply as-is.
+pub async fn api_key_middleware(
+    State(pool): State<SqlitePool>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let (parts, body) = request.into_parts();
+    let header = |headers: &HeaderMap, name: &str| -> Result<String, StatusCode> {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .ok_or(StatusCode::UNAUTHORIZED)
+    };
+    let key_id = header(&parts.headers, "X-NovaPay-Key")?;
+    let timestamp = header(&parts.headers, "X-NovaPay-Timestamp")?;
+    let signature = header(&parts.headers, "X-NovaPay-Signature")?;
+
+    let bytes = to_bytes(body, MAX_SIGNED_BODY)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE)?;
+    let ip = client_ip(&parts.headers, peer);
+    let path_and_query = parts.uri.path_and_query().map(|pq| pq.as_str()).unwrap_or("/");
+
+    let signed = SignedRequest {
+        key_id: &key_id,
+        timestamp: &timestamp,
+        signature: &signature,
+        method: parts.method.as_str(),
+        path_and_query,
+        body: &bytes,
+        ip: &ip,
+    };
+    let context = match MerchantService::authenticate(&pool, &signed).await {
+        Ok(context) => context,
+        Err(MerchantError::Database(_)) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+        Err(e @ MerchantError::IpNotAllowed(_)) => {
+            println!("⛔ API key {}: {}", key_id, e);
+            return Err(StatusCode::FORBIDDEN);
+        }
+        Err(e) => {
+            println!("⛔ API key {}: {}", key_id, e);
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    };
+
+    let mut request = Request::from_parts(parts, Body::from(bytes));
+    request.extensions_mut().insert(context.owner_user_id.clone());
+    request.extensions_mut().insert(context);
+    Ok(next.run(request).await)
+}
+
+/// Type-level name for an `ApiScope`: `_: RequireScope<scope::ReadBalance>`.
+pub trait ScopeMarker: Send + Sync + 'static {
+    const SCOPE: ApiScope;
+}
+
+pub mod scope {
+    use super::ScopeMarker;
+    use crate::models::ApiScope;
+
+    macro_rules! scope_markers {
+        ($($name:ident),* $(,)?) => {
+            $(
+                pub struct $name;
+
+                impl ScopeMarker for $name {
+                    const SCOPE: ApiScope = ApiScope::$name;
+                }
+            )*
+        };
+    }
+
+    scope_markers!(ReadBalance, CreatePayouts, CreatePaymentRequests, CreateInvoices, ReadInvoices);
+}
+
+/// Extractor that rejects the request with `403` unless the API key grants
+/// `S`. Must run after `api_key_middleware`.
+pub struct RequireScope<S: ScopeMarker> {
+    pub context: ApiKeyContext,
+    _scope: PhantomData<S>,
+}
+
+#[async_trait]
+impl<St, S> FromRequestParts<St> for RequireScope<S>
+where
+    St: Send + Sync,
+    S: ScopeMarker,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &St) -> Result<Self, Self::Rejection> {
+        let context = parts
+            .extensions
+            .get::<ApiKeyContext>()
+            .ok_or(StatusCode::UNAUTHORIZED)?
+            .clone();
+
+        if !context.has(S::SCOPE) {
+            println!("⛔ API key {} lacks {}", context.key_id, S::SCOPE.as_str());
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        Ok(Self {
+            context,
+            _scope: PhantomData,
+        })
+    }
+}
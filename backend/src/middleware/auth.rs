@@ -1,12 +1,19 @@
 use axum::{
-    extract::Request,
+    extract::{Request, State},
     http::{HeaderMap, StatusCode},
     middleware::Next,
     response::Response,
 };
-use crate::services::AuthService;
+use sqlx::SqlitePool;
+use crate::models::Role;
+use crate::services::{AuthService, SessionService};
+
+/// Session family of the authenticated request, next to the `String` user id.
+#[derive(Debug, Clone)]
+pub struct SessionId(pub String);
 
 pub async fn auth_middleware(
+    State(pool): State<SqlitePool>,
     headers: HeaderMap,
     mut request: Request,
     next: Next,
@@ -26,8 +33,18 @@ pub async fn auth_middleware(
         Err(_) => return Err(StatusCode::UNAUTHORIZED),
     };
 
+    // Access tokens stay valid only while their session has not been revoked
+    let active = SessionService::is_active(&pool, &claims.sid)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !active {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
     // Add user_id to request extensions
+    request.extensions_mut().insert(Role::from_str_or_customer(&claims.role));
     request.extensions_mut().insert(claims.sub);
+    request.extensions_mut().insert(SessionId(claims.sid));
     
     Ok(next.run(request).await)
-}
\ No newline at end of file
+}
@@ -1,3 +1,9 @@
+pub mod api_key;
 pub mod auth;
+pub mod rate_limit;
+pub mod rbac;
 
-pub use auth::*;
\ No newline at end of file
+pub use api_key::*;
+pub use auth::*;
+pub use rate_limit::*;
+pub use rbac::*;
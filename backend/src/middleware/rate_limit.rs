@@ -0,0 +1,180 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, Request, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ipnet::IpNet;
+use serde_json::Value;
+use std::env;
+use std::net::{IpAddr, SocketAddr};
+
+use crate::services::{RateLimitKey, RateLimitPolicy, RateLimiter};
+
+/// Largest body read to find the `email` key; bigger bodies are rejected.
+const MAX_INSPECTED_BODY: usize = 64 * 1024;
+
+/// Applies `policy` to a route group. Layer it inside `auth_middleware` when
+/// the policy is keyed by user. Refused requests get `429` with `Retry-After`.
+pub async fn rate_limit(
+    State(policy): State<RateLimitPolicy>,
+    ConnectInfo(peer): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let mut keys = Vec::new();
+    let mut request = request;
+
+    for kind in policy.keys {
+        match kind {
+            RateLimitKey::Ip => keys.push((*kind, client_ip(request.headers(), peer))),
+            RateLimitKey::User => {
+                if let Some(user_id) = request.extensions().get::<String>() {
+                    keys.push((*kind, user_id.clone()));
+                }
+            }
+            RateLimitKey::Email => {
+                let (parts, body) = request.into_parts();
+                let bytes = match to_bytes(body, MAX_INSPECTED_BODY).await {
+                    Ok(bytes) => bytes,
+                    Err(_) => return StatusCode::PAYLOAD_TOO_LARGE.into_response(),
+                };
+                if let Some(email) = serde_json::from_slice::<Value>(&bytes)
+                    .ok()
+                    .and_then(|body| body.get("email")?.as_str().map(|e| e.trim().to_lowercase()))
+                {
+                    keys.push((*kind, email));
+                }
+                request = Request::from_parts(parts, Body::from(bytes));
+            }
+        }
+    }
+
+    if let Err(wait) = RateLimiter::check(&policy, &keys) {
+        let retry_after = wait.as_secs() + 1;
+        let mut response = StatusCode::TOO_MANY_REQUESTS.into_response();
+        response
+            .headers_mut()
+            .insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        return response;
+    }
+
+    next.run(request).await
+}
+
+/// The address requests are counted under. `X-Forwarded-For` is only read
+/// when the peer is one of `TRUSTED_PROXIES` (comma-separated addresses or
+/// CIDR ranges); the right-most hop that is not itself a trusted proxy is the
+/// client, since anything further left was written by the client.
+pub(crate) fn client_ip(headers: &HeaderMap, peer: SocketAddr) -> String {
+    forwarded_client(headers, peer.ip(), &trusted_proxies()).to_string()
+}
+
+fn trusted_proxies() -> Vec<IpNet> {
+    env::var("TRUSTED_PROXIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .filter_map(|entry| {
+            let parsed = parse_network(entry);
+            if parsed.is_none() {
+                println!("⚠️ Ignoring invalid TRUSTED_PROXIES entry: {}", entry);
+            }
+            parsed
+        })
+        .collect()
+}
+
+/// An IP or CIDR range; a bare address becomes a single-host range.
+fn parse_network(entry: &str) -> Option<IpNet> {
+    entry
+        .parse::<IpNet>()
+        .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+        .ok()
+}
+
+fn is_trusted(ip: IpAddr, trusted: &[IpNet]) -> bool {
+    let ip = ip.to_canonical();
+    trusted.iter().any(|net| net.contains(&ip))
+}
+
+fn forwarded_client(headers: &HeaderMap, peer: IpAddr, trusted: &[IpNet]) -> IpAddr {
+    let mut client = peer.to_canonical();
+    if !is_trusted(client, trusted) {
+        return client;
+    }
+    let hops = headers
+        .get_all("X-Forwarded-For")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .collect::<Vec<_>>();
+    for hop in hops.into_iter().rev() {
+        // A malformed hop cannot be attributed to anyone, so the last proxy
+        // that forwarded it is counted instead
+        let Ok(ip) = hop.parse::<IpAddr>() else {
+            break;
+        };
+        client = ip.to_canonical();
+        if !is_trusted(client, trusted) {
+            break;
+        }
+    }
+    client
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn forwarded(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Forwarded-For", HeaderValue::from_str(value).unwrap());
+        headers
+    }
+
+    fn ip(value: &str) -> IpAddr {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_untrusted_peer_ignores_forwarded_for() {
+        let headers = forwarded("203.0.113.7");
+        assert_eq!(forwarded_client(&headers, ip("198.51.100.1"), &[]), ip("198.51.100.1"));
+        let trusted = [parse_network("10.0.0.0/8").unwrap()];
+        assert_eq!(forwarded_client(&headers, ip("198.51.100.1"), &trusted), ip("198.51.100.1"));
+    }
+
+    #[test]
+    fn test_rightmost_untrusted_hop_is_the_client() {
+        let trusted = [parse_network("10.0.0.0/8").unwrap(), parse_network("192.0.2.10").unwrap()];
+        // The client made up the first entry; the proxies appended the rest
+        let headers = forwarded("1.2.3.4, 203.0.113.7, 10.1.2.3");
+        assert_eq!(forwarded_client(&headers, ip("192.0.2.10"), &trusted), ip("203.0.113.7"));
+        // Only proxies in the chain: the outermost one is all that is known
+        let headers = forwarded("10.9.9.9, 10.1.2.3");
+        assert_eq!(forwarded_client(&headers, ip("192.0.2.10"), &trusted), ip("10.9.9.9"));
+        // No header at all
+        assert_eq!(forwarded_client(&HeaderMap::new(), ip("10.0.0.1"), &trusted), ip("10.0.0.1"));
+        // Garbage stops the walk at the proxy that passed it on
+        let headers = forwarded("203.0.113.7, not-an-ip, 10.1.2.3");
+        assert_eq!(forwarded_client(&headers, ip("192.0.2.10"), &trusted), ip("10.1.2.3"));
+    }
+
+    #[test]
+    fn test_trusted_networks() {
+        assert!(parse_network("10.0.0.0/33").is_none());
+        assert!(parse_network("proxy.internal").is_none());
+        let trusted = [parse_network("10.0.0.0/8").unwrap(), parse_network("fd00::/8").unwrap()];
+        assert!(is_trusted(ip("10.255.0.1"), &trusted));
+        assert!(!is_trusted(ip("11.0.0.1"), &trusted));
+        assert!(is_trusted(ip("fd12::1"), &trusted));
+        assert!(!is_trusted(ip("fe80::1"), &trusted));
+        // IPv4 peers seen through a dual-stack socket
+        assert!(is_trusted(ip("::ffff:10.0.0.1"), &trusted));
+        assert!(is_trusted(ip("1.2.3.4"), &[parse_network("0.0.0.0/0").unwrap()]));
+    }
+}
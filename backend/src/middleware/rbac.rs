@@ -0,0 +1,154 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use sqlx::SqlitePool;
+use std::marker::PhantomData;
+use std::net::SocketAddr;
+
+use crate::middleware::rate_limit::client_ip;
+use crate::models::{Permission, Role};
+use crate::services::{Actor, ApiKeyContext};
+
+/// Type-level name for a `Permission`, so handlers can declare what they need
+/// in their signature: `_: RequirePermission<perm::FreezeAccounts>`.
+pub trait PermissionMarker: Send + Sync + 'static {
+    const PERMISSION: Permission;
+}
+
+pub mod perm {
+    use super::PermissionMarker;
+    use crate::models::Permission;
+
+    macro_rules! permission_markers {
+        ($($name:ident),* $(,)?) => {
+            $(
+                pub struct $name;
+
+                impl PermissionMarker for $name {
+                    const PERMISSION: Permission = Permission::$name;
+                }
+            )*
+        };
+    }
+
+    permission_markers!(
+        ViewUsers,
+        ViewWallets,
+        ViewTransactions,
+        FreezeAccounts,
+        UnlockAccounts,
+        ReverseTransactions,
+        ManageJobs,
+        ManageKeys,
+        ManageRoles,
+        ViewAudit,
+    );
+}
+
+/// Builds the audit `Actor` for a request from its address and headers.
+pub(crate) fn request_actor(user_id: String, role: Role, headers: &HeaderMap, peer: Option<SocketAddr>) -> Actor {
+    Actor {
+        user_id,
+        role,
+        ip_address: peer.map(|peer| client_ip(headers, peer)),
+        user_agent: headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(512).collect()),
+    }
+}
+
+/// The caller as an audit `Actor`: the signed-in user after `auth_middleware`,
+/// or the merchant's owner after `api_key_middleware`.
+#[async_trait]
+impl<S> FromRequestParts<S> for Actor
+where
+    S: Send + Sync,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let user_id = parts.extensions.get::<String>().ok_or(StatusCode::UNAUTHORIZED)?.clone();
+        let role = match (parts.extensions.get::<Role>(), parts.extensions.get::<ApiKeyContext>()) {
+            (Some(role), _) => *role,
+            (None, Some(key)) => key.owner_role,
+            (None, None) => return Err(StatusCode::UNAUTHORIZED),
+        };
+        let peer = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(peer)| *peer);
+
+        Ok(request_actor(user_id, role, &parts.headers, peer))
+    }
+}
+
+/// Extractor that rejects the request with `403` unless the caller's role
+/// grants `P`. Must run after `auth_middleware`. Yields the caller as an
+/// audit `Actor`.
+pub struct RequirePermission<P: PermissionMarker> {
+    pub actor: Actor,
+    _permission: PhantomData<P>,
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    S: Send + Sync,
+    P: PermissionMarker,
+{
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // Staff permissions come with a signed-in session, never an API key
+        if parts.extensions.get::<Role>().is_none() {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+        let actor = Actor::from_request_parts(parts, state).await?;
+
+        if !actor.role.has(P::PERMISSION) {
+            println!("⛔ {} ({}) lacks {:?}", actor.user_id, actor.role.as_str(), P::PERMISSION);
+            return Err(StatusCode::FORBIDDEN);
+        }
+
+        Ok(Self {
+            actor,
+            _permission: PhantomData,
+        })
+    }
+}
+
+/// Restricts a router to staff roles. Handlers still check their specific
+/// permission with `RequirePermission`. Must run after `auth_middleware`.
+pub async fn require_staff(request: Request, next: Next) -> Result<Response, StatusCode> {
+    let role = request.extensions().get::<Role>().ok_or(StatusCode::UNAUTHORIZED)?;
+    if !role.is_staff() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    Ok(next.run(request).await)
+}
+
+/// Blocks money movement for frozen accounts. Checked against the database
+/// rather than the token so a freeze takes effect immediately.
+pub async fn reject_frozen(
+    State(pool): State<SqlitePool>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let user_id = request.extensions().get::<String>().ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let frozen: Option<i64> = sqlx::query_scalar("SELECT 1 FROM users WHERE id = ? AND frozen_at IS NOT NULL")
+        .bind(user_id)
+        .fetch_optional(&pool)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if frozen.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+use crate::models::{Role, User};
+
+#[derive(Debug, Deserialize)]
+pub struct UserSearchQuery {
+    /// Matched against email, name and phone number.
+    pub q: Option<String>,
+    pub role: Option<String>,
+    pub frozen: Option<bool>,
+    pub limit: Option<i64>,
+}
+
+/// A user as staff see it: the profile plus account state, never credentials.
+#[derive(Debug, Serialize)]
+pub struct AdminUserView {
+    pub id: String,
+    pub email: String,
+    pub full_name: String,
+    pub phone_number: Option<String>,
+    pub stellar_public_key: Option<String>,
+    pub preferred_language: String,
+    pub role: String,
+    pub phone_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub email_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub frozen_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub frozen_reason: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<User> for AdminUserView {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            full_name: user.full_name,
+            phone_number: user.phone_number,
+            stellar_public_key: user.stellar_public_key,
+            preferred_language: user.preferred_language,
+            role: user.role,
+            phone_verified_at: user.phone_verified_at,
+            email_verified_at: user.email_verified_at,
+            frozen_at: user.frozen_at,
+            frozen_reason: user.frozen_reason,
+            created_at: user.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct FreezeAccountRequest {
+    #[validate(length(min = 3, max = 500))]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ReverseTransactionRequest {
+    #[validate(length(min = 3, max = 500))]
+    pub reason: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRoleRequest {
+    pub role: Role,
+}
@@ -0,0 +1,67 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct AuditEvent {
+    pub id: String,
+    pub actor_id: String,
+    pub actor_role: String,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: Option<String>,
+    pub details: String,
+    pub before_state: Option<String>,
+    pub after_state: Option<String>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    /// `None` until the sealer has linked the event into the chain.
+    pub seq: Option<i64>,
+    pub prev_hash: Option<String>,
+    pub hash: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub actor_id: Option<String>,
+    pub action: Option<String>,
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditExportFormat {
+    #[default]
+    Jsonl,
+    Csv,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditExportQuery {
+    pub from: Option<chrono::DateTime<chrono::Utc>>,
+    pub to: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(default)]
+    pub format: AuditExportFormat,
+}
+
+/// Result of walking the hash chain from the first sealed event.
+#[derive(Debug, Serialize)]
+pub struct AuditVerification {
+    pub valid: bool,
+    pub checked: i64,
+    /// Events not yet linked into the chain.
+    pub unsealed: i64,
+    pub head_seq: Option<i64>,
+    pub head_hash: Option<String>,
+    pub problems: Vec<AuditChainProblem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AuditChainProblem {
+    pub seq: i64,
+    pub event_id: Option<String>,
+    /// `gap`, `broken_link` or `hash_mismatch`.
+    pub kind: &'static str,
+}
@@ -0,0 +1,97 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+/// A Stellar account created through `/sdk/wallet`. The secret key never
+/// leaves the server except through the opt-in export.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct CustodialWallet {
+    #[serde(rename = "wallet_id")]
+    pub id: String,
+    pub public_key: String,
+    #[serde(skip_serializing)]
+    pub encrypted_secret: String,
+    pub label: Option<String>,
+    pub exportable: bool,
+    pub exported_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Default, Deserialize, Validate)]
+pub struct CreateCustodialWalletRequest {
+    #[validate(length(min = 1, max = 64))]
+    pub label: Option<String>,
+    /// Lets the owner export the secret key later, e.g. to move to a
+    /// self-custody wallet.
+    #[serde(default)]
+    pub exportable: bool,
+    /// Derives the wallet from a new BIP-39 phrase and returns the phrase
+    /// once, so the wallet can be restored with `/sdk/wallet/restore`. The
+    /// phrase is the secret key in another form, so it needs `exportable`
+    /// and the same PIN and step-up as an export.
+    #[serde(default)]
+    pub backup_phrase: bool,
+    /// Transaction PIN; required with `backup_phrase`.
+    #[validate(length(min = 4, max = 6))]
+    pub pin: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CreatedCustodialWallet {
+    #[serde(flatten)]
+    pub wallet: CustodialWallet,
+    /// Only present when asked for at creation; never shown again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup_phrase: Option<String>,
+}
+
+/// Restores account `account_index` (SEP-0005 path m/44'/148'/n') of a
+/// backup phrase as a custodial wallet.
+#[derive(Debug, Deserialize, Validate)]
+pub struct RestoreCustodialWalletRequest {
+    #[validate(length(min = 1, max = 512))]
+    pub mnemonic: String,
+    /// The optional BIP-39 passphrase the phrase was used with.
+    #[validate(length(max = 256))]
+    pub passphrase: Option<String>,
+    #[serde(default)]
+    pub account_index: u32,
+    #[validate(length(min = 1, max = 64))]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub exportable: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CustodialWalletRef {
+    pub wallet_id: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CustodialPaymentRequest {
+    pub wallet_id: String,
+    #[validate(length(equal = 56))]
+    pub destination: String,
+    pub amount: String,
+    pub asset_code: Option<String>,
+    pub memo: Option<String>,
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CustodialTrustlineRequest {
+    pub wallet_id: String,
+    #[validate(length(min = 1, max = 12))]
+    pub asset_code: String,
+    #[validate(length(equal = 56))]
+    pub issuer: String,
+    pub limit: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ExportWalletKeyRequest {
+    pub wallet_id: String,
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
+}
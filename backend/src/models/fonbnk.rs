@@ -0,0 +1,36 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct FonbnkOrder {
+    pub id: String,
+    pub user_id: String,
+    pub wallet_address: String,
+    pub amount: f64,
+    pub currency: String,
+    pub network: String,
+    pub asset: String,
+    pub widget_url: String,
+    pub fonbnk_order_id: Option<String>,
+    pub status: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateFonbnkWidgetUrl {
+    #[validate(range(min = 0.01))]
+    pub amount: f64,
+    #[validate(length(equal = 3))]
+    pub currency: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FonbnkWidgetUrlResponse {
+    pub order_reference: String,
+    pub url: String,
+    pub wallet_address: String,
+    pub amount: f64,
+    pub currency: String,
+}
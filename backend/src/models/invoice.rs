@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+/// A merchant's bill for goods, payable from a NovaPay wallet or on-chain.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct Invoice {
+    pub id: String,
+    pub merchant_id: String,
+    pub reference: Option<String>,
+    pub description: Option<String>,
+    pub currency: String,
+    pub amount_due: f64,
+    pub amount_paid: f64,
+    pub status: String,
+    pub destination: String,
+    #[serde(skip_serializing)]
+    pub muxed_id: i64,
+    pub memo: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub paid_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    #[serde(skip_serializing)]
+    pub processing_transaction_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct InvoiceItem {
+    pub description: String,
+    pub quantity: i64,
+    pub unit_price: f64,
+    pub amount: f64,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct InvoicePayment {
+    pub id: String,
+    /// `wallet` or `stellar`.
+    pub source: String,
+    pub from_account: Option<String>,
+    pub amount: f64,
+    pub transaction_id: Option<String>,
+    pub tx_hash: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// What the merchant sees: the invoice with its lines, the payments made
+/// against it and how it can be paid.
+#[derive(Debug, Serialize)]
+pub struct InvoiceView {
+    #[serde(flatten)]
+    pub invoice: Invoice,
+    pub amount_remaining: f64,
+    pub items: Vec<InvoiceItem>,
+    pub payments: Vec<InvoicePayment>,
+    #[serde(flatten)]
+    pub pay: InvoicePayOptions,
+}
+
+/// What the buyer sees on the checkout page.
+#[derive(Debug, Serialize)]
+pub struct CheckoutView {
+    pub id: String,
+    pub merchant_name: String,
+    pub reference: Option<String>,
+    pub description: Option<String>,
+    pub currency: String,
+    pub amount_due: f64,
+    pub amount_paid: f64,
+    pub amount_remaining: f64,
+    pub status: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub items: Vec<InvoiceItem>,
+    #[serde(flatten)]
+    pub pay: InvoicePayOptions,
+}
+
+#[derive(Debug, Serialize)]
+pub struct InvoicePayOptions {
+    /// `M...` address that identifies this invoice; pay it with no memo.
+    pub muxed_address: String,
+    /// Plain address to pay with `memo` instead.
+    pub destination: String,
+    pub memo: String,
+    /// SEP-7 `web+stellar:pay` URI for the amount remaining.
+    pub pay_uri: String,
+    /// Checkout page in the NovaPay app.
+    pub checkout_url: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateInvoiceRequest {
+    #[validate(length(min = 1, max = 64))]
+    pub reference: Option<String>,
+    #[validate(length(max = 200))]
+    pub description: Option<String>,
+    /// `XLM` (default) or `CODE:ISSUER`.
+    pub currency: Option<String>,
+    #[validate(length(min = 1, max = 100))]
+    #[validate]
+    pub items: Vec<InvoiceItemRequest>,
+    /// Minutes until the invoice expires, default `INVOICE_TTL_MINUTES`.
+    #[validate(range(min = 5, max = 43200))]
+    pub expires_in_minutes: Option<i64>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct InvoiceItemRequest {
+    #[validate(length(min = 1, max = 200))]
+    pub description: String,
+    #[validate(range(min = 1, max = 100000))]
+    pub quantity: i64,
+    #[validate(range(min = 0.0000001))]
+    pub unit_price: f64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct InvoiceListQuery {
+    pub status: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PayInvoiceRequest {
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
+}
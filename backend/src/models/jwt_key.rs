@@ -0,0 +1,15 @@
+use serde::Serialize;
+use sqlx::FromRow;
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct JwtKey {
+    pub kid: String,
+    pub algorithm: String,
+    /// Hex Ed25519 seed, sealed with `KeyVault` under the kid.
+    #[serde(skip_serializing)]
+    pub private_key: String,
+    pub public_key: String,
+    pub status: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub retire_at: Option<chrono::DateTime<chrono::Utc>>,
+}
@@ -0,0 +1,164 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Merchant {
+    pub id: String,
+    pub owner_user_id: String,
+    pub business_name: String,
+    pub website: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// What an API key may do. Stored comma-separated in `api_keys.scopes`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ApiScope {
+    #[serde(rename = "balance:read")]
+    ReadBalance,
+    #[serde(rename = "payouts:create")]
+    CreatePayouts,
+    #[serde(rename = "payment_requests:create")]
+    CreatePaymentRequests,
+    #[serde(rename = "invoices:create")]
+    CreateInvoices,
+    #[serde(rename = "invoices:read")]
+    ReadInvoices,
+}
+
+impl ApiScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiScope::ReadBalance => "balance:read",
+            ApiScope::CreatePayouts => "payouts:create",
+            ApiScope::CreatePaymentRequests => "payment_requests:create",
+            ApiScope::CreateInvoices => "invoices:create",
+            ApiScope::ReadInvoices => "invoices:read",
+        }
+    }
+
+    pub fn parse(value: &str) -> Option<Self> {
+        match value {
+            "balance:read" => Some(ApiScope::ReadBalance),
+            "payouts:create" => Some(ApiScope::CreatePayouts),
+            "payment_requests:create" => Some(ApiScope::CreatePaymentRequests),
+            "invoices:create" => Some(ApiScope::CreateInvoices),
+            "invoices:read" => Some(ApiScope::ReadInvoices),
+            _ => None,
+        }
+    }
+
+    /// Scopes from the stored comma-separated list; unknown entries are dropped.
+    pub fn parse_list(value: &str) -> Vec<Self> {
+        value.split(',').filter_map(|scope| Self::parse(scope.trim())).collect()
+    }
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct ApiKey {
+    pub id: String,
+    pub merchant_id: String,
+    pub name: String,
+    pub scopes: String,
+    pub ip_allowlist: Option<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub rotated_to: Option<String>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_ip: Option<String>,
+    /// The key's secret, sealed under `API_KEY_SECRET`.
+    pub secret_sealed: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ApiKeyResponse {
+    pub id: String,
+    pub name: String,
+    pub scopes: Vec<ApiScope>,
+    pub ip_allowlist: Vec<String>,
+    pub expires_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub revoked_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub rotated_to: Option<String>,
+    pub last_used_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_used_ip: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl From<ApiKey> for ApiKeyResponse {
+    fn from(key: ApiKey) -> Self {
+        Self {
+            scopes: ApiScope::parse_list(&key.scopes),
+            ip_allowlist: key
+                .ip_allowlist
+                .map(|list| list.split(',').map(str::to_string).collect())
+                .unwrap_or_default(),
+            id: key.id,
+            name: key.name,
+            expires_at: key.expires_at,
+            revoked_at: key.revoked_at,
+            rotated_to: key.rotated_to,
+            last_used_at: key.last_used_at,
+            last_used_ip: key.last_used_ip,
+            created_at: key.created_at,
+        }
+    }
+}
+
+/// A newly issued key. The secret is only ever returned here.
+#[derive(Debug, Serialize)]
+pub struct ApiKeyWithSecret {
+    #[serde(flatten)]
+    pub key: ApiKeyResponse,
+    pub secret: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateMerchantRequest {
+    #[validate(length(min = 2, max = 120))]
+    pub business_name: String,
+    #[validate(url)]
+    pub website: Option<String>,
+}
+
+/// Fields left out are unchanged. Invoice events go to the owner's webhook
+/// endpoints registered with `"merchant": true`.
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateMerchantRequest {
+    #[validate(length(min = 2, max = 120))]
+    pub business_name: Option<String>,
+    #[validate(url)]
+    pub website: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateApiKeyRequest {
+    #[validate(length(min = 1, max = 60))]
+    pub name: String,
+    #[validate(length(min = 1))]
+    pub scopes: Vec<ApiScope>,
+    /// IPs or CIDR ranges the key may be used from; empty allows any address.
+    #[serde(default)]
+    #[validate(length(max = 20))]
+    pub ip_allowlist: Vec<String>,
+    /// Transaction PIN; required when the key can create payouts.
+    pub pin: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct RotateApiKeyRequest {
+    /// Transaction PIN; required when the key can create payouts.
+    pub pin: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct MerchantPayoutRequest {
+    #[validate(range(min = 0.01))]
+    pub xlm_amount: f64,
+    /// Stellar public key of the NovaPay wallet or external account to pay.
+    #[validate(length(equal = 56))]
+    pub to_wallet_id: String,
+    #[validate(length(max = 100))]
+    pub reference: Option<String>,
+}
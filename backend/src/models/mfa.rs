@@ -0,0 +1,60 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+/// A TOTP code, or a recovery code where the endpoint accepts one.
+#[derive(Debug, Deserialize, Validate)]
+pub struct TotpCodeRequest {
+    #[validate(length(min = 6, max = 11))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct MfaChallengeRequest {
+    #[validate(length(min = 1))]
+    pub mfa_token: String,
+    #[validate(length(min = 6, max = 11))]
+    pub code: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepUpKind {
+    Transfer,
+    Withdrawal,
+    SecretExport,
+}
+
+impl StepUpKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StepUpKind::Transfer => "transfer",
+            StepUpKind::Withdrawal => "withdrawal",
+            StepUpKind::SecretExport => "secret_export",
+        }
+    }
+}
+
+/// The exact operation a step-up proof authorises. A proof only unlocks a
+/// request with the same kind, amount, currency and destination.
+#[derive(Debug, Clone, Deserialize)]
+pub struct StepUpAction {
+    pub action: StepUpKind,
+    pub amount: Option<f64>,
+    pub currency: Option<String>,
+    pub destination: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StepUpMethod {
+    Totp,
+    Otp,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct StepUpProofRequest {
+    #[serde(flatten)]
+    pub action: StepUpAction,
+    pub method: StepUpMethod,
+    pub code: String,
+}
@@ -1,7 +1,49 @@
+pub mod admin;
+pub mod audit;
+pub mod custodial_wallet;
+pub mod fonbnk;
+pub mod job;
+pub mod invoice;
+pub mod jwt_key;
+pub mod merchant;
+pub mod mfa;
+pub mod multisig;
+pub mod non_custodial;
+pub mod notification;
+pub mod payment_request;
+pub mod payout;
+pub mod pin;
+pub mod role;
+pub mod scheduled_transfer;
+pub mod sep10;
+pub mod session;
+pub mod sms;
 pub mod user;
 pub mod transaction;
 pub mod wallet;
+pub mod webhook;
 
+pub use admin::*;
+pub use audit::*;
+pub use custodial_wallet::*;
+pub use fonbnk::*;
+pub use job::*;
+pub use invoice::*;
+pub use jwt_key::*;
+pub use merchant::*;
+pub use mfa::*;
+pub use multisig::*;
+pub use non_custodial::*;
+pub use notification::*;
+pub use payment_request::*;
+pub use payout::*;
+pub use pin::*;
+pub use role::*;
+pub use scheduled_transfer::*;
+pub use sep10::*;
+pub use session::*;
+pub use sms::*;
 pub use user::*;
 pub use transaction::*;
-pub use wallet::*;
\ No newline at end of file
+pub use wallet::*;
+pub use webhook::*;
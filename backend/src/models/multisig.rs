@@ -0,0 +1,45 @@
+use serde::Serialize;
+use sqlx::FromRow;
+
+/// A transaction on a multisig account waiting for co-signers.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct MultisigApproval {
+    pub id: String,
+    #[serde(rename = "proposed_by")]
+    pub user_id: String,
+    pub source_public_key: String,
+    pub kind: String,
+    #[serde(skip_serializing)]
+    pub details: String,
+    pub unsigned_xdr: String,
+    pub tx_hash: String,
+    pub network_passphrase: String,
+    pub threshold: i64,
+    pub status: String,
+    pub error: Option<String>,
+    pub transaction_id: Option<String>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub submitted_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing)]
+    pub submission_envelope: Option<String>,
+}
+
+/// One of the source account's signers and whether it has signed yet.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ApprovalSigner {
+    pub public_key: String,
+    pub weight: i64,
+    #[serde(skip_serializing)]
+    pub signature: Option<String>,
+    pub signed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct MultisigApprovalView {
+    #[serde(flatten)]
+    pub approval: MultisigApproval,
+    pub signers: Vec<ApprovalSigner>,
+    /// Combined weight of the signatures collected so far.
+    pub weight: i64,
+}
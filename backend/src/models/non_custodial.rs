@@ -0,0 +1,122 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+/// A Stellar account the user holds the key for.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ExternalAccount {
+    pub id: String,
+    pub public_key: String,
+    pub label: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RegisterExternalAccountRequest {
+    #[validate(length(equal = 56))]
+    pub public_key: String,
+    #[validate(length(min = 1, max = 64))]
+    pub label: Option<String>,
+    /// A SEP-10 challenge for `public_key` from `GET /auth/sep10`, signed by
+    /// the account, proving the caller controls it.
+    #[validate(length(min = 1))]
+    pub challenge: String,
+}
+
+/// A server-built transaction waiting for (or past) the user's signature.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct StellarTransaction {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub user_id: String,
+    pub source_public_key: String,
+    pub kind: String,
+    #[serde(skip_serializing)]
+    pub details: String,
+    pub unsigned_xdr: String,
+    pub tx_hash: String,
+    pub network_passphrase: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub transaction_id: Option<String>,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub submitted_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Assets are `XLM` or `CODE:ISSUER`; amounts are decimal strings with up to
+/// seven places, as on the network.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum StellarOperationRequest {
+    Payment {
+        destination: String,
+        asset: String,
+        amount: String,
+    },
+    Trustline {
+        asset: String,
+        /// Omit for the maximum limit; `"0"` removes the trustline.
+        limit: Option<String>,
+    },
+    /// Strict-send: spends exactly `send_amount` and fails unless the
+    /// destination gets at least `dest_min`.
+    PathPayment {
+        destination: String,
+        send_asset: String,
+        send_amount: String,
+        dest_asset: String,
+        dest_min: String,
+        #[serde(default)]
+        path: Vec<String>,
+    },
+    /// Adds signers (`weight: 0` removes one) and sets the master key weight
+    /// and thresholds, e.g. to require two signatures on every payment.
+    SetOptions {
+        #[serde(default)]
+        signers: Vec<StellarSignerRequest>,
+        master_weight: Option<u8>,
+        low_threshold: Option<u8>,
+        med_threshold: Option<u8>,
+        high_threshold: Option<u8>,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StellarSignerRequest {
+    pub key: String,
+    pub weight: u8,
+}
+
+impl StellarOperationRequest {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            StellarOperationRequest::Payment { .. } => "payment",
+            StellarOperationRequest::Trustline { .. } => "trustline",
+            StellarOperationRequest::PathPayment { .. } => "path_payment",
+            StellarOperationRequest::SetOptions { .. } => "set_options",
+        }
+    }
+
+    /// Changing signers or thresholds needs the account's high threshold;
+    /// everything else here needs the medium one.
+    pub fn needs_high_threshold(&self) -> bool {
+        matches!(self, StellarOperationRequest::SetOptions { .. })
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PrepareStellarTransactionRequest {
+    /// A registered external account; pays the fee and signs.
+    #[validate(length(equal = 56))]
+    pub source: String,
+    #[serde(flatten)]
+    pub operation: StellarOperationRequest,
+    #[validate(length(max = 28))]
+    pub memo: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SubmitStellarTransactionRequest {
+    pub signed_xdr: String,
+}
@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::{Validate, ValidationError};
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct NotificationPreferences {
+    pub user_id: String,
+    pub sms_enabled: bool,
+    pub email_enabled: bool,
+    pub push_enabled: bool,
+    pub webhook_enabled: bool,
+    pub push_token: Option<String>,
+    pub webhook_url: Option<String>,
+    #[serde(skip_serializing)]
+    pub webhook_secret: Option<String>,
+    pub quiet_hours_start: Option<String>,
+    pub quiet_hours_end: Option<String>,
+    pub utc_offset_minutes: i64,
+}
+
+impl NotificationPreferences {
+    pub fn defaults_for(user_id: &str) -> Self {
+        Self {
+            user_id: user_id.to_string(),
+            sms_enabled: true,
+            email_enabled: true,
+            push_enabled: false,
+            webhook_enabled: false,
+            push_token: None,
+            webhook_url: None,
+            webhook_secret: None,
+            quiet_hours_start: None,
+            quiet_hours_end: None,
+            utc_offset_minutes: 180,
+        }
+    }
+
+    /// When the quiet hours `now` falls in end, or `None` outside them.
+    /// Windows may span midnight, e.g. 22:00–06:00.
+    pub fn quiet_hours_end(&self, now: chrono::DateTime<chrono::Utc>) -> Option<chrono::DateTime<chrono::Utc>> {
+        let start = self.quiet_hours_start.as_deref().and_then(parse_hhmm)?;
+        let end = self.quiet_hours_end.as_deref().and_then(parse_hhmm)?;
+
+        let local = now + chrono::Duration::minutes(self.utc_offset_minutes);
+        let second_of_day = local.timestamp().rem_euclid(86_400);
+        let minute_of_day = (second_of_day / 60) as u32;
+
+        let quiet = if start <= end {
+            minute_of_day >= start && minute_of_day < end
+        } else {
+            minute_of_day >= start || minute_of_day < end
+        };
+        if !quiet {
+            return None;
+        }
+        let until_end = (end as i64 * 60 - second_of_day).rem_euclid(86_400);
+        Some(now + chrono::Duration::seconds(until_end))
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct UpdateNotificationPreferences {
+    pub sms_enabled: Option<bool>,
+    pub email_enabled: Option<bool>,
+    pub push_enabled: Option<bool>,
+    pub webhook_enabled: Option<bool>,
+    #[validate(length(min = 1, max = 4096))]
+    pub push_token: Option<String>,
+    #[validate(url)]
+    pub webhook_url: Option<String>,
+    #[validate(length(min = 16))]
+    pub webhook_secret: Option<String>,
+    #[validate(custom = "validate_hhmm")]
+    pub quiet_hours_start: Option<String>,
+    #[validate(custom = "validate_hhmm")]
+    pub quiet_hours_end: Option<String>,
+    #[validate(range(min = -720, max = 840))]
+    pub utc_offset_minutes: Option<i64>,
+}
+
+fn parse_hhmm(value: &str) -> Option<u32> {
+    let (hours, minutes) = value.split_once(':')?;
+    let hours: u32 = hours.parse().ok()?;
+    let minutes: u32 = minutes.parse().ok()?;
+    (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+}
+
+fn validate_hhmm(value: &str) -> Result<(), ValidationError> {
+    parse_hhmm(value)
+        .map(|_| ())
+        .ok_or_else(|| ValidationError::new("invalid_time"))
+}
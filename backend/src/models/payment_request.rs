@@ -0,0 +1,73 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+/// A request for money, addressed to one user or open to anyone with the link.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PaymentRequest {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub requester_id: String,
+    #[serde(skip_serializing)]
+    pub payer_id: Option<String>,
+    pub payer_contact: Option<String>,
+    pub amount: f64,
+    pub currency: String,
+    pub memo: Option<String>,
+    pub destination: String,
+    pub status: String,
+    pub expires_at: chrono::DateTime<chrono::Utc>,
+    #[serde(skip_serializing)]
+    pub paid_by: Option<String>,
+    pub transaction_id: Option<String>,
+    pub tx_hash: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub settled_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing)]
+    pub muxed_id: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PaymentRequestView {
+    #[serde(flatten)]
+    pub request: PaymentRequest,
+    pub requester_name: String,
+    /// SEP-7 `web+stellar:pay` URI for paying from any Stellar wallet.
+    pub uri: String,
+    /// Page in the NovaPay app where the request can be paid.
+    pub link: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreatePaymentRequest {
+    #[validate(range(min = 0.0000001))]
+    pub amount: f64,
+    /// `XLM` (default) or `CODE:ISSUER`.
+    pub currency: Option<String>,
+    #[validate(length(max = 28))]
+    pub memo: Option<String>,
+    /// Email or phone number of the user asked to pay; leave out for an open
+    /// link.
+    pub payer: Option<String>,
+    /// Hours until the request expires, default `PAYMENT_REQUEST_TTL_HOURS`.
+    #[validate(range(min = 1, max = 2160))]
+    pub expires_in_hours: Option<i64>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct PayPaymentRequest {
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentRequestListQuery {
+    /// `sent` (default) or `received`.
+    pub direction: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PaymentRequestQrQuery {
+    /// `png` (default) or `svg`.
+    pub format: Option<String>,
+}
@@ -0,0 +1,90 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+/// A bulk payout from one of the user's custodial wallets.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PayoutBatch {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub user_id: String,
+    pub wallet_id: String,
+    pub source_public_key: String,
+    pub asset: String,
+    pub memo: Option<String>,
+    pub reference: Option<String>,
+    pub status: String,
+    pub row_count: i64,
+    pub total_amount: f64,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct PayoutRow {
+    #[serde(skip_serializing)]
+    pub batch_id: String,
+    pub line: i64,
+    pub recipient: String,
+    pub destination: String,
+    pub amount: String,
+    pub reference: Option<String>,
+    pub status: String,
+    pub error: Option<String>,
+    pub tx_hash: Option<String>,
+    pub transaction_id: Option<String>,
+    pub attempts: i64,
+    pub updated_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PayoutBatchView {
+    #[serde(flatten)]
+    pub batch: PayoutBatch,
+    pub paid: i64,
+    pub failed: i64,
+    pub pending: i64,
+    /// Only in the single-batch view.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rows: Option<Vec<PayoutRow>>,
+}
+
+/// Recipients come either as `payments` or as `csv` text with a header line
+/// naming the `recipient` and `amount` columns and, optionally, `reference`.
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreatePayoutBatchRequest {
+    pub wallet_id: String,
+    /// `XLM` (default) or `CODE:ISSUER`.
+    pub asset: Option<String>,
+    #[validate(length(max = 28))]
+    pub memo: Option<String>,
+    #[validate(length(min = 1, max = 64))]
+    pub reference: Option<String>,
+    #[serde(default)]
+    pub payments: Vec<PayoutItem>,
+    pub csv: Option<String>,
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PayoutItem {
+    /// A Stellar address, or the email or phone number of a NovaPay user.
+    pub recipient: String,
+    pub amount: String,
+    pub reference: Option<String>,
+}
+
+/// Why one uploaded row can't be paid; the whole upload is refused.
+#[derive(Debug, Clone, Serialize)]
+pub struct PayoutRowError {
+    pub line: i64,
+    pub recipient: String,
+    pub error: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct RetryPayoutBatchRequest {
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
+}
@@ -0,0 +1,24 @@
+use serde::Deserialize;
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct SetPinRequest {
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePinRequest {
+    #[validate(length(min = 4, max = 6))]
+    pub current_pin: String,
+    #[validate(length(min = 4, max = 6))]
+    pub new_pin: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPinRequest {
+    #[validate(length(equal = 6))]
+    pub code: String,
+    #[validate(length(min = 4, max = 6))]
+    pub new_pin: String,
+}
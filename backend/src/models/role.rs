@@ -0,0 +1,75 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Role {
+    Customer,
+    Support,
+    Finance,
+    Admin,
+}
+
+impl Role {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Customer => "customer",
+            Role::Support => "support",
+            Role::Finance => "finance",
+            Role::Admin => "admin",
+        }
+    }
+
+    /// Unknown values fall back to the least privileged role.
+    pub fn from_str_or_customer(value: &str) -> Self {
+        match value {
+            "support" => Role::Support,
+            "finance" => Role::Finance,
+            "admin" => Role::Admin,
+            _ => Role::Customer,
+        }
+    }
+
+    pub fn is_staff(&self) -> bool {
+        *self != Role::Customer
+    }
+
+    pub fn permissions(&self) -> &'static [Permission] {
+        use Permission::*;
+        match self {
+            Role::Customer => &[],
+            Role::Support => &[ViewUsers, ViewWallets, ViewTransactions, FreezeAccounts, UnlockAccounts],
+            Role::Finance => &[ViewUsers, ViewWallets, ViewTransactions, ReverseTransactions, ManageJobs],
+            Role::Admin => &[
+                ViewUsers,
+                ViewWallets,
+                ViewTransactions,
+                FreezeAccounts,
+                UnlockAccounts,
+                ReverseTransactions,
+                ManageJobs,
+                ManageKeys,
+                ManageRoles,
+                ViewAudit,
+            ],
+        }
+    }
+
+    pub fn has(&self, permission: Permission) -> bool {
+        self.permissions().contains(&permission)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Permission {
+    ViewUsers,
+    ViewWallets,
+    ViewTransactions,
+    FreezeAccounts,
+    UnlockAccounts,
+    ReverseTransactions,
+    ManageJobs,
+    ManageKeys,
+    ManageRoles,
+    ViewAudit,
+}
@@ -0,0 +1,102 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+/// A transfer that repeats on a monthly or cron rule.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ScheduledTransfer {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub user_id: String,
+    pub recipient_email: String,
+    pub amount: f64,
+    pub currency: String,
+    pub target_currency: String,
+    pub frequency: String,
+    pub day_of_month: Option<i64>,
+    pub time_of_day: String,
+    pub cron: Option<String>,
+    pub timezone: String,
+    pub start_date: chrono::NaiveDate,
+    pub end_date: Option<chrono::NaiveDate>,
+    pub status: String,
+    pub next_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub last_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// One occurrence of a scheduled transfer.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct ScheduledTransferRun {
+    pub id: String,
+    #[serde(skip_serializing)]
+    pub schedule_id: String,
+    pub due_at: chrono::DateTime<chrono::Utc>,
+    pub status: String,
+    pub attempts: i64,
+    pub next_attempt_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub transaction_id: Option<String>,
+    pub error: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduledTransferView {
+    #[serde(flatten)]
+    pub schedule: ScheduledTransfer,
+    /// The next few dates the transfer will go out, only in the single view.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub upcoming: Option<Vec<chrono::DateTime<chrono::Utc>>>,
+    /// Most recent first, only in the single view.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub runs: Option<Vec<ScheduledTransferRun>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScheduleFrequency {
+    /// On `day_of_month` at `time_of_day`.
+    Monthly,
+    /// On a five-field `cron` expression.
+    Cron,
+}
+
+impl ScheduleFrequency {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ScheduleFrequency::Monthly => "monthly",
+            ScheduleFrequency::Cron => "cron",
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateScheduledTransferRequest {
+    #[validate(length(min = 1))]
+    pub recipient_email: String,
+    #[validate(range(min = 0.01))]
+    pub amount: f64,
+    pub currency: Option<String>,
+    pub target_currency: Option<String>,
+    pub frequency: ScheduleFrequency,
+    #[validate(range(min = 1, max = 31))]
+    pub day_of_month: Option<u32>,
+    /// `HH:MM`, default `09:00`.
+    pub time_of_day: Option<String>,
+    pub cron: Option<String>,
+    /// IANA time zone name, default `UTC`.
+    pub timezone: Option<String>,
+    /// Defaults to today in `timezone`.
+    pub start_date: Option<chrono::NaiveDate>,
+    pub end_date: Option<chrono::NaiveDate>,
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResumeScheduledTransferRequest {
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
+}
@@ -0,0 +1,23 @@
+use serde::{Deserialize, Serialize};
+use validator::Validate;
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct Sep10ChallengeQuery {
+    #[validate(length(equal = 56))]
+    pub account: String,
+    /// Must be this server's home domain when given.
+    pub home_domain: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Sep10ChallengeResponse {
+    pub transaction: String,
+    pub network_passphrase: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct Sep10TokenRequest {
+    /// The challenge envelope with the client's signatures added.
+    #[validate(length(min = 1))]
+    pub transaction: String,
+}
@@ -0,0 +1,17 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct SmsMessage {
+    pub id: String,
+    pub provider: String,
+    pub provider_message_id: Option<String>,
+    pub phone_number: String,
+    pub message: String,
+    pub cost: Option<String>,
+    pub status: String,
+    pub failure_reason: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub delivered_at: Option<chrono::DateTime<chrono::Utc>>,
+}
@@ -14,6 +14,12 @@ pub struct Transaction {
     pub status: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub completed_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub reversed_by: Option<String>,
+    /// Hash of the signed Stellar transaction, set when it is first sent.
+    pub submission_hash: Option<String>,
+    #[serde(skip_serializing)]
+    pub submission_envelope: Option<String>,
+    pub submitted_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -24,6 +30,8 @@ pub struct CreateTransaction {
     pub amount: f64,
     pub currency: Option<String>,
     pub target_currency: Option<String>,
+    #[validate(length(min = 4, max = 6))]
+    pub pin: String,
 }
 
 #[derive(Debug, Serialize)]
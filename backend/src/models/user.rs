@@ -1,6 +1,9 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use validator::Validate;
+use validator::{Validate, ValidationError};
+
+use crate::services::normalize_phone;
+use crate::services::notification_templates::Locale;
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct User {
@@ -10,6 +13,12 @@ pub struct User {
     pub full_name: String,
     pub phone_number: Option<String>,
     pub stellar_public_key: Option<String>,
+    pub preferred_language: String,
+    pub phone_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub email_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub role: String,
+    pub frozen_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub frozen_reason: Option<String>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
 }
@@ -18,11 +27,14 @@ pub struct User {
 pub struct CreateUser {
     #[validate(email)]
     pub email: String,
-    #[validate(length(min = 6))]
+    #[validate(length(min = 10, max = 128))]
     pub password: String,
     #[validate(length(min = 2))]
     pub full_name: String,
+    #[validate(custom = "validate_phone")]
     pub phone_number: Option<String>,
+    #[validate(custom = "validate_language")]
+    pub preferred_language: Option<String>,
 }
 
 #[derive(Debug, Deserialize, Validate)]
@@ -39,11 +51,93 @@ pub struct UserResponse {
     pub full_name: String,
     pub phone_number: Option<String>,
     pub stellar_public_key: Option<String>,
+    pub preferred_language: String,
+    pub phone_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub email_verified_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        Self {
+            id: user.id,
+            email: user.email,
+            full_name: user.full_name,
+            phone_number: user.phone_number,
+            stellar_public_key: user.stellar_public_key,
+            preferred_language: user.preferred_language,
+            phone_verified_at: user.phone_verified_at,
+            email_verified_at: user.email_verified_at,
+        }
+    }
 }
 
 #[derive(Debug, Deserialize, Validate)]
 pub struct UpdateUserProfile {
     #[validate(length(min = 2))]
     pub full_name: Option<String>,
+    #[validate(custom = "validate_phone")]
     pub phone_number: Option<String>,
+    #[validate(custom = "validate_language")]
+    pub preferred_language: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct VerifyPhoneRequest {
+    #[validate(length(equal = 6))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct OtpLoginRequest {
+    #[validate(custom = "validate_phone")]
+    pub phone_number: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct OtpLoginVerify {
+    #[validate(custom = "validate_phone")]
+    pub phone_number: String,
+    #[validate(length(equal = 6))]
+    pub code: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ForgotPasswordRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ResetPasswordRequest {
+    #[validate(length(min = 1))]
+    pub token: String,
+    #[validate(length(min = 10, max = 128))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct ChangePasswordRequest {
+    pub current_password: String,
+    #[validate(length(min = 10, max = 128))]
+    pub new_password: String,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct VerifyEmailRequest {
+    #[validate(length(min = 1))]
+    pub token: String,
+}
+
+fn validate_phone(phone_number: &str) -> Result<(), ValidationError> {
+    match normalize_phone(phone_number) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("invalid_phone_number")),
+    }
+}
+
+fn validate_language(code: &str) -> Result<(), ValidationError> {
+    match Locale::from_code(code) {
+        Some(_) => Ok(()),
+        None => Err(ValidationError::new("unsupported_language")),
+    }
 }
\ No newline at end of file
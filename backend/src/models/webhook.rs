@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use validator::Validate;
+
+/// A partner URL that receives signed event notifications.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WebhookEndpoint {
+    pub id: String,
+    pub merchant_id: Option<String>,
+    pub url: String,
+    #[serde(skip_serializing)]
+    pub secret: String,
+    /// Comma-separated event types; empty receives every event.
+    pub event_types: String,
+    pub description: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A newly registered endpoint. The secret is only ever returned here.
+#[derive(Debug, Serialize)]
+pub struct WebhookEndpointWithSecret {
+    #[serde(flatten)]
+    pub endpoint: WebhookEndpoint,
+    pub secret: String,
+}
+
+/// One event posted, or to be posted, to one endpoint.
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: String,
+    pub endpoint_id: String,
+    pub event_id: String,
+    pub event_type: String,
+    /// The JSON body, posted unchanged on every attempt.
+    pub payload: String,
+    /// `pending`, `delivered` or `failed`.
+    pub status: String,
+    pub attempts: i64,
+    pub response_status: Option<i64>,
+    pub last_error: Option<String>,
+    pub replay_of: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub delivered_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Debug, Deserialize, Validate)]
+pub struct CreateWebhookEndpointRequest {
+    #[validate(url)]
+    pub url: String,
+    /// Event types to receive; empty or left out receives every event.
+    #[serde(default)]
+    #[validate(length(max = 20))]
+    pub event_types: Vec<String>,
+    /// Signing secret; one is generated when left out.
+    #[validate(length(min = 16, max = 128))]
+    pub secret: Option<String>,
+    #[validate(length(max = 200))]
+    pub description: Option<String>,
+    /// Receive the user's merchant's invoice events instead of the user's own.
+    #[serde(default)]
+    pub merchant: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct WebhookDeliveryListQuery {
+    pub status: Option<String>,
+    pub limit: Option<i64>,
+}
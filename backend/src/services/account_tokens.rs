@@ -0,0 +1,236 @@
+use sqlx::SqlitePool;
+use std::env;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::User;
+use crate::services::mailer::{mailer_from_env, EmailMessage, Mailer, MailerError};
+use crate::services::notification_templates::{Locale, NotificationTemplates, TemplateKind};
+use crate::services::signing::{hmac_sha256_hex, required_secret, verify_hmac_sha256_hex};
+
+#[derive(Error, Debug)]
+pub enum AccountTokenError {
+    #[error("Invalid or unknown link")]
+    Invalid,
+    #[error("Link has expired; request a new one")]
+    Expired,
+    #[error("Link has already been used")]
+    Used,
+    #[error("A link was sent recently; try again in {0} seconds")]
+    Cooldown(i64),
+    #[error("Failed to send email: {0}")]
+    Mail(#[from] MailerError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccountTokenPurpose {
+    PasswordReset,
+    VerifyEmail,
+}
+
+impl AccountTokenPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountTokenPurpose::PasswordReset => "password_reset",
+            AccountTokenPurpose::VerifyEmail => "verify_email",
+        }
+    }
+
+    fn path(&self) -> &'static str {
+        match self {
+            AccountTokenPurpose::PasswordReset => "reset-password",
+            AccountTokenPurpose::VerifyEmail => "verify-email",
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct AccountToken {
+    id: String,
+    user_id: String,
+    email: String,
+    expires_at: chrono::DateTime<chrono::Utc>,
+    used_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// Emailed links for password resets and email verification. A token is
+/// `<id>.<signature>`: the id names a row in `account_tokens` and the HMAC
+/// binds it to the user, address and expiry, so neither the database alone
+/// nor a guessed id is enough to forge a link.
+pub struct AccountTokenService {
+    mailer: Box<dyn Mailer>,
+    secret: String,
+    frontend_url: String,
+    reset_ttl_minutes: i64,
+    verify_ttl_hours: i64,
+    resend_cooldown_secs: i64,
+}
+
+impl AccountTokenService {
+    pub fn new() -> Self {
+        let env_i64 = |name: &str, default: i64| env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+
+        Self {
+            mailer: mailer_from_env(),
+            secret: required_secret("ACCOUNT_TOKEN_SECRET").expect("ACCOUNT_TOKEN_SECRET is checked at startup"),
+            frontend_url: env::var("FRONTEND_URL")
+                .unwrap_or_else(|_| "http://localhost:3000".to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            reset_ttl_minutes: env_i64("PASSWORD_RESET_TTL_MINUTES", 30),
+            verify_ttl_hours: env_i64("EMAIL_VERIFY_TTL_HOURS", 48),
+            resend_cooldown_secs: env_i64("ACCOUNT_TOKEN_RESEND_COOLDOWN_SECS", 60),
+        }
+    }
+
+    /// What a token's signature covers.
+    fn signed_message(
+        purpose: AccountTokenPurpose,
+        id: &str,
+        user_id: &str,
+        email: &str,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    ) -> String {
+        format!("{}:{}:{}:{}:{}", purpose.as_str(), id, user_id, email, expires_at.timestamp())
+    }
+
+    /// Issues a link for `user` and emails it to their current address. Any
+    /// outstanding link for the same purpose stops working.
+    pub async fn send(&self, pool: &SqlitePool, user: &User, purpose: AccountTokenPurpose) -> Result<(), AccountTokenError> {
+        let now = chrono::Utc::now();
+
+        let last_sent: Option<chrono::DateTime<chrono::Utc>> =
+            sqlx::query_scalar("SELECT MAX(created_at) FROM account_tokens WHERE user_id = ? AND purpose = ?")
+                .bind(&user.id)
+                .bind(purpose.as_str())
+                .fetch_one(pool)
+                .await?;
+        if let Some(last_sent) = last_sent {
+            let wait = self.resend_cooldown_secs - (now - last_sent).num_seconds();
+            if wait > 0 {
+                return Err(AccountTokenError::Cooldown(wait));
+            }
+        }
+
+        let ttl = match purpose {
+            AccountTokenPurpose::PasswordReset => chrono::Duration::minutes(self.reset_ttl_minutes),
+            AccountTokenPurpose::VerifyEmail => chrono::Duration::hours(self.verify_ttl_hours),
+        };
+        let expires_at = now + ttl;
+        let id = Uuid::new_v4().simple().to_string();
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("UPDATE account_tokens SET used_at = ? WHERE user_id = ? AND purpose = ? AND used_at IS NULL")
+            .bind(now)
+            .bind(&user.id)
+            .bind(purpose.as_str())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            "INSERT INTO account_tokens (id, user_id, purpose, email, expires_at, created_at) VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&id)
+        .bind(&user.id)
+        .bind(purpose.as_str())
+        .bind(&user.email)
+        .bind(expires_at)
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let message = Self::signed_message(purpose, &id, &user.id, &user.email, expires_at);
+        let token = format!("{}.{}", id, hmac_sha256_hex(self.secret.as_bytes(), message.as_bytes()));
+        let link = format!("{}/{}?token={}", self.frontend_url, purpose.path(), token);
+        let locale = Locale::from_preference(Some(&user.preferred_language));
+        let (kind, vars) = match purpose {
+            AccountTokenPurpose::PasswordReset => (
+                TemplateKind::PasswordReset,
+                vec![("link", link), ("minutes", self.reset_ttl_minutes.to_string())],
+            ),
+            AccountTokenPurpose::VerifyEmail => (
+                TemplateKind::VerifyEmail,
+                vec![("link", link), ("hours", self.verify_ttl_hours.to_string())],
+            ),
+        };
+
+        self.mailer
+            .send(&EmailMessage {
+                to: user.email.clone(),
+                subject: NotificationTemplates::subject(kind, locale).to_string(),
+                body: NotificationTemplates::render(kind, locale, &vars),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// Checks `token` without using it up, so a request that fails for
+    /// another reason (say, a weak new password) can be retried with the same
+    /// link. Returns the user id and the email address the link was sent to;
+    /// callers must compare the address with the user's current one.
+    pub async fn check(
+        &self,
+        pool: &SqlitePool,
+        purpose: AccountTokenPurpose,
+        token: &str,
+    ) -> Result<(String, String), AccountTokenError> {
+        let row = self.lookup(pool, purpose, token).await?;
+        Ok((row.user_id, row.email))
+    }
+
+    /// Checks and consumes `token`; see [`Self::check`].
+    pub async fn consume(
+        &self,
+        pool: &SqlitePool,
+        purpose: AccountTokenPurpose,
+        token: &str,
+    ) -> Result<(String, String), AccountTokenError> {
+        let row = self.lookup(pool, purpose, token).await?;
+
+        // Single use, even if two requests race with the same link
+        let consumed = sqlx::query("UPDATE account_tokens SET used_at = ? WHERE id = ? AND used_at IS NULL")
+            .bind(chrono::Utc::now())
+            .bind(&row.id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+        if consumed == 0 {
+            return Err(AccountTokenError::Used);
+        }
+
+        Ok((row.user_id, row.email))
+    }
+
+    async fn lookup(
+        &self,
+        pool: &SqlitePool,
+        purpose: AccountTokenPurpose,
+        token: &str,
+    ) -> Result<AccountToken, AccountTokenError> {
+        let (id, signature) = token.trim().split_once('.').ok_or(AccountTokenError::Invalid)?;
+
+        let row = sqlx::query_as::<_, AccountToken>(
+            "SELECT id, user_id, email, expires_at, used_at FROM account_tokens WHERE id = ? AND purpose = ?",
+        )
+        .bind(id)
+        .bind(purpose.as_str())
+        .fetch_optional(pool)
+        .await?
+        .ok_or(AccountTokenError::Invalid)?;
+
+        let message = Self::signed_message(purpose, &row.id, &row.user_id, &row.email, row.expires_at);
+        if !verify_hmac_sha256_hex(self.secret.as_bytes(), message.as_bytes(), signature) {
+            return Err(AccountTokenError::Invalid);
+        }
+        if row.used_at.is_some() {
+            return Err(AccountTokenError::Used);
+        }
+        if row.expires_at <= chrono::Utc::now() {
+            return Err(AccountTokenError::Expired);
+        }
+
+        Ok(row)
+    }
+}
@@ -0,0 +1,347 @@
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::env;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{Role, Transaction, User, UserSearchQuery, Wallet};
+use crate::services::{Actor, AuditLog, Change, Ledger, SessionService};
+
+#[derive(Error, Debug)]
+pub enum AdminError {
+    #[error("Not found")]
+    NotFound,
+    #[error("{0}")]
+    Conflict(&'static str),
+    #[error("Insufficient balance to reverse")]
+    InsufficientBalance,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Back-office operations. Every change is audited in the same database
+/// transaction as the change itself.
+pub struct AdminService;
+
+impl AdminService {
+    /// Gives the users listed in `ADMIN_USER_IDS` (comma-separated) the admin
+    /// role at startup, so a fresh deployment has someone who can grant roles.
+    pub async fn bootstrap_admins(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let admins = env::var("ADMIN_USER_IDS").unwrap_or_default();
+        for user_id in admins.split(',').map(str::trim).filter(|id| !id.is_empty()) {
+            let promoted = sqlx::query("UPDATE users SET role = 'admin' WHERE id = ? AND role != 'admin'")
+                .bind(user_id)
+                .execute(pool)
+                .await?
+                .rows_affected();
+            if promoted > 0 {
+                println!("🛡️ Granted admin role to {} from ADMIN_USER_IDS", user_id);
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn search_users(pool: &SqlitePool, query: &UserSearchQuery) -> Result<Vec<User>, sqlx::Error> {
+        let pattern = query.q.as_ref().map(|q| format!("%{}%", q.trim()));
+        sqlx::query_as::<_, User>(
+            r#"
+            SELECT * FROM users
+            WHERE (? IS NULL OR email LIKE ? OR full_name LIKE ? OR phone_number LIKE ?)
+              AND (? IS NULL OR role = ?)
+              AND (? IS NULL OR (frozen_at IS NOT NULL) = ?)
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(&pattern)
+        .bind(&query.role)
+        .bind(&query.role)
+        .bind(query.frozen)
+        .bind(query.frozen)
+        .bind(query.limit.unwrap_or(50).clamp(1, 500))
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn wallet(pool: &SqlitePool, user_id: &str) -> Result<Option<Wallet>, sqlx::Error> {
+        sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn transactions(pool: &SqlitePool, user_id: &str, limit: i64) -> Result<Vec<Transaction>, sqlx::Error> {
+        sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE user_id = ? ORDER BY created_at DESC LIMIT ?")
+            .bind(user_id)
+            .bind(limit.clamp(1, 500))
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Freezes or unfreezes an account. Frozen accounts can still sign in and
+    /// look around but cannot move money.
+    pub async fn set_frozen(
+        pool: &SqlitePool,
+        actor: &Actor,
+        user_id: &str,
+        reason: Option<&str>,
+    ) -> Result<User, AdminError> {
+        let freeze = reason.is_some();
+        let mut tx = pool.begin().await?;
+
+        let previous = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(AdminError::NotFound)?;
+
+        let user = sqlx::query_as::<_, User>(
+            "UPDATE users SET frozen_at = ?, frozen_reason = ?, updated_at = ? WHERE id = ? RETURNING *",
+        )
+        .bind(freeze.then(chrono::Utc::now))
+        .bind(reason)
+        .bind(chrono::Utc::now())
+        .bind(user_id)
+        .fetch_one(&mut *tx)
+        .await?;
+
+        AuditLog::record_change(
+            &mut *tx,
+            actor,
+            if freeze { "user.freeze" } else { "user.unfreeze" },
+            "user",
+            Some(user_id),
+            json!({ "reason": reason }),
+            Change {
+                before: json!({ "frozen_at": previous.frozen_at, "frozen_reason": previous.frozen_reason }),
+                after: json!({ "frozen_at": user.frozen_at, "frozen_reason": user.frozen_reason }),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(user)
+    }
+
+    /// Changes a user's role and signs them out everywhere so their next
+    /// access token carries the new role.
+    pub async fn set_role(pool: &SqlitePool, actor: &Actor, user_id: &str, role: Role) -> Result<User, AdminError> {
+        if user_id == actor.user_id {
+            return Err(AdminError::Conflict("Staff cannot change their own role"));
+        }
+
+        let mut tx = pool.begin().await?;
+        let previous: String = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(AdminError::NotFound)?;
+
+        let user = sqlx::query_as::<_, User>("UPDATE users SET role = ?, updated_at = ? WHERE id = ? RETURNING *")
+            .bind(role.as_str())
+            .bind(chrono::Utc::now())
+            .bind(user_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+        AuditLog::record_change(
+            &mut *tx,
+            actor,
+            "user.role_change",
+            "user",
+            Some(user_id),
+            json!({}),
+            Change {
+                before: json!({ "role": previous }),
+                after: json!({ "role": role.as_str() }),
+            },
+        )
+        .await?;
+        tx.commit().await?;
+
+        SessionService::revoke_all(pool, user_id, None, "role_changed").await?;
+        Ok(user)
+    }
+
+    /// Books a compensating transaction that undoes the ledger entries of a
+    /// completed one: every credit it booked is debited back and every debit
+    /// refunded. Transactions with no entries, such as payments that only
+    /// happened on-chain, are refused rather than guessed at.
+    pub async fn reverse_transaction(
+        pool: &SqlitePool,
+        actor: &Actor,
+        transaction_id: &str,
+        reason: &str,
+    ) -> Result<Transaction, AdminError> {
+        let mut tx = pool.begin().await?;
+
+        let original = sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = ?")
+            .bind(transaction_id)
+            .fetch_optional(&mut *tx)
+            .await?
+            .ok_or(AdminError::NotFound)?;
+        if original.status != "completed" {
+            return Err(AdminError::Conflict("Only completed transactions can be reversed"));
+        }
+        if original.reversed_by.is_some() || original.recipient_email == "reversal" {
+            return Err(AdminError::Conflict("Transaction is already a reversal or has been reversed"));
+        }
+
+        let entries = Ledger::entries(&mut tx, &original.id).await?;
+        if entries.is_empty() {
+            return Err(AdminError::Conflict("Transaction has no recorded balance changes to reverse"));
+        }
+
+        let reversal = sqlx::query_as::<_, Transaction>(
+            r#"
+            INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, stellar_tx_hash, status, created_at, completed_at)
+            VALUES (?, ?, 'reversal', ?, ?, ?, ?, 'completed', ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&original.user_id)
+        .bind(original.amount)
+        .bind(&original.currency)
+        .bind(&original.target_currency)
+        .bind(format!("reversal_of_{}", original.id))
+        .bind(chrono::Utc::now())
+        .bind(chrono::Utc::now())
+        .fetch_one(&mut *tx)
+        .await?;
+
+        for (user_id, amount) in &entries {
+            if Ledger::post(&mut tx, &reversal.id, user_id, -amount).await?.is_none() {
+                return Err(AdminError::InsufficientBalance);
+            }
+        }
+
+        sqlx::query("UPDATE transactions SET status = 'reversed', reversed_by = ? WHERE id = ?")
+            .bind(&reversal.id)
+            .bind(&original.id)
+            .execute(&mut *tx)
+            .await?;
+
+        AuditLog::record(
+            &mut *tx,
+            actor,
+            "transaction.reverse",
+            "transaction",
+            Some(&original.id),
+            json!({
+                "reason": reason,
+                "reversal_id": reversal.id,
+                "user_id": original.user_id,
+                "amount": original.amount,
+                "currency": original.currency,
+                "entries": entries
+                    .iter()
+                    .map(|(user_id, amount)| json!({ "user_id": user_id, "amount": -amount }))
+                    .collect::<Vec<_>>(),
+            }),
+        )
+        .await?;
+        tx.commit().await?;
+
+        Ok(reversal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool};
+
+    async fn wallet(pool: &SqlitePool, user_id: &str, balance: f64) {
+        sqlx::query("INSERT INTO wallets (id, user_id, stellar_public_key, stellar_secret_key, balance) VALUES (?, ?, ?, 'S', ?)")
+            .bind(Uuid::new_v4().to_string())
+            .bind(user_id)
+            .bind(format!("G{}", user_id))
+            .bind(balance)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn balance(pool: &SqlitePool, user_id: &str) -> f64 {
+        sqlx::query_scalar("SELECT balance FROM wallets WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn completed(pool: &SqlitePool, user_id: &str, recipient: &str, amount: f64) -> String {
+        let id = Uuid::new_v4().to_string();
+        sqlx::query("INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, status) VALUES (?, ?, ?, ?, 'XLM', 'XLM', 'completed')")
+            .bind(&id)
+            .bind(user_id)
+            .bind(recipient)
+            .bind(amount)
+            .execute(pool)
+            .await
+            .unwrap();
+        id
+    }
+
+    #[tokio::test]
+    async fn test_reversal_undoes_recorded_entries_only() {
+        let pool = memory_pool().await;
+        for user in ["admin", "alice", "bob"] {
+            insert_user(&pool, user, &format!("{}@example.com", user)).await;
+        }
+        wallet(&pool, "alice", 100.0).await;
+        wallet(&pool, "bob", 0.0).await;
+        let actor = Actor { user_id: "admin".to_string(), role: Role::Admin, ip_address: None, user_agent: None };
+
+        let transfer = completed(&pool, "alice", "Gbob", 40.0).await;
+        let mut conn = pool.acquire().await.unwrap();
+        Ledger::post(&mut conn, &transfer, "alice", -40.0).await.unwrap().unwrap();
+        Ledger::post(&mut conn, &transfer, "bob", 40.0).await.unwrap().unwrap();
+        drop(conn);
+
+        // A completed payment that never touched a balance has nothing to undo
+        let on_chain = completed(&pool, "alice", "GEXTERNAL", 25.0).await;
+        let refused = AdminService::reverse_transaction(&pool, &actor, &on_chain, "test").await;
+        assert!(matches!(refused, Err(AdminError::Conflict(_))));
+        assert_eq!(balance(&pool, "alice").await, 60.0);
+
+        let reversal = AdminService::reverse_transaction(&pool, &actor, &transfer, "test").await.unwrap();
+        assert_eq!(reversal.recipient_email, "reversal");
+        assert_eq!(balance(&pool, "alice").await, 100.0);
+        assert_eq!(balance(&pool, "bob").await, 0.0);
+
+        let again = AdminService::reverse_transaction(&pool, &actor, &transfer, "test").await;
+        assert!(matches!(again, Err(AdminError::Conflict(_))));
+    }
+
+    #[tokio::test]
+    async fn test_reversal_needs_the_credited_funds() {
+        let pool = memory_pool().await;
+        for user in ["admin", "alice"] {
+            insert_user(&pool, user, &format!("{}@example.com", user)).await;
+        }
+        wallet(&pool, "alice", 0.0).await;
+        let actor = Actor { user_id: "admin".to_string(), role: Role::Admin, ip_address: None, user_agent: None };
+
+        let deposit = completed(&pool, "alice", "deposit", 30.0).await;
+        let mut conn = pool.acquire().await.unwrap();
+        Ledger::post(&mut conn, &deposit, "alice", 30.0).await.unwrap().unwrap();
+        assert!(Ledger::post(&mut conn, &deposit, "alice", -50.0).await.unwrap().is_none());
+        sqlx::query("UPDATE wallets SET balance = 10.0 WHERE user_id = 'alice'").execute(&mut *conn).await.unwrap();
+        drop(conn);
+
+        let refused = AdminService::reverse_transaction(&pool, &actor, &deposit, "test").await;
+        assert!(matches!(refused, Err(AdminError::InsufficientBalance)));
+        assert_eq!(balance(&pool, "alice").await, 10.0);
+        let status: String = sqlx::query_scalar("SELECT status FROM transactions WHERE id = ?")
+            .bind(&deposit)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "completed");
+    }
+}
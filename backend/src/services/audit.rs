@@ -0,0 +1,452 @@
+use serde_json::{json, Value};
+use sqlx::{Executor, Sqlite, SqlitePool};
+use std::env;
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use uuid::Uuid;
+
+use crate::models::{AuditChainProblem, AuditEvent, AuditExportQuery, AuditQuery, AuditVerification, Role};
+use crate::services::signing::sha256_hex;
+
+/// `prev_hash` of the first event in the chain.
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Events sealed or verified per query.
+const CHAIN_BATCH: i64 = 500;
+
+/// Reported problems are capped; the first one is what matters.
+const MAX_PROBLEMS: usize = 100;
+
+/// Who performed an audited action, and from where.
+#[derive(Debug, Clone)]
+pub struct Actor {
+    pub user_id: String,
+    pub role: Role,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// State of the target before and after an audited change.
+pub struct Change {
+    pub before: Value,
+    pub after: Value,
+}
+
+struct Entry<'a> {
+    action: &'a str,
+    target_type: &'a str,
+    target_id: Option<&'a str>,
+    details: Value,
+    before: Option<Value>,
+    after: Option<Value>,
+}
+
+fn seal_lock() -> &'static Mutex<()> {
+    static SEAL_LOCK: OnceLock<Mutex<()>> = OnceLock::new();
+    SEAL_LOCK.get_or_init(|| Mutex::new(()))
+}
+
+/// Append-only, hash-chained record of staff, security and money events.
+///
+/// Writes take an executor so the entry can share a transaction with the
+/// change it describes. That transaction may still roll back, so events are
+/// inserted unlinked and a sealer then chains them in commit order: each
+/// sealed event gets the next `seq`, the previous event's hash as `prev_hash`
+/// and `hash = SHA-256(prev_hash \n canonical row)`. Triggers stop rows from
+/// being deleted or edited; `verify` catches anyone who gets around them.
+pub struct AuditLog;
+
+impl AuditLog {
+    pub async fn record<'e, E>(
+        executor: E,
+        actor: &Actor,
+        action: &str,
+        target_type: &str,
+        target_id: Option<&str>,
+        details: Value,
+    ) -> Result<(), sqlx::Error>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let entry = Entry {
+            action,
+            target_type,
+            target_id,
+            details,
+            before: None,
+            after: None,
+        };
+        Self::insert(executor, actor, &entry).await
+    }
+
+    /// Records a change with the target's state before and after it.
+    pub async fn record_change<'e, E>(
+        executor: E,
+        actor: &Actor,
+        action: &str,
+        target_type: &str,
+        target_id: Option<&str>,
+        details: Value,
+        change: Change,
+    ) -> Result<(), sqlx::Error>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let entry = Entry {
+            action,
+            target_type,
+            target_id,
+            details,
+            before: Some(change.before),
+            after: Some(change.after),
+        };
+        Self::insert(executor, actor, &entry).await
+    }
+
+    /// For events recorded after something that can't be undone, like money
+    /// having moved or a session being issued: a failed write is logged
+    /// instead of failing the request.
+    pub async fn record_after(
+        pool: &SqlitePool,
+        actor: &Actor,
+        action: &str,
+        target_type: &str,
+        target_id: Option<&str>,
+        details: Value,
+        change: Option<Change>,
+    ) {
+        let (before, after) = match change {
+            Some(change) => (Some(change.before), Some(change.after)),
+            None => (None, None),
+        };
+        let entry = Entry {
+            action,
+            target_type,
+            target_id,
+            details,
+            before,
+            after,
+        };
+        if let Err(e) = Self::insert(pool, actor, &entry).await {
+            println!("🚨 Failed to record audit event {} for {}: {}", action, actor.user_id, e);
+        }
+    }
+
+    async fn insert<'e, E>(executor: E, actor: &Actor, entry: &Entry<'_>) -> Result<(), sqlx::Error>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        sqlx::query(
+            r#"
+            INSERT INTO audit_events (id, actor_id, actor_role, action, target_type, target_id, details, before_state, after_state, ip_address, user_agent, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&actor.user_id)
+        .bind(actor.role.as_str())
+        .bind(entry.action)
+        .bind(entry.target_type)
+        .bind(entry.target_id)
+        .bind(entry.details.to_string())
+        .bind(entry.before.as_ref().map(Value::to_string))
+        .bind(entry.after.as_ref().map(Value::to_string))
+        .bind(&actor.ip_address)
+        .bind(&actor.user_agent)
+        .bind(chrono::Utc::now())
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    pub async fn list(pool: &SqlitePool, query: &AuditQuery) -> Result<Vec<AuditEvent>, sqlx::Error> {
+        sqlx::query_as::<_, AuditEvent>(
+            r#"
+            SELECT * FROM audit_events
+            WHERE (? IS NULL OR actor_id = ?)
+              AND (? IS NULL OR action = ?)
+              AND (? IS NULL OR target_type = ?)
+              AND (? IS NULL OR target_id = ?)
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(&query.actor_id)
+        .bind(&query.actor_id)
+        .bind(&query.action)
+        .bind(&query.action)
+        .bind(&query.target_type)
+        .bind(&query.target_type)
+        .bind(&query.target_id)
+        .bind(&query.target_id)
+        .bind(query.limit.unwrap_or(100).clamp(1, 1000))
+        .fetch_all(pool)
+        .await
+    }
+
+    /// Links committed but unsealed events into the chain. Returns how many
+    /// were sealed.
+    pub async fn seal(pool: &SqlitePool) -> Result<u64, sqlx::Error> {
+        let _guard = seal_lock().lock().await;
+        let mut sealed = 0;
+
+        loop {
+            let mut tx = pool.begin().await?;
+            let head: Option<(i64, String)> =
+                sqlx::query_as("SELECT seq, hash FROM audit_events WHERE seq IS NOT NULL ORDER BY seq DESC LIMIT 1")
+                    .fetch_optional(&mut *tx)
+                    .await?;
+            let (mut seq, mut prev_hash) = head.unwrap_or((0, GENESIS_HASH.to_string()));
+
+            let pending = sqlx::query_as::<_, AuditEvent>(
+                "SELECT * FROM audit_events WHERE hash IS NULL ORDER BY created_at, rowid LIMIT ?",
+            )
+            .bind(CHAIN_BATCH)
+            .fetch_all(&mut *tx)
+            .await?;
+            if pending.is_empty() {
+                return Ok(sealed);
+            }
+
+            for event in &pending {
+                seq += 1;
+                let hash = Self::chain_hash(&prev_hash, seq, event);
+                sqlx::query("UPDATE audit_events SET seq = ?, prev_hash = ?, hash = ? WHERE id = ?")
+                    .bind(seq)
+                    .bind(&prev_hash)
+                    .bind(&hash)
+                    .bind(&event.id)
+                    .execute(&mut *tx)
+                    .await?;
+                prev_hash = hash;
+            }
+            tx.commit().await?;
+            sealed += pending.len() as u64;
+        }
+    }
+
+    /// Seals new events every `AUDIT_SEAL_INTERVAL_SECS` (default 2).
+    pub fn spawn_sealer(pool: SqlitePool) {
+        let interval_secs = env::var("AUDIT_SEAL_INTERVAL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(2u64)
+            .max(1);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                if let Err(e) = Self::seal(&pool).await {
+                    println!("⚠️ Failed to seal audit events: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Walks the whole chain and reports gaps in `seq`, links that don't
+    /// point at the previous event, and events whose content no longer
+    /// matches their hash. Deleting the newest events leaves a shorter but
+    /// valid chain, so compare `head_seq`/`head_hash` with a copy kept
+    /// elsewhere (e.g. the last export).
+    pub async fn verify(pool: &SqlitePool) -> Result<AuditVerification, sqlx::Error> {
+        let mut problems = Vec::new();
+        let mut checked = 0;
+        let mut expected_seq = 1;
+        let mut prev_hash = GENESIS_HASH.to_string();
+
+        loop {
+            let batch = sqlx::query_as::<_, AuditEvent>(
+                "SELECT * FROM audit_events WHERE seq IS NOT NULL AND seq >= ? ORDER BY seq LIMIT ?",
+            )
+            .bind(expected_seq)
+            .bind(CHAIN_BATCH)
+            .fetch_all(pool)
+            .await?;
+            if batch.is_empty() {
+                break;
+            }
+
+            for event in &batch {
+                let seq = event.seq.unwrap_or_default();
+                let mut problem = |kind| {
+                    if problems.len() < MAX_PROBLEMS {
+                        problems.push(AuditChainProblem {
+                            seq,
+                            event_id: Some(event.id.clone()),
+                            kind,
+                        });
+                    }
+                };
+
+                if seq != expected_seq {
+                    problem("gap");
+                }
+                if event.prev_hash.as_deref() != Some(prev_hash.as_str()) {
+                    problem("broken_link");
+                }
+                let hash = Self::chain_hash(event.prev_hash.as_deref().unwrap_or_default(), seq, event);
+                if event.hash.as_deref() != Some(hash.as_str()) {
+                    problem("hash_mismatch");
+                }
+
+                checked += 1;
+                expected_seq = seq + 1;
+                prev_hash = event.hash.clone().unwrap_or_default();
+            }
+        }
+
+        let unsealed: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM audit_events WHERE hash IS NULL")
+            .fetch_one(pool)
+            .await?;
+
+        Ok(AuditVerification {
+            valid: problems.is_empty(),
+            checked,
+            unsealed,
+            head_seq: (checked > 0).then_some(expected_seq - 1),
+            head_hash: (checked > 0).then_some(prev_hash),
+            problems,
+        })
+    }
+
+    /// Sealed events in `[from, to)`, oldest first, for auditors.
+    pub async fn export(pool: &SqlitePool, query: &AuditExportQuery) -> Result<Vec<AuditEvent>, sqlx::Error> {
+        sqlx::query_as::<_, AuditEvent>(
+            r#"
+            SELECT * FROM audit_events
+            WHERE seq IS NOT NULL
+              AND (? IS NULL OR created_at >= ?)
+              AND (? IS NULL OR created_at < ?)
+            ORDER BY seq
+            "#,
+        )
+        .bind(query.from)
+        .bind(query.from)
+        .bind(query.to)
+        .bind(query.to)
+        .fetch_all(pool)
+        .await
+    }
+
+    /// `SHA-256(prev_hash "\n" canonical)`, where the canonical form is a JSON
+    /// array of `seq` and every recorded column in a fixed order.
+    pub fn chain_hash(prev_hash: &str, seq: i64, event: &AuditEvent) -> String {
+        let canonical = json!([
+            seq,
+            event.id,
+            event.actor_id,
+            event.actor_role,
+            event.action,
+            event.target_type,
+            event.target_id,
+            event.details,
+            event.before_state,
+            event.after_state,
+            event.ip_address,
+            event.user_agent,
+            event.created_at.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true),
+        ]);
+        sha256_hex(format!("{}\n{}", prev_hash, canonical).as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool};
+
+    /// Three sealed events by `u1`.
+    async fn sealed_chain() -> SqlitePool {
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+        let actor = Actor {
+            user_id: "u1".to_string(),
+            role: Role::Customer,
+            ip_address: Some("203.0.113.7".to_string()),
+            user_agent: None,
+        };
+        for action in ["auth.login", "wallet.deposit", "wallet.withdraw"] {
+            AuditLog::record(&pool, &actor, action, "user", Some("u1"), json!({ "n": action })).await.unwrap();
+        }
+        assert_eq!(AuditLog::seal(&pool).await.unwrap(), 3);
+        pool
+    }
+
+    /// Stands in for someone editing the database file directly.
+    async fn drop_triggers(pool: &SqlitePool) {
+        for trigger in ["audit_events_no_delete", "audit_events_no_update"] {
+            sqlx::query(&format!("DROP TRIGGER {}", trigger)).execute(pool).await.unwrap();
+        }
+    }
+
+    async fn problems(pool: &SqlitePool) -> Vec<(i64, &'static str)> {
+        let verification = AuditLog::verify(pool).await.unwrap();
+        assert_eq!(verification.valid, verification.problems.is_empty());
+        verification.problems.iter().map(|p| (p.seq, p.kind)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_sealed_chain_verifies() {
+        let pool = sealed_chain().await;
+        assert_eq!(AuditLog::seal(&pool).await.unwrap(), 0);
+
+        let verification = AuditLog::verify(&pool).await.unwrap();
+        assert!(verification.valid);
+        assert_eq!((verification.checked, verification.unsealed, verification.head_seq), (3, 0, Some(3)));
+        let head: String = sqlx::query_scalar("SELECT hash FROM audit_events WHERE seq = 3").fetch_one(&pool).await.unwrap();
+        assert_eq!(verification.head_hash, Some(head));
+    }
+
+    #[tokio::test]
+    async fn test_triggers_reject_edits_and_deletes() {
+        let pool = sealed_chain().await;
+        let edit = sqlx::query("UPDATE audit_events SET details = '{}' WHERE seq = 2").execute(&pool).await;
+        assert!(edit.unwrap_err().to_string().contains("append-only"));
+        let delete = sqlx::query("DELETE FROM audit_events WHERE seq = 3").execute(&pool).await;
+        assert!(delete.unwrap_err().to_string().contains("append-only"));
+        let reseal = sqlx::query("UPDATE audit_events SET hash = 'x' WHERE seq = 1").execute(&pool).await;
+        assert!(reseal.is_err());
+
+        // Before sealing only the chain columns can be filled in
+        let actor = Actor { user_id: "u1".to_string(), role: Role::Customer, ip_address: None, user_agent: None };
+        AuditLog::record(&pool, &actor, "auth.logout", "user", Some("u1"), json!({})).await.unwrap();
+        let edit = sqlx::query("UPDATE audit_events SET action = 'auth.login' WHERE hash IS NULL").execute(&pool).await;
+        assert!(edit.is_err());
+        assert_eq!(AuditLog::seal(&pool).await.unwrap(), 1);
+        assert!(AuditLog::verify(&pool).await.unwrap().valid);
+    }
+
+    #[tokio::test]
+    async fn test_edited_row_is_detected() {
+        let pool = sealed_chain().await;
+        drop_triggers(&pool).await;
+        sqlx::query("UPDATE audit_events SET details = '{\"n\":\"forged\"}' WHERE seq = 2").execute(&pool).await.unwrap();
+        assert_eq!(problems(&pool).await, [(2, "hash_mismatch")]);
+    }
+
+    #[tokio::test]
+    async fn test_missing_seq_is_detected() {
+        let pool = sealed_chain().await;
+        drop_triggers(&pool).await;
+        sqlx::query("DELETE FROM audit_events WHERE seq = 2").execute(&pool).await.unwrap();
+        assert_eq!(problems(&pool).await, [(3, "gap"), (3, "broken_link")]);
+    }
+
+    #[tokio::test]
+    async fn test_broken_link_is_detected() {
+        let pool = sealed_chain().await;
+        drop_triggers(&pool).await;
+        // Relinked and rehashed consistently, but no longer pointing at seq 2
+        let mut event = sqlx::query_as::<_, AuditEvent>("SELECT * FROM audit_events WHERE seq = 3").fetch_one(&pool).await.unwrap();
+        event.prev_hash = Some(GENESIS_HASH.to_string());
+        sqlx::query("UPDATE audit_events SET prev_hash = ?, hash = ? WHERE seq = 3")
+            .bind(GENESIS_HASH)
+            .bind(AuditLog::chain_hash(GENESIS_HASH, 3, &event))
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(problems(&pool).await, [(3, "broken_link")]);
+    }
+}
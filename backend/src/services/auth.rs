@@ -1,13 +1,28 @@
 use argon2::{Argon2, PasswordHash, PasswordHasher, PasswordVerifier};
 use argon2::password_hash::{rand_core::OsRng, SaltString};
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::errors::ErrorKind;
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, Header, Validation};
 use serde::{Deserialize, Serialize};
 use std::env;
+use uuid::Uuid;
+
+use crate::models::Role;
+use crate::services::KeyManager;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
     pub sub: String,
+    /// Session family the token was issued for; see `SessionService`.
+    pub sid: String,
+    pub iat: usize,
     pub exp: usize,
+    pub iss: String,
+    pub aud: String,
+    pub jti: String,
+    /// Role at issue time; see `Role`. Tokens from before roles existed have
+    /// none and are treated as customers.
+    #[serde(default)]
+    pub role: String,
 }
 
 pub struct AuthService;
@@ -26,32 +41,62 @@ impl AuthService {
         Ok(argon2.verify_password(password.as_bytes(), &parsed_hash).is_ok())
     }
 
-    pub fn generate_jwt(user_id: &str) -> Result<String, jsonwebtoken::errors::Error> {
-        let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-        let expiration = chrono::Utc::now()
-            .checked_add_signed(chrono::Duration::hours(24))
+    /// Access token lifetime, `ACCESS_TOKEN_TTL_MINUTES` (default 15).
+    pub fn access_token_ttl() -> chrono::Duration {
+        let minutes = env::var("ACCESS_TOKEN_TTL_MINUTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(15);
+        chrono::Duration::minutes(minutes)
+    }
+
+    fn issuer() -> String {
+        env::var("JWT_ISSUER").unwrap_or_else(|_| "novapay".to_string())
+    }
+
+    fn audience() -> String {
+        env::var("JWT_AUDIENCE").unwrap_or_else(|_| "novapay-api".to_string())
+    }
+
+    pub fn generate_jwt(user_id: &str, session_id: &str, role: Role) -> Result<String, jsonwebtoken::errors::Error> {
+        let keys = KeyManager::current();
+        let (kid, key) = keys.signing_key().ok_or(ErrorKind::InvalidKeyFormat)?;
+
+        let now = chrono::Utc::now();
+        let expiration = now
+            .checked_add_signed(Self::access_token_ttl())
             .expect("valid timestamp")
             .timestamp() as usize;
 
         let claims = Claims {
             sub: user_id.to_string(),
+            sid: session_id.to_string(),
+            iat: now.timestamp() as usize,
             exp: expiration,
+            iss: Self::issuer(),
+            aud: Self::audience(),
+            jti: Uuid::new_v4().to_string(),
+            role: role.as_str().to_string(),
         };
 
-        encode(
-            &Header::default(),
-            &claims,
-            &EncodingKey::from_secret(secret.as_ref()),
-        )
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(kid.to_string());
+
+        encode(&header, &claims, key)
     }
 
+    /// Verifies a token against the key named by its `kid`, so tokens signed
+    /// before a rotation stay valid until they expire.
     pub fn verify_jwt(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-        let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-        let token_data = decode::<Claims>(
-            token,
-            &DecodingKey::from_secret(secret.as_ref()),
-            &Validation::default(),
-        )?;
+        let kid = decode_header(token)?.kid.ok_or(ErrorKind::InvalidToken)?;
+        let keys = KeyManager::current();
+        let key = keys.decoding_key(&kid).ok_or(ErrorKind::InvalidToken)?;
+
+        let mut validation = Validation::new(Algorithm::EdDSA);
+        validation.set_issuer(&[Self::issuer()]);
+        validation.set_audience(&[Self::audience()]);
+
+        let token_data = decode::<Claims>(token, key, &validation)?;
         Ok(token_data.claims)
     }
 }
\ No newline at end of file
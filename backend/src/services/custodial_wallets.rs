@@ -0,0 +1,104 @@
+use sqlx::SqlitePool;
+use thiserror::Error;
+
+use crate::models::{CreateCustodialWalletRequest, CustodialWallet};
+use crate::services::key_vault::{KeyVault, KeyVaultError};
+use crate::wallet_sdk_service::WalletCreateResponse;
+
+#[derive(Error, Debug)]
+pub enum CustodialWalletError {
+    #[error("Wallet not found")]
+    NotFound,
+    #[error("Export was not enabled when this wallet was created")]
+    NotExportable,
+    #[error("A wallet with this key already exists")]
+    AlreadyExists,
+    #[error("Key error: {0}")]
+    Key(#[from] KeyVaultError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Server-held Stellar accounts for `/sdk/wallet`. Secrets are stored
+/// encrypted and only decrypted for the request that signs with them; callers
+/// refer to a wallet by id and may only use their own.
+pub struct CustodialWalletService;
+
+impl CustodialWalletService {
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        keypair: WalletCreateResponse,
+        request: &CreateCustodialWalletRequest,
+    ) -> Result<CustodialWallet, CustodialWalletError> {
+        let wallet = sqlx::query_as::<_, CustodialWallet>(
+            r#"
+            INSERT INTO custodial_wallets (id, user_id, public_key, encrypted_secret, label, exportable, exported_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&keypair.wallet_id)
+        .bind(user_id)
+        .bind(&keypair.public_key)
+        .bind(KeyVault::encrypt(&keypair.wallet_id, &keypair.secret_key))
+        .bind(request.label.as_deref().map(str::trim))
+        .bind(request.exportable)
+        // Handing out a backup phrase counts as exporting the key
+        .bind(request.backup_phrase.then(chrono::Utc::now))
+        .bind(chrono::Utc::now())
+        .fetch_one(pool)
+        .await
+        .map_err(|e| match e {
+            sqlx::Error::Database(db) if db.is_unique_violation() => CustodialWalletError::AlreadyExists,
+            e => CustodialWalletError::Database(e),
+        })?;
+
+        println!("👛 Custodial wallet {} created for user {}", wallet.id, user_id);
+        Ok(wallet)
+    }
+
+    pub async fn list(pool: &SqlitePool, user_id: &str) -> Result<Vec<CustodialWallet>, sqlx::Error> {
+        sqlx::query_as::<_, CustodialWallet>("SELECT * FROM custodial_wallets WHERE user_id = ? ORDER BY created_at")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// The wallet if it belongs to `user_id`. Someone else's wallet is
+    /// reported as missing so ids can't be probed.
+    pub async fn find_owned(
+        pool: &SqlitePool,
+        user_id: &str,
+        wallet_id: &str,
+    ) -> Result<CustodialWallet, CustodialWalletError> {
+        sqlx::query_as::<_, CustodialWallet>("SELECT * FROM custodial_wallets WHERE id = ? AND user_id = ?")
+            .bind(wallet_id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(CustodialWalletError::NotFound)
+    }
+
+    pub fn secret(wallet: &CustodialWallet) -> Result<String, CustodialWalletError> {
+        Ok(KeyVault::decrypt(&wallet.id, &wallet.encrypted_secret)?)
+    }
+
+    /// Hands the secret to its owner. Only for wallets created exportable;
+    /// the caller is expected to have checked PIN and step-up.
+    pub async fn export(pool: &SqlitePool, wallet: &CustodialWallet) -> Result<String, CustodialWalletError> {
+        if !wallet.exportable {
+            return Err(CustodialWalletError::NotExportable);
+        }
+        let secret = Self::secret(wallet)?;
+
+        sqlx::query("UPDATE custodial_wallets SET exported_at = ? WHERE id = ?")
+            .bind(chrono::Utc::now())
+            .bind(&wallet.id)
+            .execute(pool)
+            .await?;
+
+        println!("🔓 Secret key of custodial wallet {} exported", wallet.id);
+        Ok(secret)
+    }
+}
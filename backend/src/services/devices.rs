@@ -0,0 +1,55 @@
+use sqlx::SqlitePool;
+use uuid::Uuid;
+
+use crate::services::signing::sha256_hex;
+
+pub struct DeviceService;
+
+impl DeviceService {
+    /// Records a sign-in from `user_agent` and reports whether it came from a
+    /// device the user has not used before. A user's very first device is not
+    /// reported as new.
+    pub async fn record_login(
+        pool: &SqlitePool,
+        user_id: &str,
+        user_agent: &str,
+        ip_address: Option<&str>,
+    ) -> Result<bool, sqlx::Error> {
+        let fingerprint = sha256_hex(user_agent.as_bytes());
+
+        let known_devices: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_devices WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+
+        let inserted = sqlx::query(
+            r#"
+            INSERT INTO user_devices (id, user_id, fingerprint, user_agent, last_ip)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (user_id, fingerprint) DO NOTHING
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(&fingerprint)
+        .bind(user_agent)
+        .bind(ip_address)
+        .execute(pool)
+        .await?
+        .rows_affected()
+            > 0;
+
+        if !inserted {
+            sqlx::query(
+                "UPDATE user_devices SET last_seen_at = CURRENT_TIMESTAMP, last_ip = ? WHERE user_id = ? AND fingerprint = ?",
+            )
+            .bind(ip_address)
+            .bind(user_id)
+            .bind(&fingerprint)
+            .execute(pool)
+            .await?;
+        }
+
+        Ok(inserted && known_devices > 0)
+    }
+}
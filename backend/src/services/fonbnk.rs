@@ -1,6 +1,11 @@
-use reqwest::Client;
+use jsonwebtoken::{encode, EncodingKey, Header};
+use reqwest::{Client, Url};
 use serde::{Deserialize, Serialize};
+use sqlx::SqlitePool;
 use std::env;
+use uuid::Uuid;
+
+use crate::models::FonbnkOrder;
 
 #[derive(Debug, Serialize)]
 pub struct FonbnkConvertRequest {
@@ -19,6 +24,16 @@ pub struct FonbnkConvertResponse {
     pub message: Option<String>,
 }
 
+/// Payload of the `signature` query parameter on widget and pay URLs.
+/// Fonbnk verifies it with the merchant's URL signature secret (HS256).
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FonbnkUrlClaims {
+    pub uid: String,
+    pub address: String,
+    pub amount: String,
+    pub currency: String,
+}
+
 #[derive(Debug)]
 pub struct FonbnkService {
     client: Client,
@@ -27,6 +42,9 @@ pub struct FonbnkService {
     url_signature_secret: String,
     source_param: String,
     base_url: String,
+    widget_url: String,
+    network: String,
+    asset: String,
 }
 
 impl FonbnkService {
@@ -41,6 +59,10 @@ impl FonbnkService {
             .expect("FONBNK_SOURCE_PARAM must be set");
         let base_url = env::var("FONBNK_BASE_URL")
             .unwrap_or_else(|_| "https://api.fonbnk.com".to_string());
+        let widget_url = env::var("FONBNK_WIDGET_URL")
+            .unwrap_or_else(|_| "https://pay.fonbnk.com".to_string());
+        let network = env::var("FONBNK_NETWORK").unwrap_or_else(|_| "STELLAR".to_string());
+        let asset = env::var("FONBNK_ASSET").unwrap_or_else(|_| "USDC".to_string());
         
         println!("📱 Fonbnk API: {} (Client: {})", base_url, &client_id[..8]);
         
@@ -51,9 +73,88 @@ impl FonbnkService {
             url_signature_secret,
             source_param,
             base_url,
+            widget_url,
+            network,
+            asset,
         }
     }
 
+    pub fn sign_url_claims(&self, claims: &FonbnkUrlClaims) -> Result<String, jsonwebtoken::errors::Error> {
+        encode(
+            &Header::default(),
+            claims,
+            &EncodingKey::from_secret(self.url_signature_secret.as_bytes()),
+        )
+    }
+
+    /// Builds the hosted on-ramp URL for an order. The order reference travels
+    /// as `orderParams` so the webhook can be matched back to `fonbnk_orders`.
+    pub fn build_widget_url(
+        &self,
+        order_reference: &str,
+        wallet_address: &str,
+        amount: f64,
+        currency: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        let amount = format!("{:.2}", amount);
+        let signature = self.sign_url_claims(&FonbnkUrlClaims {
+            uid: order_reference.to_string(),
+            address: wallet_address.to_string(),
+            amount: amount.clone(),
+            currency: currency.to_string(),
+        })?;
+
+        let url = Url::parse_with_params(
+            &self.widget_url,
+            &[
+                ("source", self.source_param.as_str()),
+                ("signature", signature.as_str()),
+                ("network", self.network.as_str()),
+                ("asset", self.asset.as_str()),
+                ("address", wallet_address),
+                ("amount", amount.as_str()),
+                ("currency", currency),
+                ("orderParams", order_reference),
+            ],
+        )?;
+
+        Ok(url.to_string())
+    }
+
+    pub async fn create_widget_order(
+        &self,
+        pool: &SqlitePool,
+        user_id: &str,
+        wallet_address: &str,
+        amount: f64,
+        currency: &str,
+    ) -> Result<FonbnkOrder, Box<dyn std::error::Error>> {
+        let order_reference = format!("np_{}", Uuid::new_v4().simple());
+        let currency = currency.to_uppercase();
+        let widget_url = self.build_widget_url(&order_reference, wallet_address, amount, &currency)?;
+
+        let order = sqlx::query_as::<_, FonbnkOrder>(
+            r#"
+            INSERT INTO fonbnk_orders (id, user_id, wallet_address, amount, currency, network, asset, widget_url)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&order_reference)
+        .bind(user_id)
+        .bind(wallet_address)
+        .bind(amount)
+        .bind(&currency)
+        .bind(&self.network)
+        .bind(&self.asset)
+        .bind(&widget_url)
+        .fetch_one(pool)
+        .await?;
+
+        println!("🔗 Fonbnk widget order {} for user {}: {} {}", order.id, user_id, amount, currency);
+        Ok(order)
+    }
+
     pub async fn convert_airtime(
         &self,
         phone_number: &str,
@@ -115,4 +216,63 @@ impl FonbnkService {
             Err("Failed to get conversion rate".into())
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ADDRESS: &str = "GBRPYHIL2CI3FNQ4BXLFMNDLFJUNPU2HY3ZMFSHONUCEOASW7QC7OX2H";
+
+    // HS256 over `{"typ":"JWT","alg":"HS256"}` and the claims below, keyed
+    // with "fonbnk-url-secret"
+    const SIGNATURE: &str = "eyJ0eXAiOiJKV1QiLCJhbGciOiJIUzI1NiJ9.\
+        eyJ1aWQiOiJucF9vcmRlcjEiLCJhZGRyZXNzIjoiR0JSUFlISUwyQ0kzRk5RNEJYTEZNTkRMRkpVTlBVMkhZM1pNRlNIT05VQ0VPQVNXN1FDN09YMkgiLCJhbW91bnQiOiIyNS4wMCIsImN1cnJlbmN5IjoiS0VTIn0.\
+        -yiH4BU4_mGBapXDALQUnyca7FCfo2hd5koEA1Vu6aY";
+
+    fn service() -> FonbnkService {
+        FonbnkService {
+            client: Client::new(),
+            client_id: "client-id".to_string(),
+            api_signature_secret: "fonbnk-api-secret".to_string(),
+            url_signature_secret: "fonbnk-url-secret".to_string(),
+            source_param: "novapay".to_string(),
+            base_url: "https://api.fonbnk.com".to_string(),
+            widget_url: "https://pay.fonbnk.com".to_string(),
+            network: "STELLAR".to_string(),
+            asset: "USDC".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_url_claims_signature() {
+        let signature = service()
+            .sign_url_claims(&FonbnkUrlClaims {
+                uid: "np_order1".to_string(),
+                address: ADDRESS.to_string(),
+                amount: "25.00".to_string(),
+                currency: "KES".to_string(),
+            })
+            .unwrap();
+        assert_eq!(signature, SIGNATURE);
+    }
+
+    #[test]
+    fn test_widget_url() {
+        let url = service().build_widget_url("np_order1", ADDRESS, 25.0, "KES").unwrap();
+        assert_eq!(
+            url,
+            format!(
+                "https://pay.fonbnk.com/?source=novapay&signature={}&network=STELLAR&asset=USDC&address={}\
+                 &amount=25.00&currency=KES&orderParams=np_order1",
+                SIGNATURE, ADDRESS
+            )
+        );
+    }
+
+    #[test]
+    fn test_widget_url_rounds_amount_before_signing() {
+        let url = service().build_widget_url("np_order1", ADDRESS, 24.999, "KES").unwrap();
+        assert!(url.contains(&format!("signature={}&", SIGNATURE)));
+        assert!(url.contains("&amount=25.00&"));
+    }
+}
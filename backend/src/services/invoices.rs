@@ -0,0 +1,950 @@
+use chrono::{Duration, Utc};
+use rand::Rng;
+use serde_json::{json, Value};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::env;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{
+    CheckoutView, CreateInvoiceRequest, CreateTransaction, Invoice, InvoiceItem, InvoicePayOptions, InvoicePayment, InvoiceView, Merchant,
+};
+use crate::services::non_custodial::asset_code;
+use crate::services::payment_requests::{muxed_address, sep7_pay_uri};
+use crate::services::payouts::receiving_account;
+use crate::services::stellar_tx::{StellarNetwork, StellarTxBuilder, StellarTxError};
+use crate::services::webhooks::event_body;
+use crate::services::{
+    JobPayload, Outbox, PaymentOutcome, PaymentRequestService, TransactionService, WalletPaymentError, WebhookOwner, WebhookService,
+};
+
+/// Half a stroop: amounts closer than this are equal.
+pub(crate) const AMOUNT_EPSILON: f64 = 0.00000005;
+
+/// Horizon page size when reading an account's payments.
+const WATCH_PAGE_SIZE: u32 = 200;
+
+/// Pages read per account on each pass, so one busy account can't hold up
+/// the others.
+const WATCH_MAX_PAGES: usize = 5;
+
+#[derive(Error, Debug)]
+pub enum InvoiceError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Invalid currency: {0}")]
+    InvalidCurrency(String),
+    #[error("Create a wallet to receive payments first")]
+    NoReceivingAccount,
+    #[error("You can't pay your own invoice")]
+    OwnInvoice,
+    #[error("Invoice is {0}")]
+    NotPayable(String),
+    #[error("Invoice has expired")]
+    Expired,
+    #[error("Insufficient balance")]
+    InsufficientFunds,
+    #[error("Payment failed: {0}")]
+    Payment(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<WalletPaymentError> for InvoiceError {
+    fn from(error: WalletPaymentError) -> Self {
+        match error {
+            WalletPaymentError::InsufficientFunds => InvoiceError::InsufficientFunds,
+            WalletPaymentError::Failed(e) | WalletPaymentError::Unavailable(e) => InvoiceError::Payment(e),
+            WalletPaymentError::Database(e) => InvoiceError::Database(e),
+        }
+    }
+}
+
+/// A payment found for an invoice, from a wallet or on the network.
+struct ReceivedPayment {
+    source: &'static str,
+    payer_id: Option<String>,
+    from_account: Option<String>,
+    amount: f64,
+    transaction_id: Option<String>,
+    tx_hash: Option<String>,
+    paging_token: Option<String>,
+}
+
+/// Merchant invoices. Each one is paid into the merchant owner's receiving
+/// account and gets its own muxed address and memo, so a payment from any
+/// Stellar wallet can be matched to it. Every payment and expiry is posted
+/// to the merchant's webhook endpoints as `invoice.paid`,
+/// `invoice.underpaid`, `invoice.overpaid` or `invoice.expired`.
+pub struct InvoiceService;
+
+impl InvoiceService {
+    pub async fn create(
+        pool: &SqlitePool,
+        merchant: &Merchant,
+        request: &CreateInvoiceRequest,
+    ) -> Result<InvoiceView, InvoiceError> {
+        let currency = request.currency.clone().unwrap_or_else(|| "XLM".to_string());
+        StellarTxBuilder::asset(&currency).map_err(|e| InvoiceError::InvalidCurrency(e.to_string()))?;
+        let destination = receiving_account(pool, &merchant.owner_user_id)
+            .await?
+            .ok_or(InvoiceError::NoReceivingAccount)?;
+
+        let amounts: Vec<f64> = request
+            .items
+            .iter()
+            .map(|item| round_amount(item.quantity as f64 * item.unit_price))
+            .collect();
+        let amount_due = round_amount(amounts.iter().sum());
+        let ttl_minutes = request.expires_in_minutes.unwrap_or_else(|| {
+            env::var("INVOICE_TTL_MINUTES")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(60)
+        });
+        let now = Utc::now();
+        let id = Uuid::new_v4().to_string();
+        let muxed_id: i64 = rand::thread_rng().gen_range(1..i64::MAX);
+
+        let mut db_tx = pool.begin().await?;
+        let invoice = sqlx::query_as::<_, Invoice>(
+            r#"
+            INSERT INTO invoices
+                (id, merchant_id, reference, description, currency, amount_due, destination, muxed_id, memo,
+                 expires_at, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(&merchant.id)
+        .bind(&request.reference)
+        .bind(&request.description)
+        .bind(&currency)
+        .bind(amount_due)
+        .bind(&destination)
+        .bind(muxed_id)
+        .bind(format!("NP-{}", &Uuid::new_v4().simple().to_string()[..12].to_uppercase()))
+        .bind(now + Duration::minutes(ttl_minutes))
+        .bind(now)
+        .bind(now)
+        .fetch_one(&mut *db_tx)
+        .await?;
+        for (position, (item, amount)) in request.items.iter().zip(amounts).enumerate() {
+            sqlx::query(
+                r#"
+                INSERT INTO invoice_items (id, invoice_id, position, description, quantity, unit_price, amount)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&id)
+            .bind(position as i64)
+            .bind(item.description.trim())
+            .bind(item.quantity)
+            .bind(item.unit_price)
+            .bind(amount)
+            .execute(&mut *db_tx)
+            .await?;
+        }
+        db_tx.commit().await?;
+
+        println!("🧾 Invoice {} for {} {} created by merchant {}", id, amount_due, currency, merchant.id);
+        Self::view(pool, invoice).await
+    }
+
+    pub async fn list(
+        pool: &SqlitePool,
+        merchant_id: &str,
+        status: Option<&str>,
+    ) -> Result<Vec<InvoiceView>, InvoiceError> {
+        Self::expire(pool).await?;
+        let invoices = sqlx::query_as::<_, Invoice>(
+            "SELECT * FROM invoices WHERE merchant_id = ? AND (? IS NULL OR status = ?) ORDER BY created_at DESC",
+        )
+        .bind(merchant_id)
+        .bind(status)
+        .bind(status)
+        .fetch_all(pool)
+        .await?;
+
+        let mut views = Vec::with_capacity(invoices.len());
+        for invoice in invoices {
+            views.push(Self::view(pool, invoice).await?);
+        }
+        Ok(views)
+    }
+
+    pub async fn find(pool: &SqlitePool, merchant_id: &str, id: &str) -> Result<InvoiceView, InvoiceError> {
+        let invoice = Self::get(pool, id).await?;
+        if invoice.merchant_id != merchant_id {
+            return Err(InvoiceError::NotFound);
+        }
+        Self::view(pool, invoice).await
+    }
+
+    /// The public checkout page for an invoice: anyone with its id can see
+    /// what is owed and how to pay it.
+    pub async fn checkout(pool: &SqlitePool, id: &str) -> Result<CheckoutView, InvoiceError> {
+        let invoice = Self::get(pool, id).await?;
+        let merchant_name: String = sqlx::query_scalar("SELECT business_name FROM merchants WHERE id = ?")
+            .bind(&invoice.merchant_id)
+            .fetch_one(pool)
+            .await?;
+        let items = Self::items(pool, &invoice.id).await?;
+
+        Ok(CheckoutView {
+            pay: pay_options(&invoice),
+            amount_remaining: remaining(&invoice),
+            id: invoice.id,
+            merchant_name,
+            reference: invoice.reference,
+            description: invoice.description,
+            currency: invoice.currency,
+            amount_due: invoice.amount_due,
+            amount_paid: invoice.amount_paid,
+            status: invoice.status,
+            expires_at: invoice.expires_at,
+            items,
+        })
+    }
+
+    /// Pays what is left on an invoice from the user's NovaPay wallet. The
+    /// invoice is held `processing` while the payment goes out, so it can't
+    /// be paid twice, and is released only if the payment definitely failed.
+    /// If Horizon can't say whether it landed, the invoice comes back still
+    /// `processing` and the `InvoiceSettlement` job records the payment.
+    pub async fn pay(pool: &SqlitePool, payer_id: &str, id: &str) -> Result<CheckoutView, InvoiceError> {
+        let invoice = Self::payable(pool, payer_id, id).await?;
+        TransactionService::new().ensure_funds(pool, payer_id, remaining(&invoice)).await?;
+        let transaction_id = Self::claim(pool, &invoice, payer_id).await?;
+
+        match Self::complete(pool, &invoice, &transaction_id).await? {
+            PaymentOutcome::Failed(reason) => Err(InvoiceError::Payment(reason)),
+            PaymentOutcome::Completed(_) | PaymentOutcome::Unknown(_) => Self::checkout(pool, id).await,
+        }
+    }
+
+    /// Finishes a wallet payment for its settlement job. Errors while the
+    /// payment's outcome is still unknown, so the job retries.
+    pub async fn settle_payment(pool: &SqlitePool, invoice_id: &str, transaction_id: &str) -> Result<(), String> {
+        let invoice = sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE id = ?")
+            .bind(invoice_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        if invoice.processing_transaction_id.as_deref() != Some(transaction_id) {
+            return Ok(());
+        }
+
+        match Self::complete(pool, &invoice, transaction_id).await.map_err(|e| e.to_string())? {
+            PaymentOutcome::Unknown(e) => Err(format!("payment outcome unknown: {}", e)),
+            PaymentOutcome::Completed(_) | PaymentOutcome::Failed(_) => Ok(()),
+        }
+    }
+
+    /// Checks an invoice can be paid from a wallet by this user, and returns it.
+    pub async fn payable(pool: &SqlitePool, payer_id: &str, id: &str) -> Result<Invoice, InvoiceError> {
+        let invoice = Self::get(pool, id).await?;
+        let owner_id: String = sqlx::query_scalar("SELECT owner_user_id FROM merchants WHERE id = ?")
+            .bind(&invoice.merchant_id)
+            .fetch_one(pool)
+            .await?;
+        if owner_id == payer_id {
+            return Err(InvoiceError::OwnInvoice);
+        }
+        if invoice.status == "expired" || invoice.expires_at <= Utc::now() {
+            return Err(InvoiceError::Expired);
+        }
+        if invoice.status != "open" && invoice.status != "underpaid" {
+            return Err(InvoiceError::NotPayable(invoice.status));
+        }
+        Ok(invoice)
+    }
+
+    pub fn spawn_watcher(pool: SqlitePool) {
+        let interval_secs = env::var("INVOICE_WATCH_POLL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(15u64)
+            .max(1);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                if let Err(e) = Self::tick(&pool).await {
+                    println!("⚠️ Invoice watcher failed: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Expires invoices whose time is up, then reads new payments into every
+    /// account that has an invoice or a payment request still open or
+    /// expired within the last `INVOICE_LATE_PAYMENT_HOURS` (default 24), so
+    /// late payments are counted too.
+    pub async fn tick(pool: &SqlitePool) -> Result<(), String> {
+        Self::expire(pool).await.map_err(|e| e.to_string())?;
+
+        let late_hours = env::var("INVOICE_LATE_PAYMENT_HOURS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(24);
+        let since = Utc::now() - Duration::hours(late_hours);
+        let accounts: Vec<String> = sqlx::query_scalar(
+            r#"
+            SELECT destination FROM invoices WHERE expires_at > ?
+            UNION
+            SELECT destination FROM payment_requests WHERE muxed_id IS NOT NULL AND expires_at > ?
+            "#,
+        )
+        .bind(since)
+        .bind(since)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        let network = StellarNetwork::from_env();
+        for account in accounts {
+            if let Err(e) = Self::watch(pool, &network, &account).await {
+                println!("⚠️ Could not read payments to {}: {}", account, e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn expire(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        let mut db_tx = pool.begin().await?;
+        let expired = sqlx::query_as::<_, Invoice>(
+            r#"
+            UPDATE invoices SET status = 'expired', updated_at = ?
+            WHERE status IN ('open', 'underpaid') AND expires_at <= ?
+            RETURNING *
+            "#,
+        )
+        .bind(now)
+        .bind(now)
+        .fetch_all(&mut *db_tx)
+        .await?;
+        for invoice in &expired {
+            notify(&mut db_tx, invoice, None).await?;
+        }
+        db_tx.commit().await?;
+
+        for invoice in &expired {
+            println!("⌛ Invoice {} expired with {} of {} paid", invoice.id, invoice.amount_paid, invoice.amount_due);
+        }
+        Ok(())
+    }
+
+    /// Reads the account's payments since the last pass and records those
+    /// sent to an invoice's muxed address or with its memo. Payments to the
+    /// muxed address of a payment request settle the request.
+    async fn watch(pool: &SqlitePool, network: &StellarNetwork, account: &str) -> Result<(), StellarTxError> {
+        let database = |e: sqlx::Error| StellarTxError::Horizon(format!("database error: {}", e));
+        let mut cursor: Option<String> = sqlx::query_scalar("SELECT cursor FROM payment_watch_cursors WHERE account = ?")
+            .bind(account)
+            .fetch_optional(pool)
+            .await
+            .map_err(database)?;
+
+        for _ in 0..WATCH_MAX_PAGES {
+            let records = network.payments(account, cursor.as_deref(), WATCH_PAGE_SIZE).await?;
+            for record in &records {
+                Self::match_payment(pool, account, record).await.map_err(database)?;
+                if let Some(token) = record["paging_token"].as_str() {
+                    cursor = Some(token.to_string());
+                }
+            }
+
+            if let Some(cursor) = &cursor {
+                sqlx::query(
+                    r#"
+                    INSERT INTO payment_watch_cursors (account, cursor, updated_at) VALUES (?, ?, ?)
+                    ON CONFLICT(account) DO UPDATE SET cursor = excluded.cursor, updated_at = excluded.updated_at
+                    "#,
+                )
+                .bind(account)
+                .bind(cursor)
+                .bind(Utc::now())
+                .execute(pool)
+                .await
+                .map_err(database)?;
+            }
+            if records.len() < WATCH_PAGE_SIZE as usize {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    async fn match_payment(pool: &SqlitePool, account: &str, record: &Value) -> Result<(), sqlx::Error> {
+        let kind = record["type"].as_str().unwrap_or_default();
+        if !matches!(kind, "payment" | "path_payment_strict_receive" | "path_payment_strict_send")
+            || record["to"].as_str() != Some(account)
+        {
+            return Ok(());
+        }
+
+        let muxed_id = record["to_muxed_id"].as_str().and_then(|id| id.parse::<u64>().ok());
+        let invoice = if let Some(muxed_id) = muxed_id {
+            sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE destination = ? AND muxed_id = ?")
+                .bind(account)
+                .bind(muxed_id as i64)
+                .fetch_optional(pool)
+                .await?
+        } else if record["transaction"]["memo_type"] == "text" {
+            sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE destination = ? AND memo = ?")
+                .bind(account)
+                .bind(record["transaction"]["memo"].as_str())
+                .fetch_optional(pool)
+                .await?
+        } else {
+            None
+        };
+        let Some(invoice) = invoice else {
+            if let Some(muxed_id) = muxed_id {
+                PaymentRequestService::receive(pool, account, muxed_id, record).await?;
+            }
+            return Ok(());
+        };
+
+        let paging_token = record["paging_token"].as_str().map(str::to_string);
+        let seen: bool = sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM invoice_payments WHERE paging_token = ?)")
+            .bind(&paging_token)
+            .fetch_one(pool)
+            .await?;
+        if seen {
+            return Ok(());
+        }
+
+        let asset = payment_asset(record);
+        if asset != invoice.currency {
+            println!("⚠️ Ignoring {} payment to invoice {}, which is billed in {}", asset, invoice.id, invoice.currency);
+            return Ok(());
+        }
+        let Some(amount) = payment_amount(record) else {
+            return Ok(());
+        };
+
+        let payment = ReceivedPayment {
+            source: "stellar",
+            payer_id: None,
+            from_account: record["from"].as_str().map(str::to_string),
+            amount,
+            transaction_id: None,
+            tx_hash: record["transaction_hash"].as_str().map(str::to_string),
+            paging_token,
+        };
+        Self::record_payment(pool, &invoice.id, payment).await?;
+        Ok(())
+    }
+
+    /// Holds an open or underpaid invoice for `payer_id` and records the
+    /// transaction that pays what is left, along with the job that settles
+    /// the payment should this request not get that far.
+    async fn claim(pool: &SqlitePool, invoice: &Invoice, payer_id: &str) -> Result<String, InvoiceError> {
+        let owner_email: String = sqlx::query_scalar(
+            "SELECT u.email FROM merchants m JOIN users u ON u.id = m.owner_user_id WHERE m.id = ?",
+        )
+        .bind(&invoice.merchant_id)
+        .fetch_one(pool)
+        .await?;
+
+        let mut db_tx = pool.begin().await?;
+        let transaction = TransactionService::new()
+            .create_transaction(
+                &mut *db_tx,
+                payer_id,
+                CreateTransaction {
+                    recipient_email: owner_email,
+                    amount: remaining(invoice),
+                    currency: Some(asset_code(&invoice.currency)),
+                    target_currency: Some(asset_code(&invoice.currency)),
+                    pin: String::new(),
+                },
+            )
+            .await?;
+        // The amount paid is part of the claim, so an on-chain payment
+        // recorded in the meantime isn't paid over again
+        let claimed = sqlx::query(
+            r#"
+            UPDATE invoices SET status = 'processing', processing_transaction_id = ?, updated_at = ?
+            WHERE id = ? AND status IN ('open', 'underpaid') AND processing_transaction_id IS NULL
+              AND amount_paid = ? AND expires_at > ?
+            "#,
+        )
+        .bind(&transaction.id)
+        .bind(Utc::now())
+        .bind(&invoice.id)
+        .bind(invoice.amount_paid)
+        .bind(Utc::now())
+        .execute(&mut *db_tx)
+        .await?;
+        if claimed.rows_affected() != 1 {
+            let status: String = sqlx::query_scalar("SELECT status FROM invoices WHERE id = ?")
+                .bind(&invoice.id)
+                .fetch_one(&mut *db_tx)
+                .await?;
+            return Err(InvoiceError::NotPayable(status));
+        }
+        Outbox::enqueue(
+            &mut *db_tx,
+            &JobPayload::InvoiceSettlement {
+                invoice_id: invoice.id.clone(),
+                transaction_id: transaction.id.clone(),
+            },
+        )
+        .await?;
+        db_tx.commit().await?;
+        Ok(transaction.id)
+    }
+
+    /// Sends the transaction paying a held invoice, or finds out what became
+    /// of it, then records the payment or releases the invoice. An unknown
+    /// outcome leaves it `processing`. Safe to call again.
+    async fn complete(pool: &SqlitePool, invoice: &Invoice, transaction_id: &str) -> Result<PaymentOutcome, sqlx::Error> {
+        let outcome = TransactionService::new()
+            .pay_from_wallet(pool, transaction_id, &invoice.destination)
+            .await?;
+
+        match &outcome {
+            PaymentOutcome::Completed(tx_hash) => {
+                let (payer_id, amount): (String, f64) =
+                    sqlx::query_as("SELECT user_id, amount FROM transactions WHERE id = ?")
+                        .bind(transaction_id)
+                        .fetch_one(pool)
+                        .await?;
+                let payment = ReceivedPayment {
+                    source: "wallet",
+                    payer_id: Some(payer_id),
+                    from_account: None,
+                    amount,
+                    transaction_id: Some(transaction_id.to_string()),
+                    tx_hash: Some(tx_hash.clone()),
+                    paging_token: None,
+                };
+                Self::record_payment(pool, &invoice.id, payment).await?;
+            }
+            PaymentOutcome::Failed(reason) => {
+                sqlx::query(
+                    r#"
+                    UPDATE invoices
+                    SET status = CASE
+                            WHEN status <> 'processing' THEN status
+                            WHEN amount_paid > 0 THEN 'underpaid'
+                            ELSE 'open'
+                        END,
+                        processing_transaction_id = NULL, updated_at = ?
+                    WHERE id = ? AND processing_transaction_id = ?
+                    "#,
+                )
+                .bind(Utc::now())
+                .bind(&invoice.id)
+                .bind(transaction_id)
+                .execute(pool)
+                .await?;
+                println!("❌ Wallet payment of invoice {} failed: {}", invoice.id, reason);
+            }
+            PaymentOutcome::Unknown(e) => {
+                println!("⏳ Wallet payment of invoice {} left processing, outcome unknown: {}", invoice.id, e);
+            }
+        }
+        Ok(outcome)
+    }
+
+    /// Adds a payment to the invoice's total, sets its status from the new
+    /// total and queues the webhook, all in one database transaction. A
+    /// wallet payment also releases the hold its transaction has on the
+    /// invoice, and is recorded only while it has it, so it counts once.
+    async fn record_payment(
+        pool: &SqlitePool,
+        invoice_id: &str,
+        payment: ReceivedPayment,
+    ) -> Result<Option<Invoice>, sqlx::Error> {
+        let now = Utc::now();
+        let mut db_tx = pool.begin().await?;
+        if let Some(transaction_id) = &payment.transaction_id {
+            let released = sqlx::query(
+                "UPDATE invoices SET processing_transaction_id = NULL WHERE id = ? AND processing_transaction_id = ?",
+            )
+            .bind(invoice_id)
+            .bind(transaction_id)
+            .execute(&mut *db_tx)
+            .await?;
+            if released.rows_affected() == 0 {
+                return Ok(None);
+            }
+        }
+        let recorded = sqlx::query_as::<_, InvoicePayment>(
+            r#"
+            INSERT INTO invoice_payments
+                (id, invoice_id, source, payer_id, from_account, amount, transaction_id, tx_hash, paging_token, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(invoice_id)
+        .bind(payment.source)
+        .bind(&payment.payer_id)
+        .bind(&payment.from_account)
+        .bind(payment.amount)
+        .bind(&payment.transaction_id)
+        .bind(&payment.tx_hash)
+        .bind(&payment.paging_token)
+        .bind(now)
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        let updated = sqlx::query_as::<_, Invoice>(
+            r#"
+            UPDATE invoices
+            SET amount_paid = amount_paid + ?,
+                status = CASE
+                    WHEN amount_paid + ? < amount_due - ? THEN 'underpaid'
+                    WHEN amount_paid + ? > amount_due + ? THEN 'overpaid'
+                    ELSE 'paid'
+                END,
+                paid_at = CASE WHEN amount_paid + ? >= amount_due - ? THEN COALESCE(paid_at, ?) ELSE paid_at END,
+                updated_at = ?
+            WHERE id = ?
+            RETURNING *
+            "#,
+        )
+        .bind(payment.amount)
+        .bind(payment.amount)
+        .bind(AMOUNT_EPSILON)
+        .bind(payment.amount)
+        .bind(AMOUNT_EPSILON)
+        .bind(payment.amount)
+        .bind(AMOUNT_EPSILON)
+        .bind(now)
+        .bind(now)
+        .bind(invoice_id)
+        .fetch_one(&mut *db_tx)
+        .await?;
+        notify(&mut db_tx, &updated, Some(&recorded)).await?;
+        db_tx.commit().await?;
+
+        println!(
+            "💸 Invoice {} received {} {} ({}), now {}",
+            updated.id, payment.amount, updated.currency, payment.source, updated.status
+        );
+        Ok(Some(updated))
+    }
+
+    async fn get(pool: &SqlitePool, id: &str) -> Result<Invoice, InvoiceError> {
+        Self::expire(pool).await?;
+        sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(InvoiceError::NotFound)
+    }
+
+    async fn items(pool: &SqlitePool, invoice_id: &str) -> Result<Vec<InvoiceItem>, sqlx::Error> {
+        sqlx::query_as::<_, InvoiceItem>("SELECT * FROM invoice_items WHERE invoice_id = ? ORDER BY position")
+            .bind(invoice_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    async fn view(pool: &SqlitePool, invoice: Invoice) -> Result<InvoiceView, InvoiceError> {
+        let items = Self::items(pool, &invoice.id).await?;
+        let payments = sqlx::query_as::<_, InvoicePayment>(
+            "SELECT * FROM invoice_payments WHERE invoice_id = ? ORDER BY created_at",
+        )
+        .bind(&invoice.id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(InvoiceView {
+            pay: pay_options(&invoice),
+            amount_remaining: remaining(&invoice),
+            invoice,
+            items,
+            payments,
+        })
+    }
+}
+
+/// Posts the invoice's current status to the merchant's webhook endpoints.
+async fn notify(
+    conn: &mut SqliteConnection,
+    invoice: &Invoice,
+    payment: Option<&InvoicePayment>,
+) -> Result<(), sqlx::Error> {
+    let body = event_body(
+        &format!("evt_{}", Uuid::new_v4().simple()),
+        &format!("invoice.{}", invoice.status),
+        json!({
+            "invoice": invoice,
+            "amount_remaining": remaining(invoice),
+            "payment": payment,
+        }),
+    );
+    WebhookService::publish(conn, WebhookOwner::Merchant(&invoice.merchant_id), &body).await?;
+    Ok(())
+}
+
+fn pay_options(invoice: &Invoice) -> InvoicePayOptions {
+    let frontend_url = env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    InvoicePayOptions {
+        muxed_address: muxed_address(&invoice.destination, invoice.muxed_id),
+        destination: invoice.destination.clone(),
+        memo: invoice.memo.clone(),
+        pay_uri: sep7_pay_uri(
+            &invoice.destination,
+            remaining(invoice),
+            &invoice.currency,
+            Some(&invoice.memo),
+        ),
+        checkout_url: format!("{}/checkout/{}", frontend_url.trim_end_matches('/'), invoice.id),
+    }
+}
+
+/// The asset of a Horizon payment record, `XLM` or `CODE:ISSUER`.
+pub(crate) fn payment_asset(record: &Value) -> String {
+    match record["asset_type"].as_str() {
+        Some("native") => "XLM".to_string(),
+        _ => format!(
+            "{}:{}",
+            record["asset_code"].as_str().unwrap_or_default(),
+            record["asset_issuer"].as_str().unwrap_or_default()
+        ),
+    }
+}
+
+pub(crate) fn payment_amount(record: &Value) -> Option<f64> {
+    record["amount"].as_str().and_then(|amount| amount.parse().ok())
+}
+
+/// What is left to pay on an invoice, never negative.
+pub fn remaining(invoice: &Invoice) -> f64 {
+    round_amount(invoice.amount_due - invoice.amount_paid).max(0.0)
+}
+
+/// Rounds to whole stroops.
+fn round_amount(amount: f64) -> f64 {
+    (amount * 10_000_000.0).round() / 10_000_000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::CreateWebhookEndpointRequest;
+    use crate::services::signing::verify_hmac_sha256_hex;
+    use crate::test_support::{insert_user, memory_pool, stub_server};
+    use axum::{body::Bytes, extract::State, http::HeaderMap, routing::post, Router};
+    use std::sync::{Arc, Mutex};
+
+    const SECRET: &str = "whsec_test_secret_0123456789";
+
+    async fn open_invoice(pool: &SqlitePool) -> Invoice {
+        insert_user(pool, "owner", "owner@example.com").await;
+        insert_user(pool, "u2", "payer@example.com").await;
+        sqlx::query("INSERT INTO merchants (id, owner_user_id, business_name, created_at, updated_at) VALUES ('m1', 'owner', 'Mama Mboga', ?, ?)")
+            .bind(Utc::now())
+            .bind(Utc::now())
+            .execute(pool)
+            .await
+            .unwrap();
+        sqlx::query_as::<_, Invoice>(
+            r#"
+            INSERT INTO invoices
+                (id, merchant_id, currency, amount_due, destination, muxed_id, memo, expires_at, created_at, updated_at)
+            VALUES ('i1', 'm1', 'XLM', 10, 'GDEST', 42, 'NP-ABC', ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Utc::now() + Duration::hours(1))
+        .bind(Utc::now())
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn invoice(pool: &SqlitePool) -> Invoice {
+        sqlx::query_as::<_, Invoice>("SELECT * FROM invoices WHERE id = 'i1'")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    /// A Horizon payment record to GDEST, by muxed id or text memo.
+    fn payment(token: &str, amount: &str, muxed_id: Option<&str>, memo: Option<&str>) -> Value {
+        json!({
+            "type": "payment",
+            "paging_token": token,
+            "to": "GDEST",
+            "to_muxed_id": muxed_id,
+            "from": "GPAYER",
+            "asset_type": "native",
+            "amount": amount,
+            "transaction_hash": format!("hash-{}", token),
+            "transaction": { "memo_type": if memo.is_some() { "text" } else { "none" }, "memo": memo },
+        })
+    }
+
+    fn wallet_payment(transaction_id: &str, amount: f64) -> ReceivedPayment {
+        ReceivedPayment {
+            source: "wallet",
+            payer_id: Some("u2".to_string()),
+            from_account: None,
+            amount,
+            transaction_id: Some(transaction_id.to_string()),
+            tx_hash: Some("hash-wallet".to_string()),
+            paging_token: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_on_chain_payments_are_matched_and_counted_once() {
+        let pool = memory_pool().await;
+        open_invoice(&pool).await;
+
+        InvoiceService::match_payment(&pool, "GDEST", &payment("t1", "4.0000000", Some("42"), None)).await.unwrap();
+        let underpaid = invoice(&pool).await;
+        assert_eq!((underpaid.status.as_str(), underpaid.amount_paid), ("underpaid", 4.0));
+        // Horizon returns the same payment again
+        InvoiceService::match_payment(&pool, "GDEST", &payment("t1", "4.0000000", Some("42"), None)).await.unwrap();
+        assert_eq!(invoice(&pool).await.amount_paid, 4.0);
+
+        // Another asset, another muxed id, another memo and another account
+        // are all someone else's payment
+        let mut other_asset = payment("t2", "6.0000000", Some("42"), None);
+        other_asset["asset_type"] = json!("credit_alphanum4");
+        other_asset["asset_code"] = json!("USDC");
+        other_asset["asset_issuer"] = json!("GISSUER");
+        InvoiceService::match_payment(&pool, "GDEST", &other_asset).await.unwrap();
+        InvoiceService::match_payment(&pool, "GDEST", &payment("t3", "6.0000000", Some("43"), None)).await.unwrap();
+        InvoiceService::match_payment(&pool, "GDEST", &payment("t4", "6.0000000", None, Some("NP-XYZ"))).await.unwrap();
+        InvoiceService::match_payment(&pool, "GOTHER", &payment("t5", "6.0000000", Some("42"), None)).await.unwrap();
+        assert_eq!(invoice(&pool).await.amount_paid, 4.0);
+
+        InvoiceService::match_payment(&pool, "GDEST", &payment("t6", "6.0000000", None, Some("NP-ABC"))).await.unwrap();
+        let paid = invoice(&pool).await;
+        assert_eq!((paid.status.as_str(), paid.amount_paid), ("paid", 10.0));
+        assert!(paid.paid_at.is_some());
+
+        InvoiceService::match_payment(&pool, "GDEST", &payment("t7", "0.5000000", Some("42"), None)).await.unwrap();
+        assert_eq!(invoice(&pool).await.status, "overpaid");
+        let events: Vec<String> = sqlx::query_scalar("SELECT event_type FROM webhook_deliveries ORDER BY rowid")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert!(events.is_empty(), "no endpoints are registered");
+    }
+
+    #[tokio::test]
+    async fn test_wallet_payment_holds_the_invoice_until_it_settles() {
+        let pool = memory_pool().await;
+        open_invoice(&pool).await;
+        InvoiceService::match_payment(&pool, "GDEST", &payment("t1", "4.0000000", Some("42"), None)).await.unwrap();
+
+        // The payer has no wallet, so the payment can't go out
+        let transaction_id = InvoiceService::claim(&pool, &invoice(&pool).await, "u2").await.unwrap();
+        assert_eq!(invoice(&pool).await.status, "processing");
+        let amount: f64 = sqlx::query_scalar("SELECT amount FROM transactions WHERE id = ?")
+            .bind(&transaction_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(amount, 6.0);
+        assert!(matches!(
+            InvoiceService::payable(&pool, "u2", "i1").await,
+            Err(InvoiceError::NotPayable(status)) if status == "processing"
+        ));
+        assert!(matches!(
+            InvoiceService::claim(&pool, &invoice(&pool).await, "u2").await,
+            Err(InvoiceError::NotPayable(_))
+        ));
+
+        let outcome = InvoiceService::complete(&pool, &invoice(&pool).await, &transaction_id).await.unwrap();
+        assert!(matches!(outcome, PaymentOutcome::Failed(_)));
+        let released = invoice(&pool).await;
+        assert_eq!((released.status.as_str(), released.processing_transaction_id), ("underpaid", None));
+        InvoiceService::settle_payment(&pool, "i1", &transaction_id).await.unwrap();
+        assert_eq!(invoice(&pool).await.status, "underpaid");
+
+        // A payment that landed is recorded once, however often it is settled
+        let transaction_id = InvoiceService::claim(&pool, &invoice(&pool).await, "u2").await.unwrap();
+        assert!(InvoiceService::record_payment(&pool, "i1", wallet_payment(&transaction_id, 6.0)).await.unwrap().is_some());
+        assert!(InvoiceService::record_payment(&pool, "i1", wallet_payment(&transaction_id, 6.0)).await.unwrap().is_none());
+        let paid = invoice(&pool).await;
+        assert_eq!((paid.status.as_str(), paid.amount_paid), ("paid", 10.0));
+        assert_eq!(paid.processing_transaction_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_expiry_moves_only_payable_invoices() {
+        let pool = memory_pool().await;
+        open_invoice(&pool).await;
+        sqlx::query("UPDATE invoices SET expires_at = ? WHERE id = 'i1'")
+            .bind(Utc::now() - Duration::minutes(1))
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(matches!(InvoiceService::payable(&pool, "u2", "i1").await, Err(InvoiceError::Expired)));
+        assert_eq!(invoice(&pool).await.status, "expired");
+
+        // A late payment is still counted
+        InvoiceService::match_payment(&pool, "GDEST", &payment("t1", "10.0000000", Some("42"), None)).await.unwrap();
+        assert_eq!(invoice(&pool).await.status, "paid");
+        InvoiceService::expire(&pool).await.unwrap();
+        assert_eq!(invoice(&pool).await.status, "paid");
+    }
+
+    #[derive(Clone, Default)]
+    struct Receiver(Arc<Mutex<Vec<(HeaderMap, String)>>>);
+
+    async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: Bytes) {
+        receiver.0.lock().unwrap().push((headers, String::from_utf8_lossy(&body).to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_invoice_events_are_signed_for_merchant_endpoints() {
+        // The stub receiver listens on loopback
+        env::set_var("WEBHOOK_ALLOWED_HOSTS", "127.0.0.1");
+        let pool = memory_pool().await;
+        open_invoice(&pool).await;
+        let receiver = Receiver::default();
+        let url = stub_server(Router::new().route("/hooks", post(receive)).with_state(receiver.clone())).await;
+        let request = CreateWebhookEndpointRequest {
+            url: format!("{}/hooks", url),
+            event_types: vec!["invoice.paid".to_string()],
+            secret: Some(SECRET.to_string()),
+            description: None,
+            merchant: true,
+        };
+        WebhookService::register(&pool, "owner", Some("m1"), &request).await.unwrap();
+
+        InvoiceService::match_payment(&pool, "GDEST", &payment("t1", "4.0000000", Some("42"), None)).await.unwrap();
+        InvoiceService::match_payment(&pool, "GDEST", &payment("t2", "6.0000000", Some("42"), None)).await.unwrap();
+        let deliveries: Vec<String> = sqlx::query_scalar("SELECT id FROM webhook_deliveries")
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(deliveries.len(), 1, "only invoice.paid is subscribed");
+        WebhookService::deliver(&pool, &deliveries[0]).await.unwrap();
+
+        let received = receiver.0.lock().unwrap().clone();
+        let (headers, body) = &received[0];
+        assert_eq!(headers["x-novapay-event"], "invoice.paid");
+        let signature = headers["x-novapay-signature"].to_str().unwrap();
+        let (timestamp, v1) = signature.split_once(',').unwrap();
+        let timestamp = timestamp.strip_prefix("t=").unwrap();
+        let v1 = v1.strip_prefix("v1=").unwrap();
+        assert!(verify_hmac_sha256_hex(SECRET.as_bytes(), format!("{}.{}", timestamp, body).as_bytes(), v1));
+        assert!(!verify_hmac_sha256_hex(b"whsec_someone_else_entirely", format!("{}.{}", timestamp, body).as_bytes(), v1));
+
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["data"]["invoice"]["id"], "i1");
+        assert_eq!(body["data"]["amount_remaining"], 0.0);
+        assert_eq!(body["data"]["payment"]["amount"], 6.0);
+    }
+}
@@ -0,0 +1,580 @@
+use serde::{Deserialize, Serialize};
+use sqlx::{Executor, Sqlite, SqlitePool};
+use std::env;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::models::{Job, JobListQuery, Wallet};
+use crate::services::notification_templates::TemplateKind;
+use crate::services::stellar_tx::{StellarNetwork, StellarTxError};
+use crate::services::{
+    DomainEvent, InvoiceService, MultisigError, MultisigService, NotificationDispatcher, PaymentOutcome, PaymentRequestService, PayoutService, ScheduledTransferService,
+    StellarService, TransactionService, WalletPaymentError, WebhookService,
+};
+
+/// Side effects that run outside the request that caused them. Each variant is
+/// stored as JSON in `jobs.payload`, tagged with `jobs.kind`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JobPayload {
+    Notify {
+        event: DomainEvent,
+    },
+    /// Delivers one user's notification of an event on the channels that
+    /// held it back during their quiet hours.
+    QuietHoursNotification {
+        user_id: String,
+        template: TemplateKind,
+        event: DomainEvent,
+    },
+    FriendbotFund {
+        public_key: String,
+    },
+    /// Submits a payment that was recorded as a pending transaction.
+    StellarPayment {
+        transaction_id: String,
+        user_id: String,
+        destination: String,
+        amount: f64,
+        currency: String,
+    },
+    /// Pays the pending rows of a payout batch.
+    PayoutBatch {
+        batch_id: String,
+    },
+    /// Sends one occurrence of a scheduled transfer.
+    ScheduledTransfer {
+        run_id: String,
+    },
+    /// Finishes paying a payment request that was held for an in-app
+    /// payment, until the payment has landed or failed.
+    PaymentRequestSettlement {
+        request_id: String,
+    },
+    /// Records a wallet payment of an invoice, until the payment has landed
+    /// or failed.
+    InvoiceSettlement {
+        invoice_id: String,
+        transaction_id: String,
+    },
+    /// Posts one event to one partner webhook endpoint.
+    WebhookDelivery {
+        delivery_id: String,
+    },
+    /// Submits a multisig approval that reached its threshold, until the
+    /// network has accepted or rejected it.
+    MultisigSubmission {
+        approval_id: String,
+    },
+}
+
+impl JobPayload {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            JobPayload::Notify { .. } => "notify",
+            JobPayload::QuietHoursNotification { .. } => "quiet_hours_notification",
+            JobPayload::FriendbotFund { .. } => "friendbot_fund",
+            JobPayload::StellarPayment { .. } => "stellar_payment",
+            JobPayload::PayoutBatch { .. } => "payout_batch",
+            JobPayload::ScheduledTransfer { .. } => "scheduled_transfer",
+            JobPayload::PaymentRequestSettlement { .. } => "payment_request_settlement",
+            JobPayload::InvoiceSettlement { .. } => "invoice_settlement",
+            JobPayload::WebhookDelivery { .. } => "webhook_delivery",
+            JobPayload::MultisigSubmission { .. } => "multisig_submission",
+        }
+    }
+}
+
+pub struct Outbox;
+
+impl Outbox {
+    /// Writes a job using `executor`, which may be a pool or an open
+    /// transaction. Enqueueing inside the business transaction means the side
+    /// effect exists if and only if the change commits.
+    pub async fn enqueue<'e, E>(executor: E, payload: &JobPayload) -> Result<String, sqlx::Error>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        Self::enqueue_at(executor, payload, chrono::Utc::now()).await
+    }
+
+    /// Like `enqueue`, for a job that shouldn't run before `run_at`.
+    pub async fn enqueue_at<'e, E>(
+        executor: E,
+        payload: &JobPayload,
+        run_at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<String, sqlx::Error>
+    where
+        E: Executor<'e, Database = Sqlite>,
+    {
+        let id = Uuid::new_v4().to_string();
+        let max_attempts: i64 = env::var("JOB_MAX_ATTEMPTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5);
+
+        sqlx::query(
+            "INSERT INTO jobs (id, kind, payload, max_attempts, run_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(&id)
+        .bind(payload.kind())
+        .bind(serde_json::to_string(payload).map_err(|e| sqlx::Error::Protocol(format!("unserializable job payload: {}", e)))?)
+        .bind(max_attempts)
+        .bind(run_at)
+        .execute(executor)
+        .await?;
+
+        Ok(id)
+    }
+}
+
+pub struct JobQueue;
+
+impl JobQueue {
+    /// Atomically leases the next due job. Jobs whose lease expired (a worker
+    /// crashed mid-run) become claimable again.
+    pub async fn claim(pool: &SqlitePool, worker_id: &str, lease: Duration) -> Result<Option<Job>, sqlx::Error> {
+        let now = chrono::Utc::now();
+        let locked_until = now + chrono::Duration::from_std(lease).unwrap_or_else(|_| chrono::Duration::seconds(60));
+
+        sqlx::query_as::<_, Job>(
+            r#"
+            UPDATE jobs
+            SET status = 'running', locked_by = ?, locked_until = ?, attempts = attempts + 1, updated_at = ?
+            WHERE id = (
+                SELECT id FROM jobs
+                WHERE (status = 'pending' AND run_at <= ?)
+                   OR (status = 'running' AND locked_until <= ?)
+                ORDER BY run_at
+                LIMIT 1
+            )
+            RETURNING *
+            "#,
+        )
+        .bind(worker_id)
+        .bind(locked_until)
+        .bind(now)
+        .bind(now)
+        .bind(now)
+        .fetch_optional(pool)
+        .await
+    }
+
+    /// Only the worker holding the job's lease can finish it: one whose lease
+    /// expired and was taken over leaves the job to the new holder.
+    pub async fn complete(pool: &SqlitePool, job: &Job) -> Result<bool, sqlx::Error> {
+        let now = chrono::Utc::now();
+        let completed = sqlx::query(
+            r#"
+            UPDATE jobs SET status = 'completed', locked_by = NULL, locked_until = NULL, completed_at = ?, updated_at = ?
+            WHERE id = ? AND status = 'running' AND locked_by = ?
+            "#,
+        )
+        .bind(now)
+        .bind(now)
+        .bind(&job.id)
+        .bind(&job.locked_by)
+        .execute(pool)
+        .await?
+        .rows_affected();
+        Ok(completed == 1)
+    }
+
+    /// Schedules a retry with exponential backoff, or moves the job to the
+    /// dead-letter state once it has used all its attempts. Returns `true`
+    /// when the job is now dead. Like `complete`, only for the lease holder.
+    pub async fn fail(pool: &SqlitePool, job: &Job, error: &str) -> Result<bool, sqlx::Error> {
+        let now = chrono::Utc::now();
+        let dead = job.attempts >= job.max_attempts;
+        let run_at = now + Self::backoff(job.attempts);
+
+        let failed = sqlx::query(
+            r#"
+            UPDATE jobs
+            SET status = ?, run_at = ?, last_error = ?, locked_by = NULL, locked_until = NULL, updated_at = ?
+            WHERE id = ? AND status = 'running' AND locked_by = ?
+            "#,
+        )
+        .bind(if dead { "dead" } else { "pending" })
+        .bind(run_at)
+        .bind(error)
+        .bind(now)
+        .bind(&job.id)
+        .bind(&job.locked_by)
+        .execute(pool)
+        .await?
+        .rows_affected();
+
+        Ok(dead && failed == 1)
+    }
+
+    /// 5s, 10s, 20s, ... capped at one hour.
+    fn backoff(attempts: i64) -> chrono::Duration {
+        let exponent = attempts.saturating_sub(1).clamp(0, 20) as u32;
+        chrono::Duration::seconds((5i64 << exponent).min(3600))
+    }
+
+    pub async fn list(pool: &SqlitePool, query: &JobListQuery) -> Result<Vec<Job>, sqlx::Error> {
+        sqlx::query_as::<_, Job>(
+            r#"
+            SELECT * FROM jobs
+            WHERE (? IS NULL OR status = ?) AND (? IS NULL OR kind = ?)
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(&query.status)
+        .bind(&query.status)
+        .bind(&query.kind)
+        .bind(&query.kind)
+        .bind(query.limit.unwrap_or(50).clamp(1, 500))
+        .fetch_all(pool)
+        .await
+    }
+
+    pub async fn get(pool: &SqlitePool, job_id: &str) -> Result<Option<Job>, sqlx::Error> {
+        sqlx::query_as::<_, Job>("SELECT * FROM jobs WHERE id = ?")
+            .bind(job_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    /// Re-queues a dead or cancelled job with a fresh set of attempts.
+    pub async fn retry(pool: &SqlitePool, job_id: &str) -> Result<Option<Job>, sqlx::Error> {
+        let now = chrono::Utc::now();
+        sqlx::query_as::<_, Job>(
+            r#"
+            UPDATE jobs
+            SET status = 'pending', attempts = 0, run_at = ?, last_error = NULL, updated_at = ?
+            WHERE id = ? AND status IN ('dead', 'cancelled')
+            RETURNING *
+            "#,
+        )
+        .bind(now)
+        .bind(now)
+        .bind(job_id)
+        .fetch_optional(pool)
+        .await
+    }
+
+    pub async fn cancel(pool: &SqlitePool, job_id: &str) -> Result<Option<Job>, sqlx::Error> {
+        sqlx::query_as::<_, Job>(
+            "UPDATE jobs SET status = 'cancelled', updated_at = ? WHERE id = ? AND status = 'pending' RETURNING *",
+        )
+        .bind(chrono::Utc::now())
+        .bind(job_id)
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+pub struct JobWorker {
+    pool: SqlitePool,
+    worker_id: String,
+    lease: Duration,
+    poll_interval: Duration,
+}
+
+impl JobWorker {
+    /// Spawns `JOB_WORKERS` (default 4) workers polling the `jobs` table.
+    pub fn spawn_pool(pool: SqlitePool) {
+        let workers: usize = env::var("JOB_WORKERS").ok().and_then(|v| v.parse().ok()).unwrap_or(4);
+
+        for n in 0..workers {
+            let worker = JobWorker {
+                pool: pool.clone(),
+                worker_id: format!("worker-{}-{}", n, &Uuid::new_v4().simple().to_string()[..8]),
+                lease: Duration::from_secs(120),
+                poll_interval: Duration::from_secs(1),
+            };
+            tokio::spawn(worker.run());
+        }
+
+        println!("🧵 Started {} background job workers", workers);
+    }
+
+    async fn run(self) {
+        loop {
+            match JobQueue::claim(&self.pool, &self.worker_id, self.lease).await {
+                Ok(Some(job)) => self.process(job).await,
+                Ok(None) => tokio::time::sleep(self.poll_interval).await,
+                Err(e) => {
+                    println!("⚠️ {} failed to claim job: {}", self.worker_id, e);
+                    tokio::time::sleep(self.poll_interval).await;
+                }
+            }
+        }
+    }
+
+    async fn process(&self, job: Job) {
+        let result = match serde_json::from_str::<JobPayload>(&job.payload) {
+            Ok(payload) => self.execute(&job.id, &payload).await.map_err(|e| (Some(payload), e)),
+            Err(e) => Err((None, format!("invalid payload: {}", e))),
+        };
+
+        match result {
+            Ok(()) => match JobQueue::complete(&self.pool, &job).await {
+                Ok(true) => {}
+                Ok(false) => println!("⚠️ {} lost the lease on job {} before it finished", self.worker_id, job.id),
+                Err(e) => println!("⚠️ Failed to mark job {} completed: {}", job.id, e),
+            },
+            Err((payload, error)) => {
+                println!("⚠️ Job {} ({}) attempt {} failed: {}", job.id, job.kind, job.attempts, error);
+                match JobQueue::fail(&self.pool, &job, &error).await {
+                    Ok(true) => {
+                        println!("💀 Job {} moved to dead-letter after {} attempts", job.id, job.attempts);
+                        if let Some(payload) = payload {
+                            self.on_dead(&payload, &error).await;
+                        }
+                    }
+                    Ok(false) => {}
+                    Err(e) => println!("⚠️ Failed to record failure of job {}: {}", job.id, e),
+                }
+            }
+        }
+    }
+
+    async fn execute(&self, job_id: &str, payload: &JobPayload) -> Result<(), String> {
+        match payload {
+            JobPayload::Notify { event } => {
+                // The job id names the event, so a retried job doesn't post
+                // it to partners twice
+                let event_id = format!("evt_{}", job_id.replace('-', ""));
+                WebhookService::publish_event(&self.pool, &event_id, event)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                NotificationDispatcher::new()
+                    .dispatch(&self.pool, event)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            JobPayload::QuietHoursNotification { user_id, template, event } => NotificationDispatcher::new()
+                .dispatch_held(&self.pool, user_id, *template, event)
+                .await
+                .map_err(|e| e.to_string()),
+            JobPayload::FriendbotFund { public_key } => {
+                let funded = StellarService::new()
+                    .fund_test_account(public_key)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                if funded {
+                    Ok(())
+                } else {
+                    Err("friendbot refused to fund the account".to_string())
+                }
+            }
+            JobPayload::StellarPayment { transaction_id, user_id, destination, amount, currency } => {
+                let wallet = sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE user_id = ?")
+                    .bind(user_id)
+                    .fetch_one(&self.pool)
+                    .await
+                    .map_err(|e| e.to_string())?;
+                let withdrawal_failed = |reason: &str| DomainEvent::WithdrawalFailed {
+                    user_id: user_id.clone(),
+                    amount: *amount,
+                    currency: currency.clone(),
+                    reason: reason.to_string(),
+                };
+
+                // The payment is signed once; a retry of this job (or a
+                // re-run after a lost lease) submits the same envelope, which
+                // can't pay twice
+                let service = TransactionService::new();
+                match service.prepare(&self.pool, transaction_id, &wallet.stellar_secret_key, destination).await {
+                    Ok(_) => {}
+                    Err(WalletPaymentError::Failed(reason)) => {
+                        TransactionService::fail(&self.pool, transaction_id, &reason, Some(withdrawal_failed(&reason)))
+                            .await
+                            .map_err(|e| e.to_string())?;
+                        return Ok(());
+                    }
+                    Err(e) => return Err(e.to_string()),
+                }
+
+                match service.submit(&self.pool, transaction_id).await.map_err(|e| e.to_string())? {
+                    PaymentOutcome::Completed(_) => Ok(()),
+                    PaymentOutcome::Failed(reason) => {
+                        TransactionService::fail(&self.pool, transaction_id, &reason, Some(withdrawal_failed(&reason)))
+                            .await
+                            .map_err(|e| e.to_string())?;
+                        Ok(())
+                    }
+                    PaymentOutcome::Unknown(e) => Err(format!("payment outcome unknown: {}", e)),
+                }
+            }
+            JobPayload::PayoutBatch { batch_id } => PayoutService::process(&self.pool, batch_id).await,
+            JobPayload::ScheduledTransfer { run_id } => ScheduledTransferService::execute(&self.pool, run_id).await,
+            JobPayload::PaymentRequestSettlement { request_id } => {
+                PaymentRequestService::settle_payment(&self.pool, request_id).await
+            }
+            JobPayload::InvoiceSettlement { invoice_id, transaction_id } => {
+                InvoiceService::settle_payment(&self.pool, invoice_id, transaction_id).await
+            }
+            JobPayload::WebhookDelivery { delivery_id } => WebhookService::deliver(&self.pool, delivery_id).await,
+            JobPayload::MultisigSubmission { approval_id } => {
+                match MultisigService::submit(&self.pool, &StellarNetwork::from_env(), approval_id).await {
+                    // A rejection is an outcome: the approval is now failed
+                    Ok(()) | Err(MultisigError::Stellar(StellarTxError::Rejected(_))) => Ok(()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Compensation for jobs that will never succeed.
+    async fn on_dead(&self, payload: &JobPayload, error: &str) {
+        if let JobPayload::PayoutBatch { batch_id } = payload {
+            if let Err(e) = PayoutService::abandon(&self.pool, batch_id, error).await {
+                println!("⚠️ Failed to close payout batch {}: {}", batch_id, e);
+            }
+        }
+        if let JobPayload::ScheduledTransfer { run_id } = payload {
+            if let Err(e) = ScheduledTransferService::abandon(&self.pool, run_id, error).await {
+                println!("⚠️ Failed to fail scheduled transfer run {}: {}", run_id, e);
+            }
+        }
+        if let JobPayload::WebhookDelivery { delivery_id } = payload {
+            if let Err(e) = WebhookService::abandon(&self.pool, delivery_id, error).await {
+                println!("⚠️ Failed to fail webhook delivery {}: {}", delivery_id, e);
+            }
+        }
+        if let JobPayload::PaymentRequestSettlement { request_id } = payload {
+            // Its payment may have landed, so it stays `processing`
+            println!("⚠️ Payment request {} left for reconciliation after its job died: {}", request_id, error);
+        }
+        if let JobPayload::InvoiceSettlement { invoice_id, .. } = payload {
+            println!("⚠️ Invoice {} left for reconciliation after its job died: {}", invoice_id, error);
+        }
+        if let JobPayload::MultisigSubmission { approval_id } = payload {
+            // It may have landed, so it stays `submitting` until the job is retried
+            println!("⚠️ Multisig approval {} left for reconciliation after its job died: {}", approval_id, error);
+        }
+        if let JobPayload::StellarPayment { transaction_id, user_id, amount, currency, .. } = payload {
+            // A payment that was signed may have been sent; it stays
+            // `submitting` until a retry finds out. Only one that never got
+            // that far is known to have failed.
+            let status: Result<Option<String>, _> = sqlx::query_scalar("SELECT status FROM transactions WHERE id = ?")
+                .bind(transaction_id)
+                .fetch_optional(&self.pool)
+                .await;
+            let result = match status {
+                Ok(Some(status)) if status == "pending" => {
+                    let event = DomainEvent::WithdrawalFailed {
+                        user_id: user_id.clone(),
+                        amount: *amount,
+                        currency: currency.clone(),
+                        reason: error.to_string(),
+                    };
+                    TransactionService::fail(&self.pool, transaction_id, error, Some(event)).await.map(|_| ())
+                }
+                Ok(_) => {
+                    println!("⚠️ Transaction {} left for reconciliation after its job died", transaction_id);
+                    Ok(())
+                }
+                Err(e) => Err(e),
+            };
+
+            if let Err(e) = result {
+                println!("⚠️ Failed to fail transaction {}: {}", transaction_id, e);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_pool;
+
+    #[tokio::test]
+    async fn test_expired_lease_can_only_be_finished_by_new_holder() {
+        let pool = memory_pool().await;
+        Outbox::enqueue(&pool, &JobPayload::PayoutBatch { batch_id: "b1".to_string() }).await.unwrap();
+
+        let first = JobQueue::claim(&pool, "worker-a", Duration::ZERO).await.unwrap().unwrap();
+        let second = JobQueue::claim(&pool, "worker-b", Duration::from_secs(60)).await.unwrap().unwrap();
+        assert_eq!(first.id, second.id);
+        assert_eq!(second.attempts, 2);
+
+        // The worker whose lease expired can neither complete nor fail it
+        assert!(!JobQueue::complete(&pool, &first).await.unwrap());
+        assert!(!JobQueue::fail(&pool, &first, "late").await.unwrap());
+        assert_eq!(JobQueue::get(&pool, &first.id).await.unwrap().unwrap().locked_by.as_deref(), Some("worker-b"));
+
+        assert!(JobQueue::complete(&pool, &second).await.unwrap());
+        assert_eq!(JobQueue::get(&pool, &first.id).await.unwrap().unwrap().status, "completed");
+    }
+
+    async fn make_due(pool: &SqlitePool) {
+        sqlx::query("UPDATE jobs SET run_at = ?").bind(chrono::Utc::now()).execute(pool).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_failures_back_off_exponentially() {
+        assert_eq!(JobQueue::backoff(1), chrono::Duration::seconds(5));
+        assert_eq!(JobQueue::backoff(2), chrono::Duration::seconds(10));
+        assert_eq!(JobQueue::backoff(4), chrono::Duration::seconds(40));
+        assert_eq!(JobQueue::backoff(11), chrono::Duration::seconds(3600));
+        assert_eq!(JobQueue::backoff(500), chrono::Duration::seconds(3600));
+
+        let pool = memory_pool().await;
+        let id = Outbox::enqueue(&pool, &JobPayload::PayoutBatch { batch_id: "b1".to_string() }).await.unwrap();
+        for attempt in 1..=3 {
+            let job = JobQueue::claim(&pool, "worker-a", Duration::from_secs(60)).await.unwrap().unwrap();
+            assert_eq!(job.attempts, attempt);
+            let failed_at = chrono::Utc::now();
+            assert!(!JobQueue::fail(&pool, &job, "boom").await.unwrap());
+
+            let job = JobQueue::get(&pool, &id).await.unwrap().unwrap();
+            assert_eq!(job.status, "pending");
+            assert_eq!(job.last_error.as_deref(), Some("boom"));
+            let delay = job.run_at - failed_at;
+            let expected = JobQueue::backoff(attempt);
+            assert!(delay >= expected && delay < expected + chrono::Duration::seconds(1), "{:?}", delay);
+
+            // Not picked up again before its retry is due
+            assert!(JobQueue::claim(&pool, "worker-a", Duration::from_secs(60)).await.unwrap().is_none());
+            make_due(&pool).await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_dead_letter_after_max_attempts() {
+        let pool = memory_pool().await;
+        let id = Outbox::enqueue(&pool, &JobPayload::PayoutBatch { batch_id: "b1".to_string() }).await.unwrap();
+        sqlx::query("UPDATE jobs SET max_attempts = 2").execute(&pool).await.unwrap();
+
+        let job = JobQueue::claim(&pool, "worker-a", Duration::from_secs(60)).await.unwrap().unwrap();
+        assert!(!JobQueue::fail(&pool, &job, "first").await.unwrap());
+        make_due(&pool).await;
+
+        let job = JobQueue::claim(&pool, "worker-a", Duration::from_secs(60)).await.unwrap().unwrap();
+        assert!(JobQueue::fail(&pool, &job, "second").await.unwrap());
+
+        let job = JobQueue::get(&pool, &id).await.unwrap().unwrap();
+        assert_eq!(job.status, "dead");
+        assert_eq!(job.attempts, 2);
+        assert_eq!(job.last_error.as_deref(), Some("second"));
+        make_due(&pool).await;
+        assert!(JobQueue::claim(&pool, "worker-a", Duration::from_secs(60)).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_crashed_workers_lease_is_reclaimed_once() {
+        let pool = memory_pool().await;
+        let id = Outbox::enqueue(&pool, &JobPayload::PayoutBatch { batch_id: "b1".to_string() }).await.unwrap();
+
+        // worker-a crashes holding a lease that has now run out
+        JobQueue::claim(&pool, "worker-a", Duration::ZERO).await.unwrap().unwrap();
+
+        let (b, c) = tokio::join!(
+            JobQueue::claim(&pool, "worker-b", Duration::from_secs(60)),
+            JobQueue::claim(&pool, "worker-c", Duration::from_secs(60)),
+        );
+        let reclaimed: Vec<Job> = [b.unwrap(), c.unwrap()].into_iter().flatten().collect();
+        assert_eq!(reclaimed.len(), 1);
+        assert_eq!(reclaimed[0].id, id);
+        assert_eq!(reclaimed[0].attempts, 2);
+        assert!(JobQueue::claim(&pool, "worker-d", Duration::from_secs(60)).await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,112 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::{Aes256Gcm, Nonce};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use std::env;
+use thiserror::Error;
+
+const NONCE_LEN: usize = 12;
+
+#[derive(Error, Debug)]
+pub enum KeyVaultError {
+    #[error("Stored secret is malformed")]
+    Malformed,
+    #[error("Stored secret failed to decrypt")]
+    Decrypt,
+}
+
+/// Encrypts secrets at rest with AES-256-GCM under `WALLET_ENCRYPTION_KEY`, a
+/// random 32-byte key given as hex or base64. Each ciphertext is bound to the
+/// id of the row it belongs to, so it can't be copied onto another row and
+/// still decrypt.
+pub struct KeyVault;
+
+impl KeyVault {
+    /// Reads `WALLET_ENCRYPTION_KEY`. Checked at startup, so a missing or
+    /// malformed key stops the server instead of locking wallets later.
+    pub fn master_key() -> Result<[u8; 32], String> {
+        let value = env::var("WALLET_ENCRYPTION_KEY").map_err(|_| "WALLET_ENCRYPTION_KEY must be set".to_string())?;
+        Self::parse_key(&value)
+            .ok_or_else(|| "WALLET_ENCRYPTION_KEY must be 32 bytes as hex or base64, e.g. from `openssl rand -hex 32`".to_string())
+    }
+
+    fn parse_key(value: &str) -> Option<[u8; 32]> {
+        let value = value.trim();
+        let bytes = hex::decode(value).or_else(|_| STANDARD.decode(value)).ok()?;
+        bytes.try_into().ok()
+    }
+
+    fn cipher() -> Aes256Gcm {
+        let key = Self::master_key().expect("WALLET_ENCRYPTION_KEY is checked at startup");
+        Aes256Gcm::new_from_slice(&key).expect("a 32-byte key is a valid AES-256 key")
+    }
+
+    /// `base64(nonce || ciphertext)`
+    pub fn encrypt(owner_id: &str, secret: &str) -> String {
+        Self::seal(&Self::cipher(), owner_id, secret)
+    }
+
+    pub fn decrypt(owner_id: &str, sealed: &str) -> Result<String, KeyVaultError> {
+        Self::open(&Self::cipher(), owner_id, sealed)
+    }
+
+    /// [`KeyVault::encrypt`] under another key, for secrets that must not
+    /// share the wallet key.
+    pub fn seal(cipher: &Aes256Gcm, owner_id: &str, secret: &str) -> String {
+        let nonce: [u8; NONCE_LEN] = rand::random();
+        let payload = Payload {
+            msg: secret.as_bytes(),
+            aad: owner_id.as_bytes(),
+        };
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), payload)
+            .expect("AES-GCM encryption of an in-memory buffer cannot fail");
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&ciphertext);
+        STANDARD.encode(sealed)
+    }
+
+    pub fn open(cipher: &Aes256Gcm, owner_id: &str, sealed: &str) -> Result<String, KeyVaultError> {
+        let sealed = STANDARD.decode(sealed).map_err(|_| KeyVaultError::Malformed)?;
+        if sealed.len() <= NONCE_LEN {
+            return Err(KeyVaultError::Malformed);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let payload = Payload {
+            msg: ciphertext,
+            aad: owner_id.as_bytes(),
+        };
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), payload)
+            .map_err(|_| KeyVaultError::Decrypt)?;
+        String::from_utf8(plaintext).map_err(|_| KeyVaultError::Malformed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key() {
+        let hex_key = "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f";
+        let key = KeyVault::parse_key(hex_key).unwrap();
+        assert_eq!(key[31], 0x1f);
+        assert_eq!(KeyVault::parse_key(&format!(" {}\n", STANDARD.encode(key))), Some(key));
+
+        for value in ["", "your-wallet-encryption-key", &hex_key[2..], &STANDARD.encode([7u8; 31])] {
+            assert!(KeyVault::parse_key(value).is_none(), "{:?} should be refused", value);
+        }
+    }
+
+    #[test]
+    fn test_sealed_secret_is_bound_to_its_row() {
+        let cipher = Aes256Gcm::new_from_slice(&[9u8; 32]).unwrap();
+        let sealed = KeyVault::seal(&cipher, "wallet-1", "SSECRET");
+        assert_ne!(KeyVault::seal(&cipher, "wallet-1", "SSECRET"), sealed);
+        assert_eq!(KeyVault::open(&cipher, "wallet-1", &sealed).unwrap(), "SSECRET");
+        assert!(matches!(KeyVault::open(&cipher, "wallet-2", &sealed), Err(KeyVaultError::Decrypt)));
+        assert!(matches!(KeyVault::open(&cipher, "wallet-1", "bm9wZQ=="), Err(KeyVaultError::Malformed)));
+    }
+}
@@ -0,0 +1,271 @@
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ed25519_dalek::SigningKey;
+use jsonwebtoken::{DecodingKey, EncodingKey};
+use serde_json::{json, Value};
+use sqlx::SqlitePool;
+use std::collections::HashMap;
+use std::sync::{Arc, OnceLock, RwLock};
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::models::JwtKey;
+use crate::services::key_vault::KeyVault;
+use crate::services::AuthService;
+
+/// PKCS#8 v1 prefix for a bare Ed25519 seed (RFC 8410).
+const ED25519_PKCS8_PREFIX: [u8; 16] = [
+    0x30, 0x2e, 0x02, 0x01, 0x00, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x04, 0x22, 0x04, 0x20,
+];
+
+/// How long a rotated-out key stays valid beyond the access token lifetime.
+const RETIRE_GRACE_MINUTES: i64 = 5;
+
+static KEYS: OnceLock<RwLock<Arc<KeySet>>> = OnceLock::new();
+
+#[derive(Default)]
+pub struct KeySet {
+    signing: Option<(String, EncodingKey)>,
+    verifying: HashMap<String, DecodingKey>,
+    jwks: Vec<Value>,
+}
+
+impl KeySet {
+    pub fn signing_key(&self) -> Option<(&str, &EncodingKey)> {
+        self.signing.as_ref().map(|(kid, key)| (kid.as_str(), key))
+    }
+
+    pub fn decoding_key(&self, kid: &str) -> Option<&DecodingKey> {
+        self.verifying.get(kid)
+    }
+}
+
+/// Process-wide set of JWT signing keys, loaded from `jwt_keys`. Every
+/// instance reloads the table periodically so a rotation done on one instance
+/// reaches the others well within the grace period.
+pub struct KeyManager;
+
+impl KeyManager {
+    /// Loads the keys, creating the first one on a fresh database, and starts
+    /// the background refresh. Call once at startup.
+    pub async fn init(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        Self::seal_plaintext_keys(pool).await?;
+        if Self::load(pool).await?.is_empty() {
+            Self::insert_key(pool).await?;
+            println!("🔑 Generated initial JWT signing key");
+        }
+        Self::reload(pool).await?;
+
+        let pool = pool.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                if let Err(e) = Self::reload(&pool).await {
+                    println!("⚠️ Failed to reload JWT keys: {}", e);
+                }
+            }
+        });
+        Ok(())
+    }
+
+    pub fn current() -> Arc<KeySet> {
+        KEYS.get()
+            .map(|keys| keys.read().expect("key set lock poisoned").clone())
+            .unwrap_or_default()
+    }
+
+    /// Public keys in JWK Set format for `/.well-known/jwks.json`.
+    pub fn jwks() -> Value {
+        json!({ "keys": Self::current().jwks })
+    }
+
+    /// Makes a new key the signer. The previous one keeps verifying until
+    /// every token it signed has expired.
+    pub async fn rotate(pool: &SqlitePool) -> Result<JwtKey, sqlx::Error> {
+        let retire_at = chrono::Utc::now() + AuthService::access_token_ttl() + chrono::Duration::minutes(RETIRE_GRACE_MINUTES);
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("UPDATE jwt_keys SET status = 'verify', retire_at = ? WHERE status = 'active'")
+            .bind(retire_at)
+            .execute(&mut *tx)
+            .await?;
+        let key = Self::insert_key(&mut *tx).await?;
+        tx.commit().await?;
+
+        Self::reload(pool).await?;
+        println!("🔑 Rotated JWT signing key, new kid {}", key.kid);
+        Ok(key)
+    }
+
+    /// Keys that are still signing or verifying.
+    pub async fn load(pool: &SqlitePool) -> Result<Vec<JwtKey>, sqlx::Error> {
+        sqlx::query_as::<_, JwtKey>(
+            r#"
+            SELECT * FROM jwt_keys
+            WHERE status = 'active' OR (status = 'verify' AND retire_at > ?)
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(chrono::Utc::now())
+        .fetch_all(pool)
+        .await
+    }
+
+    async fn reload(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let mut set = KeySet::default();
+
+        for key in Self::load(pool).await? {
+            let Some(seed) = KeyVault::decrypt(&key.kid, &key.private_key).ok().and_then(|seed| hex::decode(seed).ok()) else {
+                println!("⚠️ Skipping malformed JWT key {}", key.kid);
+                continue;
+            };
+
+            if key.status == "active" && set.signing.is_none() {
+                let der = [ED25519_PKCS8_PREFIX.as_slice(), &seed].concat();
+                set.signing = Some((key.kid.clone(), EncodingKey::from_ed_der(&der)));
+            }
+            match DecodingKey::from_ed_components(&key.public_key) {
+                Ok(decoding) => {
+                    set.verifying.insert(key.kid.clone(), decoding);
+                }
+                Err(e) => println!("⚠️ Skipping malformed JWT key {}: {}", key.kid, e),
+            }
+            set.jwks.push(json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "alg": key.algorithm,
+                "use": "sig",
+                "kid": key.kid,
+                "x": key.public_key,
+            }));
+        }
+
+        let set = Arc::new(set);
+        match KEYS.get() {
+            Some(keys) => *keys.write().expect("key set lock poisoned") = set,
+            None => {
+                let _ = KEYS.set(RwLock::new(set));
+            }
+        }
+        Ok(())
+    }
+
+    /// Private keys were stored as plain hex before they were sealed with
+    /// `KeyVault`; seals any still left that way.
+    async fn seal_plaintext_keys(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let keys = sqlx::query_as::<_, JwtKey>("SELECT * FROM jwt_keys WHERE length(private_key) = 64")
+            .fetch_all(pool)
+            .await?;
+        for key in keys {
+            sqlx::query("UPDATE jwt_keys SET private_key = ? WHERE kid = ? AND private_key = ?")
+                .bind(KeyVault::encrypt(&key.kid, &key.private_key))
+                .bind(&key.kid)
+                .bind(&key.private_key)
+                .execute(pool)
+                .await?;
+        }
+        Ok(())
+    }
+
+    async fn insert_key<'e, E>(executor: E) -> Result<JwtKey, sqlx::Error>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let signing_key = SigningKey::from_bytes(&rand::random::<[u8; 32]>());
+        let kid = Uuid::new_v4().simple().to_string();
+
+        sqlx::query_as::<_, JwtKey>(
+            r#"
+            INSERT INTO jwt_keys (kid, algorithm, private_key, public_key, status, created_at)
+            VALUES (?, 'EdDSA', ?, ?, 'active', ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&kid)
+        .bind(KeyVault::encrypt(&kid, &hex::encode(signing_key.to_bytes())))
+        .bind(URL_SAFE_NO_PAD.encode(signing_key.verifying_key().as_bytes()))
+        .bind(chrono::Utc::now())
+        .fetch_one(executor)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jsonwebtoken::{encode, Algorithm, Header};
+
+    use super::*;
+    use crate::models::Role;
+    use crate::services::Claims;
+    use crate::test_support::{jwt_keys, memory_pool};
+
+    #[tokio::test]
+    async fn test_private_keys_are_sealed() {
+        let pool = memory_pool().await;
+        let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+        sqlx::query("INSERT INTO jwt_keys (kid, private_key, public_key, status, created_at) VALUES ('old', ?, ?, 'active', ?)")
+            .bind(hex::encode(signing_key.to_bytes()))
+            .bind(URL_SAFE_NO_PAD.encode(signing_key.verifying_key().as_bytes()))
+            .bind(chrono::Utc::now())
+            .execute(&pool)
+            .await
+            .unwrap();
+        let _keys = jwt_keys(&pool).await;
+
+        // A key stored before sealing is sealed in place and still signs
+        let key = KeyManager::load(&pool).await.unwrap().remove(0);
+        assert_ne!(key.private_key, hex::encode(signing_key.to_bytes()));
+        assert_eq!(KeyVault::decrypt("old", &key.private_key).unwrap(), hex::encode(signing_key.to_bytes()));
+        assert_eq!(KeyManager::current().signing_key().unwrap().0, "old");
+
+        let rotated = KeyManager::rotate(&pool).await.unwrap();
+        assert_eq!(KeyVault::decrypt(&rotated.kid, &rotated.private_key).unwrap().len(), 64);
+        // Sealed under its own kid, so it can't be moved onto another row
+        assert!(KeyVault::decrypt("old", &rotated.private_key).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_tokens_verify_until_their_key_retires() {
+        let pool = memory_pool().await;
+        let _keys = jwt_keys(&pool).await;
+
+        let token = AuthService::generate_jwt("u1", "s1", Role::Customer).unwrap();
+        let claims = AuthService::verify_jwt(&token).unwrap();
+        assert_eq!((claims.sub.as_str(), claims.sid.as_str()), ("u1", "s1"));
+
+        // Rotated out, the old key still verifies during the grace period
+        let old_kid = KeyManager::current().signing_key().unwrap().0.to_string();
+        let new_key = KeyManager::rotate(&pool).await.unwrap();
+        assert_eq!(AuthService::verify_jwt(&token).unwrap().sub, "u1");
+        let fresh = AuthService::generate_jwt("u1", "s1", Role::Customer).unwrap();
+        assert_eq!(jsonwebtoken::decode_header(&fresh).unwrap().kid, Some(new_key.kid));
+
+        // Once retired it doesn't
+        sqlx::query("UPDATE jwt_keys SET retire_at = ? WHERE kid = ?")
+            .bind(chrono::Utc::now() - chrono::Duration::seconds(1))
+            .bind(&old_kid)
+            .execute(&pool)
+            .await
+            .unwrap();
+        KeyManager::reload(&pool).await.unwrap();
+        assert!(AuthService::verify_jwt(&token).is_err());
+        assert!(AuthService::verify_jwt(&fresh).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_unknown_kid_is_rejected() {
+        let pool = memory_pool().await;
+        let _keys = jwt_keys(&pool).await;
+        let token = AuthService::generate_jwt("u1", "s1", Role::Customer).unwrap();
+        let claims: Claims = AuthService::verify_jwt(&token).unwrap();
+
+        let keys = KeyManager::current();
+        let (_, key) = keys.signing_key().unwrap();
+        for kid in [Some("unknown".to_string()), None] {
+            let mut header = Header::new(Algorithm::EdDSA);
+            header.kid = kid;
+            let forged = encode(&header, &claims, key).unwrap();
+            assert!(AuthService::verify_jwt(&forged).is_err());
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use sqlx::SqliteConnection;
+use uuid::Uuid;
+
+/// Wallet balance changes booked against the transaction that caused them.
+/// Reversals undo these entries and nothing else, so a transaction that never
+/// moved a NovaPay balance cannot be reversed into one.
+pub struct Ledger;
+
+impl Ledger {
+    /// Adds `amount` (negative to debit) to the user's wallet balance and
+    /// records it against `transaction_id`. Returns the new balance, or
+    /// `None` without changing anything if the user has no wallet or a debit
+    /// would overdraw it.
+    pub async fn post(
+        conn: &mut SqliteConnection,
+        transaction_id: &str,
+        user_id: &str,
+        amount: f64,
+    ) -> Result<Option<f64>, sqlx::Error> {
+        let balance: Option<f64> = sqlx::query_scalar(
+            "UPDATE wallets SET balance = balance + ? WHERE user_id = ? AND (? >= 0 OR balance + ? >= 0) RETURNING balance",
+        )
+        .bind(amount)
+        .bind(user_id)
+        .bind(amount)
+        .bind(amount)
+        .fetch_optional(&mut *conn)
+        .await?;
+        if balance.is_none() {
+            return Ok(None);
+        }
+
+        sqlx::query("INSERT INTO ledger_entries (id, transaction_id, user_id, amount, created_at) VALUES (?, ?, ?, ?, ?)")
+            .bind(Uuid::new_v4().to_string())
+            .bind(transaction_id)
+            .bind(user_id)
+            .bind(amount)
+            .bind(chrono::Utc::now())
+            .execute(&mut *conn)
+            .await?;
+        Ok(balance)
+    }
+
+    /// The `(user_id, amount)` entries booked against a transaction.
+    pub async fn entries(conn: &mut SqliteConnection, transaction_id: &str) -> Result<Vec<(String, f64)>, sqlx::Error> {
+        sqlx::query_as("SELECT user_id, amount FROM ledger_entries WHERE transaction_id = ? ORDER BY created_at, id")
+            .bind(transaction_id)
+            .fetch_all(conn)
+            .await
+    }
+}
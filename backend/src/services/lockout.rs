@@ -0,0 +1,155 @@
+use sqlx::SqlitePool;
+use std::env;
+
+/// Progressive account lockout for password and SMS code logins. Every
+/// `LOGIN_LOCKOUT_THRESHOLD` consecutive failures lock the account, first for
+/// `LOGIN_LOCKOUT_BASE_SECS` and twice as long each further time, up to
+/// `LOGIN_LOCKOUT_MAX_SECS`. A successful login or an admin unlock clears it.
+pub struct LoginLockout;
+
+impl LoginLockout {
+    fn env_i64(name: &str, default: i64) -> i64 {
+        env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    /// Returns when the lock ends if the account is currently locked.
+    pub async fn locked_until(pool: &SqlitePool, user_id: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>, sqlx::Error> {
+        let locked_until: Option<Option<chrono::DateTime<chrono::Utc>>> =
+            sqlx::query_scalar("SELECT locked_until FROM login_lockouts WHERE user_id = ?")
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await?;
+        let now = chrono::Utc::now();
+        Ok(locked_until.flatten().filter(|until| *until > now))
+    }
+
+    /// Counts a wrong password or sign-in code. Returns when the lock ends if this failure
+    /// locked the account.
+    pub async fn record_failure(pool: &SqlitePool, user_id: &str) -> Result<Option<chrono::DateTime<chrono::Utc>>, sqlx::Error> {
+        let now = chrono::Utc::now();
+        let threshold = Self::env_i64("LOGIN_LOCKOUT_THRESHOLD", 5).max(1);
+        let base_secs = Self::env_i64("LOGIN_LOCKOUT_BASE_SECS", 60);
+        let max_secs = Self::env_i64("LOGIN_LOCKOUT_MAX_SECS", 86_400);
+        // Failures older than this no longer count towards the next lock
+        let window = chrono::Duration::hours(Self::env_i64("LOGIN_FAILURE_WINDOW_HOURS", 24));
+
+        // The count is bumped in the database so concurrent failures all
+        // register; failures outside the window start a fresh count
+        let (failed_attempts, lockouts): (i64, i64) = sqlx::query_as(
+            r#"
+            INSERT INTO login_lockouts (user_id, failed_attempts, lockouts, locked_until, last_failed_at)
+            VALUES (?, 1, 0, NULL, ?)
+            ON CONFLICT (user_id) DO UPDATE SET
+                failed_attempts = CASE WHEN login_lockouts.last_failed_at > ?
+                    THEN login_lockouts.failed_attempts + 1 ELSE 1 END,
+                lockouts = CASE WHEN login_lockouts.last_failed_at > ?
+                    THEN login_lockouts.lockouts ELSE 0 END,
+                last_failed_at = excluded.last_failed_at
+            RETURNING failed_attempts, lockouts
+            "#,
+        )
+        .bind(user_id)
+        .bind(now)
+        .bind(now - window)
+        .bind(now - window)
+        .fetch_one(pool)
+        .await?;
+
+        if failed_attempts < threshold {
+            return Ok(None);
+        }
+
+        let exponent = lockouts.clamp(0, 20) as u32;
+        let secs = base_secs.saturating_mul(1i64 << exponent).min(max_secs);
+        let until = now + chrono::Duration::seconds(secs);
+
+        // Only the request that still sees the full count takes the lock, so
+        // racing failures cannot lock twice or skip a doubling
+        let locked = sqlx::query(
+            r#"
+            UPDATE login_lockouts
+            SET failed_attempts = 0, lockouts = lockouts + 1, locked_until = ?
+            WHERE user_id = ? AND failed_attempts >= ? AND lockouts = ?
+            "#,
+        )
+        .bind(until)
+        .bind(user_id)
+        .bind(threshold)
+        .bind(lockouts)
+        .execute(pool)
+        .await?;
+        if locked.rows_affected() == 0 {
+            return Ok(None);
+        }
+
+        println!("🔒 Login locked for user {} until {} (lockout #{})", user_id, until, lockouts + 1);
+        Ok(Some(until))
+    }
+
+    pub async fn record_success(pool: &SqlitePool, user_id: &str) -> Result<(), sqlx::Error> {
+        Self::unlock(pool, user_id).await.map(|_| ())
+    }
+
+    /// Clears failures and any active lock. Returns `false` if there was
+    /// nothing to clear.
+    pub async fn unlock(pool: &SqlitePool, user_id: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query("DELETE FROM login_lockouts WHERE user_id = ?")
+            .bind(user_id)
+            .execute(pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool};
+
+    #[tokio::test]
+    async fn test_failures_lock_with_doubling() {
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+
+        for _ in 0..4 {
+            assert!(LoginLockout::record_failure(&pool, "u1").await.unwrap().is_none());
+        }
+        assert!(LoginLockout::locked_until(&pool, "u1").await.unwrap().is_none());
+
+        let first = LoginLockout::record_failure(&pool, "u1").await.unwrap().unwrap();
+        let secs = (first - chrono::Utc::now()).num_seconds();
+        assert!((55..=60).contains(&secs), "first lock lasted {}s", secs);
+        assert_eq!(LoginLockout::locked_until(&pool, "u1").await.unwrap(), Some(first));
+
+        for _ in 0..4 {
+            assert!(LoginLockout::record_failure(&pool, "u1").await.unwrap().is_none());
+        }
+        let second = LoginLockout::record_failure(&pool, "u1").await.unwrap().unwrap();
+        let secs = (second - chrono::Utc::now()).num_seconds();
+        assert!((115..=120).contains(&secs), "second lock lasted {}s", secs);
+
+        LoginLockout::record_success(&pool, "u1").await.unwrap();
+        assert!(LoginLockout::locked_until(&pool, "u1").await.unwrap().is_none());
+        assert!(!LoginLockout::unlock(&pool, "u1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_failures_all_count() {
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+
+        let results = tokio::join!(
+            LoginLockout::record_failure(&pool, "u1"),
+            LoginLockout::record_failure(&pool, "u1"),
+            LoginLockout::record_failure(&pool, "u1"),
+            LoginLockout::record_failure(&pool, "u1"),
+            LoginLockout::record_failure(&pool, "u1"),
+        );
+        let locks = [results.0, results.1, results.2, results.3, results.4]
+            .into_iter()
+            .filter(|r| r.as_ref().unwrap().is_some())
+            .count();
+        assert_eq!(locks, 1);
+        assert!(LoginLockout::locked_until(&pool, "u1").await.unwrap().is_some());
+    }
+}
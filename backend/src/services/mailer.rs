@@ -0,0 +1,242 @@
+use async_trait::async_trait;
+use lettre::message::Mailbox;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::env;
+use std::path::PathBuf;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum MailerError {
+    #[error("Invalid address: {0}")]
+    InvalidAddress(String),
+    #[error("Failed to build email: {0}")]
+    Build(String),
+    #[error("Failed to deliver email: {0}")]
+    Delivery(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct EmailMessage {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, message: &EmailMessage) -> Result<(), MailerError>;
+}
+
+/// Development mailer that only logs the message.
+pub struct ConsoleMailer;
+
+#[async_trait]
+impl Mailer for ConsoleMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<(), MailerError> {
+        println!("📧 Email to {}: {} — {}", message.to, message.subject, message.body);
+        Ok(())
+    }
+}
+
+/// Writes each message to its own file under a directory, so tests and local
+/// development can read reset and verification links back out.
+pub struct FileMailer {
+    dir: PathBuf,
+}
+
+impl FileMailer {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+#[async_trait]
+impl Mailer for FileMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<(), MailerError> {
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|e| MailerError::Delivery(e.to_string()))?;
+
+        let file_name = format!(
+            "{}_{}.eml",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%.6f"),
+            message.to.replace(|c: char| !c.is_ascii_alphanumeric() && c != '@' && c != '.', "_"),
+        );
+        let contents = format!("To: {}\nSubject: {}\n\n{}\n", message.to, message.subject, message.body);
+
+        tokio::fs::write(self.dir.join(file_name), contents)
+            .await
+            .map_err(|e| MailerError::Delivery(e.to_string()))
+    }
+}
+
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+impl SmtpMailer {
+    /// Reads `SMTP_HOST`, `SMTP_PORT`, `SMTP_USERNAME`, `SMTP_PASSWORD`,
+    /// `SMTP_FROM` and `SMTP_TLS` (`starttls`, `tls` or `none`). `none` is
+    /// meant for a local SMTP sink during development and tests.
+    pub fn from_env() -> Result<Self, MailerError> {
+        let host = env::var("SMTP_HOST").map_err(|_| MailerError::Build("SMTP_HOST must be set".to_string()))?;
+        let from = env::var("SMTP_FROM").unwrap_or_else(|_| "NovaPay <no-reply@novapay.app>".to_string());
+        let tls = env::var("SMTP_TLS").unwrap_or_else(|_| "starttls".to_string());
+        let port = env::var("SMTP_PORT").ok().and_then(|p| p.parse::<u16>().ok());
+
+        let mut builder = match tls.as_str() {
+            "none" => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&host),
+            "tls" => AsyncSmtpTransport::<Tokio1Executor>::relay(&host)
+                .map_err(|e| MailerError::Build(e.to_string()))?,
+            _ => AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&host)
+                .map_err(|e| MailerError::Build(e.to_string()))?,
+        };
+
+        if let Some(port) = port {
+            builder = builder.port(port);
+        }
+        if let (Ok(username), Ok(password)) = (env::var("SMTP_USERNAME"), env::var("SMTP_PASSWORD")) {
+            builder = builder.credentials(Credentials::new(username, password));
+        }
+
+        Ok(Self {
+            transport: builder.build(),
+            from: from.parse().map_err(|_| MailerError::InvalidAddress(from.clone()))?,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    async fn send(&self, message: &EmailMessage) -> Result<(), MailerError> {
+        let to: Mailbox = message
+            .to
+            .parse()
+            .map_err(|_| MailerError::InvalidAddress(message.to.clone()))?;
+
+        let email = Message::builder()
+            .from(self.from.clone())
+            .to(to)
+            .subject(&message.subject)
+            .body(message.body.clone())
+            .map_err(|e| MailerError::Build(e.to_string()))?;
+
+        self.transport
+            .send(email)
+            .await
+            .map_err(|e| MailerError::Delivery(e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Picks the file mailer when `MAIL_FILE_DIR` is set, the SMTP mailer when
+/// `SMTP_HOST` is configured, and falls back to logging otherwise.
+pub fn mailer_from_env() -> Box<dyn Mailer> {
+    if let Some(dir) = env::var("MAIL_FILE_DIR").ok().filter(|dir| !dir.is_empty()) {
+        return Box::new(FileMailer::new(dir));
+    }
+    if env::var("SMTP_HOST").is_err() {
+        return Box::new(ConsoleMailer);
+    }
+
+    match SmtpMailer::from_env() {
+        Ok(mailer) => Box::new(mailer),
+        Err(e) => {
+            println!("⚠️ SMTP mailer unavailable, logging emails instead: {}", e);
+            Box::new(ConsoleMailer)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    /// A minimal SMTP server that accepts every message and keeps the
+    /// commands and message data it was sent. Returns its port.
+    async fn smtp_sink(received: Arc<Mutex<Vec<String>>>) -> u16 {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let received = received.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+                    let mut in_data = false;
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        received.lock().unwrap().push(line.clone());
+                        let reply: &[u8] = if in_data {
+                            if line != "." {
+                                continue;
+                            }
+                            in_data = false;
+                            b"250 queued\r\n"
+                        } else if line.starts_with("EHLO") {
+                            b"250-sink\r\n250 8BITMIME\r\n"
+                        } else if line == "DATA" {
+                            in_data = true;
+                            b"354 go ahead\r\n"
+                        } else if line == "QUIT" {
+                            writer.write_all(b"221 bye\r\n").await.unwrap();
+                            break;
+                        } else {
+                            b"250 ok\r\n"
+                        };
+                        writer.write_all(reply).await.unwrap();
+                    }
+                });
+            }
+        });
+        port
+    }
+
+    #[tokio::test]
+    async fn test_smtp_mailer_delivers_to_sink() {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let port = smtp_sink(received.clone()).await;
+        let mailer = SmtpMailer {
+            transport: AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous("127.0.0.1")
+                .port(port)
+                .build(),
+            from: "NovaPay <no-reply@novapay.app>".parse().unwrap(),
+        };
+
+        mailer
+            .send(&EmailMessage {
+                to: "amina@example.com".to_string(),
+                subject: "Money received".to_string(),
+                body: "You received KES 1,500.00 from Baraka.".to_string(),
+            })
+            .await
+            .unwrap();
+
+        let received = received.lock().unwrap().clone();
+        assert!(received.contains(&"MAIL FROM:<no-reply@novapay.app>".to_string()));
+        assert!(received.contains(&"RCPT TO:<amina@example.com>".to_string()));
+        assert!(received.contains(&"Subject: Money received".to_string()));
+        assert!(received.contains(&"To: amina@example.com".to_string()));
+        assert!(received.contains(&"You received KES 1,500.00 from Baraka.".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_smtp_mailer_rejects_bad_address() {
+        let mailer = SmtpMailer {
+            transport: AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous("127.0.0.1").port(1).build(),
+            from: "NovaPay <no-reply@novapay.app>".parse().unwrap(),
+        };
+        let message = EmailMessage {
+            to: "not an address".to_string(),
+            subject: "x".to_string(),
+            body: "x".to_string(),
+        };
+        assert!(matches!(mailer.send(&message).await, Err(MailerError::InvalidAddress(_))));
+    }
+}
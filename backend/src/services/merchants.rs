@@ -0,0 +1,482 @@
+use aes_gcm::{Aes256Gcm, KeyInit};
+use ipnet::IpNet;
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::env;
+use std::net::IpAddr;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{ApiKey, ApiScope, CreateApiKeyRequest, CreateMerchantRequest, Merchant, Role, UpdateMerchantRequest};
+use crate::services::key_vault::KeyVault;
+use crate::services::signing::{hmac_sha256_hex, required_secret, sha256_hex, verify_hmac_sha256_hex};
+
+#[derive(Error, Debug)]
+pub enum MerchantError {
+    #[error("Not found")]
+    NotFound,
+    #[error("This user already has a merchant account")]
+    AlreadyExists,
+    #[error("Key has been revoked or rotated out")]
+    KeyInactive,
+    #[error("Invalid IP allowlist entry: {0}")]
+    InvalidAllowlist(String),
+    #[error("Unknown key or bad signature")]
+    BadSignature,
+    #[error("Timestamp outside the allowed window")]
+    StaleTimestamp,
+    #[error("Request from {0} is not in the key's allowlist")]
+    IpNotAllowed(String),
+    #[error("A payout with this idempotency key is already in progress")]
+    IdempotencyInProgress,
+    #[error("Idempotency key was already used for a different payout")]
+    IdempotencyMismatch,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// The merchant behind an authenticated API-key request.
+#[derive(Debug, Clone)]
+pub struct ApiKeyContext {
+    pub key_id: String,
+    pub merchant_id: String,
+    pub owner_user_id: String,
+    pub owner_role: Role,
+    pub scopes: Vec<ApiScope>,
+}
+
+impl ApiKeyContext {
+    pub fn has(&self, scope: ApiScope) -> bool {
+        self.scopes.contains(&scope)
+    }
+}
+
+/// The parts of an incoming request covered by its signature.
+pub struct SignedRequest<'a> {
+    pub key_id: &'a str,
+    pub timestamp: &'a str,
+    pub signature: &'a str,
+    pub method: &'a str,
+    pub path_and_query: &'a str,
+    pub body: &'a [u8],
+    pub ip: &'a str,
+}
+
+impl SignedRequest<'_> {
+    /// `<METHOD>\n<path?query>\n<timestamp>\n<hex SHA-256 of body>`
+    pub fn canonical(&self) -> String {
+        format!(
+            "{}\n{}\n{}\n{}",
+            self.method.to_uppercase(),
+            self.path_and_query,
+            self.timestamp,
+            sha256_hex(self.body)
+        )
+    }
+}
+
+/// Business accounts and their API keys. Each key gets its own random secret,
+/// kept only sealed under a key derived from `API_KEY_SECRET`: verifying an
+/// HMAC signature needs the secret itself, so it can't be reduced to a hash.
+pub struct MerchantService;
+
+impl MerchantService {
+    fn env_i64(name: &str, default: i64) -> i64 {
+        env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default)
+    }
+
+    fn secret_cipher() -> Aes256Gcm {
+        let master = required_secret("API_KEY_SECRET").expect("API_KEY_SECRET is checked at startup");
+        let key = hex::decode(hmac_sha256_hex(master.as_bytes(), b"api-key-secrets")).expect("HMAC output is hex");
+        Aes256Gcm::new_from_slice(&key).expect("HMAC-SHA256 output is a valid AES-256 key")
+    }
+
+    pub async fn create(pool: &SqlitePool, user_id: &str, request: &CreateMerchantRequest) -> Result<Merchant, MerchantError> {
+        if Self::for_user(pool, user_id).await?.is_some() {
+            return Err(MerchantError::AlreadyExists);
+        }
+
+        let now = chrono::Utc::now();
+        let merchant = sqlx::query_as::<_, Merchant>(
+            r#"
+            INSERT INTO merchants (id, owner_user_id, business_name, website, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(request.business_name.trim())
+        .bind(&request.website)
+        .bind(now)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        println!("🏪 Merchant {} created for user {}", merchant.id, user_id);
+        Ok(merchant)
+    }
+
+    pub async fn update(
+        pool: &SqlitePool,
+        merchant_id: &str,
+        request: &UpdateMerchantRequest,
+    ) -> Result<Merchant, MerchantError> {
+        sqlx::query_as::<_, Merchant>(
+            r#"
+            UPDATE merchants
+            SET business_name = COALESCE(?, business_name),
+                website = COALESCE(?, website),
+                updated_at = ?
+            WHERE id = ?
+            RETURNING *
+            "#,
+        )
+        .bind(request.business_name.as_deref().map(str::trim))
+        .bind(&request.website)
+        .bind(chrono::Utc::now())
+        .bind(merchant_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(MerchantError::NotFound)
+    }
+
+    pub async fn for_user(pool: &SqlitePool, user_id: &str) -> Result<Option<Merchant>, sqlx::Error> {
+        sqlx::query_as::<_, Merchant>("SELECT * FROM merchants WHERE owner_user_id = ?")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn list_keys(pool: &SqlitePool, merchant_id: &str) -> Result<Vec<ApiKey>, sqlx::Error> {
+        sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys WHERE merchant_id = ? ORDER BY created_at DESC")
+            .bind(merchant_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Issues a key and returns it with its secret, which is not shown again.
+    pub async fn create_key(
+        pool: &SqlitePool,
+        merchant_id: &str,
+        request: &CreateApiKeyRequest,
+    ) -> Result<(ApiKey, String), MerchantError> {
+        let allowlist = Self::normalize_allowlist(&request.ip_allowlist)?;
+        let mut scopes: Vec<&str> = request.scopes.iter().map(ApiScope::as_str).collect();
+        scopes.sort_unstable();
+        scopes.dedup();
+
+        Self::insert_key(pool, merchant_id, request.name.trim(), &scopes.join(","), allowlist.as_deref()).await
+    }
+
+    /// Replaces a key with a new one carrying the same name, scopes and
+    /// allowlist. The old key keeps working for `API_KEY_ROTATION_GRACE_HOURS`
+    /// so integrations can switch over without downtime.
+    pub async fn rotate_key(pool: &SqlitePool, merchant_id: &str, key_id: &str) -> Result<(ApiKey, String), MerchantError> {
+        let old = Self::find_key(pool, merchant_id, key_id).await?;
+        let now = chrono::Utc::now();
+        if old.revoked_at.is_some() || old.rotated_to.is_some() || old.expires_at.is_some_and(|at| at <= now) {
+            return Err(MerchantError::KeyInactive);
+        }
+
+        let (key, secret) =
+            Self::insert_key(pool, merchant_id, &old.name, &old.scopes, old.ip_allowlist.as_deref()).await?;
+
+        let grace = chrono::Duration::hours(Self::env_i64("API_KEY_ROTATION_GRACE_HOURS", 24));
+        sqlx::query("UPDATE api_keys SET rotated_to = ?, expires_at = ? WHERE id = ?")
+            .bind(&key.id)
+            .bind(now + grace)
+            .bind(&old.id)
+            .execute(pool)
+            .await?;
+
+        println!("🔁 API key {} rotated to {}", old.id, key.id);
+        Ok((key, secret))
+    }
+
+    pub async fn revoke_key(pool: &SqlitePool, merchant_id: &str, key_id: &str) -> Result<ApiKey, MerchantError> {
+        sqlx::query_as::<_, ApiKey>(
+            "UPDATE api_keys SET revoked_at = COALESCE(revoked_at, ?) WHERE id = ? AND merchant_id = ? RETURNING *",
+        )
+        .bind(chrono::Utc::now())
+        .bind(key_id)
+        .bind(merchant_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(MerchantError::NotFound)
+    }
+
+    /// Verifies a signed request: the key must be live, the timestamp within
+    /// `API_SIGNATURE_TOLERANCE_SECS` of now, the signature must match and the
+    /// caller must be on the key's allowlist. Records the key as used.
+    pub async fn authenticate(pool: &SqlitePool, request: &SignedRequest<'_>) -> Result<ApiKeyContext, MerchantError> {
+        let now = chrono::Utc::now();
+        let tolerance = Self::env_i64("API_SIGNATURE_TOLERANCE_SECS", 300);
+        let timestamp: i64 = request.timestamp.parse().map_err(|_| MerchantError::StaleTimestamp)?;
+        if (now.timestamp() - timestamp).abs() > tolerance {
+            return Err(MerchantError::StaleTimestamp);
+        }
+
+        let key = sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys WHERE id = ?")
+            .bind(request.key_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(MerchantError::BadSignature)?;
+
+        // Keys issued before secrets were stored have none and must be rotated
+        let secret = key
+            .secret_sealed
+            .as_deref()
+            .and_then(|sealed| KeyVault::open(&Self::secret_cipher(), &key.id, sealed).ok())
+            .ok_or(MerchantError::BadSignature)?;
+        if !verify_hmac_sha256_hex(secret.as_bytes(), request.canonical().as_bytes(), request.signature) {
+            return Err(MerchantError::BadSignature);
+        }
+        if key.revoked_at.is_some() || key.expires_at.is_some_and(|at| at <= now) {
+            return Err(MerchantError::KeyInactive);
+        }
+        if let Some(allowlist) = &key.ip_allowlist {
+            let ip: IpAddr = request.ip.parse().map_err(|_| MerchantError::IpNotAllowed(request.ip.to_string()))?;
+            let allowed = allowlist
+                .split(',')
+                .filter_map(|entry| entry.parse::<IpNet>().ok())
+                .any(|net| net.contains(&ip));
+            if !allowed {
+                return Err(MerchantError::IpNotAllowed(request.ip.to_string()));
+            }
+        }
+
+        let (owner_user_id, owner_role): (String, String) = sqlx::query_as(
+            "SELECT u.id, u.role FROM merchants m JOIN users u ON u.id = m.owner_user_id WHERE m.id = ?",
+        )
+        .bind(&key.merchant_id)
+        .fetch_one(pool)
+        .await?;
+
+        sqlx::query("UPDATE api_keys SET last_used_at = ?, last_used_ip = ? WHERE id = ?")
+            .bind(now)
+            .bind(request.ip)
+            .bind(&key.id)
+            .execute(pool)
+            .await?;
+
+        Ok(ApiKeyContext {
+            scopes: ApiScope::parse_list(&key.scopes),
+            key_id: key.id,
+            merchant_id: key.merchant_id,
+            owner_user_id,
+            owner_role: Role::from_str_or_customer(&owner_role),
+        })
+    }
+
+    /// Claims `idempotency_key` for a payout. Returns the stored response if
+    /// the key already paid out the same request, so a retry answers the
+    /// same without paying again.
+    pub async fn begin_payout(
+        pool: &SqlitePool,
+        merchant_id: &str,
+        idempotency_key: &str,
+        request_hash: &str,
+    ) -> Result<Option<Value>, MerchantError> {
+        let claimed = sqlx::query(
+            r#"
+            INSERT INTO merchant_payouts (merchant_id, idempotency_key, request_hash, created_at)
+            VALUES (?, ?, ?, ?)
+            ON CONFLICT (merchant_id, idempotency_key) DO NOTHING
+            "#,
+        )
+        .bind(merchant_id)
+        .bind(idempotency_key)
+        .bind(request_hash)
+        .bind(chrono::Utc::now())
+        .execute(pool)
+        .await?
+        .rows_affected();
+        if claimed == 1 {
+            return Ok(None);
+        }
+
+        let (stored_hash, response): (String, Option<String>) = sqlx::query_as(
+            "SELECT request_hash, response FROM merchant_payouts WHERE merchant_id = ? AND idempotency_key = ?",
+        )
+        .bind(merchant_id)
+        .bind(idempotency_key)
+        .fetch_one(pool)
+        .await?;
+        if stored_hash != request_hash {
+            return Err(MerchantError::IdempotencyMismatch);
+        }
+        response
+            .and_then(|response| serde_json::from_str(&response).ok())
+            .map(Some)
+            .ok_or(MerchantError::IdempotencyInProgress)
+    }
+
+    /// Stores the response of a payout that went through.
+    pub async fn finish_payout(
+        pool: &SqlitePool,
+        merchant_id: &str,
+        idempotency_key: &str,
+        response: &Value,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE merchant_payouts SET response = ?, completed_at = ? WHERE merchant_id = ? AND idempotency_key = ?",
+        )
+        .bind(response.to_string())
+        .bind(chrono::Utc::now())
+        .bind(merchant_id)
+        .bind(idempotency_key)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Frees the key of a payout that was refused before any money moved, so
+    /// the caller can retry it.
+    pub async fn release_payout(pool: &SqlitePool, merchant_id: &str, idempotency_key: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM merchant_payouts WHERE merchant_id = ? AND idempotency_key = ? AND response IS NULL")
+            .bind(merchant_id)
+            .bind(idempotency_key)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_key(pool: &SqlitePool, merchant_id: &str, key_id: &str) -> Result<ApiKey, MerchantError> {
+        sqlx::query_as::<_, ApiKey>("SELECT * FROM api_keys WHERE id = ? AND merchant_id = ?")
+            .bind(key_id)
+            .bind(merchant_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(MerchantError::NotFound)
+    }
+
+    async fn insert_key(
+        pool: &SqlitePool,
+        merchant_id: &str,
+        name: &str,
+        scopes: &str,
+        ip_allowlist: Option<&str>,
+    ) -> Result<(ApiKey, String), MerchantError> {
+        let id = format!("npk_{}", Uuid::new_v4().simple());
+        let secret = format!("nps_{}", hex::encode(rand::random::<[u8; 32]>()));
+        let key = sqlx::query_as::<_, ApiKey>(
+            r#"
+            INSERT INTO api_keys (id, merchant_id, name, scopes, ip_allowlist, secret_sealed, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(merchant_id)
+        .bind(name)
+        .bind(scopes)
+        .bind(ip_allowlist)
+        .bind(KeyVault::seal(&Self::secret_cipher(), &id, &secret))
+        .bind(chrono::Utc::now())
+        .fetch_one(pool)
+        .await?;
+
+        Ok((key, secret))
+    }
+
+    /// Parses each entry as an IP or CIDR range and stores them in canonical
+    /// form. A bare address becomes a single-host range.
+    fn normalize_allowlist(entries: &[String]) -> Result<Option<String>, MerchantError> {
+        if entries.is_empty() {
+            return Ok(None);
+        }
+        let nets = entries
+            .iter()
+            .map(|entry| {
+                let entry = entry.trim();
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map(|net| net.trunc().to_string())
+                    .map_err(|_| MerchantError::InvalidAllowlist(entry.to_string()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(nets.join(",")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool};
+    use serde_json::json;
+
+    async fn merchant(pool: &SqlitePool) -> Merchant {
+        env::set_var("API_KEY_SECRET", "test-api-key-secret-at-least-32-characters");
+        insert_user(pool, "owner", "owner@example.com").await;
+        let request = CreateMerchantRequest { business_name: "Mama Mboga".to_string(), website: None };
+        MerchantService::create(pool, "owner", &request).await.unwrap()
+    }
+
+    fn signed<'a>(key_id: &'a str, timestamp: &'a str, signature: &'a str, body: &'a [u8]) -> SignedRequest<'a> {
+        SignedRequest {
+            key_id,
+            timestamp,
+            signature,
+            method: "POST",
+            path_and_query: "/merchant/v1/payouts",
+            body,
+            ip: "203.0.113.7",
+        }
+    }
+
+    #[tokio::test]
+    async fn test_key_secrets_are_random_and_verify() {
+        let pool = memory_pool().await;
+        let merchant = merchant(&pool).await;
+        let request = CreateApiKeyRequest {
+            name: "payouts".to_string(),
+            scopes: vec![ApiScope::CreatePayouts],
+            ip_allowlist: vec![],
+            pin: None,
+        };
+        let (first, secret) = MerchantService::create_key(&pool, &merchant.id, &request).await.unwrap();
+        let (_, other) = MerchantService::create_key(&pool, &merchant.id, &request).await.unwrap();
+        assert!(secret.starts_with("nps_"));
+        assert_ne!(secret, other);
+        assert!(!first.secret_sealed.as_deref().unwrap().contains(&secret[4..]));
+
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let body = br#"{"xlm_amount": 1}"#;
+        let canonical = signed(&first.id, &timestamp, "", body).canonical();
+        let signature = hmac_sha256_hex(secret.as_bytes(), canonical.as_bytes());
+        let context = MerchantService::authenticate(&pool, &signed(&first.id, &timestamp, &signature, body))
+            .await
+            .unwrap();
+        assert_eq!(context.merchant_id, merchant.id);
+
+        let forged = hmac_sha256_hex(other.as_bytes(), canonical.as_bytes());
+        let refused = MerchantService::authenticate(&pool, &signed(&first.id, &timestamp, &forged, body)).await;
+        assert!(matches!(refused, Err(MerchantError::BadSignature)));
+    }
+
+    #[tokio::test]
+    async fn test_payout_idempotency() {
+        let pool = memory_pool().await;
+        let merchant = merchant(&pool).await;
+
+        assert!(MerchantService::begin_payout(&pool, &merchant.id, "k1", "hash-a").await.unwrap().is_none());
+        let in_flight = MerchantService::begin_payout(&pool, &merchant.id, "k1", "hash-a").await;
+        assert!(matches!(in_flight, Err(MerchantError::IdempotencyInProgress)));
+
+        let response = json!({ "success": true, "tx_hash": "abc" });
+        MerchantService::finish_payout(&pool, &merchant.id, "k1", &response).await.unwrap();
+        let replay = MerchantService::begin_payout(&pool, &merchant.id, "k1", "hash-a").await.unwrap();
+        assert_eq!(replay, Some(response));
+        let reused = MerchantService::begin_payout(&pool, &merchant.id, "k1", "hash-b").await;
+        assert!(matches!(reused, Err(MerchantError::IdempotencyMismatch)));
+
+        // A finished payout can't be released; an unfinished one can
+        MerchantService::release_payout(&pool, &merchant.id, "k1").await.unwrap();
+        assert!(MerchantService::begin_payout(&pool, &merchant.id, "k1", "hash-a").await.unwrap().is_some());
+        assert!(MerchantService::begin_payout(&pool, &merchant.id, "k2", "hash-a").await.unwrap().is_none());
+        MerchantService::release_payout(&pool, &merchant.id, "k2").await.unwrap();
+        assert!(MerchantService::begin_payout(&pool, &merchant.id, "k2", "hash-b").await.unwrap().is_none());
+    }
+}
@@ -0,0 +1,368 @@
+use sqlx::SqlitePool;
+use std::env;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::services::signing::sha256_hex;
+use crate::services::totp;
+
+const RECOVERY_CODE_COUNT: usize = 10;
+const CHALLENGE_TTL_MINUTES: i64 = 5;
+const CHALLENGE_MAX_ATTEMPTS: i64 = 5;
+
+#[derive(Error, Debug)]
+pub enum MfaError {
+    #[error("Two-factor authentication is not enabled")]
+    NotEnrolled,
+    #[error("Two-factor authentication is already enabled")]
+    AlreadyEnabled,
+    #[error("Incorrect code")]
+    InvalidCode,
+    #[error("Challenge expired or already used")]
+    ChallengeExpired,
+    #[error("Too many incorrect codes; try again after {0}")]
+    Locked(chrono::DateTime<chrono::Utc>),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct UserTotp {
+    secret: String,
+    enabled_at: Option<chrono::DateTime<chrono::Utc>>,
+    last_used_step: Option<i64>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct MfaChallenge {
+    id: String,
+    user_id: String,
+}
+
+/// Secret and provisioning URI shown once while enrolling.
+#[derive(Debug)]
+pub struct TotpEnrollment {
+    pub secret: String,
+    pub provisioning_uri: String,
+}
+
+pub struct MfaService;
+
+impl MfaService {
+    pub async fn is_enabled(pool: &SqlitePool, user_id: &str) -> Result<bool, sqlx::Error> {
+        let enabled: Option<i64> =
+            sqlx::query_scalar("SELECT 1 FROM user_totp WHERE user_id = ? AND enabled_at IS NOT NULL")
+                .bind(user_id)
+                .fetch_optional(pool)
+                .await?;
+        Ok(enabled.is_some())
+    }
+
+    /// Starts (or restarts) enrolment with a new secret. TOTP is not enforced
+    /// until `confirm_totp` succeeds.
+    pub async fn enroll_totp(pool: &SqlitePool, user_id: &str, account: &str) -> Result<TotpEnrollment, MfaError> {
+        if Self::is_enabled(pool, user_id).await? {
+            return Err(MfaError::AlreadyEnabled);
+        }
+
+        let secret = totp::generate_secret();
+        sqlx::query(
+            r#"
+            INSERT INTO user_totp (user_id, secret, created_at) VALUES (?, ?, ?)
+            ON CONFLICT (user_id) DO UPDATE SET secret = excluded.secret, last_used_step = NULL, created_at = excluded.created_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(&secret)
+        .bind(chrono::Utc::now())
+        .execute(pool)
+        .await?;
+
+        Ok(TotpEnrollment {
+            provisioning_uri: totp::provisioning_uri(&secret, "NovaPay", account),
+            secret,
+        })
+    }
+
+    /// Enables TOTP once the user proves their authenticator works, and
+    /// returns a fresh set of single-use recovery codes.
+    pub async fn confirm_totp(pool: &SqlitePool, user_id: &str, code: &str) -> Result<Vec<String>, MfaError> {
+        let enrolment = Self::totp(pool, user_id).await?.ok_or(MfaError::NotEnrolled)?;
+        if enrolment.enabled_at.is_some() {
+            return Err(MfaError::AlreadyEnabled);
+        }
+        let step = totp::verify(&enrolment.secret, code, chrono::Utc::now(), enrolment.last_used_step)
+            .ok_or(MfaError::InvalidCode)?;
+
+        let now = chrono::Utc::now();
+        let codes: Vec<String> = (0..RECOVERY_CODE_COUNT).map(|_| Self::generate_recovery_code()).collect();
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("UPDATE user_totp SET enabled_at = ?, last_used_step = ? WHERE user_id = ?")
+            .bind(now)
+            .bind(step)
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        for code in &codes {
+            sqlx::query("INSERT INTO recovery_codes (id, user_id, code_hash, created_at) VALUES (?, ?, ?, ?)")
+                .bind(Uuid::new_v4().to_string())
+                .bind(user_id)
+                .bind(Self::hash_recovery_code(code))
+                .bind(now)
+                .execute(&mut *tx)
+                .await?;
+        }
+        tx.commit().await?;
+
+        Ok(codes)
+    }
+
+    /// Turns TOTP off after checking a current code or a recovery code.
+    pub async fn disable_totp(pool: &SqlitePool, user_id: &str, code: &str) -> Result<(), MfaError> {
+        if !Self::verify_code_limited(pool, user_id, code).await? {
+            return Err(MfaError::InvalidCode);
+        }
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("DELETE FROM user_totp WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("DELETE FROM recovery_codes WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// `verify_code` with a cap on guessing: after `MFA_MAX_ATTEMPTS` (default
+    /// 5) wrong codes in a row the user's codes are refused for
+    /// `MFA_LOCKOUT_MINUTES` (default 15). The attempt is counted before the
+    /// code is checked, so concurrent guesses can't get past the cap.
+    pub async fn verify_code_limited(pool: &SqlitePool, user_id: &str, code: &str) -> Result<bool, MfaError> {
+        let env_i64 = |name: &str, default: i64| env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+        let max_attempts = env_i64("MFA_MAX_ATTEMPTS", 5).max(1);
+        let now = chrono::Utc::now();
+
+        // The attempt that reaches the cap locks at once; getting it right
+        // lifts the lock again
+        let reserved: Option<i64> = sqlx::query_scalar(
+            r#"
+            UPDATE user_totp
+            SET failed_attempts = CASE WHEN failed_attempts + 1 >= ? THEN 0 ELSE failed_attempts + 1 END,
+                locked_until = CASE WHEN failed_attempts + 1 >= ? THEN ? ELSE NULL END
+            WHERE user_id = ? AND enabled_at IS NOT NULL AND (locked_until IS NULL OR locked_until <= ?)
+            RETURNING 1
+            "#,
+        )
+        .bind(max_attempts)
+        .bind(max_attempts)
+        .bind(now + chrono::Duration::minutes(env_i64("MFA_LOCKOUT_MINUTES", 15)))
+        .bind(user_id)
+        .bind(now)
+        .fetch_optional(pool)
+        .await?;
+        if reserved.is_none() {
+            let locked_until: Option<Option<chrono::DateTime<chrono::Utc>>> =
+                sqlx::query_scalar("SELECT locked_until FROM user_totp WHERE user_id = ? AND enabled_at IS NOT NULL")
+                    .bind(user_id)
+                    .fetch_optional(pool)
+                    .await?;
+            return Err(match locked_until {
+                Some(Some(until)) => MfaError::Locked(until),
+                _ => MfaError::NotEnrolled,
+            });
+        }
+
+        let verified = Self::verify_code(pool, user_id, code).await?;
+        if verified {
+            sqlx::query("UPDATE user_totp SET failed_attempts = 0, locked_until = NULL WHERE user_id = ?")
+                .bind(user_id)
+                .execute(pool)
+                .await?;
+        }
+        Ok(verified)
+    }
+
+    /// Checks a TOTP code, or else a recovery code, and uses it up.
+    async fn verify_code(pool: &SqlitePool, user_id: &str, code: &str) -> Result<bool, MfaError> {
+        let enrolment = Self::totp(pool, user_id).await?.filter(|t| t.enabled_at.is_some());
+        let Some(enrolment) = enrolment else {
+            return Err(MfaError::NotEnrolled);
+        };
+
+        if let Some(step) = totp::verify(&enrolment.secret, code, chrono::Utc::now(), enrolment.last_used_step) {
+            // Conditional update so a code cannot be used twice concurrently
+            let updated = sqlx::query(
+                "UPDATE user_totp SET last_used_step = ? WHERE user_id = ? AND (last_used_step IS NULL OR last_used_step < ?)",
+            )
+            .bind(step)
+            .bind(user_id)
+            .bind(step)
+            .execute(pool)
+            .await?
+            .rows_affected();
+            return Ok(updated > 0);
+        }
+
+        let used = sqlx::query(
+            "UPDATE recovery_codes SET used_at = ? WHERE user_id = ? AND code_hash = ? AND used_at IS NULL",
+        )
+        .bind(chrono::Utc::now())
+        .bind(user_id)
+        .bind(Self::hash_recovery_code(code))
+        .execute(pool)
+        .await?
+        .rows_affected();
+        Ok(used > 0)
+    }
+
+    /// Issues the short-lived token a client trades, with a second factor,
+    /// for a session after the password check passed.
+    pub async fn create_challenge(pool: &SqlitePool, user_id: &str) -> Result<String, sqlx::Error> {
+        let token = hex::encode(rand::random::<[u8; 32]>());
+        let now = chrono::Utc::now();
+
+        sqlx::query(
+            "INSERT INTO mfa_challenges (id, token_hash, user_id, expires_at, created_at) VALUES (?, ?, ?, ?, ?)",
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(sha256_hex(token.as_bytes()))
+        .bind(user_id)
+        .bind(now + chrono::Duration::minutes(CHALLENGE_TTL_MINUTES))
+        .bind(now)
+        .execute(pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Completes a challenge. Returns the user id on success.
+    pub async fn complete_challenge(pool: &SqlitePool, token: &str, code: &str) -> Result<String, MfaError> {
+        let now = chrono::Utc::now();
+        // Taking the attempt and checking the limit in one statement keeps
+        // concurrent guesses from all reading the same count
+        let challenge = sqlx::query_as::<_, MfaChallenge>(
+            r#"
+            UPDATE mfa_challenges SET attempts = attempts + 1
+            WHERE token_hash = ? AND consumed_at IS NULL AND expires_at > ? AND attempts < ?
+            RETURNING id, user_id
+            "#,
+        )
+        .bind(sha256_hex(token.as_bytes()))
+        .bind(now)
+        .bind(CHALLENGE_MAX_ATTEMPTS)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(MfaError::ChallengeExpired)?;
+
+        if !Self::verify_code_limited(pool, &challenge.user_id, code).await? {
+            return Err(MfaError::InvalidCode);
+        }
+
+        let consumed = sqlx::query("UPDATE mfa_challenges SET consumed_at = ? WHERE id = ? AND consumed_at IS NULL")
+            .bind(now)
+            .bind(&challenge.id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+        if consumed == 0 {
+            return Err(MfaError::ChallengeExpired);
+        }
+
+        Ok(challenge.user_id)
+    }
+
+    async fn totp(pool: &SqlitePool, user_id: &str) -> Result<Option<UserTotp>, sqlx::Error> {
+        sqlx::query_as::<_, UserTotp>("SELECT secret, enabled_at, last_used_step FROM user_totp WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    /// `xxxxx-xxxxx` in lowercase hex.
+    fn generate_recovery_code() -> String {
+        let hex = hex::encode(rand::random::<[u8; 5]>());
+        format!("{}-{}", &hex[..5], &hex[5..])
+    }
+
+    fn hash_recovery_code(code: &str) -> String {
+        sha256_hex(code.trim().to_lowercase().as_bytes())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool};
+
+    /// Enrols `user-1` with the RFC 6238 test key and returns its recovery
+    /// codes.
+    async fn enrolled(pool: &SqlitePool) -> Vec<String> {
+        insert_user(pool, "user-1", "mfa@example.com").await;
+        MfaService::enroll_totp(pool, "user-1", "mfa@example.com").await.unwrap();
+        sqlx::query("UPDATE user_totp SET secret = 'GEZDGNBVGY3TQOJQGEZDGNBVGY3TQOJQ' WHERE user_id = 'user-1'")
+            .execute(pool)
+            .await
+            .unwrap();
+        let code = totp::code_at(b"12345678901234567890", totp::current_step(chrono::Utc::now()));
+        MfaService::confirm_totp(pool, "user-1", &code).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_wrong_codes_lock_out() {
+        let pool = memory_pool().await;
+        let recovery = enrolled(&pool).await;
+
+        for _ in 0..4 {
+            assert!(!MfaService::verify_code_limited(&pool, "user-1", "000000").await.unwrap());
+        }
+        // A right code resets the count
+        assert!(MfaService::verify_code_limited(&pool, "user-1", &recovery[0]).await.unwrap());
+        for _ in 0..4 {
+            assert!(!MfaService::verify_code_limited(&pool, "user-1", "000000").await.unwrap());
+        }
+
+        // The fifth wrong code in a row locks, and then a right one is refused
+        assert!(!MfaService::verify_code_limited(&pool, "user-1", "000000").await.unwrap());
+        assert!(matches!(
+            MfaService::verify_code_limited(&pool, "user-1", &recovery[1]).await,
+            Err(MfaError::Locked(_))
+        ));
+        assert!(matches!(
+            MfaService::disable_totp(&pool, "user-1", &recovery[1]).await,
+            Err(MfaError::Locked(_))
+        ));
+
+        sqlx::query("UPDATE user_totp SET locked_until = ? WHERE user_id = 'user-1'")
+            .bind(chrono::Utc::now() - chrono::Duration::seconds(1))
+            .execute(&pool)
+            .await
+            .unwrap();
+        MfaService::disable_totp(&pool, "user-1", &recovery[1]).await.unwrap();
+        assert!(!MfaService::is_enabled(&pool, "user-1").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_challenge_is_single_use() {
+        let pool = memory_pool().await;
+        let recovery = enrolled(&pool).await;
+        let token = MfaService::create_challenge(&pool, "user-1").await.unwrap();
+
+        assert!(matches!(
+            MfaService::complete_challenge(&pool, &token, "000000").await,
+            Err(MfaError::InvalidCode)
+        ));
+        assert_eq!(MfaService::complete_challenge(&pool, &token, &recovery[0]).await.unwrap(), "user-1");
+        assert!(matches!(
+            MfaService::complete_challenge(&pool, &token, &recovery[1]).await,
+            Err(MfaError::ChallengeExpired)
+        ));
+    }
+}
@@ -1,15 +1,76 @@
+pub mod account_tokens;
+pub mod admin;
+pub mod audit;
 pub mod auth;
+pub mod custodial_wallets;
+pub mod devices;
 pub mod fonbnk;
+pub mod invoices;
+pub mod jobs;
+pub mod keys;
+pub mod key_vault;
+pub mod ledger;
+pub mod lockout;
+pub mod mailer;
+pub mod merchants;
+pub mod mfa;
+pub mod multisig;
+pub mod non_custodial;
+pub mod notification_templates;
+pub mod notifications;
+pub mod otp;
+pub mod outbound;
+pub mod password_policy;
+pub mod payment_requests;
+pub mod payouts;
+pub mod phone;
+pub mod rate_limit;
+pub mod scheduled_transfers;
+pub mod sep10;
+pub mod pin;
+pub mod sessions;
+pub mod signing;
 pub mod sms;
+pub mod step_up;
 pub mod stellar;
 pub mod stellar_sdk;
+pub mod stellar_tx;
+pub mod totp;
 pub mod transaction;
 pub mod wallet;
+pub mod webhooks;
 
+pub use account_tokens::*;
+pub use admin::*;
+pub use audit::*;
 pub use auth::*;
+pub use custodial_wallets::*;
+pub use devices::*;
 pub use fonbnk::*;
+pub use invoices::*;
+pub use jobs::*;
+pub use keys::KeyManager;
+pub use ledger::Ledger;
+pub use lockout::LoginLockout;
+pub use merchants::*;
+pub use mfa::*;
+pub use multisig::*;
+pub use non_custodial::*;
+pub use notifications::{DomainEvent, NotificationDispatcher};
+pub use otp::*;
+pub use password_policy::*;
+pub use payment_requests::*;
+pub use payouts::*;
+pub use phone::normalize_phone;
+pub use pin::*;
+pub use rate_limit::*;
+pub use scheduled_transfers::*;
+pub use sep10::*;
+pub use sessions::*;
 pub use sms::*;
+pub use step_up::StepUpService;
 pub use stellar::*;
 pub use stellar_sdk::*;
 pub use transaction::*;
-pub use wallet::*;
\ No newline at end of file
+pub use wallet::*;
+pub use webhooks::*;
@@ -0,0 +1,682 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sqlx::SqlitePool;
+use std::env;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{
+    ApprovalSigner, MultisigApproval, MultisigApprovalView, PrepareStellarTransactionRequest, StellarOperationRequest,
+};
+use crate::services::non_custodial::record_operation;
+use crate::services::stellar_tx::{EnvelopeSignature, StellarNetwork, StellarTxBuilder, StellarTxError};
+use crate::services::{DomainEvent, JobPayload, Outbox};
+
+#[derive(Error, Debug)]
+pub enum MultisigError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Source account is not registered to this user")]
+    AccountNotRegistered,
+    #[error("The account's signers can't reach the required threshold")]
+    ThresholdUnreachable,
+    #[error("Approval expired before enough signers signed")]
+    Expired,
+    #[error("Approval is no longer pending")]
+    NotPending,
+    #[error("Signed envelope does not match the proposed transaction")]
+    EnvelopeMismatch,
+    #[error("Envelope is not signed by any of the account's signers")]
+    BadSignature,
+    #[error("None of the signatures are from a key registered to this user")]
+    NotASigner,
+    #[error(transparent)]
+    Stellar(#[from] StellarTxError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Transactions on accounts that need more than one signature. One user
+/// proposes a transaction, the account's co-signers each post it back
+/// signed with their own key, and it is submitted as soon as the collected
+/// weight reaches the threshold its operation needs.
+pub struct MultisigService;
+
+impl MultisigService {
+    /// Builds the transaction and snapshots the account's current signers.
+    /// It expires after `MULTISIG_APPROVAL_TTL_HOURS` (default 24), both here
+    /// and on the network, and co-signers with a NovaPay account are notified.
+    pub async fn propose(
+        pool: &SqlitePool,
+        user_id: &str,
+        request: &PrepareStellarTransactionRequest,
+    ) -> Result<MultisigApprovalView, MultisigError> {
+        let source = request.source.trim();
+        let registered: Option<String> =
+            sqlx::query_scalar("SELECT id FROM external_accounts WHERE user_id = ? AND public_key = ?")
+                .bind(user_id)
+                .bind(source)
+                .fetch_optional(pool)
+                .await?;
+        if registered.is_none() {
+            return Err(MultisigError::AccountNotRegistered);
+        }
+
+        let network = StellarNetwork::from_env();
+        let ttl = env::var("MULTISIG_APPROVAL_TTL_HOURS").ok().and_then(|v| v.parse().ok()).unwrap_or(24);
+        let now = chrono::Utc::now();
+        let expires_at = now + chrono::Duration::hours(ttl);
+
+        let sequence = network.sequence(source).await?;
+        let account = network.signers(source).await?;
+        let threshold = if request.operation.needs_high_threshold() {
+            account.high_threshold
+        } else {
+            account.med_threshold
+        }
+        .max(1);
+        if account.signers.iter().map(|(_, weight)| weight).sum::<u32>() < threshold {
+            return Err(MultisigError::ThresholdUnreachable);
+        }
+
+        let tx = StellarTxBuilder::build(
+            source,
+            sequence,
+            &request.operation,
+            request.memo.as_deref(),
+            expires_at.timestamp() as u64,
+        )?;
+        let unsigned_xdr = StellarTxBuilder::unsigned_envelope_xdr(&tx)?;
+        let tx_hash = hex::encode(network.hash(&tx)?);
+
+        let mut db_tx = pool.begin().await?;
+        let approval = sqlx::query_as::<_, MultisigApproval>(
+            r#"
+            INSERT INTO multisig_approvals (id, user_id, source_public_key, kind, details, unsigned_xdr, tx_hash, network_passphrase, threshold, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(source)
+        .bind(request.operation.kind())
+        .bind(serde_json::to_string(&request.operation).unwrap_or_default())
+        .bind(&unsigned_xdr)
+        .bind(&tx_hash)
+        .bind(network.passphrase)
+        .bind(threshold)
+        .bind(expires_at)
+        .bind(now)
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        let mut signer_ids: Vec<String> = Vec::new();
+        for (public_key, weight) in &account.signers {
+            sqlx::query("INSERT INTO multisig_approval_signers (approval_id, public_key, weight) VALUES (?, ?, ?)")
+                .bind(&approval.id)
+                .bind(public_key)
+                .bind(weight)
+                .execute(&mut *db_tx)
+                .await?;
+
+            let owners: Vec<String> =
+                sqlx::query_scalar("SELECT user_id FROM external_accounts WHERE public_key = ? AND user_id != ?")
+                    .bind(public_key)
+                    .bind(user_id)
+                    .fetch_all(&mut *db_tx)
+                    .await?;
+            for owner in owners {
+                if !signer_ids.contains(&owner) {
+                    signer_ids.push(owner);
+                }
+            }
+        }
+
+        Outbox::enqueue(&mut *db_tx, &JobPayload::Notify {
+            event: DomainEvent::ApprovalRequested {
+                approval_id: approval.id.clone(),
+                proposer_id: user_id.to_string(),
+                signer_ids,
+                kind: approval.kind.clone(),
+                source: approval.source_public_key.clone(),
+                expires_at,
+            },
+        })
+        .await?;
+        db_tx.commit().await?;
+
+        println!("✍️ Multisig {} {} proposed on {}", approval.kind, approval.id, approval.source_public_key);
+        Self::view(pool, approval).await
+    }
+
+    /// Approvals the user proposed or holds a signer key for, newest first.
+    pub async fn list(pool: &SqlitePool, user_id: &str) -> Result<Vec<MultisigApprovalView>, MultisigError> {
+        Self::expire_stale(pool).await?;
+        let approvals = sqlx::query_as::<_, MultisigApproval>(
+            r#"
+            SELECT * FROM multisig_approvals a
+            WHERE a.user_id = ? OR EXISTS (
+                SELECT 1 FROM multisig_approval_signers s
+                JOIN external_accounts e ON e.public_key = s.public_key
+                WHERE s.approval_id = a.id AND e.user_id = ?
+            )
+            ORDER BY a.created_at DESC
+            LIMIT 100
+            "#,
+        )
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut views = Vec::with_capacity(approvals.len());
+        for approval in approvals {
+            views.push(Self::view(pool, approval).await?);
+        }
+        Ok(views)
+    }
+
+    pub async fn find(pool: &SqlitePool, user_id: &str, id: &str) -> Result<MultisigApprovalView, MultisigError> {
+        let approval = Self::find_approval(pool, user_id, id).await?;
+        Self::view(pool, approval).await
+    }
+
+    /// Stores the caller's signatures from `signed_xdr` and submits the
+    /// transaction once the signed weight reaches the threshold. Only
+    /// signatures from keys the caller registered count. A network rejection
+    /// marks the approval failed; if Horizon can't be reached it stays
+    /// `submitting` and a background job retries the same envelope.
+    pub async fn sign(
+        pool: &SqlitePool,
+        user_id: &str,
+        id: &str,
+        signed_xdr: &str,
+    ) -> Result<MultisigApprovalView, MultisigError> {
+        let approval = Self::find_approval(pool, user_id, id).await?;
+        match approval.status.as_str() {
+            "pending" => {}
+            "expired" => return Err(MultisigError::Expired),
+            _ => return Err(MultisigError::NotPending),
+        }
+
+        let (signed_tx, signatures) = StellarTxBuilder::parse_envelope(signed_xdr)?;
+        if signed_tx != StellarTxBuilder::stored_transaction(&approval.unsigned_xdr)? {
+            return Err(MultisigError::EnvelopeMismatch);
+        }
+        let mut hash = [0u8; 32];
+        hex::decode_to_slice(&approval.tx_hash, &mut hash).map_err(|_| StellarTxError::MalformedXdr)?;
+
+        let own_keys: Vec<String> = sqlx::query_scalar("SELECT public_key FROM external_accounts WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?;
+        let mut signed_by_anyone = false;
+        let mut accepted = 0;
+        for signer in Self::signers(pool, &approval.id).await? {
+            let Some((_, signature)) = StellarTxBuilder::signature_by(&signer.public_key, &hash, &signatures) else {
+                continue;
+            };
+            signed_by_anyone = true;
+            if !own_keys.contains(&signer.public_key) {
+                continue;
+            }
+
+            sqlx::query(
+                r#"
+                UPDATE multisig_approval_signers SET signature = ?, signed_by = ?, signed_at = ?
+                WHERE approval_id = ? AND public_key = ? AND signature IS NULL
+                "#,
+            )
+            .bind(STANDARD.encode(signature))
+            .bind(user_id)
+            .bind(chrono::Utc::now())
+            .bind(&approval.id)
+            .bind(&signer.public_key)
+            .execute(pool)
+            .await?;
+            accepted += 1;
+        }
+        if accepted == 0 {
+            return Err(if signed_by_anyone {
+                MultisigError::NotASigner
+            } else {
+                MultisigError::BadSignature
+            });
+        }
+
+        Self::submit_if_ready(pool, &approval).await?;
+        let approval = Self::find_approval(pool, user_id, id).await?;
+        Self::view(pool, approval).await
+    }
+
+    async fn submit_if_ready(pool: &SqlitePool, approval: &MultisigApproval) -> Result<(), MultisigError> {
+        let source_key = StellarTxBuilder::public_key_bytes(&approval.source_public_key)?;
+        let mut signed = Vec::new();
+        for signer in Self::signers(pool, &approval.id).await? {
+            let Some(signature) = signer.signature.as_deref().and_then(|s| STANDARD.decode(s).ok()) else {
+                continue;
+            };
+            signed.push((StellarTxBuilder::public_key_bytes(&signer.public_key)?, signer.weight, signature));
+        }
+        let Some(envelope_signatures) = Self::threshold_signatures(&source_key, signed, approval.threshold) else {
+            return Ok(());
+        };
+        let tx = StellarTxBuilder::stored_transaction(&approval.unsigned_xdr)?;
+        let envelope = StellarTxBuilder::signed_envelope_xdr(&tx, &envelope_signatures)?;
+
+        // The envelope is kept with the claim and a job retries it, so a crash
+        // or an unreachable Horizon can't leave the approval `submitting`
+        let mut db_tx = pool.begin().await?;
+        let claimed = sqlx::query(
+            "UPDATE multisig_approvals SET status = 'submitting', submission_envelope = ? WHERE id = ? AND status = 'pending'",
+        )
+        .bind(&envelope)
+        .bind(&approval.id)
+        .execute(&mut *db_tx)
+        .await?
+        .rows_affected();
+        if claimed == 0 {
+            return Ok(());
+        }
+        Outbox::enqueue(&mut *db_tx, &JobPayload::MultisigSubmission { approval_id: approval.id.clone() }).await?;
+        db_tx.commit().await?;
+
+        match Self::submit(pool, &StellarNetwork::from_env(), &approval.id).await {
+            Err(MultisigError::Stellar(StellarTxError::Horizon(e))) => {
+                println!("⚠️ Multisig approval {} left for retry, outcome unknown: {}", approval.id, e);
+                Ok(())
+            }
+            result => result,
+        }
+    }
+
+    /// The signatures to put on the envelope, or `None` if their weight is
+    /// short of `threshold`. Core counts the master key first, then signers in
+    /// key order, stops at the threshold and rejects envelopes carrying
+    /// signatures it didn't use.
+    fn threshold_signatures(
+        source_key: &[u8; 32],
+        mut signed: Vec<([u8; 32], i64, Vec<u8>)>,
+        threshold: i64,
+    ) -> Option<Vec<EnvelopeSignature>> {
+        signed.sort_by_key(|(key, _, _)| (key != source_key, *key));
+        let mut weight = 0;
+        let mut envelope_signatures: Vec<EnvelopeSignature> = Vec::new();
+        for (key, signer_weight, signature) in signed {
+            if weight >= threshold {
+                break;
+            }
+            weight += signer_weight;
+            envelope_signatures.push(([key[28], key[29], key[30], key[31]], signature));
+        }
+        (weight >= threshold).then_some(envelope_signatures)
+    }
+
+    /// Sends the envelope of a `submitting` approval and records the outcome.
+    /// Safe to run again: a transaction already in a ledger isn't sent twice
+    /// and only one run records it. If Horizon can't be reached the approval
+    /// stays `submitting` for the `MultisigSubmission` job to retry.
+    pub async fn submit(pool: &SqlitePool, network: &StellarNetwork, approval_id: &str) -> Result<(), MultisigError> {
+        let approval = sqlx::query_as::<_, MultisigApproval>("SELECT * FROM multisig_approvals WHERE id = ?")
+            .bind(approval_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(MultisigError::NotFound)?;
+        let Some(envelope) = approval.submission_envelope.as_deref().filter(|_| approval.status == "submitting") else {
+            return Ok(());
+        };
+
+        let tx_hash = match network.resubmit(&approval.tx_hash, envelope).await {
+            Ok(tx_hash) => tx_hash,
+            Err(StellarTxError::Rejected(codes)) => {
+                let failed = sqlx::query(
+                    "UPDATE multisig_approvals SET status = 'failed', error = ? WHERE id = ? AND status = 'submitting'",
+                )
+                .bind(&codes)
+                .bind(&approval.id)
+                .execute(pool)
+                .await?
+                .rows_affected();
+                if failed == 1 {
+                    println!("❌ Multisig approval {} rejected: {}", approval.id, codes);
+                }
+                return Err(StellarTxError::Rejected(codes).into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut db_tx = pool.begin().await?;
+        let finished = sqlx::query(
+            r#"
+            UPDATE multisig_approvals SET status = 'submitted', tx_hash = ?, submitted_at = ?
+            WHERE id = ? AND status = 'submitting'
+            "#,
+        )
+        .bind(&tx_hash)
+        .bind(chrono::Utc::now())
+        .bind(&approval.id)
+        .execute(&mut *db_tx)
+        .await?
+        .rows_affected();
+        if finished == 0 {
+            return Ok(());
+        }
+        let transaction_id = match serde_json::from_str::<StellarOperationRequest>(&approval.details) {
+            Ok(operation) => {
+                record_operation(&mut db_tx, &approval.user_id, approval.created_at, &tx_hash, &operation).await?
+            }
+            Err(_) => None,
+        };
+        sqlx::query("UPDATE multisig_approvals SET transaction_id = ? WHERE id = ?")
+            .bind(&transaction_id)
+            .bind(&approval.id)
+            .execute(&mut *db_tx)
+            .await?;
+        db_tx.commit().await?;
+
+        println!("✅ Multisig {} {} submitted: {}", approval.kind, approval.id, tx_hash);
+        Ok(())
+    }
+
+    /// Visible to whoever proposed it and to users holding one of its signer keys.
+    async fn find_approval(pool: &SqlitePool, user_id: &str, id: &str) -> Result<MultisigApproval, MultisigError> {
+        Self::expire_stale(pool).await?;
+        sqlx::query_as::<_, MultisigApproval>(
+            r#"
+            SELECT * FROM multisig_approvals a
+            WHERE a.id = ? AND (a.user_id = ? OR EXISTS (
+                SELECT 1 FROM multisig_approval_signers s
+                JOIN external_accounts e ON e.public_key = s.public_key
+                WHERE s.approval_id = a.id AND e.user_id = ?
+            ))
+            "#,
+        )
+        .bind(id)
+        .bind(user_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(MultisigError::NotFound)
+    }
+
+    async fn expire_stale(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE multisig_approvals SET status = 'expired' WHERE status = 'pending' AND expires_at <= ?")
+            .bind(chrono::Utc::now())
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn signers(pool: &SqlitePool, approval_id: &str) -> Result<Vec<ApprovalSigner>, sqlx::Error> {
+        sqlx::query_as::<_, ApprovalSigner>(
+            "SELECT public_key, weight, signature, signed_at FROM multisig_approval_signers WHERE approval_id = ? ORDER BY weight DESC, public_key",
+        )
+        .bind(approval_id)
+        .fetch_all(pool)
+        .await
+    }
+
+    async fn view(pool: &SqlitePool, approval: MultisigApproval) -> Result<MultisigApprovalView, MultisigError> {
+        let signers = Self::signers(pool, &approval.id).await?;
+        let weight = signers.iter().filter(|s| s.signature.is_some()).map(|s| s.weight).sum();
+        Ok(MultisigApprovalView { approval, signers, weight })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool, stub_server};
+    use axum::{extract::State, http::StatusCode, routing::{get, post}, Json, Router};
+    use ed25519_dalek::{Signer, SigningKey};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    fn keypair(seed: u8) -> (SigningKey, String) {
+        let key = SigningKey::from_bytes(&[seed; 32]);
+        let public_key = stellar_strkey::ed25519::PublicKey(key.verifying_key().to_bytes()).to_string();
+        (key, public_key)
+    }
+
+    fn sign_with(key: &SigningKey, hash: &[u8; 32]) -> EnvelopeSignature {
+        let hint = key.verifying_key().to_bytes()[28..].try_into().unwrap();
+        (hint, key.sign(hash).to_bytes().to_vec())
+    }
+
+    /// A pending payment from `source` needing `threshold`, with one signer
+    /// of weight 1 per key. Returns its id, transaction and hash.
+    async fn insert_approval(
+        pool: &SqlitePool,
+        source: &str,
+        signers: &[&str],
+        threshold: i64,
+    ) -> (String, stellar_xdr::curr::Transaction, [u8; 32]) {
+        let (_, destination) = keypair(9);
+        let operation = StellarOperationRequest::Payment {
+            destination,
+            asset: "XLM".to_string(),
+            amount: "5".to_string(),
+        };
+        let tx = StellarTxBuilder::build(source, 41, &operation, None, 1_900_000_000).unwrap();
+        let hash = StellarNetwork::from_env().hash(&tx).unwrap();
+        let id = Uuid::new_v4().to_string();
+        sqlx::query(
+            r#"
+            INSERT INTO multisig_approvals (id, user_id, source_public_key, kind, details, unsigned_xdr, tx_hash, network_passphrase, threshold, expires_at, created_at)
+            VALUES (?, 'u1', ?, 'payment', ?, ?, ?, 'test', ?, ?, ?)
+            "#,
+        )
+        .bind(&id)
+        .bind(source)
+        .bind(serde_json::to_string(&operation).unwrap())
+        .bind(StellarTxBuilder::unsigned_envelope_xdr(&tx).unwrap())
+        .bind(hex::encode(hash))
+        .bind(threshold)
+        .bind(chrono::Utc::now() + chrono::Duration::hours(1))
+        .bind(chrono::Utc::now())
+        .execute(pool)
+        .await
+        .unwrap();
+        for signer in signers {
+            sqlx::query("INSERT INTO multisig_approval_signers (approval_id, public_key, weight) VALUES (?, ?, 1)")
+                .bind(&id)
+                .bind(signer)
+                .execute(pool)
+                .await
+                .unwrap();
+        }
+        (id, tx, hash)
+    }
+
+    async fn register(pool: &SqlitePool, user_id: &str, public_key: &str) {
+        sqlx::query("INSERT INTO external_accounts (id, user_id, public_key, created_at) VALUES (?, ?, ?, ?)")
+            .bind(Uuid::new_v4().to_string())
+            .bind(user_id)
+            .bind(public_key)
+            .bind(chrono::Utc::now())
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_threshold_signatures() {
+        let source = [5u8; 32];
+        let signed = vec![([9u8; 32], 2, vec![9]), ([1u8; 32], 1, vec![1]), (source, 1, vec![5])];
+
+        // The master key goes first, then signers in key order until the
+        // threshold is met; later signatures are left off
+        let signatures = MultisigService::threshold_signatures(&source, signed.clone(), 2).unwrap();
+        assert_eq!(signatures, vec![([5; 4], vec![5]), ([1; 4], vec![1])]);
+        let signatures = MultisigService::threshold_signatures(&source, signed.clone(), 4).unwrap();
+        assert_eq!(signatures.len(), 3);
+        assert!(MultisigService::threshold_signatures(&source, signed.clone(), 5).is_none());
+        assert!(MultisigService::threshold_signatures(&source, Vec::new(), 1).is_none());
+
+        // Without the master key's signature the others still count
+        let signatures = MultisigService::threshold_signatures(&source, signed[..2].to_vec(), 3).unwrap();
+        assert_eq!(signatures, vec![([1; 4], vec![1]), ([9; 4], vec![9])]);
+    }
+
+    #[tokio::test]
+    async fn test_signatures_count_once_and_only_from_own_keys() {
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+        insert_user(&pool, "u2", "u2@example.com").await;
+        let (source_key, source) = keypair(1);
+        let (cosigner_key, cosigner) = keypair(2);
+        let (_, third) = keypair(3);
+        register(&pool, "u1", &source).await;
+        register(&pool, "u2", &cosigner).await;
+        let (id, tx, hash) = insert_approval(&pool, &source, &[&source, &cosigner, &third], 3).await;
+
+        let by_source = StellarTxBuilder::signed_envelope_xdr(&tx, &[sign_with(&source_key, &hash)]).unwrap();
+        let view = MultisigService::sign(&pool, "u1", &id, &by_source).await.unwrap();
+        assert_eq!(view.weight, 1);
+        let signed_at = view.signers.iter().find(|s| s.public_key == source).unwrap().signed_at;
+
+        // Signing again adds nothing and keeps the first signature
+        let view = MultisigService::sign(&pool, "u1", &id, &by_source).await.unwrap();
+        assert_eq!(view.weight, 1);
+        assert_eq!(view.signers.iter().find(|s| s.public_key == source).unwrap().signed_at, signed_at);
+
+        let by_cosigner = StellarTxBuilder::signed_envelope_xdr(&tx, &[sign_with(&cosigner_key, &hash)]).unwrap();
+        assert!(matches!(
+            MultisigService::sign(&pool, "u1", &id, &by_cosigner).await,
+            Err(MultisigError::NotASigner)
+        ));
+        let unsigned = StellarTxBuilder::unsigned_envelope_xdr(&tx).unwrap();
+        assert!(matches!(
+            MultisigService::sign(&pool, "u1", &id, &unsigned).await,
+            Err(MultisigError::BadSignature)
+        ));
+
+        // The co-signer's envelope also carries the source's signature, which
+        // is already counted
+        let both = StellarTxBuilder::signed_envelope_xdr(
+            &tx,
+            &[sign_with(&source_key, &hash), sign_with(&cosigner_key, &hash)],
+        )
+        .unwrap();
+        let view = MultisigService::sign(&pool, "u2", &id, &both).await.unwrap();
+        assert_eq!(view.weight, 2);
+        assert_eq!(view.approval.status, "pending");
+        let signed_by: Vec<(String, Option<String>)> =
+            sqlx::query_as("SELECT public_key, signed_by FROM multisig_approval_signers WHERE approval_id = ? ORDER BY weight")
+                .bind(&id)
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        assert!(signed_by.contains(&(source.clone(), Some("u1".to_string()))));
+        assert!(signed_by.contains(&(cosigner.clone(), Some("u2".to_string()))));
+        assert!(signed_by.contains(&(third.clone(), None)));
+    }
+
+    #[derive(Clone, Default)]
+    struct Horizon {
+        submissions: Arc<AtomicUsize>,
+        /// Submissions answered with 503 before one gets through
+        unavailable: usize,
+        reject: bool,
+    }
+
+    async fn horizon(state: Horizon) -> StellarNetwork {
+        let app = Router::new()
+            .route(
+                "/transactions/:hash",
+                get(|State(horizon): State<Horizon>| async move {
+                    if !horizon.reject && horizon.submissions.load(Ordering::SeqCst) > horizon.unavailable {
+                        (StatusCode::OK, Json(json!({ "successful": true })))
+                    } else {
+                        (StatusCode::NOT_FOUND, Json(json!({})))
+                    }
+                }),
+            )
+            .route(
+                "/transactions",
+                post(|State(horizon): State<Horizon>| async move {
+                    let n = horizon.submissions.fetch_add(1, Ordering::SeqCst) + 1;
+                    if n <= horizon.unavailable {
+                        (StatusCode::SERVICE_UNAVAILABLE, Json(json!({})))
+                    } else if horizon.reject {
+                        let codes = json!({ "extras": { "result_codes": { "transaction": "tx_bad_auth" } } });
+                        (StatusCode::BAD_REQUEST, Json(codes))
+                    } else {
+                        (StatusCode::OK, Json(json!({ "hash": "landed" })))
+                    }
+                }),
+            )
+            .with_state(state);
+
+        let mut network = StellarNetwork::from_env();
+        network.horizon_url = stub_server(app).await;
+        network
+    }
+
+    async fn claim(pool: &SqlitePool, id: &str) {
+        sqlx::query("UPDATE multisig_approvals SET status = 'submitting', submission_envelope = 'AAAA' WHERE id = ?")
+            .bind(id)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn status(pool: &SqlitePool, id: &str) -> (String, Option<String>) {
+        sqlx::query_as("SELECT status, transaction_id FROM multisig_approvals WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_unknown_submission_is_retried_and_recorded_once() {
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+        let (_, source) = keypair(1);
+        let (id, _, _) = insert_approval(&pool, &source, &[&source], 1).await;
+        claim(&pool, &id).await;
+        let state = Horizon { unavailable: 1, ..Default::default() };
+        let network = horizon(state.clone()).await;
+
+        assert!(matches!(
+            MultisigService::submit(&pool, &network, &id).await,
+            Err(MultisigError::Stellar(StellarTxError::Horizon(_)))
+        ));
+        assert_eq!(status(&pool, &id).await, ("submitting".to_string(), None));
+
+        MultisigService::submit(&pool, &network, &id).await.unwrap();
+        let (status_now, transaction_id) = status(&pool, &id).await;
+        assert_eq!(status_now, "submitted");
+        assert!(transaction_id.is_some());
+
+        // A late retry finds it done and neither sends nor records it again
+        MultisigService::submit(&pool, &network, &id).await.unwrap();
+        assert_eq!(state.submissions.load(Ordering::SeqCst), 2);
+        let recorded: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE user_id = 'u1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(recorded, 1);
+    }
+
+    #[tokio::test]
+    async fn test_rejected_submission_fails_the_approval() {
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+        let (_, source) = keypair(1);
+        let (id, _, _) = insert_approval(&pool, &source, &[&source], 1).await;
+        claim(&pool, &id).await;
+        let network = horizon(Horizon { reject: true, ..Default::default() }).await;
+
+        assert!(matches!(
+            MultisigService::submit(&pool, &network, &id).await,
+            Err(MultisigError::Stellar(StellarTxError::Rejected(_)))
+        ));
+        assert_eq!(status(&pool, &id).await, ("failed".to_string(), None));
+        // Nothing left to submit
+        MultisigService::submit(&pool, &network, &id).await.unwrap();
+    }
+}
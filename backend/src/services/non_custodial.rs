@@ -0,0 +1,302 @@
+use sqlx::SqlitePool;
+use std::env;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{
+    ExternalAccount, PrepareStellarTransactionRequest, RegisterExternalAccountRequest, StellarOperationRequest,
+    StellarTransaction,
+};
+use crate::services::stellar_tx::{stroops_to_units, StellarNetwork, StellarTxBuilder, StellarTxError};
+use crate::services::{DomainEvent, JobPayload, Outbox, Sep10Error, Sep10Service};
+
+#[derive(Error, Debug)]
+pub enum NonCustodialError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Account is already registered")]
+    AlreadyRegistered,
+    #[error("Source account is not registered to this user")]
+    AccountNotRegistered,
+    #[error("Transaction expired before it was submitted")]
+    Expired,
+    #[error("Transaction is no longer pending")]
+    NotPending,
+    #[error("Signed envelope does not match the prepared transaction")]
+    EnvelopeMismatch,
+    #[error("Envelope is not signed by the source account")]
+    BadSignature,
+    #[error("Control of the account was not proven: {0}")]
+    Unproven(Sep10Error),
+    #[error(transparent)]
+    Stellar(#[from] StellarTxError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Transactions for accounts whose keys stay on the user's device: the server
+/// builds the unsigned envelope, the client signs its hash and posts the
+/// envelope back, and the server checks it is the same transaction signed by
+/// the registered key before submitting it.
+pub struct NonCustodialService;
+
+impl NonCustodialService {
+    pub async fn register_account(
+        pool: &SqlitePool,
+        user_id: &str,
+        request: &RegisterExternalAccountRequest,
+    ) -> Result<ExternalAccount, NonCustodialError> {
+        let public_key = request.public_key.trim();
+        StellarTxBuilder::public_key_bytes(public_key)?;
+        // Registering lets the account sign in as this user through SEP-10,
+        // so the caller has to show they can sign for it
+        let proven = Sep10Service::verify_challenge(pool, &StellarNetwork::from_env(), &request.challenge)
+            .await
+            .map_err(NonCustodialError::Unproven)?;
+        if proven != public_key {
+            return Err(NonCustodialError::Unproven(Sep10Error::InvalidChallenge("challenge is for another account")));
+        }
+
+        let account = sqlx::query_as::<_, ExternalAccount>(
+            r#"
+            INSERT INTO external_accounts (id, user_id, public_key, label, created_at)
+            VALUES (?, ?, ?, ?, ?)
+            ON CONFLICT (user_id, public_key) DO NOTHING
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(public_key)
+        .bind(request.label.as_deref().map(str::trim))
+        .bind(chrono::Utc::now())
+        .fetch_optional(pool)
+        .await?
+        .ok_or(NonCustodialError::AlreadyRegistered)?;
+
+        println!("🔐 External account {} registered for user {}", account.public_key, user_id);
+        Ok(account)
+    }
+
+    pub async fn list_accounts(pool: &SqlitePool, user_id: &str) -> Result<Vec<ExternalAccount>, sqlx::Error> {
+        sqlx::query_as::<_, ExternalAccount>("SELECT * FROM external_accounts WHERE user_id = ? ORDER BY created_at")
+            .bind(user_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Builds the unsigned transaction against the source account's current
+    /// sequence number. It expires after `STELLAR_TX_TIMEOUT_SECS` (default
+    /// 300), both here and on the network.
+    pub async fn prepare(
+        pool: &SqlitePool,
+        user_id: &str,
+        request: &PrepareStellarTransactionRequest,
+    ) -> Result<StellarTransaction, NonCustodialError> {
+        let source = request.source.trim();
+        let registered: Option<String> =
+            sqlx::query_scalar("SELECT id FROM external_accounts WHERE user_id = ? AND public_key = ?")
+                .bind(user_id)
+                .bind(source)
+                .fetch_optional(pool)
+                .await?;
+        if registered.is_none() {
+            return Err(NonCustodialError::AccountNotRegistered);
+        }
+
+        let network = StellarNetwork::from_env();
+        let timeout = env::var("STELLAR_TX_TIMEOUT_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(300);
+        let now = chrono::Utc::now();
+        let expires_at = now + chrono::Duration::seconds(timeout);
+
+        let sequence = network.sequence(source).await?;
+        let tx = StellarTxBuilder::build(
+            source,
+            sequence,
+            &request.operation,
+            request.memo.as_deref(),
+            expires_at.timestamp() as u64,
+        )?;
+        let unsigned_xdr = StellarTxBuilder::unsigned_envelope_xdr(&tx)?;
+        let tx_hash = hex::encode(network.hash(&tx)?);
+
+        let prepared = sqlx::query_as::<_, StellarTransaction>(
+            r#"
+            INSERT INTO stellar_transactions (id, user_id, source_public_key, kind, details, unsigned_xdr, tx_hash, network_passphrase, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(source)
+        .bind(request.operation.kind())
+        .bind(serde_json::to_string(&request.operation).unwrap_or_default())
+        .bind(&unsigned_xdr)
+        .bind(&tx_hash)
+        .bind(network.passphrase)
+        .bind(expires_at)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        Ok(prepared)
+    }
+
+    pub async fn find(pool: &SqlitePool, user_id: &str, id: &str) -> Result<StellarTransaction, NonCustodialError> {
+        sqlx::query_as::<_, StellarTransaction>("SELECT * FROM stellar_transactions WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(NonCustodialError::NotFound)
+    }
+
+    /// Verifies and submits the signed envelope. Payments and path payments
+    /// are then recorded in `transactions` like custodial ones. A network
+    /// rejection marks the transaction failed; if Horizon can't be reached it
+    /// stays pending and can be submitted again. The envelope keeps its
+    /// sequence number, so a repeated or concurrent submit can't land twice,
+    /// and only the one that moves the row out of `pending` records it.
+    pub async fn submit(
+        pool: &SqlitePool,
+        user_id: &str,
+        id: &str,
+        signed_xdr: &str,
+    ) -> Result<StellarTransaction, NonCustodialError> {
+        let prepared = Self::find(pool, user_id, id).await?;
+        if prepared.status != "pending" {
+            return Err(NonCustodialError::NotPending);
+        }
+        if prepared.expires_at <= chrono::Utc::now() {
+            return Err(NonCustodialError::Expired);
+        }
+
+        let (signed_tx, signatures) = StellarTxBuilder::parse_envelope(signed_xdr)?;
+        if signed_tx != StellarTxBuilder::stored_transaction(&prepared.unsigned_xdr)? {
+            return Err(NonCustodialError::EnvelopeMismatch);
+        }
+        let mut hash = [0u8; 32];
+        hex::decode_to_slice(&prepared.tx_hash, &mut hash).map_err(|_| StellarTxError::MalformedXdr)?;
+        if !StellarTxBuilder::signed_by(&prepared.source_public_key, &hash, &signatures) {
+            return Err(NonCustodialError::BadSignature);
+        }
+
+        let tx_hash = match StellarNetwork::from_env().resubmit(&prepared.tx_hash, signed_xdr.trim()).await {
+            Ok(tx_hash) => tx_hash,
+            Err(StellarTxError::Rejected(codes)) => {
+                sqlx::query("UPDATE stellar_transactions SET status = 'failed', error = ? WHERE id = ? AND status = 'pending'")
+                    .bind(&codes)
+                    .bind(&prepared.id)
+                    .execute(pool)
+                    .await?;
+                println!("❌ Stellar transaction {} rejected: {}", prepared.id, codes);
+                return Err(StellarTxError::Rejected(codes).into());
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        let now = chrono::Utc::now();
+        let mut db_tx = pool.begin().await?;
+        let claimed = sqlx::query(
+            "UPDATE stellar_transactions SET status = 'submitted', tx_hash = ?, submitted_at = ? WHERE id = ? AND status = 'pending'",
+        )
+        .bind(&tx_hash)
+        .bind(now)
+        .bind(&prepared.id)
+        .execute(&mut *db_tx)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            // Another submit of the same envelope got here first and recorded it
+            return Err(NonCustodialError::NotPending);
+        }
+
+        let transaction_id = match serde_json::from_str::<StellarOperationRequest>(&prepared.details) {
+            Ok(operation) => record_operation(&mut db_tx, &prepared.user_id, prepared.created_at, &tx_hash, &operation).await?,
+            Err(_) => None,
+        };
+        let submitted = sqlx::query_as::<_, StellarTransaction>(
+            "UPDATE stellar_transactions SET transaction_id = ? WHERE id = ? RETURNING *",
+        )
+        .bind(&transaction_id)
+        .bind(&prepared.id)
+        .fetch_one(&mut *db_tx)
+        .await?;
+        db_tx.commit().await?;
+
+        println!("✅ Client-signed {} {} submitted: {}", submitted.kind, submitted.id, tx_hash);
+        Ok(submitted)
+    }
+}
+
+/// Records a submitted payment or path payment in `transactions` like a
+/// custodial transfer and queues its notifications. Other operations move no
+/// money and give `None`.
+pub(crate) async fn record_operation(
+    db_tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+    user_id: &str,
+    created_at: chrono::DateTime<chrono::Utc>,
+    tx_hash: &str,
+    operation: &StellarOperationRequest,
+) -> Result<Option<String>, sqlx::Error> {
+    let (destination, amount, currency, target_currency) = match operation {
+        StellarOperationRequest::Payment {
+            destination,
+            asset,
+            amount,
+        } => (destination, amount, asset, asset),
+        StellarOperationRequest::PathPayment {
+            destination,
+            send_asset,
+            send_amount,
+            dest_asset,
+            ..
+        } => (destination, send_amount, send_asset, dest_asset),
+        _ => return Ok(None),
+    };
+    // Checked when the transaction was built
+    let amount = StellarTxBuilder::amount(amount, false).map(stroops_to_units).unwrap_or_default();
+    let currency = asset_code(currency);
+    let target_currency = asset_code(target_currency);
+
+    let transaction_id: String = sqlx::query_scalar(
+        r#"
+        INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, stellar_tx_hash, status, created_at, completed_at)
+        VALUES (?, ?, ?, ?, ?, ?, ?, 'completed', ?, ?)
+        RETURNING id
+        "#,
+    )
+    .bind(Uuid::new_v4().to_string())
+    .bind(user_id)
+    .bind(destination)
+    .bind(amount)
+    .bind(&currency)
+    .bind(&target_currency)
+    .bind(tx_hash)
+    .bind(created_at)
+    .bind(chrono::Utc::now())
+    .fetch_one(&mut **db_tx)
+    .await?;
+
+    Outbox::enqueue(&mut **db_tx, &JobPayload::Notify {
+        event: DomainEvent::TransferCompleted {
+            transaction_id: transaction_id.clone(),
+            sender_id: user_id.to_string(),
+            recipient: destination.to_string(),
+            amount,
+            currency: target_currency,
+            tx_hash: tx_hash.to_string(),
+        },
+    })
+    .await?;
+
+    Ok(Some(transaction_id))
+}
+
+/// `XLM` or the code part of `CODE:ISSUER`.
+pub(crate) fn asset_code(asset: &str) -> String {
+    match asset.split_once(':') {
+        Some((code, _)) => code.to_string(),
+        None => "XLM".to_string(),
+    }
+}
@@ -0,0 +1,268 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Locale {
+    En,
+    Sw,
+    Lg,
+}
+
+impl Locale {
+    pub const DEFAULT: Locale = Locale::En;
+
+    pub fn from_code(code: &str) -> Option<Self> {
+        // Accept region-qualified tags such as "sw-KE" or "lg_UG"
+        let language = code.split(['-', '_']).next().unwrap_or_default();
+        match language.to_lowercase().as_str() {
+            "en" => Some(Locale::En),
+            "sw" => Some(Locale::Sw),
+            "lg" => Some(Locale::Lg),
+            _ => None,
+        }
+    }
+
+    /// Resolves a stored preference, falling back to English for unknown codes.
+    pub fn from_preference(code: Option<&str>) -> Self {
+        code.and_then(Self::from_code).unwrap_or(Self::DEFAULT)
+    }
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            Locale::En => "en",
+            Locale::Sw => "sw",
+            Locale::Lg => "lg",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TemplateKind {
+    MoneyReceived,
+    MoneySent,
+    DepositConfirmed,
+    WithdrawalFailed,
+    LoginNewDevice,
+    Otp,
+    PasswordReset,
+    VerifyEmail,
+    ApprovalRequested,
+    PayoutBatchFinished,
+    ScheduledTransferRetrying,
+    ScheduledTransferMissed,
+    PaymentRequested,
+    PaymentRequestPaid,
+    PaymentRequestDeclined,
+}
+
+const TEMPLATES: &[(TemplateKind, Locale, &str)] = &[
+    (TemplateKind::MoneyReceived, Locale::En, "You have received {amount} from {sender}. Transaction completed via NovaPay. Thank you!"),
+    (TemplateKind::MoneyReceived, Locale::Sw, "Umepokea {amount} kutoka kwa {sender}. Muamala umekamilika kupitia NovaPay. Asante!"),
+    (TemplateKind::MoneyReceived, Locale::Lg, "Ofunye {amount} okuva eri {sender}. Okusasula kuwedde okuyita mu NovaPay. Webale!"),
+    (TemplateKind::MoneySent, Locale::En, "You have sent {amount} to {recipient}. Ref: {reference}."),
+    (TemplateKind::MoneySent, Locale::Sw, "Umetuma {amount} kwa {recipient}. Kumbukumbu: {reference}."),
+    (TemplateKind::MoneySent, Locale::Lg, "Oweerezza {amount} eri {recipient}. Namba: {reference}."),
+    (TemplateKind::DepositConfirmed, Locale::En, "Your deposit of {amount} has been confirmed. New balance: {balance}."),
+    (TemplateKind::DepositConfirmed, Locale::Sw, "Amana yako ya {amount} imethibitishwa. Salio jipya: {balance}."),
+    (TemplateKind::DepositConfirmed, Locale::Lg, "Ssente zo {amount} z'oteresezza zikakasiddwa. Bbalansi empya: {balance}."),
+    (TemplateKind::WithdrawalFailed, Locale::En, "Your withdrawal of {amount} could not be completed: {reason}. No funds were deducted."),
+    (TemplateKind::WithdrawalFailed, Locale::Sw, "Utoaji wako wa {amount} haukufanikiwa: {reason}. Hakuna pesa zilizokatwa."),
+    (TemplateKind::WithdrawalFailed, Locale::Lg, "Okuggyayo {amount} tekuwedde: {reason}. Tewali ssente zitwaliddwa."),
+    (TemplateKind::LoginNewDevice, Locale::En, "New sign-in to your NovaPay account from {device} ({ip}). If this wasn't you, reset your password now."),
+    (TemplateKind::LoginNewDevice, Locale::Sw, "Kuingia kupya kwenye akaunti yako ya NovaPay kutoka {device} ({ip}). Ikiwa si wewe, badilisha nenosiri lako sasa."),
+    (TemplateKind::LoginNewDevice, Locale::Lg, "Waliwo ayingidde mu akawunti yo eya NovaPay ng'ayita ku {device} ({ip}). Bw'oba si ggwe, kyusa ekigambo kyo eky'ekyama kati."),
+    (TemplateKind::Otp, Locale::En, "Your NovaPay verification code is {code}. It expires in {minutes} minutes. Do not share it with anyone."),
+    (TemplateKind::Otp, Locale::Sw, "Nambari yako ya uthibitisho ya NovaPay ni {code}. Itaisha baada ya dakika {minutes}. Usimpe mtu yeyote."),
+    (TemplateKind::Otp, Locale::Lg, "Ennamba yo ey'okukakasa eya NovaPay ye {code}. Eggwaako mu ddakiika {minutes}. Togiwa muntu yenna."),
+    (TemplateKind::PasswordReset, Locale::En, "Someone asked to reset the password for your NovaPay account. Open {link} within {minutes} minutes to choose a new one. If this wasn't you, ignore this email."),
+    (TemplateKind::PasswordReset, Locale::Sw, "Kuna aliyeomba kubadilisha nenosiri la akaunti yako ya NovaPay. Fungua {link} ndani ya dakika {minutes} kuchagua jipya. Ikiwa si wewe, puuza barua pepe hii."),
+    (TemplateKind::PasswordReset, Locale::Lg, "Waliwo asabye okukyusa ekigambo eky'ekyama eky'akawunti yo eya NovaPay. Ggulawo {link} mu ddakiika {minutes} olonde ekipya. Bw'oba si ggwe, buuka email eno."),
+    (TemplateKind::VerifyEmail, Locale::En, "Welcome to NovaPay! Confirm your email address by opening {link}. The link expires in {hours} hours."),
+    (TemplateKind::VerifyEmail, Locale::Sw, "Karibu NovaPay! Thibitisha anwani yako ya barua pepe kwa kufungua {link}. Kiungo kitaisha baada ya saa {hours}."),
+    (TemplateKind::VerifyEmail, Locale::Lg, "Tukwaniriza mu NovaPay! Kakasa email yo ng'oggulawo {link}. Link eggwaako mu ssaawa {hours}."),
+    (TemplateKind::ApprovalRequested, Locale::En, "{proposer} needs your approval for a {operation} from {account}. Sign it in NovaPay before {expires}."),
+    (TemplateKind::ApprovalRequested, Locale::Sw, "{proposer} anahitaji idhini yako kwa {operation} kutoka {account}. Itie sahihi kwenye NovaPay kabla ya {expires}."),
+    (TemplateKind::ApprovalRequested, Locale::Lg, "{proposer} yeetaaga okukkiriza kwo ku {operation} okuva ku {account}. Kissaako omukono mu NovaPay nga {expires} tannatuuka."),
+    (TemplateKind::PayoutBatchFinished, Locale::En, "Your payout batch {reference} has finished: {paid} paid, {failed} failed."),
+    (TemplateKind::PayoutBatchFinished, Locale::Sw, "Malipo yako ya pamoja {reference} yamekamilika: {paid} yamelipwa, {failed} yameshindikana."),
+    (TemplateKind::PayoutBatchFinished, Locale::Lg, "Okusasula kwo okw'ekibinja {reference} kuwedde: {paid} basasuddwa, {failed} kulemye."),
+    (TemplateKind::ScheduledTransferRetrying, Locale::En, "Your scheduled transfer of {amount} to {recipient} is waiting: your balance is too low. We will try again at {retry}."),
+    (TemplateKind::ScheduledTransferRetrying, Locale::Sw, "Uhamisho wako uliopangwa wa {amount} kwa {recipient} unasubiri: salio lako halitoshi. Tutajaribu tena saa {retry}."),
+    (TemplateKind::ScheduledTransferRetrying, Locale::Lg, "Okuweereza kwo okwategekebwa okwa {amount} eri {recipient} kulinze: bbalansi yo temala. Tujja kuddamu okugezaako ku {retry}."),
+    (TemplateKind::ScheduledTransferMissed, Locale::En, "Your scheduled transfer of {amount} to {recipient} was not sent because your balance was too low. The next one will go out as planned."),
+    (TemplateKind::ScheduledTransferMissed, Locale::Sw, "Uhamisho wako uliopangwa wa {amount} kwa {recipient} haukutumwa kwa sababu salio lako halikutosha. Unaofuata utatumwa kama ilivyopangwa."),
+    (TemplateKind::ScheduledTransferMissed, Locale::Lg, "Okuweereza kwo okwategekebwa okwa {amount} eri {recipient} tekwaweerezeddwa kubanga bbalansi yo teyamala. Okuddako kujja kuweerezebwa nga bwe kwategekebwa."),
+    (TemplateKind::PaymentRequested, Locale::En, "{requester} has asked you to pay {amount}. Ref: {reference}. Open NovaPay to pay or decline."),
+    (TemplateKind::PaymentRequested, Locale::Sw, "{requester} amekuomba ulipe {amount}. Kumbukumbu: {reference}. Fungua NovaPay kulipa au kukataa."),
+    (TemplateKind::PaymentRequested, Locale::Lg, "{requester} akusabye osasule {amount}. Namba: {reference}. Ggulawo NovaPay osasule oba ogaane."),
+    (TemplateKind::PaymentRequestPaid, Locale::En, "{payer} has paid your request for {amount}. Ref: {reference}."),
+    (TemplateKind::PaymentRequestPaid, Locale::Sw, "{payer} amelipa ombi lako la {amount}. Kumbukumbu: {reference}."),
+    (TemplateKind::PaymentRequestPaid, Locale::Lg, "{payer} asasudde okusaba kwo okwa {amount}. Namba: {reference}."),
+    (TemplateKind::PaymentRequestDeclined, Locale::En, "{payer} has declined your request for {amount}. Ref: {reference}."),
+    (TemplateKind::PaymentRequestDeclined, Locale::Sw, "{payer} amekataa ombi lako la {amount}. Kumbukumbu: {reference}."),
+    (TemplateKind::PaymentRequestDeclined, Locale::Lg, "{payer} agaanye okusaba kwo okwa {amount}. Namba: {reference}."),
+];
+
+/// Short titles used for email subjects and push notifications.
+const SUBJECTS: &[(TemplateKind, Locale, &str)] = &[
+    (TemplateKind::MoneyReceived, Locale::En, "You received money"),
+    (TemplateKind::MoneyReceived, Locale::Sw, "Umepokea pesa"),
+    (TemplateKind::MoneyReceived, Locale::Lg, "Ofunye ssente"),
+    (TemplateKind::MoneySent, Locale::En, "Payment sent"),
+    (TemplateKind::MoneySent, Locale::Sw, "Malipo yametumwa"),
+    (TemplateKind::MoneySent, Locale::Lg, "Ssente ziweerezeddwa"),
+    (TemplateKind::DepositConfirmed, Locale::En, "Deposit confirmed"),
+    (TemplateKind::DepositConfirmed, Locale::Sw, "Amana imethibitishwa"),
+    (TemplateKind::DepositConfirmed, Locale::Lg, "Ssente zikakasiddwa"),
+    (TemplateKind::WithdrawalFailed, Locale::En, "Withdrawal failed"),
+    (TemplateKind::WithdrawalFailed, Locale::Sw, "Utoaji haukufanikiwa"),
+    (TemplateKind::WithdrawalFailed, Locale::Lg, "Okuggyayo tekuwedde"),
+    (TemplateKind::LoginNewDevice, Locale::En, "New sign-in to NovaPay"),
+    (TemplateKind::LoginNewDevice, Locale::Sw, "Kuingia kupya NovaPay"),
+    (TemplateKind::LoginNewDevice, Locale::Lg, "Okuyingira okupya mu NovaPay"),
+    (TemplateKind::Otp, Locale::En, "Your NovaPay code"),
+    (TemplateKind::Otp, Locale::Sw, "Nambari yako ya NovaPay"),
+    (TemplateKind::Otp, Locale::Lg, "Ennamba yo eya NovaPay"),
+    (TemplateKind::PasswordReset, Locale::En, "Reset your NovaPay password"),
+    (TemplateKind::PasswordReset, Locale::Sw, "Badilisha nenosiri lako la NovaPay"),
+    (TemplateKind::PasswordReset, Locale::Lg, "Kyusa ekigambo kyo eky'ekyama ekya NovaPay"),
+    (TemplateKind::VerifyEmail, Locale::En, "Confirm your email address"),
+    (TemplateKind::VerifyEmail, Locale::Sw, "Thibitisha barua pepe yako"),
+    (TemplateKind::VerifyEmail, Locale::Lg, "Kakasa email yo"),
+    (TemplateKind::ApprovalRequested, Locale::En, "Approval needed"),
+    (TemplateKind::ApprovalRequested, Locale::Sw, "Idhini inahitajika"),
+    (TemplateKind::ApprovalRequested, Locale::Lg, "Okukkiriza kwetaagisa"),
+    (TemplateKind::PayoutBatchFinished, Locale::En, "Payout batch finished"),
+    (TemplateKind::PayoutBatchFinished, Locale::Sw, "Malipo ya pamoja yamekamilika"),
+    (TemplateKind::PayoutBatchFinished, Locale::Lg, "Okusasula okw'ekibinja kuwedde"),
+    (TemplateKind::ScheduledTransferRetrying, Locale::En, "Scheduled transfer waiting for funds"),
+    (TemplateKind::ScheduledTransferRetrying, Locale::Sw, "Uhamisho uliopangwa unasubiri pesa"),
+    (TemplateKind::ScheduledTransferRetrying, Locale::Lg, "Okuweereza okwategekebwa kulinze ssente"),
+    (TemplateKind::ScheduledTransferMissed, Locale::En, "Scheduled transfer not sent"),
+    (TemplateKind::ScheduledTransferMissed, Locale::Sw, "Uhamisho uliopangwa haukutumwa"),
+    (TemplateKind::ScheduledTransferMissed, Locale::Lg, "Okuweereza okwategekebwa tekwaweerezeddwa"),
+    (TemplateKind::PaymentRequested, Locale::En, "Payment request"),
+    (TemplateKind::PaymentRequested, Locale::Sw, "Ombi la malipo"),
+    (TemplateKind::PaymentRequested, Locale::Lg, "Okusaba okusasulwa"),
+    (TemplateKind::PaymentRequestPaid, Locale::En, "Payment request paid"),
+    (TemplateKind::PaymentRequestPaid, Locale::Sw, "Ombi la malipo limelipwa"),
+    (TemplateKind::PaymentRequestPaid, Locale::Lg, "Okusaba kusasuddwa"),
+    (TemplateKind::PaymentRequestDeclined, Locale::En, "Payment request declined"),
+    (TemplateKind::PaymentRequestDeclined, Locale::Sw, "Ombi la malipo limekataliwa"),
+    (TemplateKind::PaymentRequestDeclined, Locale::Lg, "Okusaba kugaaniddwa"),
+];
+
+pub struct NotificationTemplates;
+
+impl NotificationTemplates {
+    fn lookup(table: &[(TemplateKind, Locale, &'static str)], kind: TemplateKind, locale: Locale) -> &'static str {
+        let find = |locale: Locale| {
+            table
+                .iter()
+                .find(|(k, l, _)| *k == kind && *l == locale)
+                .map(|(_, _, template)| *template)
+        };
+        find(locale).or_else(|| find(Locale::DEFAULT)).unwrap_or_default()
+    }
+
+    pub fn subject(kind: TemplateKind, locale: Locale) -> &'static str {
+        Self::lookup(SUBJECTS, kind, locale)
+    }
+
+    /// Renders `kind` in `locale`, falling back to English when the locale has
+    /// no template. Placeholders are written as `{name}`.
+    pub fn render(kind: TemplateKind, locale: Locale, vars: &[(&str, String)]) -> String {
+        let template = Self::lookup(TEMPLATES, kind, locale);
+
+        vars.iter().fold(template.to_string(), |message, (name, value)| {
+            message.replace(&format!("{{{}}}", name), value)
+        })
+    }
+}
+
+/// Formats an amount for display, e.g. `KES 1,250.00`, `UGX 35,000` or `XLM 12.5`.
+pub fn format_amount(amount: f64, currency: &str) -> String {
+    let currency = currency.to_uppercase();
+    let decimals = match currency.as_str() {
+        // Shilling denominations below one unit are not in circulation
+        "UGX" | "TZS" => 0,
+        "XLM" | "USDC" => 7,
+        _ => 2,
+    };
+
+    let mut formatted = format!("{:.*}", decimals, amount.abs());
+    if decimals == 7 {
+        formatted = formatted.trim_end_matches('0').trim_end_matches('.').to_string();
+    }
+
+    let (whole, fraction) = match formatted.split_once('.') {
+        Some((whole, fraction)) => (whole.to_string(), Some(fraction.to_string())),
+        None => (formatted, None),
+    };
+
+    let mut grouped = String::new();
+    for (i, digit) in whole.chars().enumerate() {
+        if i > 0 && (whole.len() - i) % 3 == 0 {
+            grouped.push(',');
+        }
+        grouped.push(digit);
+    }
+
+    let sign = if amount < 0.0 { "-" } else { "" };
+    match fraction {
+        Some(fraction) => format!("{} {}{}.{}", currency, sign, grouped, fraction),
+        None => format!("{} {}{}", currency, sign, grouped),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(format_amount(1250.0, "kes"), "KES 1,250.00");
+        assert_eq!(format_amount(1234567.891, "USD"), "USD 1,234,567.89");
+        assert_eq!(format_amount(999.0, "USD"), "USD 999.00");
+        assert_eq!(format_amount(-1500.0, "KES"), "KES -1,500.00");
+        // Shillings without cents in circulation have no decimals
+        assert_eq!(format_amount(35000.4, "UGX"), "UGX 35,000");
+        assert_eq!(format_amount(1999.6, "TZS"), "TZS 2,000");
+        // Stellar amounts keep up to 7 decimals, without trailing zeros
+        assert_eq!(format_amount(12.5, "XLM"), "XLM 12.5");
+        assert_eq!(format_amount(0.12345678, "XLM"), "XLM 0.1234568");
+        assert_eq!(format_amount(1000.0, "XLM"), "XLM 1,000");
+    }
+
+    #[test]
+    fn test_missing_translations_fall_back_to_english() {
+        let table = [
+            (TemplateKind::Otp, Locale::En, "Your code is {code}"),
+            (TemplateKind::Otp, Locale::Sw, "Nambari yako ni {code}"),
+        ];
+        assert_eq!(NotificationTemplates::lookup(&table, TemplateKind::Otp, Locale::Sw), "Nambari yako ni {code}");
+        assert_eq!(NotificationTemplates::lookup(&table, TemplateKind::Otp, Locale::Lg), "Your code is {code}");
+        assert_eq!(NotificationTemplates::lookup(&table, TemplateKind::MoneySent, Locale::Lg), "");
+
+        // Every kind has an English template and subject to fall back to
+        for kind in [TemplateKind::MoneyReceived, TemplateKind::Otp, TemplateKind::PaymentRequestDeclined] {
+            for locale in [Locale::En, Locale::Sw, Locale::Lg] {
+                assert!(!NotificationTemplates::render(kind, locale, &[]).is_empty());
+                assert!(!NotificationTemplates::subject(kind, locale).is_empty());
+            }
+        }
+        assert_eq!(Locale::from_preference(Some("fr")), Locale::En);
+        assert_eq!(Locale::from_preference(Some("sw-KE")), Locale::Sw);
+    }
+
+    #[test]
+    fn test_render_fills_placeholders() {
+        let message = NotificationTemplates::render(
+            TemplateKind::PaymentRequestPaid,
+            Locale::En,
+            &[("payer", "Amina".to_string()), ("amount", "KES 500.00".to_string()), ("reference", "Lunch".to_string())],
+        );
+        assert_eq!(message, "Amina has paid your request for KES 500.00. Ref: Lunch.");
+    }
+}
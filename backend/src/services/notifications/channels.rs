@@ -0,0 +1,237 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
+use sqlx::SqlitePool;
+use std::env;
+use std::time::Duration;
+
+use super::Notification;
+use crate::models::{NotificationPreferences, User};
+use crate::services::mailer::{mailer_from_env, EmailMessage, Mailer};
+use crate::services::notification_templates::Locale;
+use crate::services::outbound::webhook_client;
+use crate::services::signing::webhook_signature_header;
+use crate::services::SmsService;
+
+pub type ChannelError = Box<dyn std::error::Error + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channel {
+    Sms,
+    Email,
+    Push,
+    Webhook,
+}
+
+impl Channel {
+    pub fn enabled_in(&self, preferences: &NotificationPreferences) -> bool {
+        match self {
+            Channel::Sms => preferences.sms_enabled,
+            Channel::Email => preferences.email_enabled,
+            Channel::Push => preferences.push_enabled,
+            Channel::Webhook => preferences.webhook_enabled,
+        }
+    }
+}
+
+#[async_trait]
+pub trait NotificationChannel: Send + Sync {
+    fn kind(&self) -> Channel;
+
+    /// Interruptive channels are held back during the user's quiet hours.
+    fn respects_quiet_hours(&self) -> bool {
+        true
+    }
+
+    async fn send(
+        &self,
+        pool: &SqlitePool,
+        user: &User,
+        preferences: &NotificationPreferences,
+        notification: &Notification,
+    ) -> Result<(), ChannelError>;
+}
+
+fn locale_of(user: &User) -> Locale {
+    Locale::from_preference(Some(&user.preferred_language))
+}
+
+pub struct SmsChannel {
+    sms_service: SmsService,
+}
+
+impl SmsChannel {
+    pub fn new() -> Self {
+        Self { sms_service: SmsService::new() }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for SmsChannel {
+    fn kind(&self) -> Channel {
+        Channel::Sms
+    }
+
+    async fn send(
+        &self,
+        pool: &SqlitePool,
+        user: &User,
+        _preferences: &NotificationPreferences,
+        notification: &Notification,
+    ) -> Result<(), ChannelError> {
+        let Some(phone) = &user.phone_number else {
+            return Ok(());
+        };
+        self.sms_service.send(pool, phone, &notification.body(locale_of(user))).await?;
+        Ok(())
+    }
+}
+
+pub struct EmailChannel {
+    mailer: Box<dyn Mailer>,
+}
+
+impl EmailChannel {
+    pub fn new() -> Self {
+        Self { mailer: mailer_from_env() }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for EmailChannel {
+    fn kind(&self) -> Channel {
+        Channel::Email
+    }
+
+    fn respects_quiet_hours(&self) -> bool {
+        false
+    }
+
+    async fn send(
+        &self,
+        _pool: &SqlitePool,
+        user: &User,
+        _preferences: &NotificationPreferences,
+        notification: &Notification,
+    ) -> Result<(), ChannelError> {
+        let locale = locale_of(user);
+        self.mailer
+            .send(&EmailMessage {
+                to: user.email.clone(),
+                subject: notification.subject(locale).to_string(),
+                body: notification.body(locale),
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+/// FCM-style push: posts `{to, notification, data}` to `PUSH_GATEWAY_URL`
+/// with `Authorization: key=<PUSH_SERVER_KEY>`.
+pub struct PushChannel {
+    client: Client,
+    gateway_url: Option<String>,
+    server_key: String,
+}
+
+impl PushChannel {
+    pub fn new() -> Self {
+        Self {
+            client: Client::builder().timeout(Duration::from_secs(10)).build().unwrap_or_default(),
+            gateway_url: env::var("PUSH_GATEWAY_URL").ok(),
+            server_key: env::var("PUSH_SERVER_KEY").unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for PushChannel {
+    fn kind(&self) -> Channel {
+        Channel::Push
+    }
+
+    async fn send(
+        &self,
+        _pool: &SqlitePool,
+        user: &User,
+        preferences: &NotificationPreferences,
+        notification: &Notification,
+    ) -> Result<(), ChannelError> {
+        let (Some(gateway_url), Some(token)) = (&self.gateway_url, &preferences.push_token) else {
+            return Ok(());
+        };
+        let locale = locale_of(user);
+
+        let response = self
+            .client
+            .post(gateway_url)
+            .header("Authorization", format!("key={}", self.server_key))
+            .json(&json!({
+                "to": token,
+                "notification": {
+                    "title": notification.subject(locale),
+                    "body": notification.body(locale),
+                },
+                "data": notification.payload,
+            }))
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(format!("push gateway returned {}", response.status()).into());
+        }
+        Ok(())
+    }
+}
+
+/// Posts the raw event to the user's own webhook, signed with their secret.
+/// The URL must pass the outbound checks at send time too, since its host
+/// may have been re-pointed since it was saved.
+pub struct WebhookChannel;
+
+impl WebhookChannel {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+#[async_trait]
+impl NotificationChannel for WebhookChannel {
+    fn kind(&self) -> Channel {
+        Channel::Webhook
+    }
+
+    fn respects_quiet_hours(&self) -> bool {
+        false
+    }
+
+    async fn send(
+        &self,
+        _pool: &SqlitePool,
+        _user: &User,
+        preferences: &NotificationPreferences,
+        notification: &Notification,
+    ) -> Result<(), ChannelError> {
+        let (Some(url), Some(secret)) = (&preferences.webhook_url, &preferences.webhook_secret) else {
+            return Ok(());
+        };
+
+        let body = notification.payload.to_string();
+        let timestamp = chrono::Utc::now().timestamp();
+
+        let response = webhook_client(url, Duration::from_secs(10))
+            .await?
+            .post(url)
+            .header("Content-Type", "application/json")
+            .header("X-NovaPay-Event", notification.event_type)
+            .header("X-NovaPay-Signature", webhook_signature_header(secret, timestamp, &body))
+            .body(body)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(format!("webhook returned {}", response.status()).into());
+        }
+        Ok(())
+    }
+}
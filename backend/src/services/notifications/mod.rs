@@ -0,0 +1,703 @@
+pub mod channels;
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use sqlx::SqlitePool;
+
+use crate::models::{NotificationPreferences, UpdateNotificationPreferences, User};
+use crate::services::notification_templates::{format_amount, Locale, NotificationTemplates, TemplateKind};
+use crate::services::non_custodial::asset_code;
+use crate::services::{normalize_phone, JobPayload, Outbox, SmsService};
+
+pub use channels::{EmailChannel, NotificationChannel, PushChannel, SmsChannel, WebhookChannel};
+
+/// Something that happened in the domain that users may want to hear about.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DomainEvent {
+    TransferCompleted {
+        transaction_id: String,
+        sender_id: String,
+        recipient: String,
+        amount: f64,
+        currency: String,
+        tx_hash: String,
+    },
+    /// A transfer was given up on. Only partner webhooks hear about it: the
+    /// user was told by the failed request or by `WithdrawalFailed`.
+    TransferFailed {
+        transaction_id: String,
+        sender_id: String,
+        recipient: String,
+        amount: f64,
+        currency: String,
+        reason: String,
+    },
+    DepositCredited {
+        user_id: String,
+        amount: f64,
+        currency: String,
+        balance: f64,
+        reference: String,
+    },
+    WithdrawalFailed {
+        user_id: String,
+        amount: f64,
+        currency: String,
+        reason: String,
+    },
+    LoginFromNewDevice {
+        user_id: String,
+        device: String,
+        ip_address: Option<String>,
+    },
+    /// A multisig transaction is waiting for co-signers.
+    ApprovalRequested {
+        approval_id: String,
+        proposer_id: String,
+        signer_ids: Vec<String>,
+        kind: String,
+        source: String,
+        expires_at: chrono::DateTime<chrono::Utc>,
+    },
+    PayoutBatchFinished {
+        batch_id: String,
+        user_id: String,
+        reference: Option<String>,
+        paid: i64,
+        failed: i64,
+    },
+    /// A scheduled transfer found too little money in the wallet.
+    /// `retry_at` is when it tries again, `None` once it has given up.
+    ScheduledTransferFailed {
+        schedule_id: String,
+        user_id: String,
+        recipient: String,
+        amount: f64,
+        currency: String,
+        retry_at: Option<chrono::DateTime<chrono::Utc>>,
+    },
+    /// Someone asked `payer_id` for money.
+    PaymentRequested {
+        request_id: String,
+        requester_id: String,
+        payer_id: String,
+        amount: f64,
+        currency: String,
+        memo: Option<String>,
+    },
+    PaymentRequestPaid {
+        request_id: String,
+        requester_id: String,
+        payer_id: String,
+        amount: f64,
+        currency: String,
+        memo: Option<String>,
+    },
+    PaymentRequestDeclined {
+        request_id: String,
+        requester_id: String,
+        payer_id: String,
+        amount: f64,
+        currency: String,
+        memo: Option<String>,
+    },
+}
+
+impl DomainEvent {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            DomainEvent::TransferCompleted { .. } => "transfer.completed",
+            DomainEvent::TransferFailed { .. } => "transfer.failed",
+            DomainEvent::DepositCredited { .. } => "deposit.credited",
+            DomainEvent::WithdrawalFailed { .. } => "withdrawal.failed",
+            DomainEvent::LoginFromNewDevice { .. } => "login.new_device",
+            DomainEvent::ApprovalRequested { .. } => "approval.requested",
+            DomainEvent::PayoutBatchFinished { .. } => "payout_batch.finished",
+            DomainEvent::ScheduledTransferFailed { .. } => "scheduled_transfer.failed",
+            DomainEvent::PaymentRequested { .. } => "payment_request.created",
+            DomainEvent::PaymentRequestPaid { .. } => "payment_request.paid",
+            DomainEvent::PaymentRequestDeclined { .. } => "payment_request.declined",
+        }
+    }
+}
+
+/// A rendered-on-demand message for one recipient of an event.
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub event_type: &'static str,
+    pub kind: TemplateKind,
+    pub vars: Vec<(&'static str, String)>,
+    /// Security notices are delivered even during quiet hours.
+    pub critical: bool,
+    pub payload: serde_json::Value,
+}
+
+impl Notification {
+    pub fn subject(&self, locale: Locale) -> &'static str {
+        NotificationTemplates::subject(self.kind, locale)
+    }
+
+    pub fn body(&self, locale: Locale) -> String {
+        NotificationTemplates::render(self.kind, locale, &self.vars)
+    }
+}
+
+enum Recipient {
+    User(Box<User>),
+    /// Someone without a NovaPay account, reachable only by SMS.
+    Phone(String),
+}
+
+pub struct NotificationDispatcher {
+    sms_service: SmsService,
+    channels: Vec<Box<dyn NotificationChannel>>,
+}
+
+impl NotificationDispatcher {
+    pub fn new() -> Self {
+        Self {
+            sms_service: SmsService::new(),
+            channels: vec![
+                Box::new(SmsChannel::new()),
+                Box::new(EmailChannel::new()),
+                Box::new(PushChannel::new()),
+                Box::new(WebhookChannel::new()),
+            ],
+        }
+    }
+
+    /// Fans an event out to every affected user on the channels they have
+    /// enabled. Channel failures are logged and never fail the caller.
+    pub async fn dispatch(&self, pool: &SqlitePool, event: &DomainEvent) -> Result<(), sqlx::Error> {
+        for (recipient, notification) in self.resolve(pool, event).await? {
+            match recipient {
+                Recipient::User(user) => self.deliver(pool, &user, &notification, event, false).await?,
+                Recipient::Phone(phone) => {
+                    let message = notification.body(Locale::DEFAULT);
+                    if let Err(e) = self.sms_service.send(pool, &phone, &message).await {
+                        println!("Failed to send SMS: {}", e);
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends a user's notification of an event that was held back during
+    /// their quiet hours, on the channels that held it.
+    pub async fn dispatch_held(
+        &self,
+        pool: &SqlitePool,
+        user_id: &str,
+        kind: TemplateKind,
+        event: &DomainEvent,
+    ) -> Result<(), sqlx::Error> {
+        for (recipient, notification) in self.resolve(pool, event).await? {
+            if let Recipient::User(user) = recipient {
+                if user.id == user_id && notification.kind == kind {
+                    self.deliver(pool, &user, &notification, event, true).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Sends on every enabled channel, or with `held` only on those that
+    /// respect quiet hours. During quiet hours those channels are skipped and
+    /// the notification is queued again for when the quiet hours end;
+    /// critical notifications go out at once.
+    async fn deliver(
+        &self,
+        pool: &SqlitePool,
+        user: &User,
+        notification: &Notification,
+        event: &DomainEvent,
+        held: bool,
+    ) -> Result<(), sqlx::Error> {
+        let preferences = Self::preferences(pool, &user.id).await?;
+        let quiet_until = match notification.critical {
+            true => None,
+            false => preferences.quiet_hours_end(chrono::Utc::now()),
+        };
+
+        let mut deferred = false;
+        for channel in &self.channels {
+            if !channel.kind().enabled_in(&preferences) || (held && !channel.respects_quiet_hours()) {
+                continue;
+            }
+            if quiet_until.is_some() && channel.respects_quiet_hours() {
+                deferred = true;
+                continue;
+            }
+            if let Err(e) = channel.send(pool, user, &preferences, notification).await {
+                println!("⚠️ {} notification via {:?} failed: {}", notification.event_type, channel.kind(), e);
+            }
+        }
+
+        if let (true, Some(run_at)) = (deferred, quiet_until) {
+            let payload = JobPayload::QuietHoursNotification {
+                user_id: user.id.clone(),
+                template: notification.kind,
+                event: event.clone(),
+            };
+            Outbox::enqueue_at(pool, &payload, run_at).await?;
+        }
+        Ok(())
+    }
+
+    async fn resolve(&self, pool: &SqlitePool, event: &DomainEvent) -> Result<Vec<(Recipient, Notification)>, sqlx::Error> {
+        let payload = json!({ "event": event.event_type(), "data": event });
+        let notification = |kind, vars, critical| Notification {
+            event_type: event.event_type(),
+            kind,
+            vars,
+            critical,
+            payload: payload.clone(),
+        };
+
+        let mut recipients = Vec::new();
+        match event {
+            DomainEvent::TransferCompleted { sender_id, recipient, amount, currency, tx_hash, .. } => {
+                let sender = Self::find_user(pool, sender_id).await?;
+                // recipient holds an email address, a phone number or, for
+                // transactions the user signed themselves, a Stellar address
+                let recipient_user = sqlx::query_as::<_, User>(
+                    r#"
+                    SELECT * FROM users
+                    WHERE email = ? OR phone_number = ?
+                       OR id = (SELECT user_id FROM external_accounts WHERE public_key = ? LIMIT 1)
+                    "#,
+                )
+                .bind(recipient)
+                .bind(recipient)
+                .bind(recipient)
+                .fetch_optional(pool)
+                .await?;
+                let amount = format_amount(*amount, currency);
+                let sender_name = sender
+                    .as_ref()
+                    .map(|u| u.full_name.clone())
+                    .unwrap_or_else(|| "a NovaPay user".to_string());
+                let recipient_name = recipient_user
+                    .as_ref()
+                    .map(|u| u.full_name.clone())
+                    .unwrap_or_else(|| recipient.clone());
+
+                let received = notification(
+                    TemplateKind::MoneyReceived,
+                    vec![("amount", amount.clone()), ("sender", sender_name)],
+                    false,
+                );
+                match recipient_user {
+                    Some(user) => recipients.push((Recipient::User(Box::new(user)), received)),
+                    None if normalize_phone(recipient).is_some() => {
+                        recipients.push((Recipient::Phone(recipient.clone()), received))
+                    }
+                    None => {}
+                }
+
+                if let Some(sender) = sender {
+                    recipients.push((
+                        Recipient::User(Box::new(sender)),
+                        notification(
+                            TemplateKind::MoneySent,
+                            vec![("amount", amount), ("recipient", recipient_name), ("reference", tx_hash.clone())],
+                            false,
+                        ),
+                    ));
+                }
+            }
+            DomainEvent::DepositCredited { user_id, amount, currency, balance, .. } => {
+                if let Some(user) = Self::find_user(pool, user_id).await? {
+                    recipients.push((
+                        Recipient::User(Box::new(user)),
+                        notification(
+                            TemplateKind::DepositConfirmed,
+                            vec![("amount", format_amount(*amount, currency)), ("balance", format_amount(*balance, "XLM"))],
+                            false,
+                        ),
+                    ));
+                }
+            }
+            DomainEvent::WithdrawalFailed { user_id, amount, currency, reason } => {
+                if let Some(user) = Self::find_user(pool, user_id).await? {
+                    recipients.push((
+                        Recipient::User(Box::new(user)),
+                        notification(
+                            TemplateKind::WithdrawalFailed,
+                            vec![("amount", format_amount(*amount, currency)), ("reason", reason.clone())],
+                            false,
+                        ),
+                    ));
+                }
+            }
+            DomainEvent::LoginFromNewDevice { user_id, device, ip_address } => {
+                if let Some(user) = Self::find_user(pool, user_id).await? {
+                    recipients.push((
+                        Recipient::User(Box::new(user)),
+                        notification(
+                            TemplateKind::LoginNewDevice,
+                            vec![
+                                ("device", device.clone()),
+                                ("ip", ip_address.clone().unwrap_or_else(|| "unknown IP".to_string())),
+                            ],
+                            true,
+                        ),
+                    ));
+                }
+            }
+            DomainEvent::ApprovalRequested { proposer_id, signer_ids, kind, source, expires_at, .. } => {
+                let proposer = Self::find_user(pool, proposer_id)
+                    .await?
+                    .map(|u| u.full_name)
+                    .unwrap_or_else(|| "A co-signer".to_string());
+                for signer_id in signer_ids {
+                    if let Some(user) = Self::find_user(pool, signer_id).await? {
+                        recipients.push((
+                            Recipient::User(Box::new(user)),
+                            notification(
+                                TemplateKind::ApprovalRequested,
+                                vec![
+                                    ("proposer", proposer.clone()),
+                                    ("operation", kind.replace('_', " ")),
+                                    ("account", source.clone()),
+                                    ("expires", expires_at.format("%Y-%m-%d %H:%M UTC").to_string()),
+                                ],
+                                false,
+                            ),
+                        ));
+                    }
+                }
+            }
+            DomainEvent::PayoutBatchFinished { batch_id, user_id, reference, paid, failed } => {
+                if let Some(user) = Self::find_user(pool, user_id).await? {
+                    recipients.push((
+                        Recipient::User(Box::new(user)),
+                        notification(
+                            TemplateKind::PayoutBatchFinished,
+                            vec![
+                                ("reference", reference.clone().unwrap_or_else(|| batch_id[..8].to_string())),
+                                ("paid", paid.to_string()),
+                                ("failed", failed.to_string()),
+                            ],
+                            false,
+                        ),
+                    ));
+                }
+            }
+            DomainEvent::ScheduledTransferFailed { user_id, recipient, amount, currency, retry_at, .. } => {
+                if let Some(user) = Self::find_user(pool, user_id).await? {
+                    let mut vars = vec![("amount", format_amount(*amount, currency)), ("recipient", recipient.clone())];
+                    let kind = match retry_at {
+                        Some(retry_at) => {
+                            vars.push(("retry", retry_at.format("%Y-%m-%d %H:%M UTC").to_string()));
+                            TemplateKind::ScheduledTransferRetrying
+                        }
+                        None => TemplateKind::ScheduledTransferMissed,
+                    };
+                    recipients.push((Recipient::User(Box::new(user)), notification(kind, vars, false)));
+                }
+            }
+            DomainEvent::PaymentRequested { request_id, requester_id, payer_id, amount, currency, memo } => {
+                let requester = Self::find_user(pool, requester_id)
+                    .await?
+                    .map(|u| u.full_name)
+                    .unwrap_or_else(|| "Someone".to_string());
+                if let Some(payer) = Self::find_user(pool, payer_id).await? {
+                    recipients.push((
+                        Recipient::User(Box::new(payer)),
+                        notification(
+                            TemplateKind::PaymentRequested,
+                            vec![
+                                ("requester", requester),
+                                ("amount", format_amount(*amount, &asset_code(currency))),
+                                ("reference", memo.clone().unwrap_or_else(|| request_id[..8].to_string())),
+                            ],
+                            false,
+                        ),
+                    ));
+                }
+            }
+            DomainEvent::PaymentRequestPaid { request_id, requester_id, payer_id, amount, currency, memo }
+            | DomainEvent::PaymentRequestDeclined { request_id, requester_id, payer_id, amount, currency, memo } => {
+                let kind = match event {
+                    DomainEvent::PaymentRequestPaid { .. } => TemplateKind::PaymentRequestPaid,
+                    _ => TemplateKind::PaymentRequestDeclined,
+                };
+                let payer = Self::find_user(pool, payer_id)
+                    .await?
+                    .map(|u| u.full_name)
+                    .unwrap_or_else(|| "Someone".to_string());
+                if let Some(requester) = Self::find_user(pool, requester_id).await? {
+                    recipients.push((
+                        Recipient::User(Box::new(requester)),
+                        notification(
+                            kind,
+                            vec![
+                                ("payer", payer),
+                                ("amount", format_amount(*amount, &asset_code(currency))),
+                                ("reference", memo.clone().unwrap_or_else(|| request_id[..8].to_string())),
+                            ],
+                            false,
+                        ),
+                    ));
+                }
+            }
+            DomainEvent::TransferFailed { .. } => {}
+        }
+
+        Ok(recipients)
+    }
+
+    async fn find_user(pool: &SqlitePool, user_id: &str) -> Result<Option<User>, sqlx::Error> {
+        sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    pub async fn preferences(pool: &SqlitePool, user_id: &str) -> Result<NotificationPreferences, sqlx::Error> {
+        let preferences = sqlx::query_as::<_, NotificationPreferences>(
+            "SELECT * FROM notification_preferences WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?;
+
+        Ok(preferences.unwrap_or_else(|| NotificationPreferences::defaults_for(user_id)))
+    }
+
+    pub async fn update_preferences(
+        pool: &SqlitePool,
+        user_id: &str,
+        update: &UpdateNotificationPreferences,
+    ) -> Result<NotificationPreferences, sqlx::Error> {
+        let current = Self::preferences(pool, user_id).await?;
+
+        sqlx::query_as::<_, NotificationPreferences>(
+            r#"
+            INSERT INTO notification_preferences (
+                user_id, sms_enabled, email_enabled, push_enabled, webhook_enabled, push_token,
+                webhook_url, webhook_secret, quiet_hours_start, quiet_hours_end, utc_offset_minutes
+            )
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT (user_id) DO UPDATE SET
+                sms_enabled = excluded.sms_enabled,
+                email_enabled = excluded.email_enabled,
+                push_enabled = excluded.push_enabled,
+                webhook_enabled = excluded.webhook_enabled,
+                push_token = excluded.push_token,
+                webhook_url = excluded.webhook_url,
+                webhook_secret = excluded.webhook_secret,
+                quiet_hours_start = excluded.quiet_hours_start,
+                quiet_hours_end = excluded.quiet_hours_end,
+                utc_offset_minutes = excluded.utc_offset_minutes,
+                updated_at = CURRENT_TIMESTAMP
+            RETURNING user_id, sms_enabled, email_enabled, push_enabled, webhook_enabled, push_token,
+                webhook_url, webhook_secret, quiet_hours_start, quiet_hours_end, utc_offset_minutes
+            "#,
+        )
+        .bind(user_id)
+        .bind(update.sms_enabled.unwrap_or(current.sms_enabled))
+        .bind(update.email_enabled.unwrap_or(current.email_enabled))
+        .bind(update.push_enabled.unwrap_or(current.push_enabled))
+        .bind(update.webhook_enabled.unwrap_or(current.webhook_enabled))
+        .bind(update.push_token.as_ref().or(current.push_token.as_ref()))
+        .bind(update.webhook_url.as_ref().or(current.webhook_url.as_ref()))
+        .bind(update.webhook_secret.as_ref().or(current.webhook_secret.as_ref()))
+        .bind(update.quiet_hours_start.as_ref().or(current.quiet_hours_start.as_ref()))
+        .bind(update.quiet_hours_end.as_ref().or(current.quiet_hours_end.as_ref()))
+        .bind(update.utc_offset_minutes.unwrap_or(current.utc_offset_minutes))
+        .fetch_one(pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use async_trait::async_trait;
+    use chrono::{Duration, TimeZone, Timelike, Utc};
+
+    use super::channels::{Channel, ChannelError};
+    use super::*;
+    use crate::models::Job;
+    use crate::test_support::{insert_user, memory_pool};
+
+    /// Records which channels were asked to send.
+    struct RecordingChannel {
+        kind: Channel,
+        quiet: bool,
+        sent: Arc<Mutex<Vec<Channel>>>,
+    }
+
+    #[async_trait]
+    impl NotificationChannel for RecordingChannel {
+        fn kind(&self) -> Channel {
+            self.kind
+        }
+
+        fn respects_quiet_hours(&self) -> bool {
+            self.quiet
+        }
+
+        async fn send(
+            &self,
+            _pool: &SqlitePool,
+            _user: &User,
+            _preferences: &NotificationPreferences,
+            _notification: &Notification,
+        ) -> Result<(), ChannelError> {
+            self.sent.lock().unwrap().push(self.kind);
+            Ok(())
+        }
+    }
+
+    fn recording_dispatcher() -> (NotificationDispatcher, Arc<Mutex<Vec<Channel>>>) {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let channel = |kind, quiet| -> Box<dyn NotificationChannel> {
+            Box::new(RecordingChannel { kind, quiet, sent: sent.clone() })
+        };
+        let dispatcher = NotificationDispatcher {
+            sms_service: SmsService::new(),
+            channels: vec![channel(Channel::Sms, true), channel(Channel::Email, false), channel(Channel::Push, true)],
+        };
+        (dispatcher, sent)
+    }
+
+    /// Sets a user's preferences with quiet hours of 22:00–06:00 and an
+    /// offset that puts them at 23:30 local time right now.
+    async fn in_quiet_hours(pool: &SqlitePool, user_id: &str, sms: bool, email: bool, push: bool) {
+        let now = Utc::now();
+        let minute_now = (now.hour() * 60 + now.minute()) as i64;
+        let offset = (23 * 60 + 30 - minute_now).rem_euclid(1440);
+        sqlx::query(
+            "INSERT INTO notification_preferences (user_id, sms_enabled, email_enabled, push_enabled, webhook_enabled, quiet_hours_start, quiet_hours_end, utc_offset_minutes)
+             VALUES (?, ?, ?, ?, 0, '22:00', '06:00', ?)",
+        )
+        .bind(user_id)
+        .bind(sms)
+        .bind(email)
+        .bind(push)
+        .bind(offset)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    fn deposit(user_id: &str) -> DomainEvent {
+        DomainEvent::DepositCredited {
+            user_id: user_id.to_string(),
+            amount: 100.0,
+            currency: "KES".to_string(),
+            balance: 10.0,
+            reference: "dep_1".to_string(),
+        }
+    }
+
+    async fn queued(pool: &SqlitePool) -> Vec<Job> {
+        sqlx::query_as::<_, Job>("SELECT * FROM jobs").fetch_all(pool).await.unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours_crossing_midnight() {
+        let mut preferences = NotificationPreferences::defaults_for("u1");
+        preferences.quiet_hours_start = Some("22:00".to_string());
+        preferences.quiet_hours_end = Some("06:00".to_string());
+        preferences.utc_offset_minutes = 180;
+
+        // 20:30 UTC is 23:30 in Nairobi: quiet until 06:00 local, 03:00 UTC.
+        let late = Utc.with_ymd_and_hms(2026, 3, 1, 20, 30, 0).unwrap();
+        assert_eq!(preferences.quiet_hours_end(late), Some(Utc.with_ymd_and_hms(2026, 3, 2, 3, 0, 0).unwrap()));
+
+        // 01:15 local, after midnight, still inside the window.
+        let early = Utc.with_ymd_and_hms(2026, 3, 1, 22, 15, 0).unwrap();
+        assert_eq!(preferences.quiet_hours_end(early), Some(Utc.with_ymd_and_hms(2026, 3, 2, 3, 0, 0).unwrap()));
+
+        // 06:00 and noon local are outside it.
+        assert_eq!(preferences.quiet_hours_end(Utc.with_ymd_and_hms(2026, 3, 2, 3, 0, 0).unwrap()), None);
+        assert_eq!(preferences.quiet_hours_end(Utc.with_ymd_and_hms(2026, 3, 2, 9, 0, 0).unwrap()), None);
+
+        // A window within one day.
+        preferences.quiet_hours_start = Some("13:00".to_string());
+        preferences.quiet_hours_end = Some("14:00".to_string());
+        let lunch = Utc.with_ymd_and_hms(2026, 3, 2, 10, 20, 0).unwrap();
+        assert_eq!(preferences.quiet_hours_end(lunch), Some(Utc.with_ymd_and_hms(2026, 3, 2, 11, 0, 0).unwrap()));
+        assert_eq!(preferences.quiet_hours_end(Utc.with_ymd_and_hms(2026, 3, 2, 20, 30, 0).unwrap()), None);
+    }
+
+    #[tokio::test]
+    async fn test_quiet_hours_defer_until_the_window_ends() {
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+        in_quiet_hours(&pool, "u1", true, true, true).await;
+        let (dispatcher, sent) = recording_dispatcher();
+
+        let event = deposit("u1");
+        dispatcher.dispatch(&pool, &event).await.unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![Channel::Email]);
+
+        let jobs = queued(&pool).await;
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].kind, "quiet_hours_notification");
+        let until_end = jobs[0].run_at - Utc::now();
+        assert!(until_end > Duration::minutes(385) && until_end <= Duration::minutes(390), "{:?}", until_end);
+
+        // Once the window is over the held channels get it, and email doesn't again.
+        sqlx::query("UPDATE notification_preferences SET quiet_hours_start = NULL, quiet_hours_end = NULL")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let JobPayload::QuietHoursNotification { user_id, template, event } =
+            serde_json::from_str::<JobPayload>(&jobs[0].payload).unwrap()
+        else {
+            panic!("unexpected payload {}", jobs[0].payload);
+        };
+        dispatcher.dispatch_held(&pool, &user_id, template, &event).await.unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![Channel::Email, Channel::Sms, Channel::Push]);
+        assert_eq!(queued(&pool).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_opted_out_channels_are_skipped() {
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+        sqlx::query(
+            "INSERT INTO notification_preferences (user_id, sms_enabled, email_enabled, push_enabled, webhook_enabled)
+             VALUES ('u1', 0, 1, 0, 0)",
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        let (dispatcher, sent) = recording_dispatcher();
+
+        dispatcher.dispatch(&pool, &deposit("u1")).await.unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![Channel::Email]);
+
+        // Nothing is held for channels the user turned off.
+        sqlx::query("DELETE FROM notification_preferences").execute(&pool).await.unwrap();
+        in_quiet_hours(&pool, "u1", false, true, false).await;
+        dispatcher.dispatch(&pool, &deposit("u1")).await.unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![Channel::Email, Channel::Email]);
+        assert!(queued(&pool).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_critical_events_bypass_quiet_hours() {
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+        in_quiet_hours(&pool, "u1", true, true, true).await;
+        let (dispatcher, sent) = recording_dispatcher();
+
+        let event = DomainEvent::LoginFromNewDevice {
+            user_id: "u1".to_string(),
+            device: "Firefox on Linux".to_string(),
+            ip_address: Some("203.0.113.7".to_string()),
+        };
+        dispatcher.dispatch(&pool, &event).await.unwrap();
+        assert_eq!(*sent.lock().unwrap(), vec![Channel::Sms, Channel::Email, Channel::Push]);
+        assert!(queued(&pool).await.is_empty());
+    }
+}
@@ -0,0 +1,299 @@
+use sqlx::SqlitePool;
+use std::env;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::services::notification_templates::{Locale, NotificationTemplates, TemplateKind};
+use crate::services::signing::{hmac_sha256_hex, required_secret};
+use crate::services::sms::{SmsError, SmsService};
+
+#[derive(Error, Debug)]
+pub enum OtpError {
+    #[error("No active code; request a new one")]
+    NotFound,
+    #[error("Incorrect code")]
+    InvalidCode,
+    #[error("Too many incorrect attempts; request a new code")]
+    TooManyAttempts,
+    #[error("A code was sent recently; try again in {0} seconds")]
+    Cooldown(i64),
+    #[error("Failed to send code: {0}")]
+    Sms(#[from] SmsError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OtpPurpose {
+    VerifyPhone,
+    Login,
+    StepUp,
+    ResetPin,
+}
+
+impl OtpPurpose {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OtpPurpose::VerifyPhone => "verify_phone",
+            OtpPurpose::Login => "login",
+            OtpPurpose::StepUp => "step_up",
+            OtpPurpose::ResetPin => "reset_pin",
+        }
+    }
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct OtpCode {
+    id: String,
+    code_hash: String,
+    attempts: i64,
+    max_attempts: i64,
+    user_id: Option<String>,
+}
+
+/// Six-digit codes delivered by SMS. Only an HMAC of the code is stored, each
+/// code is single use, and a new code replaces any outstanding one.
+pub struct OtpService {
+    sms_service: SmsService,
+    secret: String,
+    ttl_minutes: i64,
+    max_attempts: i64,
+    resend_cooldown_secs: i64,
+}
+
+impl OtpService {
+    pub fn new() -> Self {
+        let env_i64 = |name: &str, default: i64| env::var(name).ok().and_then(|v| v.parse().ok()).unwrap_or(default);
+
+        Self {
+            sms_service: SmsService::new(),
+            secret: required_secret("OTP_SECRET").expect("OTP_SECRET is checked at startup"),
+            ttl_minutes: env_i64("OTP_TTL_MINUTES", 10),
+            max_attempts: env_i64("OTP_MAX_ATTEMPTS", 5),
+            resend_cooldown_secs: env_i64("OTP_RESEND_COOLDOWN_SECS", 60),
+        }
+    }
+
+    fn hash(&self, phone_number: &str, purpose: OtpPurpose, code: &str) -> String {
+        let message = format!("{}:{}:{}", phone_number, purpose.as_str(), code);
+        hmac_sha256_hex(self.secret.as_bytes(), message.as_bytes())
+    }
+
+    /// Issues a code for `phone_number` (already E.164) and sends it by SMS
+    /// in `locale`.
+    pub async fn send(
+        &self,
+        pool: &SqlitePool,
+        phone_number: &str,
+        purpose: OtpPurpose,
+        user_id: Option<&str>,
+        locale: Locale,
+    ) -> Result<(), OtpError> {
+        let now = chrono::Utc::now();
+
+        let last_sent: Option<chrono::DateTime<chrono::Utc>> = sqlx::query_scalar(
+            "SELECT MAX(created_at) FROM otp_codes WHERE phone_number = ? AND purpose = ?",
+        )
+        .bind(phone_number)
+        .bind(purpose.as_str())
+        .fetch_one(pool)
+        .await?;
+        if let Some(last_sent) = last_sent {
+            let wait = self.resend_cooldown_secs - (now - last_sent).num_seconds();
+            if wait > 0 {
+                return Err(OtpError::Cooldown(wait));
+            }
+        }
+
+        let code = format!("{:06}", rand::random::<u32>() % 1_000_000);
+
+        let mut tx = pool.begin().await?;
+        sqlx::query("UPDATE otp_codes SET consumed_at = ? WHERE phone_number = ? AND purpose = ? AND consumed_at IS NULL")
+            .bind(now)
+            .bind(phone_number)
+            .bind(purpose.as_str())
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query(
+            r#"
+            INSERT INTO otp_codes (id, phone_number, purpose, user_id, code_hash, max_attempts, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(phone_number)
+        .bind(purpose.as_str())
+        .bind(user_id)
+        .bind(self.hash(phone_number, purpose, &code))
+        .bind(self.max_attempts)
+        .bind(now + chrono::Duration::minutes(self.ttl_minutes))
+        .bind(now)
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        let message = NotificationTemplates::render(
+            TemplateKind::Otp,
+            locale,
+            &[("code", code), ("minutes", self.ttl_minutes.to_string())],
+        );
+        self.sms_service.send(pool, phone_number, &message).await?;
+        Ok(())
+    }
+
+    /// Checks `code` against the outstanding code for `phone_number` and
+    /// consumes it on success. Returns the user id the code was issued for.
+    pub async fn verify(
+        &self,
+        pool: &SqlitePool,
+        phone_number: &str,
+        purpose: OtpPurpose,
+        code: &str,
+    ) -> Result<Option<String>, OtpError> {
+        let now = chrono::Utc::now();
+
+        let otp_id: String = sqlx::query_scalar(
+            r#"
+            SELECT id FROM otp_codes
+            WHERE phone_number = ? AND purpose = ? AND consumed_at IS NULL AND expires_at > ?
+            ORDER BY created_at DESC
+            LIMIT 1
+            "#,
+        )
+        .bind(phone_number)
+        .bind(purpose.as_str())
+        .bind(now)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(OtpError::NotFound)?;
+
+        // Take the attempt before checking the code, so concurrent guesses
+        // can't get past the limit between a read and the increment
+        let otp = sqlx::query_as::<_, OtpCode>(
+            r#"
+            UPDATE otp_codes SET attempts = attempts + 1
+            WHERE id = ? AND consumed_at IS NULL AND attempts < max_attempts
+            RETURNING id, code_hash, attempts, max_attempts, user_id
+            "#,
+        )
+        .bind(&otp_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(OtpError::TooManyAttempts)?;
+
+        if otp.code_hash != self.hash(phone_number, purpose, code.trim()) {
+            return Err(if otp.attempts >= otp.max_attempts {
+                OtpError::TooManyAttempts
+            } else {
+                OtpError::InvalidCode
+            });
+        }
+
+        // Single use, even if two requests race with the same code
+        let consumed = sqlx::query("UPDATE otp_codes SET consumed_at = ? WHERE id = ? AND consumed_at IS NULL")
+            .bind(now)
+            .bind(&otp.id)
+            .execute(pool)
+            .await?
+            .rows_affected();
+        if consumed == 0 {
+            return Err(OtpError::NotFound);
+        }
+
+        Ok(otp.user_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::memory_pool;
+
+    const PHONE: &str = "+254712345678";
+
+    fn service() -> OtpService {
+        env::set_var("OTP_SECRET", "otp-test-secret-0123456789abcdef0123");
+        OtpService::new()
+    }
+
+    /// Stores `code` as the outstanding login code, as `send` would.
+    async fn issue(pool: &SqlitePool, service: &OtpService, code: &str, expires_in: chrono::Duration) {
+        let now = chrono::Utc::now();
+        sqlx::query(
+            r#"
+            INSERT INTO otp_codes (id, phone_number, purpose, code_hash, max_attempts, expires_at, created_at)
+            VALUES (?, ?, 'login', ?, 3, ?, ?)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(PHONE)
+        .bind(service.hash(PHONE, OtpPurpose::Login, code))
+        .bind(now + expires_in)
+        .bind(now)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_code_is_single_use() {
+        let pool = memory_pool().await;
+        let service = service();
+        issue(&pool, &service, "123456", chrono::Duration::minutes(10)).await;
+
+        assert!(service.verify(&pool, PHONE, OtpPurpose::Login, " 123456 ").await.is_ok());
+        assert!(matches!(
+            service.verify(&pool, PHONE, OtpPurpose::Login, "123456").await,
+            Err(OtpError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_expired_code_is_refused() {
+        let pool = memory_pool().await;
+        let service = service();
+        issue(&pool, &service, "123456", chrono::Duration::seconds(-1)).await;
+
+        assert!(matches!(
+            service.verify(&pool, PHONE, OtpPurpose::Login, "123456").await,
+            Err(OtpError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_attempt_limit() {
+        let pool = memory_pool().await;
+        let service = service();
+        issue(&pool, &service, "123456", chrono::Duration::minutes(10)).await;
+
+        let guess = || service.verify(&pool, PHONE, OtpPurpose::Login, "000000");
+        assert!(matches!(guess().await, Err(OtpError::InvalidCode)));
+        assert!(matches!(guess().await, Err(OtpError::InvalidCode)));
+        assert!(matches!(guess().await, Err(OtpError::TooManyAttempts)));
+        // The right code is no good once the attempts are used up
+        assert!(matches!(
+            service.verify(&pool, PHONE, OtpPurpose::Login, "123456").await,
+            Err(OtpError::TooManyAttempts)
+        ));
+        // Codes are kept apart by purpose
+        assert!(matches!(
+            service.verify(&pool, PHONE, OtpPurpose::StepUp, "123456").await,
+            Err(OtpError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_guesses_share_the_limit() {
+        let pool = memory_pool().await;
+        let service = service();
+        issue(&pool, &service, "123456", chrono::Duration::minutes(10)).await;
+
+        let guess = |code: &'static str| service.verify(&pool, PHONE, OtpPurpose::Login, code);
+        let results = tokio::join!(guess("000001"), guess("000002"), guess("000003"), guess("000004"), guess("123456"));
+        let results = [results.0, results.1, results.2, results.3, results.4];
+        let attempts: i64 = sqlx::query_scalar("SELECT attempts FROM otp_codes").fetch_one(&pool).await.unwrap();
+        assert_eq!(attempts, 3);
+        // Only three guesses were checked; the others were turned away
+        assert!(results.iter().filter(|r| matches!(r, Err(OtpError::TooManyAttempts))).count() >= 2);
+    }
+}
@@ -0,0 +1,161 @@
+use reqwest::{redirect, Client, Url};
+use std::env;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::Duration;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum OutboundUrlError {
+    #[error("Invalid URL")]
+    Invalid,
+    #[error("Webhook URLs must use https")]
+    NotHttps,
+    #[error("Host could not be resolved: {0}")]
+    Unresolvable(String),
+    #[error("Host resolves to a private address: {0}")]
+    PrivateAddress(IpAddr),
+}
+
+/// Hosts from `WEBHOOK_ALLOWED_HOSTS` (comma-separated) that may be posted to
+/// over plain http and at private addresses, e.g. a local receiver during
+/// development.
+pub fn allowed_hosts() -> Vec<String> {
+    env::var("WEBHOOK_ALLOWED_HOSTS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|host| host.trim().to_lowercase())
+        .filter(|host| !host.is_empty())
+        .collect()
+}
+
+/// Checks that a user-supplied URL is safe for the server to post to: https,
+/// and a host that resolves only to public addresses, unless the host is in
+/// `allowed`. Returns the URL and the addresses it resolved to.
+pub async fn check_url(url: &str, allowed: &[String]) -> Result<(Url, Vec<SocketAddr>), OutboundUrlError> {
+    let url = Url::parse(url).map_err(|_| OutboundUrlError::Invalid)?;
+    let host = url.host_str().ok_or(OutboundUrlError::Invalid)?.to_lowercase();
+    let port = url.port_or_known_default().ok_or(OutboundUrlError::Invalid)?;
+    let trusted = allowed.contains(&host);
+
+    match url.scheme() {
+        "https" => {}
+        "http" if trusted => {}
+        "http" => return Err(OutboundUrlError::NotHttps),
+        _ => return Err(OutboundUrlError::Invalid),
+    }
+
+    let lookup = host.trim_start_matches('[').trim_end_matches(']');
+    let addresses: Vec<SocketAddr> = tokio::net::lookup_host((lookup, port))
+        .await
+        .map_err(|_| OutboundUrlError::Unresolvable(host.clone()))?
+        .collect();
+    if addresses.is_empty() {
+        return Err(OutboundUrlError::Unresolvable(host));
+    }
+    if !trusted {
+        if let Some(address) = addresses.iter().find(|a| !is_public(a.ip())) {
+            return Err(OutboundUrlError::PrivateAddress(address.ip()));
+        }
+    }
+    Ok((url, addresses))
+}
+
+/// A client for posting to `url` after `check_url` passes. It connects only
+/// to the addresses that were checked, so the host can't be re-pointed at a
+/// private address in between, and it doesn't follow redirects.
+pub async fn webhook_client(url: &str, timeout: Duration) -> Result<Client, OutboundUrlError> {
+    let (url, addresses) = check_url(url, &allowed_hosts()).await?;
+    let mut builder = Client::builder().timeout(timeout).redirect(redirect::Policy::none());
+    if let Some(host) = url.domain() {
+        builder = builder.resolve_to_addrs(host, &addresses);
+    }
+    builder.build().map_err(|_| OutboundUrlError::Invalid)
+}
+
+/// False for loopback, private, link-local, shared, documentation,
+/// multicast and other special-purpose ranges.
+pub fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_v4(mapped),
+            None => is_public_v6(ip),
+        },
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b)) // shared address space
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (18..20).contains(&b)) // benchmarking
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00 // unique local
+        || (first & 0xffc0) == 0xfe80 // link-local
+        || first == 0x2001 && ip.segments()[1] == 0x0db8 // documentation
+        || first == 0x0064 && ip.segments()[1] == 0xff9b) // NAT64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_private_addresses() {
+        for ip in [
+            "127.0.0.1", "10.1.2.3", "172.16.0.1", "192.168.1.1", "169.254.169.254", "100.64.0.1", "0.0.0.0",
+            "::1", "::", "fd00::1", "fe80::1", "::ffff:127.0.0.1", "::ffff:169.254.169.254",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{} should be private", ip);
+        }
+        for ip in ["8.8.8.8", "1.1.1.1", "2606:4700:4700::1111", "::ffff:8.8.8.8"] {
+            assert!(is_public(ip.parse().unwrap()), "{} should be public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_check_url() {
+        assert_eq!(check_url("not a url", &[]).await.unwrap_err(), OutboundUrlError::Invalid);
+        assert_eq!(check_url("ftp://1.1.1.1/", &[]).await.unwrap_err(), OutboundUrlError::Invalid);
+        assert_eq!(check_url("http://1.1.1.1/hook", &[]).await.unwrap_err(), OutboundUrlError::NotHttps);
+        assert_eq!(
+            check_url("https://169.254.169.254/latest/meta-data", &[]).await.unwrap_err(),
+            OutboundUrlError::PrivateAddress("169.254.169.254".parse().unwrap())
+        );
+        assert!(matches!(
+            check_url("https://localhost:8443/hook", &[]).await.unwrap_err(),
+            OutboundUrlError::PrivateAddress(_)
+        ));
+        assert!(matches!(
+            check_url("https://[::1]/hook", &[]).await.unwrap_err(),
+            OutboundUrlError::PrivateAddress(_)
+        ));
+
+        let (_, addresses) = check_url("https://1.1.1.1/hook", &[]).await.unwrap();
+        assert_eq!(addresses, ["1.1.1.1:443".parse().unwrap()]);
+
+        // Listed hosts may use http and private addresses
+        let allowed = ["127.0.0.1".to_string()];
+        let (_, addresses) = check_url("http://127.0.0.1:8098/hook", &allowed).await.unwrap();
+        assert_eq!(addresses, ["127.0.0.1:8098".parse().unwrap()]);
+        assert_eq!(
+            check_url("http://localhost:8098/hook", &allowed).await.unwrap_err(),
+            OutboundUrlError::NotHttps
+        );
+    }
+}
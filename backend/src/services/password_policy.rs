@@ -0,0 +1,134 @@
+use std::collections::HashSet;
+use std::sync::OnceLock;
+use thiserror::Error;
+
+/// Bundled list of the most common breached passwords, lowercase.
+const BREACHED_PASSWORDS: &str = include_str!("../../data/breached_passwords.txt");
+
+const MIN_LENGTH: usize = 10;
+const MAX_LENGTH: usize = 128;
+/// Estimated entropy a password needs after discounting predictable parts.
+const MIN_ENTROPY_BITS: f64 = 40.0;
+
+/// Keyboard rows and alphabets; runs of three or more along these are as
+/// guessable as a single character.
+const SEQUENCES: &[&str] = &["abcdefghijklmnopqrstuvwxyz", "0123456789", "qwertyuiop", "asdfghjkl", "zxcvbnm"];
+
+#[derive(Error, Debug, PartialEq)]
+pub enum PasswordPolicyError {
+    #[error("Password must be at least {MIN_LENGTH} characters")]
+    TooShort,
+    #[error("Password must be at most {MAX_LENGTH} characters")]
+    TooLong,
+    #[error("This password has appeared in a data breach; choose a different one")]
+    Breached,
+    #[error("Password is too easy to guess; use a longer passphrase or mix in unrelated words")]
+    TooWeak,
+}
+
+fn breached_passwords() -> &'static HashSet<&'static str> {
+    static LIST: OnceLock<HashSet<&'static str>> = OnceLock::new();
+    LIST.get_or_init(|| {
+        BREACHED_PASSWORDS
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .collect()
+    })
+}
+
+pub struct PasswordPolicy;
+
+impl PasswordPolicy {
+    /// Checks a new password. `context` holds values an attacker would try
+    /// first for this account, such as the email address and name.
+    pub fn check(password: &str, context: &[&str]) -> Result<(), PasswordPolicyError> {
+        let length = password.chars().count();
+        if length < MIN_LENGTH {
+            return Err(PasswordPolicyError::TooShort);
+        }
+        if length > MAX_LENGTH {
+            return Err(PasswordPolicyError::TooLong);
+        }
+        if Self::is_breached(password) {
+            return Err(PasswordPolicyError::Breached);
+        }
+        if Self::estimate_entropy_bits(password, context) < MIN_ENTROPY_BITS {
+            return Err(PasswordPolicyError::TooWeak);
+        }
+        Ok(())
+    }
+
+    /// Matches the password itself and its base word, so `Password123!`
+    /// is caught by `password`.
+    fn is_breached(password: &str) -> bool {
+        let lowered = password.to_lowercase();
+        let base = lowered.trim_end_matches(|c: char| !c.is_alphabetic());
+        let list = breached_passwords();
+        list.contains(lowered.as_str()) || (base.len() >= 4 && list.contains(base))
+    }
+
+    /// Rough guess-resistance estimate: context words, repeated characters
+    /// and keyboard or alphabet sequences count as one character each, and
+    /// what remains is scored by the size of the character classes used.
+    pub fn estimate_entropy_bits(password: &str, context: &[&str]) -> f64 {
+        let mut lowered = password.to_lowercase();
+
+        // Email local part, name parts and the product name are all guessable
+        let mut words: Vec<String> = vec!["novapay".to_string()];
+        for value in context {
+            let value = value.to_lowercase();
+            let value = value.split('@').next().unwrap_or_default();
+            words.extend(
+                value
+                    .split(|c: char| !c.is_alphanumeric())
+                    .filter(|w| w.len() >= 3)
+                    .map(str::to_string),
+            );
+        }
+        for word in &words {
+            lowered = lowered.replace(word.as_str(), "\u{1}");
+        }
+
+        let chars: Vec<char> = lowered.chars().collect();
+        let mut remaining = 0usize;
+        let mut i = 0;
+        while i < chars.len() {
+            let mut run = 1;
+            while i + run < chars.len() && (chars[i + run] == chars[i] || Self::follows(chars[i + run - 1], chars[i + run])) {
+                run += 1;
+            }
+            // A run of two is kept as two characters; longer runs collapse
+            remaining += if run >= 3 { 1 } else { run };
+            i += run;
+        }
+
+        let mut charset = 0u32;
+        if password.chars().any(|c| c.is_ascii_lowercase()) {
+            charset += 26;
+        }
+        if password.chars().any(|c| c.is_ascii_uppercase()) {
+            charset += 26;
+        }
+        if password.chars().any(|c| c.is_ascii_digit()) {
+            charset += 10;
+        }
+        if password.chars().any(|c| !c.is_ascii_alphanumeric()) {
+            charset += 33;
+        }
+
+        remaining as f64 * f64::from(charset.max(1)).log2()
+    }
+
+    /// Whether `next` comes right after `previous` on a keyboard row or in
+    /// the alphabet, in either direction.
+    fn follows(previous: char, next: char) -> bool {
+        SEQUENCES.iter().any(|sequence| {
+            let position = |c: char| sequence.find(c);
+            match (position(previous), position(next)) {
+                (Some(a), Some(b)) => a.abs_diff(b) == 1,
+                _ => false,
+            }
+        })
+    }
+}
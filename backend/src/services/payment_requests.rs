@@ -0,0 +1,761 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use chrono::{Duration, Utc};
+use qrcode::render::svg;
+use qrcode::{Color, QrCode};
+use rand::Rng;
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::env;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{CreatePaymentRequest, CreateTransaction, PaymentRequest, PaymentRequestView};
+use crate::services::invoices::{payment_amount, payment_asset, AMOUNT_EPSILON};
+use crate::services::non_custodial::asset_code;
+use crate::services::payouts::receiving_account;
+use crate::services::stellar_tx::StellarTxBuilder;
+use crate::services::{
+    normalize_phone, DomainEvent, JobPayload, Outbox, PaymentOutcome, Sep10Service, TransactionService,
+    WalletPaymentError,
+};
+
+#[derive(Error, Debug)]
+pub enum PaymentRequestError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Invalid currency: {0}")]
+    InvalidCurrency(String),
+    #[error("No NovaPay user with this email or phone number")]
+    UnknownPayer,
+    #[error("You can't request money from yourself")]
+    OwnRequest,
+    #[error("Create a wallet to receive payments first")]
+    NoReceivingAccount,
+    #[error("Only the person asked to pay can do this")]
+    NotPayer,
+    #[error("Payment request is {0}")]
+    NotOpen(String),
+    #[error("Payment request has expired")]
+    Expired,
+    #[error("Insufficient balance")]
+    InsufficientFunds,
+    #[error("Payment failed: {0}")]
+    Payment(String),
+    #[error("Could not draw QR code: {0}")]
+    Qr(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+impl From<WalletPaymentError> for PaymentRequestError {
+    fn from(error: WalletPaymentError) -> Self {
+        match error {
+            WalletPaymentError::InsufficientFunds => PaymentRequestError::InsufficientFunds,
+            WalletPaymentError::Failed(e) | WalletPaymentError::Unavailable(e) => PaymentRequestError::Payment(e),
+            WalletPaymentError::Database(e) => PaymentRequestError::Database(e),
+        }
+    }
+}
+
+/// Requests for money. Each one is payable in the app through `pay`, which
+/// sends a normal transfer to the requester's Stellar account, or from any
+/// Stellar wallet through its signed SEP-7 URI. The URI pays the request's
+/// own muxed address of that account, so the invoice watcher can match the
+/// payment back to it.
+pub struct PaymentRequestService;
+
+impl PaymentRequestService {
+    /// Requests are paid into the requester's first SDK wallet, or else their
+    /// first registered external account. Requests addressed to a user
+    /// notify them.
+    pub async fn create(
+        pool: &SqlitePool,
+        requester_id: &str,
+        request: &CreatePaymentRequest,
+    ) -> Result<PaymentRequestView, PaymentRequestError> {
+        let currency = request.currency.clone().unwrap_or_else(|| "XLM".to_string());
+        StellarTxBuilder::asset(&currency).map_err(|e| PaymentRequestError::InvalidCurrency(e.to_string()))?;
+
+        let payer_id = match &request.payer {
+            Some(contact) => {
+                let payer_id = Self::find_user_id(pool, contact).await?.ok_or(PaymentRequestError::UnknownPayer)?;
+                if payer_id == requester_id {
+                    return Err(PaymentRequestError::OwnRequest);
+                }
+                Some(payer_id)
+            }
+            None => None,
+        };
+        let destination = receiving_account(pool, requester_id)
+            .await?
+            .ok_or(PaymentRequestError::NoReceivingAccount)?;
+
+        let ttl_hours = request.expires_in_hours.unwrap_or_else(|| {
+            env::var("PAYMENT_REQUEST_TTL_HOURS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(168)
+        });
+        let now = Utc::now();
+        let id = Uuid::new_v4().to_string();
+        let muxed_id: i64 = rand::thread_rng().gen_range(1..i64::MAX);
+
+        let mut db_tx = pool.begin().await?;
+        let created = sqlx::query_as::<_, PaymentRequest>(
+            r#"
+            INSERT INTO payment_requests
+                (id, requester_id, payer_id, payer_contact, amount, currency, memo, destination, muxed_id,
+                 expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(&id)
+        .bind(requester_id)
+        .bind(&payer_id)
+        .bind(&request.payer)
+        .bind(request.amount)
+        .bind(&currency)
+        .bind(&request.memo)
+        .bind(&destination)
+        .bind(muxed_id)
+        .bind(now + Duration::hours(ttl_hours))
+        .bind(now)
+        .fetch_one(&mut *db_tx)
+        .await?;
+        if let Some(payer_id) = &payer_id {
+            Outbox::enqueue(
+                &mut *db_tx,
+                &JobPayload::Notify {
+                    event: DomainEvent::PaymentRequested {
+                        request_id: id.clone(),
+                        requester_id: requester_id.to_string(),
+                        payer_id: payer_id.clone(),
+                        amount: request.amount,
+                        currency: currency.clone(),
+                        memo: request.memo.clone(),
+                    },
+                },
+            )
+            .await?;
+        }
+        db_tx.commit().await?;
+
+        println!("🧾 Payment request {} for {} {} created", id, request.amount, currency);
+        Self::view(pool, created).await
+    }
+
+    /// `sent` lists the user's own requests, `received` those addressed to
+    /// them or that they paid.
+    pub async fn list(
+        pool: &SqlitePool,
+        user_id: &str,
+        received: bool,
+    ) -> Result<Vec<PaymentRequestView>, PaymentRequestError> {
+        Self::expire_stale(pool).await?;
+        let requests = if received {
+            sqlx::query_as::<_, PaymentRequest>(
+                "SELECT * FROM payment_requests WHERE payer_id = ? OR paid_by = ? ORDER BY created_at DESC",
+            )
+            .bind(user_id)
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?
+        } else {
+            sqlx::query_as::<_, PaymentRequest>(
+                "SELECT * FROM payment_requests WHERE requester_id = ? ORDER BY created_at DESC",
+            )
+            .bind(user_id)
+            .fetch_all(pool)
+            .await?
+        };
+
+        let mut views = Vec::with_capacity(requests.len());
+        for request in requests {
+            views.push(Self::view(pool, request).await?);
+        }
+        Ok(views)
+    }
+
+    /// The requester and the user asked to pay can see a request; an open
+    /// link is visible to anyone who has its id.
+    pub async fn find(pool: &SqlitePool, user_id: &str, id: &str) -> Result<PaymentRequestView, PaymentRequestError> {
+        let request = Self::find_request(pool, user_id, id).await?;
+        Self::view(pool, request).await
+    }
+
+    pub async fn find_request(pool: &SqlitePool, user_id: &str, id: &str) -> Result<PaymentRequest, PaymentRequestError> {
+        Self::expire_stale(pool).await?;
+        let request = sqlx::query_as::<_, PaymentRequest>("SELECT * FROM payment_requests WHERE id = ?")
+            .bind(id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(PaymentRequestError::NotFound)?;
+
+        let visible = request.requester_id == user_id
+            || request.paid_by.as_deref() == Some(user_id)
+            || request.payer_id.as_deref().is_none_or(|payer_id| payer_id == user_id);
+        if !visible {
+            return Err(PaymentRequestError::NotFound);
+        }
+        Ok(request)
+    }
+
+    /// Pays the request from the user's wallet as a normal transfer to the
+    /// requester's account, then notifies the requester. The request is held
+    /// `processing` while the payment goes out, so it can't be paid twice,
+    /// and only reopens if the payment definitely failed. If Horizon can't
+    /// say whether it landed, the request comes back still `processing` and
+    /// the `PaymentRequestSettlement` job finishes it.
+    pub async fn pay(pool: &SqlitePool, payer_id: &str, id: &str) -> Result<PaymentRequestView, PaymentRequestError> {
+        let request = Self::find_request(pool, payer_id, id).await?;
+        if request.requester_id == payer_id {
+            return Err(PaymentRequestError::OwnRequest);
+        }
+        TransactionService::new().ensure_funds(pool, payer_id, request.amount).await?;
+        let transaction_id = Self::claim(pool, &request, payer_id).await?;
+
+        match Self::complete(pool, &request, &transaction_id).await? {
+            PaymentOutcome::Failed(reason) => Err(PaymentRequestError::Payment(reason)),
+            PaymentOutcome::Completed(_) | PaymentOutcome::Unknown(_) => Self::find(pool, payer_id, id).await,
+        }
+    }
+
+    /// Finishes paying a `processing` request for its settlement job. Errors
+    /// while the payment's outcome is still unknown, so the job retries.
+    pub async fn settle_payment(pool: &SqlitePool, request_id: &str) -> Result<(), String> {
+        let request = sqlx::query_as::<_, PaymentRequest>("SELECT * FROM payment_requests WHERE id = ?")
+            .bind(request_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        let Some(request) = request.filter(|request| request.status == "processing") else {
+            return Ok(());
+        };
+        let Some(transaction_id) = request.transaction_id.clone() else {
+            return Ok(());
+        };
+
+        match Self::complete(pool, &request, &transaction_id).await.map_err(|e| e.to_string())? {
+            PaymentOutcome::Unknown(e) => Err(format!("payment outcome unknown: {}", e)),
+            PaymentOutcome::Completed(_) | PaymentOutcome::Failed(_) => Ok(()),
+        }
+    }
+
+    /// Settles the request a payment from another Stellar wallet was sent
+    /// to, found by the muxed id in its URI. The payment must be in the
+    /// request's currency and cover its amount. A payer with a NovaPay
+    /// wallet is recorded as the one who paid.
+    pub(crate) async fn receive(
+        pool: &SqlitePool,
+        account: &str,
+        muxed_id: u64,
+        record: &Value,
+    ) -> Result<(), sqlx::Error> {
+        let request = sqlx::query_as::<_, PaymentRequest>(
+            "SELECT * FROM payment_requests WHERE destination = ? AND muxed_id = ?",
+        )
+        .bind(account)
+        .bind(muxed_id as i64)
+        .fetch_optional(pool)
+        .await?;
+        let Some(request) = request else {
+            return Ok(());
+        };
+        let tx_hash = record["transaction_hash"].as_str();
+
+        let asset = payment_asset(record);
+        let amount = payment_amount(record).unwrap_or_default();
+        if asset != request.currency || amount < request.amount - AMOUNT_EPSILON {
+            println!(
+                "⚠️ Ignoring payment of {} {} to request {}, which asks for {} {}",
+                amount, asset, request.id, request.amount, request.currency
+            );
+            return Ok(());
+        }
+
+        let from_account = record["from"].as_str().unwrap_or_default();
+        let paid_by: Option<String> = sqlx::query_scalar("SELECT user_id FROM wallets WHERE stellar_public_key = ?")
+            .bind(from_account)
+            .fetch_optional(pool)
+            .await?;
+
+        let mut db_tx = pool.begin().await?;
+        let settled = sqlx::query_as::<_, PaymentRequest>(
+            r#"
+            UPDATE payment_requests SET status = 'paid', paid_by = ?, tx_hash = ?, settled_at = ?
+            WHERE id = ? AND status IN ('open', 'expired')
+            RETURNING *
+            "#,
+        )
+        .bind(&paid_by)
+        .bind(tx_hash)
+        .bind(Utc::now())
+        .bind(&request.id)
+        .fetch_optional(&mut *db_tx)
+        .await?;
+        let Some(settled) = settled else {
+            println!(
+                "⚠️ Payment {} to request {} arrived while it was {}",
+                tx_hash.unwrap_or_default(),
+                request.id,
+                request.status
+            );
+            return Ok(());
+        };
+        Self::notify_paid(&mut db_tx, &settled, paid_by.as_deref().unwrap_or(from_account)).await?;
+        db_tx.commit().await?;
+
+        println!("✅ Payment request {} paid from {}", request.id, from_account);
+        Ok(())
+    }
+
+    /// Only the user a request is addressed to can decline it; the requester
+    /// is told.
+    pub async fn decline(pool: &SqlitePool, user_id: &str, id: &str) -> Result<PaymentRequestView, PaymentRequestError> {
+        let request = Self::find_request(pool, user_id, id).await?;
+        if request.payer_id.as_deref() != Some(user_id) {
+            return Err(PaymentRequestError::NotPayer);
+        }
+
+        let mut db_tx = pool.begin().await?;
+        let declined = Self::settle(&mut db_tx, &request, "declined").await?;
+        Outbox::enqueue(
+            &mut *db_tx,
+            &JobPayload::Notify {
+                event: DomainEvent::PaymentRequestDeclined {
+                    request_id: request.id.clone(),
+                    requester_id: request.requester_id.clone(),
+                    payer_id: user_id.to_string(),
+                    amount: request.amount,
+                    currency: request.currency.clone(),
+                    memo: request.memo.clone(),
+                },
+            },
+        )
+        .await?;
+        db_tx.commit().await?;
+
+        Self::view(pool, declined).await
+    }
+
+    pub async fn cancel(pool: &SqlitePool, user_id: &str, id: &str) -> Result<PaymentRequestView, PaymentRequestError> {
+        let request = Self::find_request(pool, user_id, id).await?;
+        if request.requester_id != user_id {
+            return Err(PaymentRequestError::NotFound);
+        }
+
+        let mut db_tx = pool.begin().await?;
+        let cancelled = Self::settle(&mut db_tx, &request, "cancelled").await?;
+        db_tx.commit().await?;
+
+        Self::view(pool, cancelled).await
+    }
+
+    /// The request's SEP-7 URI as a QR code, `svg` or PNG.
+    pub fn qr_code(view: &PaymentRequestView, format: &str) -> Result<Vec<u8>, PaymentRequestError> {
+        let code = QrCode::new(view.uri.as_bytes()).map_err(|e| PaymentRequestError::Qr(e.to_string()))?;
+        if format == "svg" {
+            return Ok(code
+                .render::<svg::Color>()
+                .min_dimensions(256, 256)
+                .build()
+                .into_bytes());
+        }
+
+        const SCALE: usize = 8;
+        const QUIET_ZONE: usize = 4;
+        let width = code.width();
+        let size = (width + 2 * QUIET_ZONE) * SCALE;
+        let mut pixels = vec![255u8; size * size];
+        for (i, color) in code.to_colors().into_iter().enumerate() {
+            if color != Color::Dark {
+                continue;
+            }
+            let (x, y) = ((i % width + QUIET_ZONE) * SCALE, (i / width + QUIET_ZONE) * SCALE);
+            for row in y..y + SCALE {
+                pixels[row * size + x..row * size + x + SCALE].fill(0);
+            }
+        }
+
+        let mut png = Vec::new();
+        let mut encoder = png::Encoder::new(&mut png, size as u32, size as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .write_header()
+            .and_then(|mut writer| writer.write_image_data(&pixels))
+            .map_err(|e| PaymentRequestError::Qr(e.to_string()))?;
+        Ok(png)
+    }
+
+    /// Holds an open request for `payer_id` and records the transaction that
+    /// will pay it, along with the job that settles the payment should this
+    /// request not get that far. Says why if the request can't be held.
+    async fn claim(pool: &SqlitePool, request: &PaymentRequest, payer_id: &str) -> Result<String, PaymentRequestError> {
+        let requester_email: String = sqlx::query_scalar("SELECT email FROM users WHERE id = ?")
+            .bind(&request.requester_id)
+            .fetch_one(pool)
+            .await?;
+
+        let mut db_tx = pool.begin().await?;
+        let transaction = TransactionService::new()
+            .create_transaction(
+                &mut *db_tx,
+                payer_id,
+                CreateTransaction {
+                    recipient_email: requester_email,
+                    amount: request.amount,
+                    currency: Some(asset_code(&request.currency)),
+                    target_currency: Some(asset_code(&request.currency)),
+                    pin: String::new(),
+                },
+            )
+            .await?;
+        let claimed = sqlx::query(
+            r#"
+            UPDATE payment_requests SET status = 'processing', paid_by = ?, transaction_id = ?
+            WHERE id = ? AND status = 'open' AND expires_at > ?
+            "#,
+        )
+        .bind(payer_id)
+        .bind(&transaction.id)
+        .bind(&request.id)
+        .bind(Utc::now())
+        .execute(&mut *db_tx)
+        .await?;
+        if claimed.rows_affected() != 1 {
+            return Err(Self::not_open(&mut *db_tx, &request.id).await?);
+        }
+        Outbox::enqueue(
+            &mut *db_tx,
+            &JobPayload::PaymentRequestSettlement {
+                request_id: request.id.clone(),
+            },
+        )
+        .await?;
+        db_tx.commit().await?;
+        Ok(transaction.id)
+    }
+
+    /// Sends the transaction paying a held request, or finds out what became
+    /// of it, and settles the request to match: `paid` with the requester
+    /// notified, or `open` again if the payment failed. An unknown outcome
+    /// leaves it `processing`. Safe to call again.
+    async fn complete(
+        pool: &SqlitePool,
+        request: &PaymentRequest,
+        transaction_id: &str,
+    ) -> Result<PaymentOutcome, sqlx::Error> {
+        let outcome = TransactionService::new()
+            .pay_from_wallet(pool, transaction_id, &request.destination)
+            .await?;
+
+        match &outcome {
+            PaymentOutcome::Completed(tx_hash) => {
+                let mut db_tx = pool.begin().await?;
+                let settled = sqlx::query_as::<_, PaymentRequest>(
+                    r#"
+                    UPDATE payment_requests SET status = 'paid', tx_hash = ?, settled_at = ?
+                    WHERE id = ? AND status = 'processing' AND transaction_id = ?
+                    RETURNING *
+                    "#,
+                )
+                .bind(tx_hash)
+                .bind(Utc::now())
+                .bind(&request.id)
+                .bind(transaction_id)
+                .fetch_optional(&mut *db_tx)
+                .await?;
+                if let Some(settled) = settled {
+                    let payer_id = settled.paid_by.clone().unwrap_or_default();
+                    Self::notify_paid(&mut db_tx, &settled, &payer_id).await?;
+                    println!("✅ Payment request {} paid: {}", request.id, tx_hash);
+                }
+                db_tx.commit().await?;
+            }
+            PaymentOutcome::Failed(reason) => {
+                sqlx::query(
+                    r#"
+                    UPDATE payment_requests SET status = 'open', paid_by = NULL, transaction_id = NULL
+                    WHERE id = ? AND status = 'processing' AND transaction_id = ?
+                    "#,
+                )
+                .bind(&request.id)
+                .bind(transaction_id)
+                .execute(pool)
+                .await?;
+                println!("❌ Payment of request {} failed: {}", request.id, reason);
+            }
+            PaymentOutcome::Unknown(e) => {
+                println!("⏳ Payment of request {} left processing, outcome unknown: {}", request.id, e);
+            }
+        }
+        Ok(outcome)
+    }
+
+    async fn notify_paid(
+        db_tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        request: &PaymentRequest,
+        payer_id: &str,
+    ) -> Result<(), sqlx::Error> {
+        Outbox::enqueue(
+            &mut **db_tx,
+            &JobPayload::Notify {
+                event: DomainEvent::PaymentRequestPaid {
+                    request_id: request.id.clone(),
+                    requester_id: request.requester_id.clone(),
+                    payer_id: payer_id.to_string(),
+                    amount: request.amount,
+                    currency: request.currency.clone(),
+                    memo: request.memo.clone(),
+                },
+            },
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn settle(
+        db_tx: &mut sqlx::Transaction<'_, sqlx::Sqlite>,
+        request: &PaymentRequest,
+        status: &str,
+    ) -> Result<PaymentRequest, PaymentRequestError> {
+        let now = Utc::now();
+        let settled = sqlx::query_as::<_, PaymentRequest>(
+            r#"
+            UPDATE payment_requests SET status = ?, settled_at = ?
+            WHERE id = ? AND status = 'open' AND expires_at > ?
+            RETURNING *
+            "#,
+        )
+        .bind(status)
+        .bind(now)
+        .bind(&request.id)
+        .bind(now)
+        .fetch_optional(&mut **db_tx)
+        .await?;
+        match settled {
+            Some(settled) => Ok(settled),
+            None => Err(Self::not_open(&mut **db_tx, &request.id).await?),
+        }
+    }
+
+    async fn not_open<'e, E>(executor: E, id: &str) -> Result<PaymentRequestError, sqlx::Error>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let (status, expires_at): (String, chrono::DateTime<Utc>) =
+            sqlx::query_as("SELECT status, expires_at FROM payment_requests WHERE id = ?")
+                .bind(id)
+                .fetch_one(executor)
+                .await?;
+        Ok(if status == "expired" || (status == "open" && expires_at <= Utc::now()) {
+            PaymentRequestError::Expired
+        } else {
+            PaymentRequestError::NotOpen(status)
+        })
+    }
+
+    async fn expire_stale(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE payment_requests SET status = 'expired', settled_at = expires_at WHERE status = 'open' AND expires_at <= ?")
+            .bind(Utc::now())
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn find_user_id(pool: &SqlitePool, contact: &str) -> Result<Option<String>, sqlx::Error> {
+        if contact.contains('@') {
+            return sqlx::query_scalar("SELECT id FROM users WHERE email = ?")
+                .bind(contact)
+                .fetch_optional(pool)
+                .await;
+        }
+        match normalize_phone(contact) {
+            Some(phone) => {
+                sqlx::query_scalar("SELECT id FROM users WHERE phone_number = ?")
+                    .bind(phone)
+                    .fetch_optional(pool)
+                    .await
+            }
+            None => Ok(None),
+        }
+    }
+
+    async fn view(pool: &SqlitePool, request: PaymentRequest) -> Result<PaymentRequestView, PaymentRequestError> {
+        let requester_name: String = sqlx::query_scalar("SELECT full_name FROM users WHERE id = ?")
+            .bind(&request.requester_id)
+            .fetch_one(pool)
+            .await?;
+        let frontend_url = env::var("FRONTEND_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+
+        let destination = match request.muxed_id {
+            Some(muxed_id) => muxed_address(&request.destination, muxed_id),
+            None => request.destination.clone(),
+        };
+
+        Ok(PaymentRequestView {
+            uri: sep7_pay_uri(
+                &destination,
+                request.amount,
+                &request.currency,
+                request.memo.as_deref(),
+            ),
+            link: format!("{}/pay/{}", frontend_url.trim_end_matches('/'), request.id),
+            requester_name,
+            request,
+        })
+    }
+}
+
+/// A SEP-7 `web+stellar:pay` URI for `amount` of `currency` (`XLM` or
+/// `CODE:ISSUER`), signed with the key from this domain's `stellar.toml` so
+/// wallets can show where it came from.
+pub(crate) fn sep7_pay_uri(destination: &str, amount: f64, currency: &str, memo: Option<&str>) -> String {
+    let amount = format!("{:.7}", amount);
+    let mut params = vec![
+        ("destination", destination.to_string()),
+        ("amount", amount.trim_end_matches('0').trim_end_matches('.').to_string()),
+    ];
+    if let Some((code, issuer)) = currency.split_once(':') {
+        params.push(("asset_code", code.to_string()));
+        params.push(("asset_issuer", issuer.to_string()));
+    }
+    if let Some(memo) = memo {
+        params.push(("memo", memo.to_string()));
+        params.push(("memo_type", "MEMO_TEXT".to_string()));
+    }
+    params.push(("origin_domain", Sep10Service::home_domain()));
+
+    let query: Vec<String> = params
+        .iter()
+        .map(|(name, value)| format!("{}={}", name, percent_encode(value)))
+        .collect();
+    let uri = format!("web+stellar:pay?{}", query.join("&"));
+
+    // 35 zero bytes and 4, then the scheme name, then the unsigned URI
+    let mut payload = vec![0u8; 35];
+    payload.push(4);
+    payload.extend_from_slice(b"stellar.sep.7 - URI Scheme");
+    payload.extend_from_slice(uri.as_bytes());
+    let signature = STANDARD.encode(Sep10Service::sign(&payload));
+
+    format!("{}&signature={}", uri, percent_encode(&signature))
+}
+
+/// The muxed (`M...`) address of `account` with `id`, or an empty string if
+/// `account` isn't a valid account id.
+pub(crate) fn muxed_address(account: &str, id: i64) -> String {
+    StellarTxBuilder::public_key_bytes(account)
+        .map(|ed25519| stellar_strkey::ed25519::MuxedAccount { ed25519, id: id as u64 }.to_string())
+        .unwrap_or_default()
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool};
+    use serde_json::json;
+
+    async fn open_request(pool: &SqlitePool) -> PaymentRequest {
+        insert_user(pool, "u1", "requester@example.com").await;
+        sqlx::query_as::<_, PaymentRequest>(
+            r#"
+            INSERT INTO payment_requests
+                (id, requester_id, amount, currency, destination, muxed_id, expires_at, created_at)
+            VALUES ('r1', 'u1', 5, 'XLM', 'GDEST', 7, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Utc::now() + Duration::hours(1))
+        .bind(Utc::now())
+        .fetch_one(pool)
+        .await
+        .unwrap()
+    }
+
+    async fn status(pool: &SqlitePool) -> (String, Option<String>) {
+        sqlx::query_as("SELECT status, paid_by FROM payment_requests WHERE id = 'r1'")
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_payment_that_never_went_out_reopens_the_request() {
+        let pool = memory_pool().await;
+        let request = open_request(&pool).await;
+        // The payer has no wallet, so nothing can be signed or sent
+        insert_user(&pool, "u2", "payer@example.com").await;
+
+        let transaction_id = PaymentRequestService::claim(&pool, &request, "u2").await.unwrap();
+        assert_eq!(status(&pool).await, ("processing".to_string(), Some("u2".to_string())));
+        assert!(matches!(
+            PaymentRequestService::claim(&pool, &request, "u2").await,
+            Err(PaymentRequestError::NotOpen(status)) if status == "processing"
+        ));
+
+        let outcome = PaymentRequestService::complete(&pool, &request, &transaction_id).await.unwrap();
+        assert!(matches!(outcome, PaymentOutcome::Failed(_)));
+        assert_eq!(status(&pool).await, ("open".to_string(), None));
+        let transaction_status: String = sqlx::query_scalar("SELECT status FROM transactions WHERE id = ?")
+            .bind(&transaction_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(transaction_status, "failed");
+
+        // The settlement job has nothing left to do
+        PaymentRequestService::settle_payment(&pool, "r1").await.unwrap();
+        assert_eq!(status(&pool).await, ("open".to_string(), None));
+    }
+
+    #[tokio::test]
+    async fn test_stellar_payment_to_muxed_address_settles_request() {
+        let pool = memory_pool().await;
+        open_request(&pool).await;
+        insert_user(&pool, "u3", "payer@example.com").await;
+        sqlx::query("INSERT INTO wallets (id, user_id, stellar_public_key, stellar_secret_key) VALUES ('w3', 'u3', 'GPAYER', 'S')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let payment = |muxed_id: &str, amount: &str| {
+            json!({
+                "type": "payment",
+                "to": "GDEST",
+                "to_muxed_id": muxed_id,
+                "from": "GPAYER",
+                "asset_type": "native",
+                "amount": amount,
+                "transaction_hash": "h1",
+            })
+        };
+
+        // Short payments and payments to another id are left alone
+        PaymentRequestService::receive(&pool, "GDEST", 7, &payment("7", "4.9999999")).await.unwrap();
+        PaymentRequestService::receive(&pool, "GDEST", 8, &payment("8", "5.0000000")).await.unwrap();
+        assert_eq!(status(&pool).await, ("open".to_string(), None));
+
+        PaymentRequestService::receive(&pool, "GDEST", 7, &payment("7", "5.0000000")).await.unwrap();
+        assert_eq!(status(&pool).await, ("paid".to_string(), Some("u3".to_string())));
+        // Seeing it again doesn't notify twice
+        PaymentRequestService::receive(&pool, "GDEST", 7, &payment("7", "5.0000000")).await.unwrap();
+        let notifications: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM jobs WHERE kind = 'notify'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(notifications, 1);
+    }
+}
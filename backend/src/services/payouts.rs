@@ -0,0 +1,852 @@
+use serde_json::Value;
+use sqlx::SqlitePool;
+use std::env;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{
+    CreatePayoutBatchRequest, CustodialWallet, PayoutBatch, PayoutBatchView, PayoutItem, PayoutRow, PayoutRowError,
+};
+use crate::services::non_custodial::asset_code;
+use crate::services::stellar_tx::{stroops_to_units, StellarNetwork, StellarTxBuilder, StellarTxError, MAX_OPERATIONS};
+use crate::services::{normalize_phone, CustodialWalletService, DomainEvent, JobPayload, Outbox};
+use crate::wallet_sdk::{WalletError, WalletManager};
+use crate::wallet_sdk_service::WalletSDKService;
+
+#[derive(Error, Debug)]
+pub enum PayoutError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Send either payments or csv")]
+    NoPayments,
+    #[error("A batch holds at most {0} payments")]
+    TooManyPayments(usize),
+    #[error("Invalid CSV: {0}")]
+    InvalidCsv(String),
+    #[error("Invalid asset: {0}")]
+    InvalidAsset(String),
+    #[error("{} rows can't be paid", .0.len())]
+    InvalidRows(Vec<PayoutRowError>),
+    #[error("Batch has no failed payments to retry")]
+    NotRetryable,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// A row that passed validation, with the Stellar account it pays.
+pub struct ValidatedPayout {
+    pub line: i64,
+    pub item: PayoutItem,
+    pub destination: String,
+}
+
+/// Bulk payments from a custodial wallet. Uploads are checked row by row
+/// before anything is stored, then a background job pays the rows, up to 100
+/// per Stellar transaction.
+pub struct PayoutService;
+
+impl PayoutService {
+    /// Parses and checks every row of the upload. Recipients that are emails
+    /// or phone numbers resolve to that user's first SDK wallet, or else their
+    /// first registered external account. Fails with all bad rows at once.
+    pub async fn validate(
+        pool: &SqlitePool,
+        request: &CreatePayoutBatchRequest,
+    ) -> Result<Vec<ValidatedPayout>, PayoutError> {
+        let asset = Self::asset(request);
+        StellarTxBuilder::asset(&asset).map_err(|e| PayoutError::InvalidAsset(e.to_string()))?;
+
+        let items: Vec<(i64, PayoutItem)> = match (&request.csv, request.payments.is_empty()) {
+            (Some(csv), true) => parse_csv(csv)?,
+            (None, false) => (1..).zip(request.payments.iter().cloned()).collect(),
+            _ => return Err(PayoutError::NoPayments),
+        };
+        let max_rows = env::var("PAYOUT_BATCH_MAX_ROWS").ok().and_then(|v| v.parse().ok()).unwrap_or(1000);
+        if items.is_empty() {
+            return Err(PayoutError::NoPayments);
+        }
+        if items.len() > max_rows {
+            return Err(PayoutError::TooManyPayments(max_rows));
+        }
+
+        let mut validated = Vec::with_capacity(items.len());
+        let mut errors = Vec::new();
+        for (line, mut item) in items {
+            item.recipient = item.recipient.trim().to_string();
+            item.amount = item.amount.trim().to_string();
+            item.reference = item.reference.map(|r| r.trim().to_string()).filter(|r| !r.is_empty());
+
+            let checked = match StellarTxBuilder::amount(&item.amount, false) {
+                Err(e) => Err(e.to_string()),
+                Ok(_) if item.reference.as_ref().is_some_and(|r| r.len() > 64) => {
+                    Err("reference is longer than 64 characters".to_string())
+                }
+                Ok(_) => Self::resolve(pool, &item.recipient).await?,
+            };
+            match checked {
+                Ok(destination) => validated.push(ValidatedPayout { line, item, destination }),
+                Err(error) => errors.push(PayoutRowError { line, recipient: item.recipient, error }),
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(validated)
+        } else {
+            Err(PayoutError::InvalidRows(errors))
+        }
+    }
+
+    /// Stores a validated batch and queues it for payment.
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        wallet: &CustodialWallet,
+        request: &CreatePayoutBatchRequest,
+        payouts: Vec<ValidatedPayout>,
+    ) -> Result<PayoutBatchView, PayoutError> {
+        let now = chrono::Utc::now();
+        let mut db_tx = pool.begin().await?;
+        let batch = sqlx::query_as::<_, PayoutBatch>(
+            r#"
+            INSERT INTO payout_batches (id, user_id, wallet_id, source_public_key, asset, memo, reference, row_count, total_amount, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(&wallet.id)
+        .bind(&wallet.public_key)
+        .bind(Self::asset(request))
+        .bind(request.memo.as_deref())
+        .bind(request.reference.as_deref().map(str::trim))
+        .bind(payouts.len() as i64)
+        .bind(Self::total(&payouts))
+        .bind(now)
+        .fetch_one(&mut *db_tx)
+        .await?;
+
+        for payout in &payouts {
+            sqlx::query(
+                r#"
+                INSERT INTO payout_batch_rows (batch_id, line, recipient, destination, amount, reference, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&batch.id)
+            .bind(payout.line)
+            .bind(&payout.item.recipient)
+            .bind(&payout.destination)
+            .bind(&payout.item.amount)
+            .bind(&payout.item.reference)
+            .bind(now)
+            .execute(&mut *db_tx)
+            .await?;
+        }
+
+        Outbox::enqueue(&mut *db_tx, &JobPayload::PayoutBatch { batch_id: batch.id.clone() }).await?;
+        db_tx.commit().await?;
+
+        println!("📦 Payout batch {} queued: {} payments of {}", batch.id, batch.row_count, batch.asset);
+        Self::view(pool, batch, false).await
+    }
+
+    pub fn total(payouts: &[ValidatedPayout]) -> f64 {
+        payouts
+            .iter()
+            .filter_map(|payout| StellarTxBuilder::amount(&payout.item.amount, false).ok())
+            .map(stroops_to_units)
+            .sum()
+    }
+
+    pub fn asset(request: &CreatePayoutBatchRequest) -> String {
+        request.asset.as_deref().map(str::trim).unwrap_or("XLM").to_string()
+    }
+
+    pub async fn list(pool: &SqlitePool, user_id: &str) -> Result<Vec<PayoutBatchView>, PayoutError> {
+        let batches = sqlx::query_as::<_, PayoutBatch>(
+            "SELECT * FROM payout_batches WHERE user_id = ? ORDER BY created_at DESC LIMIT 100",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut views = Vec::with_capacity(batches.len());
+        for batch in batches {
+            views.push(Self::view(pool, batch, false).await?);
+        }
+        Ok(views)
+    }
+
+    pub async fn find(pool: &SqlitePool, user_id: &str, id: &str) -> Result<PayoutBatchView, PayoutError> {
+        let batch = Self::find_batch(pool, user_id, id).await?;
+        Self::view(pool, batch, true).await
+    }
+
+    pub async fn find_batch(pool: &SqlitePool, user_id: &str, id: &str) -> Result<PayoutBatch, PayoutError> {
+        sqlx::query_as::<_, PayoutBatch>("SELECT * FROM payout_batches WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(PayoutError::NotFound)
+    }
+
+    pub async fn rows(pool: &SqlitePool, batch_id: &str) -> Result<Vec<PayoutRow>, sqlx::Error> {
+        sqlx::query_as::<_, PayoutRow>("SELECT * FROM payout_batch_rows WHERE batch_id = ? ORDER BY line")
+            .bind(batch_id)
+            .fetch_all(pool)
+            .await
+    }
+
+    /// Failed rows of a finished batch, which `retry` would pay again.
+    pub async fn failed_total(pool: &SqlitePool, batch: &PayoutBatch) -> Result<f64, PayoutError> {
+        if !matches!(batch.status.as_str(), "partially_failed" | "failed") {
+            return Err(PayoutError::NotRetryable);
+        }
+        let amounts: Vec<String> =
+            sqlx::query_scalar("SELECT amount FROM payout_batch_rows WHERE batch_id = ? AND status = 'failed'")
+                .bind(&batch.id)
+                .fetch_all(pool)
+                .await?;
+        if amounts.is_empty() {
+            return Err(PayoutError::NotRetryable);
+        }
+        Ok(amounts
+            .iter()
+            .filter_map(|amount| StellarTxBuilder::amount(amount, false).ok())
+            .map(stroops_to_units)
+            .sum())
+    }
+
+    /// Puts the failed rows of a finished batch back in the queue.
+    pub async fn retry(pool: &SqlitePool, batch: &PayoutBatch) -> Result<PayoutBatchView, PayoutError> {
+        let mut db_tx = pool.begin().await?;
+        let requeued = sqlx::query(
+            "UPDATE payout_batches SET status = 'queued', completed_at = NULL WHERE id = ? AND status IN ('partially_failed', 'failed')",
+        )
+        .bind(&batch.id)
+        .execute(&mut *db_tx)
+        .await?
+        .rows_affected();
+        if requeued == 0 {
+            return Err(PayoutError::NotRetryable);
+        }
+        sqlx::query(
+            "UPDATE payout_batch_rows SET status = 'pending', error = NULL, updated_at = ? WHERE batch_id = ? AND status = 'failed'",
+        )
+        .bind(chrono::Utc::now())
+        .bind(&batch.id)
+        .execute(&mut *db_tx)
+        .await?;
+        Outbox::enqueue(&mut *db_tx, &JobPayload::PayoutBatch { batch_id: batch.id.clone() }).await?;
+        db_tx.commit().await?;
+
+        let batch = Self::find_batch(pool, &batch.user_id, &batch.id).await?;
+        Self::view(pool, batch, false).await
+    }
+
+    /// Pays the batch's pending rows; run by the job worker. Each transaction
+    /// is signed and stored, and its rows marked `sending` with its hash,
+    /// before it goes out, so a re-run resubmits the same envelope and can't
+    /// pay a row twice. A transaction the network rejects fails its rows,
+    /// except that when Horizon names the operations that failed, the others
+    /// are paid again without them. If Horizon can't be reached or doesn't
+    /// answer, the rows stay as they are and the job is retried.
+    pub async fn process(pool: &SqlitePool, batch_id: &str) -> Result<(), String> {
+        let claimed = sqlx::query(
+            "UPDATE payout_batches SET status = 'processing' WHERE id = ? AND status IN ('queued', 'processing')",
+        )
+        .bind(batch_id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?
+        .rows_affected();
+        if claimed == 0 {
+            return Ok(());
+        }
+
+        let batch = sqlx::query_as::<_, PayoutBatch>("SELECT * FROM payout_batches WHERE id = ?")
+            .bind(batch_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        let wallet = CustodialWalletService::find_owned(pool, &batch.user_id, &batch.wallet_id)
+            .await
+            .map_err(|e| e.to_string())?;
+        let mut manager = WalletManager::new(WalletSDKService::new().config());
+        manager
+            .add_wallet(&wallet.id, &CustodialWalletService::secret(&wallet).map_err(|e| e.to_string())?)
+            .map_err(|e| e.to_string())?;
+        let signer = manager.get_wallet(&wallet.id).ok_or_else(|| "wallet not loaded".to_string())?;
+        let network = StellarNetwork::from_env();
+
+        loop {
+            // Transactions an earlier run sent without learning the outcome
+            Self::settle_sending(pool, &network, &batch).await?;
+
+            let pending: Vec<PayoutRow> = Self::rows(pool, &batch.id)
+                .await
+                .map_err(|e| e.to_string())?
+                .into_iter()
+                .filter(|row| row.status == "pending")
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+
+            for rows in pending.chunks(MAX_OPERATIONS) {
+                let payments: Vec<(String, String)> =
+                    rows.iter().map(|row| (row.destination.clone(), row.amount.clone())).collect();
+                let (hash, envelope) = match signer.sign_payments(&batch.asset, &payments, batch.memo.as_deref()).await {
+                    Ok(signed) => signed,
+                    Err(WalletError::NetworkError(message)) => return Err(format!("Horizon unavailable: {}", message)),
+                    Err(e) => {
+                        let message = match e {
+                            WalletError::TransactionFailed(message) => message,
+                            e => e.to_string(),
+                        };
+                        Self::mark_failed(pool, rows, &message).await.map_err(|e| e.to_string())?;
+                        continue;
+                    }
+                };
+                // Rows another run got to first are settled from `sending`
+                if Self::claim(pool, &batch, rows, &hash, &envelope).await.map_err(|e| e.to_string())? {
+                    Self::settle(pool, &network, &batch, &hash, &envelope).await?;
+                }
+            }
+        }
+
+        Self::finish(pool, &batch).await.map_err(|e| e.to_string())
+    }
+
+    /// Gives up on the rows a dead job left pending. Rows left `sending` may
+    /// have been paid, so their batch stays `processing` until a retry of the
+    /// job settles them.
+    pub async fn abandon(pool: &SqlitePool, batch_id: &str, error: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE payout_batch_rows SET status = 'failed', error = ?, updated_at = ? WHERE batch_id = ? AND status = 'pending'")
+            .bind(error)
+            .bind(chrono::Utc::now())
+            .bind(batch_id)
+            .execute(pool)
+            .await?;
+        let sending: i64 =
+            sqlx::query_scalar("SELECT COUNT(*) FROM payout_batch_rows WHERE batch_id = ? AND status = 'sending'")
+                .bind(batch_id)
+                .fetch_one(pool)
+                .await?;
+        if sending > 0 {
+            println!("⚠️ Payout batch {} left for reconciliation with {} rows sending", batch_id, sending);
+            return Ok(());
+        }
+        let batch = sqlx::query_as::<_, PayoutBatch>("SELECT * FROM payout_batches WHERE id = ?")
+            .bind(batch_id)
+            .fetch_one(pool)
+            .await?;
+        Self::finish(pool, &batch).await
+    }
+
+    /// Stores a signed transaction and moves its rows from `pending` to
+    /// `sending`, all or none. `false` if another run claimed one of them.
+    async fn claim(
+        pool: &SqlitePool,
+        batch: &PayoutBatch,
+        rows: &[PayoutRow],
+        hash: &str,
+        envelope: &str,
+    ) -> Result<bool, sqlx::Error> {
+        let now = chrono::Utc::now();
+        let mut db_tx = pool.begin().await?;
+        sqlx::query("INSERT INTO payout_submissions (tx_hash, batch_id, envelope, created_at) VALUES (?, ?, ?, ?)")
+            .bind(hash)
+            .bind(&batch.id)
+            .bind(envelope)
+            .bind(now)
+            .execute(&mut *db_tx)
+            .await?;
+        for row in rows {
+            let claimed = sqlx::query(
+                "UPDATE payout_batch_rows SET status = 'sending', tx_hash = ?, updated_at = ? WHERE batch_id = ? AND line = ? AND status = 'pending'",
+            )
+            .bind(hash)
+            .bind(now)
+            .bind(&row.batch_id)
+            .bind(row.line)
+            .execute(&mut *db_tx)
+            .await?
+            .rows_affected();
+            if claimed == 0 {
+                return Ok(false);
+            }
+        }
+        db_tx.commit().await?;
+        Ok(true)
+    }
+
+    /// Resubmits every stored transaction of the batch that still has rows
+    /// `sending`.
+    async fn settle_sending(pool: &SqlitePool, network: &StellarNetwork, batch: &PayoutBatch) -> Result<(), String> {
+        let submissions: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT s.tx_hash, s.envelope FROM payout_submissions s
+            WHERE s.batch_id = ? AND EXISTS (
+                SELECT 1 FROM payout_batch_rows r
+                WHERE r.batch_id = s.batch_id AND r.tx_hash = s.tx_hash AND r.status = 'sending'
+            )
+            ORDER BY s.created_at
+            "#,
+        )
+        .bind(&batch.id)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        for (hash, envelope) in submissions {
+            Self::settle(pool, network, batch, &hash, &envelope).await?;
+        }
+        Ok(())
+    }
+
+    /// Submits a stored transaction, or finds it already in a ledger, and
+    /// moves its `sending` rows to paid or failed.
+    async fn settle(
+        pool: &SqlitePool,
+        network: &StellarNetwork,
+        batch: &PayoutBatch,
+        hash: &str,
+        envelope: &str,
+    ) -> Result<(), String> {
+        let result = network.resubmit(hash, envelope).await;
+        // In operation order, as they were signed
+        let rows = sqlx::query_as::<_, PayoutRow>(
+            "SELECT * FROM payout_batch_rows WHERE batch_id = ? AND tx_hash = ? AND status = 'sending' ORDER BY line",
+        )
+        .bind(&batch.id)
+        .bind(hash)
+        .fetch_all(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        match result {
+            Ok(tx_hash) => Self::mark_paid(pool, batch, &rows, &tx_hash).await,
+            Err(StellarTxError::Horizon(message)) => {
+                return Err(format!("Payout transaction {} outcome unknown: {}", hash, message));
+            }
+            Err(StellarTxError::Rejected(codes)) => Self::mark_failed(pool, &rows, &codes).await,
+            Err(e) => Self::mark_failed(pool, &rows, &e.to_string()).await,
+        }
+        .map_err(|e| e.to_string())
+    }
+
+    async fn mark_paid(pool: &SqlitePool, batch: &PayoutBatch, rows: &[PayoutRow], tx_hash: &str) -> Result<(), sqlx::Error> {
+        let now = chrono::Utc::now();
+        let currency = asset_code(&batch.asset);
+        let mut db_tx = pool.begin().await?;
+        for row in rows {
+            // Only the run that moves the row out of `sending` books it
+            let paid = sqlx::query(
+                r#"
+                UPDATE payout_batch_rows
+                SET status = 'paid', error = NULL, tx_hash = ?, attempts = attempts + 1, updated_at = ?
+                WHERE batch_id = ? AND line = ? AND status = 'sending'
+                "#,
+            )
+            .bind(tx_hash)
+            .bind(now)
+            .bind(&row.batch_id)
+            .bind(row.line)
+            .execute(&mut *db_tx)
+            .await?
+            .rows_affected();
+            if paid == 0 {
+                continue;
+            }
+
+            let amount = StellarTxBuilder::amount(&row.amount, false).map(stroops_to_units).unwrap_or_default();
+            let transaction_id: String = sqlx::query_scalar(
+                r#"
+                INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, stellar_tx_hash, status, created_at, completed_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?, 'completed', ?, ?)
+                RETURNING id
+                "#,
+            )
+            .bind(Uuid::new_v4().to_string())
+            .bind(&batch.user_id)
+            .bind(&row.recipient)
+            .bind(amount)
+            .bind(&currency)
+            .bind(&currency)
+            .bind(tx_hash)
+            .bind(batch.created_at)
+            .bind(now)
+            .fetch_one(&mut *db_tx)
+            .await?;
+
+            sqlx::query("UPDATE payout_batch_rows SET transaction_id = ? WHERE batch_id = ? AND line = ?")
+                .bind(&transaction_id)
+                .bind(&row.batch_id)
+                .bind(row.line)
+                .execute(&mut *db_tx)
+                .await?;
+        }
+        db_tx.commit().await
+    }
+
+    /// Fails the rows whose operations Horizon reports as failed, or all of
+    /// them if it names none; `message` is the result codes or the error.
+    async fn mark_failed(pool: &SqlitePool, rows: &[PayoutRow], message: &str) -> Result<(), sqlx::Error> {
+        let codes: Value = serde_json::from_str(message).unwrap_or_default();
+        let operations: Vec<&str> = codes["operations"]
+            .as_array()
+            .map(|codes| codes.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        let per_operation = operations.iter().any(|code| *code != "op_success");
+
+        let now = chrono::Utc::now();
+        for (i, row) in rows.iter().enumerate() {
+            let code = operations.get(i).copied();
+            if per_operation && code.is_none_or(|code| code == "op_success") {
+                // Not this row's fault; the transaction didn't land, so it
+                // goes out again in the next one
+                sqlx::query(
+                    r#"
+                    UPDATE payout_batch_rows SET status = 'pending', tx_hash = NULL, attempts = attempts + 1, updated_at = ?
+                    WHERE batch_id = ? AND line = ? AND status IN ('pending', 'sending')
+                    "#,
+                )
+                .bind(now)
+                .bind(&row.batch_id)
+                .bind(row.line)
+                .execute(pool)
+                .await?;
+                continue;
+            }
+            let error = match code {
+                Some(code) if per_operation => code.to_string(),
+                _ => codes["transaction"].as_str().map(str::to_string).unwrap_or_else(|| message.to_string()),
+            };
+
+            sqlx::query(
+                r#"
+                UPDATE payout_batch_rows SET status = 'failed', error = ?, attempts = attempts + 1, updated_at = ?
+                WHERE batch_id = ? AND line = ? AND status IN ('pending', 'sending')
+                "#,
+            )
+            .bind(&error)
+            .bind(now)
+            .bind(&row.batch_id)
+            .bind(row.line)
+            .execute(pool)
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Settles the batch status from its rows and tells the owner.
+    async fn finish(pool: &SqlitePool, batch: &PayoutBatch) -> Result<(), sqlx::Error> {
+        let (paid, failed): (i64, i64) = sqlx::query_as(
+            r#"
+            SELECT COALESCE(SUM(status = 'paid'), 0), COALESCE(SUM(status = 'failed'), 0)
+            FROM payout_batch_rows WHERE batch_id = ?
+            "#,
+        )
+        .bind(&batch.id)
+        .fetch_one(pool)
+        .await?;
+        let status = match (paid, failed) {
+            (_, 0) => "completed",
+            (0, _) => "failed",
+            _ => "partially_failed",
+        };
+
+        let mut db_tx = pool.begin().await?;
+        sqlx::query("UPDATE payout_batches SET status = ?, completed_at = ? WHERE id = ?")
+            .bind(status)
+            .bind(chrono::Utc::now())
+            .bind(&batch.id)
+            .execute(&mut *db_tx)
+            .await?;
+        Outbox::enqueue(&mut *db_tx, &JobPayload::Notify {
+            event: DomainEvent::PayoutBatchFinished {
+                batch_id: batch.id.clone(),
+                user_id: batch.user_id.clone(),
+                reference: batch.reference.clone(),
+                paid,
+                failed,
+            },
+        })
+        .await?;
+        db_tx.commit().await?;
+
+        println!("📦 Payout batch {} {}: {} paid, {} failed", batch.id, status, paid, failed);
+        Ok(())
+    }
+
+    /// The Stellar account a recipient is paid at, or why there is none.
+    async fn resolve(pool: &SqlitePool, recipient: &str) -> Result<Result<String, String>, sqlx::Error> {
+        if recipient.starts_with('G') && recipient.len() == 56 {
+            return Ok(StellarTxBuilder::public_key_bytes(recipient)
+                .map(|_| recipient.to_string())
+                .map_err(|e| e.to_string()));
+        }
+
+        let user_id: Option<String> = if recipient.contains('@') {
+            sqlx::query_scalar("SELECT id FROM users WHERE email = ?")
+                .bind(recipient)
+                .fetch_optional(pool)
+                .await?
+        } else if let Some(phone) = normalize_phone(recipient) {
+            sqlx::query_scalar("SELECT id FROM users WHERE phone_number = ?")
+                .bind(phone)
+                .fetch_optional(pool)
+                .await?
+        } else {
+            return Ok(Err("not a Stellar address, email or phone number".to_string()));
+        };
+        let Some(user_id) = user_id else {
+            return Ok(Err("no NovaPay user with this email or phone number".to_string()));
+        };
+
+        let account = receiving_account(pool, &user_id).await?;
+        Ok(account.ok_or_else(|| "recipient has no Stellar account to pay".to_string()))
+    }
+
+    async fn view(pool: &SqlitePool, batch: PayoutBatch, with_rows: bool) -> Result<PayoutBatchView, PayoutError> {
+        let rows = Self::rows(pool, &batch.id).await?;
+        let count = |status: &str| rows.iter().filter(|row| row.status == status).count() as i64;
+        Ok(PayoutBatchView {
+            paid: count("paid"),
+            failed: count("failed"),
+            // Rows `sending` are not settled yet either
+            pending: count("pending") + count("sending"),
+            rows: with_rows.then_some(rows),
+            batch,
+        })
+    }
+}
+
+/// Where a NovaPay user is paid on Stellar: their first SDK wallet, or else
+/// their first registered external account.
+pub(crate) async fn receiving_account(pool: &SqlitePool, user_id: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar(
+        r#"
+        SELECT public_key FROM (
+            SELECT public_key, 0 AS rank, created_at FROM custodial_wallets WHERE user_id = ?
+            UNION ALL
+            SELECT public_key, 1 AS rank, created_at FROM external_accounts WHERE user_id = ?
+        )
+        ORDER BY rank, created_at
+        LIMIT 1
+        "#,
+    )
+    .bind(user_id)
+    .bind(user_id)
+    .fetch_optional(pool)
+    .await
+}
+
+/// Reads `recipient,amount[,reference]` rows under a header line naming the
+/// columns, in any order. Fields may be quoted; blank lines are skipped.
+fn parse_csv(text: &str) -> Result<Vec<(i64, PayoutItem)>, PayoutError> {
+    let mut lines = (1..).zip(text.lines()).filter(|(_, line)| !line.trim().is_empty());
+    let Some((_, header)) = lines.next() else {
+        return Ok(Vec::new());
+    };
+    let header: Vec<String> = csv_fields(header)?.iter().map(|name| name.trim().to_lowercase()).collect();
+    let column = |name: &str| header.iter().position(|column| column == name);
+    let (Some(recipient), Some(amount)) = (column("recipient"), column("amount")) else {
+        return Err(PayoutError::InvalidCsv("header must name recipient and amount columns".to_string()));
+    };
+    let reference = column("reference");
+
+    lines
+        .map(|(line, text)| {
+            let fields = csv_fields(text)?;
+            let field = |i: usize| fields.get(i).cloned().unwrap_or_default();
+            Ok((
+                line,
+                PayoutItem {
+                    recipient: field(recipient),
+                    amount: field(amount),
+                    reference: reference.map(field),
+                },
+            ))
+        })
+        .collect()
+}
+
+fn csv_fields(line: &str) -> Result<Vec<String>, PayoutError> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.is_empty() => quoted = true,
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(PayoutError::InvalidCsv(format!("unterminated quote in \"{}\"", line.trim())));
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool, stub_server};
+    use axum::{extract::State, http::StatusCode, routing::{get, post}, Json, Router};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Horizon {
+        submissions: Arc<AtomicUsize>,
+        landed: Arc<AtomicUsize>,
+        /// Submissions answered with 503 before one gets through
+        unavailable: usize,
+        rejection: Option<Value>,
+    }
+
+    async fn horizon(state: Horizon) -> StellarNetwork {
+        let app = Router::new()
+            .route(
+                "/transactions/:hash",
+                get(|State(horizon): State<Horizon>| async move {
+                    if horizon.landed.load(Ordering::SeqCst) > 0 {
+                        (StatusCode::OK, Json(json!({ "successful": true })))
+                    } else {
+                        (StatusCode::NOT_FOUND, Json(json!({})))
+                    }
+                }),
+            )
+            .route(
+                "/transactions",
+                post(|State(horizon): State<Horizon>| async move {
+                    let n = horizon.submissions.fetch_add(1, Ordering::SeqCst) + 1;
+                    if n <= horizon.unavailable {
+                        return (StatusCode::SERVICE_UNAVAILABLE, Json(json!({})));
+                    }
+                    if let Some(codes) = horizon.rejection {
+                        return (StatusCode::BAD_REQUEST, Json(json!({ "extras": { "result_codes": codes } })));
+                    }
+                    horizon.landed.fetch_add(1, Ordering::SeqCst);
+                    (StatusCode::OK, Json(json!({ "hash": "h1" })))
+                }),
+            )
+            .with_state(state);
+
+        let mut network = StellarNetwork::from_env();
+        network.horizon_url = stub_server(app).await;
+        network
+    }
+
+    /// A processing batch of two rows already signed into transaction `h1`
+    /// and marked `sending`.
+    async fn sending_batch(pool: &SqlitePool) -> PayoutBatch {
+        insert_user(pool, "u1", "u1@example.com").await;
+        let now = chrono::Utc::now();
+        sqlx::query("INSERT INTO custodial_wallets (id, user_id, public_key, encrypted_secret, created_at) VALUES ('w1', 'u1', 'GSOURCE', 'x', ?)")
+            .bind(now)
+            .execute(pool)
+            .await
+            .unwrap();
+        let batch = sqlx::query_as::<_, PayoutBatch>(
+            r#"
+            INSERT INTO payout_batches (id, user_id, wallet_id, source_public_key, asset, status, row_count, total_amount, created_at)
+            VALUES ('b1', 'u1', 'w1', 'GSOURCE', 'XLM', 'processing', 2, 3, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(now)
+        .fetch_one(pool)
+        .await
+        .unwrap();
+        for (line, amount) in [(1, "1"), (2, "2")] {
+            sqlx::query(
+                r#"
+                INSERT INTO payout_batch_rows (batch_id, line, recipient, destination, amount, status, tx_hash)
+                VALUES ('b1', ?, ?, 'GDEST', ?, 'sending', 'h1')
+                "#,
+            )
+            .bind(line)
+            .bind(format!("r{}@example.com", line))
+            .bind(amount)
+            .execute(pool)
+            .await
+            .unwrap();
+        }
+        sqlx::query("INSERT INTO payout_submissions (tx_hash, batch_id, envelope, created_at) VALUES ('h1', 'b1', 'AAAA', ?)")
+            .bind(now)
+            .execute(pool)
+            .await
+            .unwrap();
+        batch
+    }
+
+    async fn statuses(pool: &SqlitePool) -> Vec<(String, Option<String>, Option<String>)> {
+        sqlx::query_as("SELECT status, tx_hash, error FROM payout_batch_rows WHERE batch_id = 'b1' ORDER BY line")
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_unknown_outcome_is_settled_without_paying_twice() {
+        let pool = memory_pool().await;
+        let batch = sending_batch(&pool).await;
+        let state = Horizon { unavailable: 1, ..Default::default() };
+        let network = horizon(state.clone()).await;
+
+        assert!(PayoutService::settle_sending(&pool, &network, &batch).await.is_err());
+        let sending = ("sending".to_string(), Some("h1".to_string()), None);
+        assert_eq!(statuses(&pool).await, vec![sending.clone(), sending]);
+
+        // A dead job doesn't close a batch whose rows may have been paid
+        PayoutService::abandon(&pool, "b1", "gave up").await.unwrap();
+        let status: String = sqlx::query_scalar("SELECT status FROM payout_batches WHERE id = 'b1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(status, "processing");
+
+        PayoutService::settle_sending(&pool, &network, &batch).await.unwrap();
+        let paid = ("paid".to_string(), Some("h1".to_string()), None);
+        assert_eq!(statuses(&pool).await, vec![paid.clone(), paid]);
+
+        // Nothing is left sending, so nothing goes out or is booked again
+        PayoutService::settle_sending(&pool, &network, &batch).await.unwrap();
+        assert_eq!(state.submissions.load(Ordering::SeqCst), 2);
+        let booked: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transactions WHERE stellar_tx_hash = 'h1'")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(booked, 2);
+    }
+
+    #[tokio::test]
+    async fn test_rejection_fails_only_the_named_operations() {
+        let pool = memory_pool().await;
+        let batch = sending_batch(&pool).await;
+        let codes = json!({ "transaction": "tx_failed", "operations": ["op_success", "op_underfunded"] });
+        let network = horizon(Horizon { rejection: Some(codes), ..Default::default() }).await;
+
+        PayoutService::settle_sending(&pool, &network, &batch).await.unwrap();
+        assert_eq!(
+            statuses(&pool).await,
+            vec![
+                ("pending".to_string(), None, None),
+                ("failed".to_string(), Some("h1".to_string()), Some("op_underfunded".to_string())),
+            ]
+        );
+    }
+}
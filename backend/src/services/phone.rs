@@ -0,0 +1,95 @@
+use std::env;
+
+/// Countries we route money to, with their dialling code and the leading
+/// digits of valid mobile/fixed national numbers (9 digits after the code).
+const REGIONS: [(&str, &str, &[char]); 3] = [
+    ("KE", "254", &['1', '7']),
+    ("UG", "256", &['2', '3', '4', '7']),
+    ("TZ", "255", &['2', '6', '7']),
+];
+
+/// Normalizes a Kenyan, Ugandan or Tanzanian number to E.164
+/// (`+254712345678`). Numbers in national format (`0712 345 678`) are read in
+/// `DEFAULT_PHONE_REGION` (default `KE`). Returns `None` for anything else.
+pub fn normalize_phone(input: &str) -> Option<String> {
+    let trimmed = input.trim();
+    let has_plus = trimmed.starts_with('+');
+    let digits: String = trimmed
+        .chars()
+        .filter(|c| !matches!(c, ' ' | '-' | '(' | ')' | '.' | '+'))
+        .collect();
+    if digits.is_empty() || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+
+    if has_plus || digits.starts_with("00") {
+        return from_international(digits.strip_prefix("00").unwrap_or(&digits));
+    }
+    // Country code without the plus, e.g. 254712345678
+    if digits.len() == 12 {
+        return from_international(&digits);
+    }
+
+    let region = env::var("DEFAULT_PHONE_REGION").unwrap_or_else(|_| "KE".to_string());
+    let (_, code, leading) = REGIONS.iter().find(|(r, _, _)| r.eq_ignore_ascii_case(&region))?;
+    let national = digits.strip_prefix('0').unwrap_or(&digits);
+    valid_national(national, leading).then(|| format!("+{}{}", code, national))
+}
+
+fn from_international(digits: &str) -> Option<String> {
+    REGIONS.iter().find_map(|(_, code, leading)| {
+        let national = digits.strip_prefix(code)?;
+        valid_national(national, leading).then(|| format!("+{}{}", code, national))
+    })
+}
+
+fn valid_national(national: &str, leading: &[char]) -> bool {
+    national.len() == 9 && national.starts_with(leading)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_international_numbers() {
+        for (input, expected) in [
+            ("+254712345678", "+254712345678"),
+            ("+254 712 345 678", "+254712345678"),
+            ("00254712345678", "+254712345678"),
+            ("254110345678", "+254110345678"),
+            ("+256 772 123456", "+256772123456"),
+            ("256312123456", "+256312123456"),
+            ("+255 (754) 123-456", "+255754123456"),
+            ("255612123456", "+255612123456"),
+        ] {
+            assert_eq!(normalize_phone(input).as_deref(), Some(expected), "{}", input);
+        }
+    }
+
+    #[test]
+    fn test_national_numbers_read_as_kenyan() {
+        assert_eq!(normalize_phone("0712 345 678").as_deref(), Some("+254712345678"));
+        assert_eq!(normalize_phone("712345678").as_deref(), Some("+254712345678"));
+        assert_eq!(normalize_phone("0110-345-678").as_deref(), Some("+254110345678"));
+    }
+
+    #[test]
+    fn test_invalid_numbers() {
+        for input in [
+            "",
+            "   ",
+            "not a number",
+            "+1 415 555 0100",
+            "+254 812 345 678",
+            "+25471234567",
+            "+2547123456789",
+            "+256 512 123456",
+            "+255 312 123456",
+            "0812 345 678",
+            "+254712345678x",
+        ] {
+            assert_eq!(normalize_phone(input), None, "{:?} should be refused", input);
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use sqlx::SqlitePool;
+use std::env;
+use thiserror::Error;
+
+use crate::services::AuthService;
+
+#[derive(Error, Debug)]
+pub enum PinError {
+    #[error("No transaction PIN has been set")]
+    NotSet,
+    #[error("A transaction PIN is already set")]
+    AlreadySet,
+    #[error("PIN must be 4 to 6 digits and not trivially guessable")]
+    Weak,
+    #[error("Incorrect PIN")]
+    Incorrect,
+    #[error("Too many incorrect PINs; try again after {0}")]
+    Locked(chrono::DateTime<chrono::Utc>),
+    #[error("Failed to hash PIN")]
+    Hash,
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct TransactionPin {
+    pin_hash: String,
+    locked_until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// 4–6 digit PIN confirming each payment, hashed like passwords.
+pub struct PinService;
+
+impl PinService {
+    fn max_attempts() -> i64 {
+        env::var("PIN_MAX_ATTEMPTS").ok().and_then(|v| v.parse().ok()).unwrap_or(5)
+    }
+
+    fn lockout() -> chrono::Duration {
+        let minutes = env::var("PIN_LOCKOUT_MINUTES").ok().and_then(|v| v.parse().ok()).unwrap_or(30);
+        chrono::Duration::minutes(minutes)
+    }
+
+    /// Rejects non-numeric PINs, repeated digits (`0000`) and straight runs
+    /// (`1234`, `9876`).
+    pub fn validate(pin: &str) -> Result<(), PinError> {
+        if !(4..=6).contains(&pin.len()) || !pin.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(PinError::Weak);
+        }
+
+        let digits: Vec<i16> = pin.bytes().map(|b| i16::from(b - b'0')).collect();
+        let steps: Vec<i16> = digits.windows(2).map(|w| w[1] - w[0]).collect();
+        if steps.iter().all(|s| *s == steps[0]) && matches!(steps[0], -1..=1) {
+            return Err(PinError::Weak);
+        }
+        Ok(())
+    }
+
+    pub async fn is_set(pool: &SqlitePool, user_id: &str) -> Result<bool, sqlx::Error> {
+        Ok(Self::find(pool, user_id).await?.is_some())
+    }
+
+    pub async fn set(pool: &SqlitePool, user_id: &str, pin: &str) -> Result<(), PinError> {
+        if Self::is_set(pool, user_id).await? {
+            return Err(PinError::AlreadySet);
+        }
+        Self::store(pool, user_id, pin).await
+    }
+
+    pub async fn change(pool: &SqlitePool, user_id: &str, current_pin: &str, new_pin: &str) -> Result<(), PinError> {
+        Self::verify(pool, user_id, current_pin).await?;
+        Self::store(pool, user_id, new_pin).await
+    }
+
+    /// Replaces the PIN without the old one. The caller must already have
+    /// checked an OTP sent to the user's verified phone.
+    pub async fn reset(pool: &SqlitePool, user_id: &str, new_pin: &str) -> Result<(), PinError> {
+        Self::store(pool, user_id, new_pin).await
+    }
+
+    /// Checks a PIN, counting failures. Reaching `PIN_MAX_ATTEMPTS` (default
+    /// 5) locks payments for `PIN_LOCKOUT_MINUTES` (default 30).
+    pub async fn verify(pool: &SqlitePool, user_id: &str, pin: &str) -> Result<(), PinError> {
+        let now = chrono::Utc::now();
+        let max_attempts = Self::max_attempts().max(1);
+
+        // The attempt is counted before the PIN is checked, in one statement,
+        // so concurrent guesses can't all see the same count. The one that
+        // reaches the limit locks at once; a right PIN clears it again.
+        let stored = sqlx::query_as::<_, TransactionPin>(
+            r#"
+            UPDATE transaction_pins
+            SET failed_attempts = CASE WHEN failed_attempts + 1 >= ? THEN 0 ELSE failed_attempts + 1 END,
+                locked_until = CASE WHEN failed_attempts + 1 >= ? THEN ? ELSE NULL END,
+                updated_at = ?
+            WHERE user_id = ? AND (locked_until IS NULL OR locked_until <= ?)
+            RETURNING pin_hash, locked_until
+            "#,
+        )
+        .bind(max_attempts)
+        .bind(max_attempts)
+        .bind(now + Self::lockout())
+        .bind(now)
+        .bind(user_id)
+        .bind(now)
+        .fetch_optional(pool)
+        .await?;
+        let Some(stored) = stored else {
+            let stored = Self::find(pool, user_id).await?.ok_or(PinError::NotSet)?;
+            return Err(PinError::Locked(stored.locked_until.unwrap_or(now)));
+        };
+
+        let valid = AuthService::verify_password(pin, &stored.pin_hash).map_err(|_| PinError::Hash)?;
+        if valid {
+            sqlx::query("UPDATE transaction_pins SET failed_attempts = 0, locked_until = NULL WHERE user_id = ?")
+                .bind(user_id)
+                .execute(pool)
+                .await?;
+            return Ok(());
+        }
+
+        if let Some(locked_until) = stored.locked_until {
+            println!("🔒 Transaction PIN locked for user {} until {}", user_id, locked_until);
+            return Err(PinError::Locked(locked_until));
+        }
+        Err(PinError::Incorrect)
+    }
+
+    async fn store(pool: &SqlitePool, user_id: &str, pin: &str) -> Result<(), PinError> {
+        Self::validate(pin)?;
+        let pin_hash = AuthService::hash_password(pin).map_err(|_| PinError::Hash)?;
+        let now = chrono::Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO transaction_pins (user_id, pin_hash, created_at, updated_at) VALUES (?, ?, ?, ?)
+            ON CONFLICT (user_id) DO UPDATE SET
+                pin_hash = excluded.pin_hash,
+                failed_attempts = 0,
+                locked_until = NULL,
+                updated_at = excluded.updated_at
+            "#,
+        )
+        .bind(user_id)
+        .bind(pin_hash)
+        .bind(now)
+        .bind(now)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn find(pool: &SqlitePool, user_id: &str) -> Result<Option<TransactionPin>, sqlx::Error> {
+        sqlx::query_as::<_, TransactionPin>(
+            "SELECT pin_hash, locked_until FROM transaction_pins WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool};
+
+    #[test]
+    fn test_validate() {
+        for pin in ["2580", "19735", "402917"] {
+            assert!(PinService::validate(pin).is_ok(), "{} should be allowed", pin);
+        }
+        for pin in ["0000", "1234", "98765", "123", "1234567", "12a4", "", "\u{80}\u{80}\u{80}\u{80}", "١٢٣٤"] {
+            assert!(PinService::validate(pin).is_err(), "{:?} should be refused", pin);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_wrong_pins_lock() {
+        let pool = memory_pool().await;
+        insert_user(&pool, "user-1", "pin@example.com").await;
+        PinService::set(&pool, "user-1", "2580").await.unwrap();
+
+        for _ in 0..4 {
+            assert!(matches!(PinService::verify(&pool, "user-1", "1111").await, Err(PinError::Incorrect)));
+        }
+        PinService::verify(&pool, "user-1", "2580").await.unwrap();
+        for _ in 0..4 {
+            assert!(matches!(PinService::verify(&pool, "user-1", "1111").await, Err(PinError::Incorrect)));
+        }
+        assert!(matches!(PinService::verify(&pool, "user-1", "1111").await, Err(PinError::Locked(_))));
+        assert!(matches!(PinService::verify(&pool, "user-1", "2580").await, Err(PinError::Locked(_))));
+        assert!(matches!(PinService::verify(&pool, "user-2", "2580").await, Err(PinError::NotSet)));
+    }
+}
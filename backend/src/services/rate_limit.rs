@@ -0,0 +1,202 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// What a bucket is keyed by. A request is checked against one bucket per
+/// key kind the policy lists, and must have a token in all of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitKey {
+    Ip,
+    User,
+    /// The `email` field of a JSON request body, so one account cannot be
+    /// attacked from many addresses.
+    Email,
+}
+
+impl RateLimitKey {
+    fn as_str(&self) -> &'static str {
+        match self {
+            RateLimitKey::Ip => "ip",
+            RateLimitKey::User => "user",
+            RateLimitKey::Email => "email",
+        }
+    }
+}
+
+/// Token-bucket policy for one group of routes: `burst` requests at once,
+/// refilled at `per_minute`.
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitPolicy {
+    pub name: &'static str,
+    pub burst: u32,
+    pub per_minute: u32,
+    pub keys: &'static [RateLimitKey],
+}
+
+impl RateLimitPolicy {
+    /// Login, registration and the other unauthenticated credential flows.
+    pub fn auth() -> Self {
+        Self::from_env("auth", 10, 10, &[RateLimitKey::Ip, RateLimitKey::Email])
+    }
+
+    /// Public partner routes such as `/fonbnk/*`.
+    pub fn public() -> Self {
+        Self::from_env("public", 30, 30, &[RateLimitKey::Ip])
+    }
+
+    /// Endpoints that move money.
+    pub fn money() -> Self {
+        Self::from_env("money", 10, 20, &[RateLimitKey::User, RateLimitKey::Ip])
+    }
+
+    /// Everything else behind authentication.
+    pub fn api() -> Self {
+        Self::from_env("api", 60, 120, &[RateLimitKey::User])
+    }
+
+    /// Reads `RATE_LIMIT_<NAME>` as `<burst>/<per minute>`, e.g. `10/10`.
+    fn from_env(name: &'static str, burst: u32, per_minute: u32, keys: &'static [RateLimitKey]) -> Self {
+        let configured = env::var(format!("RATE_LIMIT_{}", name.to_uppercase()))
+            .ok()
+            .and_then(|v| {
+                let (burst, per_minute) = v.split_once('/')?;
+                Some((burst.trim().parse().ok()?, per_minute.trim().parse().ok()?))
+            });
+        let (burst, per_minute) = configured.unwrap_or((burst, per_minute));
+
+        Self {
+            name,
+            burst: burst.max(1),
+            per_minute: per_minute.max(1),
+            keys,
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+fn buckets() -> &'static Mutex<HashMap<String, Bucket>> {
+    static BUCKETS: OnceLock<Mutex<HashMap<String, Bucket>>> = OnceLock::new();
+    BUCKETS.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+/// In-memory token buckets shared by every route group. Limits are per
+/// process, which is enough for a single instance behind a load balancer
+/// with sticky sessions; counters reset on restart.
+pub struct RateLimiter;
+
+impl RateLimiter {
+    /// Takes one token from each `(key kind, value)` bucket of `policy`. On
+    /// refusal no token is taken and the wait until a retry can succeed is
+    /// returned.
+    pub fn check(policy: &RateLimitPolicy, keys: &[(RateLimitKey, String)]) -> Result<(), Duration> {
+        let now = Instant::now();
+        let rate = f64::from(policy.per_minute) / 60.0;
+        let capacity = f64::from(policy.burst);
+        let mut buckets = buckets().lock().unwrap_or_else(|e| e.into_inner());
+
+        let names: Vec<String> = keys
+            .iter()
+            .map(|(kind, value)| format!("{}:{}:{}", policy.name, kind.as_str(), value))
+            .collect();
+
+        let mut wait = Duration::ZERO;
+        for name in &names {
+            let bucket = buckets.entry(name.clone()).or_insert(Bucket { tokens: capacity, updated_at: now });
+            let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+            bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+            bucket.updated_at = now;
+            if bucket.tokens < 1.0 {
+                wait = wait.max(Duration::from_secs_f64((1.0 - bucket.tokens) / rate));
+            }
+        }
+        if !wait.is_zero() {
+            return Err(wait);
+        }
+
+        for name in &names {
+            if let Some(bucket) = buckets.get_mut(name) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forgets every bucket for `value`, e.g. after an admin unlocks an
+    /// account.
+    pub fn reset(kind: RateLimitKey, value: &str) {
+        let suffix = format!(":{}:{}", kind.as_str(), value);
+        let mut buckets = buckets().lock().unwrap_or_else(|e| e.into_inner());
+        buckets.retain(|name, _| !name.ends_with(&suffix));
+    }
+
+    /// Periodically drops buckets that have been idle long enough to be full
+    /// again, so memory stays bounded by recent traffic.
+    pub fn spawn_pruner() {
+        tokio::spawn(async {
+            let mut interval = tokio::time::interval(Duration::from_secs(300));
+            loop {
+                interval.tick().await;
+                let mut buckets = buckets().lock().unwrap_or_else(|e| e.into_inner());
+                buckets.retain(|_, bucket| bucket.updated_at.elapsed() < Duration::from_secs(3600));
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Buckets are process-wide, so each test uses its own policy name
+    fn policy(name: &'static str, keys: &'static [RateLimitKey]) -> RateLimitPolicy {
+        RateLimitPolicy { name, burst: 3, per_minute: 6, keys }
+    }
+
+    #[test]
+    fn test_burst_then_refusal() {
+        let policy = policy("test-burst", &[RateLimitKey::Ip]);
+        let keys = [(RateLimitKey::Ip, "203.0.113.7".to_string())];
+        for _ in 0..3 {
+            assert!(RateLimiter::check(&policy, &keys).is_ok());
+        }
+        let wait = RateLimiter::check(&policy, &keys).unwrap_err();
+        // One token every ten seconds
+        assert!(wait > Duration::from_secs(9) && wait <= Duration::from_secs(10), "waited {:?}", wait);
+
+        let other = [(RateLimitKey::Ip, "203.0.113.8".to_string())];
+        assert!(RateLimiter::check(&policy, &other).is_ok());
+    }
+
+    #[test]
+    fn test_every_key_must_have_a_token() {
+        let policy = policy("test-keys", &[RateLimitKey::User, RateLimitKey::Ip]);
+        let ip = (RateLimitKey::Ip, "198.51.100.1".to_string());
+        for user in ["a", "b", "c"] {
+            assert!(RateLimiter::check(&policy, &[(RateLimitKey::User, user.to_string()), ip.clone()]).is_ok());
+        }
+        // A fresh user from the exhausted address is still refused, and the
+        // refusal takes no token from the user's bucket
+        let fresh = (RateLimitKey::User, "d".to_string());
+        assert!(RateLimiter::check(&policy, &[fresh.clone(), ip.clone()]).is_err());
+        for _ in 0..3 {
+            assert!(RateLimiter::check(&policy, std::slice::from_ref(&fresh)).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_reset() {
+        let policy = policy("test-reset", &[RateLimitKey::Email]);
+        let keys = [(RateLimitKey::Email, "locked@example.com".to_string())];
+        for _ in 0..3 {
+            assert!(RateLimiter::check(&policy, &keys).is_ok());
+        }
+        assert!(RateLimiter::check(&policy, &keys).is_err());
+        RateLimiter::reset(RateLimitKey::Email, "locked@example.com");
+        assert!(RateLimiter::check(&policy, &keys).is_ok());
+    }
+}
@@ -0,0 +1,969 @@
+use chrono::{DateTime, Datelike, Duration, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use cron::Schedule;
+use sqlx::SqlitePool;
+use std::env;
+use std::str::FromStr;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{
+    CreateScheduledTransferRequest, ScheduleFrequency, ScheduledTransfer, ScheduledTransferRun,
+    ScheduledTransferView, Wallet,
+};
+use crate::services::payouts::receiving_account;
+use crate::services::{
+    DomainEvent, JobPayload, Ledger, Outbox, PaymentOutcome, TransactionService, WalletPaymentError,
+};
+
+#[derive(Error, Debug)]
+pub enum ScheduledTransferError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Invalid schedule: {0}")]
+    InvalidRule(String),
+    #[error("Recipient has no Stellar account to pay")]
+    RecipientUnpayable,
+    #[error("Scheduled transfer is {0}")]
+    InvalidStatus(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Transfers that repeat on a rule. A scheduler loop turns each due
+/// occurrence into a run and queues a job that sends it through
+/// `TransactionService`, like a transfer made by hand.
+pub struct ScheduledTransferService;
+
+impl ScheduledTransferService {
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        request: &CreateScheduledTransferRequest,
+    ) -> Result<ScheduledTransferView, ScheduledTransferError> {
+        let timezone = request.timezone.clone().unwrap_or_else(|| "UTC".to_string());
+        let tz: Tz = timezone
+            .parse()
+            .map_err(|_| ScheduledTransferError::InvalidRule(format!("unknown time zone {}", timezone)))?;
+        let now = Utc::now();
+
+        let (day_of_month, cron) = match request.frequency {
+            ScheduleFrequency::Monthly if request.cron.is_some() => {
+                return Err(ScheduledTransferError::InvalidRule("cron is only used with the cron frequency".to_string()))
+            }
+            ScheduleFrequency::Monthly => {
+                let day = request.day_of_month.ok_or_else(|| {
+                    ScheduledTransferError::InvalidRule("monthly transfers need day_of_month".to_string())
+                })?;
+                (Some(day as i64), None)
+            }
+            ScheduleFrequency::Cron => {
+                let cron = request.cron.clone().ok_or_else(|| {
+                    ScheduledTransferError::InvalidRule("cron transfers need a cron expression".to_string())
+                })?;
+                (None, Some(cron.split_whitespace().collect::<Vec<_>>().join(" ")))
+            }
+        };
+
+        if recipient_account(pool, &request.recipient_email).await?.is_none() {
+            return Err(ScheduledTransferError::RecipientUnpayable);
+        }
+
+        let start_date = request.start_date.unwrap_or_else(|| now.with_timezone(&tz).date_naive());
+        if request.end_date.is_some_and(|end| end < start_date) {
+            return Err(ScheduledTransferError::InvalidRule("end_date is before start_date".to_string()));
+        }
+
+        let mut schedule = ScheduledTransfer {
+            id: Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            recipient_email: request.recipient_email.clone(),
+            amount: request.amount,
+            currency: request.currency.clone().unwrap_or_else(|| "USD".to_string()),
+            target_currency: request.target_currency.clone().unwrap_or_else(|| "KES".to_string()),
+            frequency: request.frequency.as_str().to_string(),
+            day_of_month,
+            time_of_day: request.time_of_day.clone().unwrap_or_else(|| "09:00".to_string()),
+            cron,
+            timezone,
+            start_date,
+            end_date: request.end_date,
+            status: "active".to_string(),
+            next_run_at: None,
+            last_run_at: None,
+            created_at: now,
+            updated_at: now,
+        };
+        schedule.next_run_at = occurrence_after(&schedule, now).map_err(ScheduledTransferError::InvalidRule)?;
+        if schedule.next_run_at.is_none() {
+            return Err(ScheduledTransferError::InvalidRule("the schedule has no dates left".to_string()));
+        }
+
+        sqlx::query(
+            r#"
+            INSERT INTO scheduled_transfers
+                (id, user_id, recipient_email, amount, currency, target_currency, frequency, day_of_month,
+                 time_of_day, cron, timezone, start_date, end_date, status, next_run_at, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 'active', ?, ?, ?)
+            "#,
+        )
+        .bind(&schedule.id)
+        .bind(&schedule.user_id)
+        .bind(&schedule.recipient_email)
+        .bind(schedule.amount)
+        .bind(&schedule.currency)
+        .bind(&schedule.target_currency)
+        .bind(&schedule.frequency)
+        .bind(schedule.day_of_month)
+        .bind(&schedule.time_of_day)
+        .bind(&schedule.cron)
+        .bind(&schedule.timezone)
+        .bind(schedule.start_date)
+        .bind(schedule.end_date)
+        .bind(schedule.next_run_at)
+        .bind(now)
+        .bind(now)
+        .execute(pool)
+        .await?;
+
+        println!("🗓️ Scheduled transfer {} created, first run at {:?}", schedule.id, schedule.next_run_at);
+        Self::view(pool, schedule).await
+    }
+
+    pub async fn list(pool: &SqlitePool, user_id: &str) -> Result<Vec<ScheduledTransferView>, ScheduledTransferError> {
+        let schedules = sqlx::query_as::<_, ScheduledTransfer>(
+            "SELECT * FROM scheduled_transfers WHERE user_id = ? ORDER BY created_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?;
+
+        Ok(schedules
+            .into_iter()
+            .map(|schedule| ScheduledTransferView { schedule, upcoming: None, runs: None })
+            .collect())
+    }
+
+    pub async fn find(pool: &SqlitePool, user_id: &str, id: &str) -> Result<ScheduledTransferView, ScheduledTransferError> {
+        let schedule = Self::find_schedule(pool, user_id, id).await?;
+        Self::view(pool, schedule).await
+    }
+
+    /// Stops the schedule until it is resumed. A payment waiting for a retry
+    /// is dropped.
+    pub async fn pause(pool: &SqlitePool, user_id: &str, id: &str) -> Result<ScheduledTransferView, ScheduledTransferError> {
+        let schedule = Self::find_schedule(pool, user_id, id).await?;
+        if schedule.status != "active" {
+            return Err(ScheduledTransferError::InvalidStatus(schedule.status));
+        }
+        Self::stop(pool, &schedule, "paused").await?;
+        Self::find(pool, user_id, id).await
+    }
+
+    /// Restarts a paused schedule from its next date after now. Dates missed
+    /// while paused are not paid, and dates already run or skipped stay done.
+    pub async fn resume(pool: &SqlitePool, user_id: &str, id: &str) -> Result<ScheduledTransferView, ScheduledTransferError> {
+        let schedule = Self::find_schedule(pool, user_id, id).await?;
+        if schedule.status != "paused" {
+            return Err(ScheduledTransferError::InvalidStatus(schedule.status));
+        }
+
+        let now = Utc::now();
+        let last_due: Option<DateTime<Utc>> =
+            sqlx::query_scalar("SELECT due_at FROM scheduled_transfer_runs WHERE schedule_id = ? ORDER BY due_at DESC LIMIT 1")
+                .bind(&schedule.id)
+                .fetch_optional(pool)
+                .await?;
+        let after = last_due.map_or(now, |due| due.max(now));
+        let next_run_at = occurrence_after(&schedule, after).map_err(ScheduledTransferError::InvalidRule)?;
+        sqlx::query(
+            "UPDATE scheduled_transfers SET status = ?, next_run_at = ?, updated_at = ? WHERE id = ? AND status = 'paused'",
+        )
+        .bind(if next_run_at.is_some() { "active" } else { "completed" })
+        .bind(next_run_at)
+        .bind(now)
+        .bind(&schedule.id)
+        .execute(pool)
+        .await?;
+
+        Self::find(pool, user_id, id).await
+    }
+
+    /// Skips the next date only; the one after it goes out as usual.
+    pub async fn skip_next(pool: &SqlitePool, user_id: &str, id: &str) -> Result<ScheduledTransferView, ScheduledTransferError> {
+        let schedule = Self::find_schedule(pool, user_id, id).await?;
+        let due_at = match (schedule.status.as_str(), schedule.next_run_at) {
+            ("active", Some(due_at)) => due_at,
+            _ => return Err(ScheduledTransferError::InvalidStatus(schedule.status)),
+        };
+
+        let now = Utc::now();
+        let next_run_at = occurrence_after(&schedule, due_at).map_err(ScheduledTransferError::InvalidRule)?;
+        let mut db_tx = pool.begin().await?;
+        let updated = sqlx::query(
+            r#"
+            UPDATE scheduled_transfers SET status = ?, next_run_at = ?, updated_at = ?
+            WHERE id = ? AND status = 'active' AND next_run_at = ?
+            "#,
+        )
+        .bind(if next_run_at.is_some() { "active" } else { "completed" })
+        .bind(next_run_at)
+        .bind(now)
+        .bind(&schedule.id)
+        .bind(due_at)
+        .execute(&mut *db_tx)
+        .await?;
+        if updated.rows_affected() == 0 {
+            // the scheduler started this date in the meantime
+            return Err(ScheduledTransferError::InvalidStatus("already running".to_string()));
+        }
+        sqlx::query(
+            r#"
+            INSERT INTO scheduled_transfer_runs (id, schedule_id, due_at, status, error, created_at, updated_at)
+            VALUES (?, ?, ?, 'skipped', 'Skipped by user', ?, ?)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&schedule.id)
+        .bind(due_at)
+        .bind(now)
+        .bind(now)
+        .execute(&mut *db_tx)
+        .await?;
+        db_tx.commit().await?;
+
+        Self::find(pool, user_id, id).await
+    }
+
+    /// Ends the schedule for good. A payment waiting for a retry is dropped.
+    pub async fn cancel(pool: &SqlitePool, user_id: &str, id: &str) -> Result<ScheduledTransferView, ScheduledTransferError> {
+        let schedule = Self::find_schedule(pool, user_id, id).await?;
+        if schedule.status != "active" && schedule.status != "paused" {
+            return Err(ScheduledTransferError::InvalidStatus(schedule.status));
+        }
+        Self::stop(pool, &schedule, "cancelled").await?;
+        Self::find(pool, user_id, id).await
+    }
+
+    /// Runs the scheduler every `SCHEDULED_TRANSFER_POLL_SECS` (default 30).
+    pub fn spawn_scheduler(pool: SqlitePool) {
+        let interval_secs = env::var("SCHEDULED_TRANSFER_POLL_SECS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30u64)
+            .max(1);
+
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(interval_secs));
+            loop {
+                interval.tick().await;
+                if let Err(e) = Self::tick(&pool).await {
+                    println!("⚠️ Failed to start scheduled transfers: {}", e);
+                }
+            }
+        });
+    }
+
+    /// Starts a run for every schedule that is due and requeues runs whose
+    /// retry time has come. A schedule that was due several times while the
+    /// server was down is paid once, then moves on to its next date after now.
+    pub async fn tick(pool: &SqlitePool) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        let due = sqlx::query_as::<_, ScheduledTransfer>(
+            "SELECT * FROM scheduled_transfers WHERE status = 'active' AND next_run_at <= ?",
+        )
+        .bind(now)
+        .fetch_all(pool)
+        .await?;
+
+        for schedule in due {
+            let Some(due_at) = schedule.next_run_at else { continue };
+            let next_run_at = occurrence_after(&schedule, due_at.max(now)).ok().flatten();
+            let run_id = Uuid::new_v4().to_string();
+
+            let mut db_tx = pool.begin().await?;
+            let updated = sqlx::query(
+                r#"
+                UPDATE scheduled_transfers SET status = ?, next_run_at = ?, last_run_at = ?, updated_at = ?
+                WHERE id = ? AND status = 'active' AND next_run_at = ?
+                "#,
+            )
+            .bind(if next_run_at.is_some() { "active" } else { "completed" })
+            .bind(next_run_at)
+            .bind(due_at)
+            .bind(now)
+            .bind(&schedule.id)
+            .bind(due_at)
+            .execute(&mut *db_tx)
+            .await?;
+            if updated.rows_affected() == 0 {
+                continue;
+            }
+            sqlx::query(
+                "INSERT INTO scheduled_transfer_runs (id, schedule_id, due_at, created_at, updated_at) VALUES (?, ?, ?, ?, ?)",
+            )
+            .bind(&run_id)
+            .bind(&schedule.id)
+            .bind(due_at)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *db_tx)
+            .await?;
+            Outbox::enqueue(&mut *db_tx, &JobPayload::ScheduledTransfer { run_id: run_id.clone() }).await?;
+            db_tx.commit().await?;
+
+            println!("🗓️ Scheduled transfer {} due at {}, run {} queued", schedule.id, due_at, run_id);
+        }
+
+        let retries: Vec<String> = sqlx::query_scalar(
+            "SELECT id FROM scheduled_transfer_runs WHERE status = 'retrying' AND next_attempt_at <= ?",
+        )
+        .bind(now)
+        .fetch_all(pool)
+        .await?;
+
+        for run_id in retries {
+            let mut db_tx = pool.begin().await?;
+            let updated = sqlx::query(
+                r#"
+                UPDATE scheduled_transfer_runs SET status = 'pending', next_attempt_at = NULL, updated_at = ?
+                WHERE id = ? AND status = 'retrying'
+                "#,
+            )
+            .bind(now)
+            .bind(&run_id)
+            .execute(&mut *db_tx)
+            .await?;
+            if updated.rows_affected() == 0 {
+                continue;
+            }
+            Outbox::enqueue(&mut *db_tx, &JobPayload::ScheduledTransfer { run_id }).await?;
+            db_tx.commit().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Sends one run. Before anything goes out the run moves from `pending`
+    /// to `sending` in the database transaction that creates its transfer and
+    /// debits the wallet, so two runs can't spend the same balance and a
+    /// re-run only resubmits the signed payment. Without enough funds the run
+    /// is checked again every `SCHEDULED_TRANSFER_RETRY_MINUTES` (default
+    /// 360), up to `SCHEDULED_TRANSFER_MAX_RETRIES` (default 3) times. The
+    /// owner hears about the first shortfall and about giving up. A payment
+    /// that fails is credited back; if Horizon can't say whether it went out,
+    /// the error is returned so the job retries the same payment.
+    pub async fn execute(pool: &SqlitePool, run_id: &str) -> Result<(), String> {
+        let Some(run) = Self::find_run(pool, run_id).await.map_err(|e| e.to_string())? else {
+            return Ok(());
+        };
+        if run.status != "pending" && run.status != "sending" {
+            return Ok(());
+        }
+        let schedule = sqlx::query_as::<_, ScheduledTransfer>("SELECT * FROM scheduled_transfers WHERE id = ?")
+            .bind(&run.schedule_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        if run.status == "pending" {
+            if schedule.status == "paused" || schedule.status == "cancelled" {
+                let error = format!("Schedule {}", schedule.status);
+                return Self::close_run(pool, &run.id, "skipped", &error).await.map_err(|e| e.to_string());
+            }
+            let frozen: Option<i64> = sqlx::query_scalar("SELECT 1 FROM users WHERE id = ? AND frozen_at IS NOT NULL")
+                .bind(&schedule.user_id)
+                .fetch_optional(pool)
+                .await
+                .map_err(|e| e.to_string())?;
+            if frozen.is_some() {
+                return Self::close_run(pool, &run.id, "failed", "Account frozen").await.map_err(|e| e.to_string());
+            }
+        }
+        let Some(wallet) = sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE user_id = ?")
+            .bind(&schedule.user_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| e.to_string())?
+        else {
+            return Self::close_run(pool, &run.id, "failed", "No wallet").await.map_err(|e| e.to_string());
+        };
+        let destination = recipient_account(pool, &schedule.recipient_email).await.map_err(|e| e.to_string())?;
+
+        let transaction_id = match (&run.transaction_id, run.status.as_str()) {
+            (Some(transaction_id), "sending") => transaction_id.clone(),
+            _ => {
+                if destination.is_none() {
+                    return Self::close_run(pool, &run.id, "failed", "Recipient has no Stellar account")
+                        .await
+                        .map_err(|e| e.to_string());
+                }
+                match Self::start_run(pool, &schedule, &run).await.map_err(|e| e.to_string())? {
+                    RunStart::Started(transaction_id) => transaction_id,
+                    RunStart::Underfunded => {
+                        return Self::underfunded(pool, &schedule, &run).await.map_err(|e| e.to_string());
+                    }
+                    RunStart::Taken => return Ok(()),
+                }
+            }
+        };
+
+        let tx_service = TransactionService::new();
+        // A transfer that is already signed only needs submitting; one whose
+        // recipient lost their account since can't be signed and fails below
+        if let Some(destination) = &destination {
+            match tx_service.prepare(pool, &transaction_id, &wallet.stellar_secret_key, destination).await {
+                Ok(_) => {}
+                Err(WalletPaymentError::Failed(reason)) => {
+                    return Self::fail_run(pool, &run.id, &transaction_id, &reason).await.map_err(|e| e.to_string());
+                }
+                Err(e) => return Err(e.to_string()),
+            }
+        }
+        let tx_hash = match tx_service.submit(pool, &transaction_id).await.map_err(|e| e.to_string())? {
+            PaymentOutcome::Completed(tx_hash) => tx_hash,
+            PaymentOutcome::Failed(reason) => {
+                return Self::fail_run(pool, &run.id, &transaction_id, &reason).await.map_err(|e| e.to_string());
+            }
+            PaymentOutcome::Unknown(e) => return Err(format!("payment outcome unknown: {}", e)),
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE scheduled_transfer_runs SET status = 'completed', attempts = attempts + 1, error = NULL, updated_at = ?
+            WHERE id = ? AND status = 'sending'
+            "#,
+        )
+        .bind(Utc::now())
+        .bind(&run.id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        println!("✅ Scheduled transfer {} run {} sent: {}", schedule.id, run.id, tx_hash);
+        Ok(())
+    }
+
+    /// Marks a run failed once its job has used all its attempts. A run
+    /// whose payment was signed may have been sent, so it stays `sending`
+    /// until a retry of the job finds out.
+    pub async fn abandon(pool: &SqlitePool, run_id: &str, error: &str) -> Result<(), sqlx::Error> {
+        let Some(run) = Self::find_run(pool, run_id).await? else {
+            return Ok(());
+        };
+        let Some(transaction_id) = run.transaction_id.filter(|_| run.status == "sending") else {
+            return Self::close_run(pool, run_id, "failed", error).await;
+        };
+        let status: Option<String> = sqlx::query_scalar("SELECT status FROM transactions WHERE id = ?")
+            .bind(&transaction_id)
+            .fetch_optional(pool)
+            .await?;
+        if status.as_deref() == Some("pending") {
+            return Self::fail_run(pool, run_id, &transaction_id, error).await;
+        }
+        println!("⚠️ Scheduled transfer run {} left for reconciliation after its job died", run_id);
+        Ok(())
+    }
+
+    /// Claims a pending run: creates its transfer and debits the wallet,
+    /// all or nothing.
+    async fn start_run(
+        pool: &SqlitePool,
+        schedule: &ScheduledTransfer,
+        run: &ScheduledTransferRun,
+    ) -> Result<RunStart, sqlx::Error> {
+        let now = Utc::now();
+        let mut db_tx = pool.begin().await?;
+        let claimed = sqlx::query(
+            "UPDATE scheduled_transfer_runs SET status = 'sending', updated_at = ? WHERE id = ? AND status = 'pending'",
+        )
+        .bind(now)
+        .bind(&run.id)
+        .execute(&mut *db_tx)
+        .await?
+        .rows_affected();
+        if claimed == 0 {
+            return Ok(RunStart::Taken);
+        }
+
+        let transaction_id: String = sqlx::query_scalar(
+            r#"
+            INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, status)
+            VALUES (?, ?, ?, ?, ?, ?, 'pending')
+            RETURNING id
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&schedule.user_id)
+        .bind(&schedule.recipient_email)
+        .bind(schedule.amount)
+        .bind(&schedule.currency)
+        .bind(&schedule.target_currency)
+        .fetch_one(&mut *db_tx)
+        .await?;
+        if Ledger::post(&mut db_tx, &transaction_id, &schedule.user_id, -schedule.amount).await?.is_none() {
+            return Ok(RunStart::Underfunded);
+        }
+        sqlx::query("UPDATE scheduled_transfer_runs SET transaction_id = ? WHERE id = ?")
+            .bind(&transaction_id)
+            .bind(&run.id)
+            .execute(&mut *db_tx)
+            .await?;
+        db_tx.commit().await?;
+        Ok(RunStart::Started(transaction_id))
+    }
+
+    /// Fails a `sending` run whose payment is known not to have gone out,
+    /// and credits back what the run debited.
+    async fn fail_run(pool: &SqlitePool, run_id: &str, transaction_id: &str, reason: &str) -> Result<(), sqlx::Error> {
+        TransactionService::fail(pool, transaction_id, reason, None).await?;
+
+        let mut db_tx = pool.begin().await?;
+        let failed = sqlx::query(
+            r#"
+            UPDATE scheduled_transfer_runs SET status = 'failed', attempts = attempts + 1, error = ?, updated_at = ?
+            WHERE id = ? AND status = 'sending'
+            "#,
+        )
+        .bind(reason)
+        .bind(Utc::now())
+        .bind(run_id)
+        .execute(&mut *db_tx)
+        .await?
+        .rows_affected();
+        if failed == 1 {
+            for (user_id, amount) in Ledger::entries(&mut db_tx, transaction_id).await? {
+                Ledger::post(&mut db_tx, transaction_id, &user_id, -amount).await?;
+            }
+        }
+        db_tx.commit().await?;
+
+        println!("❌ Scheduled transfer run {} failed: {}", run_id, reason);
+        Ok(())
+    }
+
+    async fn underfunded(pool: &SqlitePool, schedule: &ScheduledTransfer, run: &ScheduledTransferRun) -> Result<(), sqlx::Error> {
+        let max_retries: i64 = env::var("SCHEDULED_TRANSFER_MAX_RETRIES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(3);
+        let retry_minutes: i64 = env::var("SCHEDULED_TRANSFER_RETRY_MINUTES")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(360);
+
+        let now = Utc::now();
+        let attempts = run.attempts + 1;
+        let retry_at = (attempts <= max_retries).then(|| now + Duration::minutes(retry_minutes));
+
+        let mut db_tx = pool.begin().await?;
+        sqlx::query(
+            r#"
+            UPDATE scheduled_transfer_runs
+            SET status = ?, attempts = ?, next_attempt_at = ?, error = 'Insufficient funds', updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(if retry_at.is_some() { "retrying" } else { "failed" })
+        .bind(attempts)
+        .bind(retry_at)
+        .bind(now)
+        .bind(&run.id)
+        .execute(&mut *db_tx)
+        .await?;
+        if attempts == 1 || retry_at.is_none() {
+            Outbox::enqueue(
+                &mut *db_tx,
+                &JobPayload::Notify {
+                    event: DomainEvent::ScheduledTransferFailed {
+                        schedule_id: schedule.id.clone(),
+                        user_id: schedule.user_id.clone(),
+                        recipient: schedule.recipient_email.clone(),
+                        amount: schedule.amount,
+                        currency: schedule.currency.clone(),
+                        retry_at,
+                    },
+                },
+            )
+            .await?;
+        }
+        db_tx.commit().await?;
+
+        println!("⚠️ Scheduled transfer {} run {} short of funds (attempt {})", schedule.id, run.id, attempts);
+        Ok(())
+    }
+
+    async fn stop(pool: &SqlitePool, schedule: &ScheduledTransfer, status: &str) -> Result<(), sqlx::Error> {
+        let now = Utc::now();
+        let mut db_tx = pool.begin().await?;
+        sqlx::query("UPDATE scheduled_transfers SET status = ?, next_run_at = NULL, updated_at = ? WHERE id = ?")
+            .bind(status)
+            .bind(now)
+            .bind(&schedule.id)
+            .execute(&mut *db_tx)
+            .await?;
+        sqlx::query(
+            r#"
+            UPDATE scheduled_transfer_runs SET status = 'skipped', next_attempt_at = NULL, error = ?, updated_at = ?
+            WHERE schedule_id = ? AND status = 'retrying'
+            "#,
+        )
+        .bind(format!("Schedule {}", status))
+        .bind(now)
+        .bind(&schedule.id)
+        .execute(&mut *db_tx)
+        .await?;
+        db_tx.commit().await
+    }
+
+    async fn close_run(pool: &SqlitePool, run_id: &str, status: &str, error: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            r#"
+            UPDATE scheduled_transfer_runs SET status = ?, error = ?, next_attempt_at = NULL, updated_at = ?
+            WHERE id = ? AND status = 'pending'
+            "#,
+        )
+        .bind(status)
+        .bind(error)
+        .bind(Utc::now())
+        .bind(run_id)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn find_schedule(pool: &SqlitePool, user_id: &str, id: &str) -> Result<ScheduledTransfer, ScheduledTransferError> {
+        sqlx::query_as::<_, ScheduledTransfer>("SELECT * FROM scheduled_transfers WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(ScheduledTransferError::NotFound)
+    }
+
+    async fn find_run(pool: &SqlitePool, run_id: &str) -> Result<Option<ScheduledTransferRun>, sqlx::Error> {
+        sqlx::query_as::<_, ScheduledTransferRun>("SELECT * FROM scheduled_transfer_runs WHERE id = ?")
+            .bind(run_id)
+            .fetch_optional(pool)
+            .await
+    }
+
+    async fn view(pool: &SqlitePool, schedule: ScheduledTransfer) -> Result<ScheduledTransferView, ScheduledTransferError> {
+        let runs = sqlx::query_as::<_, ScheduledTransferRun>(
+            "SELECT * FROM scheduled_transfer_runs WHERE schedule_id = ? ORDER BY due_at DESC LIMIT 50",
+        )
+        .bind(&schedule.id)
+        .fetch_all(pool)
+        .await?;
+
+        let mut upcoming = Vec::new();
+        let mut next = schedule.next_run_at;
+        while let Some(at) = next {
+            upcoming.push(at);
+            if upcoming.len() == 5 {
+                break;
+            }
+            next = occurrence_after(&schedule, at).ok().flatten();
+        }
+
+        Ok(ScheduledTransferView { schedule, upcoming: Some(upcoming), runs: Some(runs) })
+    }
+}
+
+/// How claiming a pending run ended.
+enum RunStart {
+    Started(String),
+    Underfunded,
+    /// Another run of the job claimed it first.
+    Taken,
+}
+
+/// The Stellar account a schedule pays: where the NovaPay user with the
+/// recipient's email is paid.
+async fn recipient_account(pool: &SqlitePool, email: &str) -> Result<Option<String>, sqlx::Error> {
+    let user_id: Option<String> = sqlx::query_scalar("SELECT id FROM users WHERE email = ?")
+        .bind(email.trim())
+        .fetch_optional(pool)
+        .await?;
+    match user_id {
+        Some(user_id) => receiving_account(pool, &user_id).await,
+        None => Ok(None),
+    }
+}
+
+/// The first date of the schedule strictly after `after`, or `None` once it
+/// is past `end_date`. Dates are worked out in the schedule's time zone; a
+/// time that falls in a daylight-saving gap moves forward an hour.
+fn occurrence_after(schedule: &ScheduledTransfer, after: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, String> {
+    let tz: Tz = schedule
+        .timezone
+        .parse()
+        .map_err(|_| format!("unknown time zone {}", schedule.timezone))?;
+    let start = local_instant(&tz, schedule.start_date, NaiveTime::MIN);
+    let after = after.max(start - Duration::seconds(1)).with_timezone(&tz);
+
+    let next = match (&schedule.cron, schedule.day_of_month) {
+        (Some(expression), _) => {
+            let fields: Vec<&str> = expression.split_whitespace().collect();
+            if fields.len() != 5 {
+                return Err("cron needs five fields: minute hour day-of-month month day-of-week".to_string());
+            }
+            let expression = format!("0 {} {} {} {} {}", fields[0], fields[1], fields[2], fields[3], cron_weekdays(fields[4]));
+            let cron = Schedule::from_str(&expression).map_err(|e| format!("invalid cron expression: {}", e))?;
+            cron.after(&after).next().map(|at| at.with_timezone(&Utc))
+        }
+        (None, Some(day)) => {
+            let time = NaiveTime::parse_from_str(&schedule.time_of_day, "%H:%M")
+                .map_err(|_| format!("invalid time_of_day {}, expected HH:MM", schedule.time_of_day))?;
+            let (mut year, mut month) = (after.year(), after.month());
+            loop {
+                let date = NaiveDate::from_ymd_opt(year, month, (day as u32).min(days_in_month(year, month)))
+                    .ok_or_else(|| "invalid day_of_month".to_string())?;
+                let at = local_instant(&tz, date, time);
+                if at > after.with_timezone(&Utc) {
+                    break Some(at);
+                }
+                (year, month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+            }
+        }
+        (None, None) => return Err("no rule".to_string()),
+    };
+
+    Ok(next.filter(|at| {
+        schedule
+            .end_date
+            .is_none_or(|end| at.with_timezone(&tz).date_naive() <= end)
+    }))
+}
+
+fn local_instant(tz: &Tz, date: NaiveDate, time: NaiveTime) -> DateTime<Utc> {
+    let local = date.and_time(time);
+    tz.from_local_datetime(&local)
+        .earliest()
+        .or_else(|| tz.from_local_datetime(&(local + Duration::hours(1))).earliest())
+        .map(|at| at.with_timezone(&Utc))
+        .unwrap_or_else(|| Utc.from_utc_datetime(&local))
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 { (year + 1, 1) } else { (year, month + 1) };
+    NaiveDate::from_ymd_opt(next_year, next_month, 1)
+        .and_then(|first| first.pred_opt())
+        .map(|last| last.day())
+        .unwrap_or(28)
+}
+
+/// Standard cron numbers weekdays 0-6 (or 7) from Sunday; the `cron` crate
+/// uses 1-7. Numbers are rewritten as names so both mean the same day.
+fn cron_weekdays(field: &str) -> String {
+    const DAYS: [&str; 8] = ["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT", "SUN"];
+    let name = |value: &str| value.parse::<usize>().ok().and_then(|n| DAYS.get(n)).map_or(value.to_string(), |d| d.to_string());
+
+    field
+        .split(',')
+        .map(|part| {
+            let (range, step) = match part.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (part, None),
+            };
+            let range = range.split('-').map(name).collect::<Vec<_>>().join("-");
+            match step {
+                Some(step) => format!("{}/{}", range, step),
+                None => range,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool};
+
+    fn at(value: &str) -> DateTime<Utc> {
+        value.parse().unwrap()
+    }
+
+    fn date(value: &str) -> NaiveDate {
+        value.parse().unwrap()
+    }
+
+    fn monthly(day: i64, time: &str, timezone: &str) -> ScheduledTransfer {
+        let now = Utc::now();
+        ScheduledTransfer {
+            id: "s1".to_string(),
+            user_id: "u1".to_string(),
+            recipient_email: "mama@example.com".to_string(),
+            amount: 40.0,
+            currency: "USD".to_string(),
+            target_currency: "KES".to_string(),
+            frequency: "monthly".to_string(),
+            day_of_month: Some(day),
+            time_of_day: time.to_string(),
+            cron: None,
+            timezone: timezone.to_string(),
+            start_date: date("2024-01-01"),
+            end_date: None,
+            status: "active".to_string(),
+            next_run_at: None,
+            last_run_at: None,
+            created_at: now,
+            updated_at: now,
+        }
+    }
+
+    fn cron(expression: &str, timezone: &str) -> ScheduledTransfer {
+        ScheduledTransfer {
+            frequency: "cron".to_string(),
+            day_of_month: None,
+            cron: Some(expression.to_string()),
+            ..monthly(1, "09:00", timezone)
+        }
+    }
+
+    fn next(schedule: &ScheduledTransfer, after: &str) -> Option<DateTime<Utc>> {
+        occurrence_after(schedule, at(after)).unwrap()
+    }
+
+    #[test]
+    fn test_cron_weekdays() {
+        assert_eq!(cron_weekdays("0"), "SUN");
+        assert_eq!(cron_weekdays("7"), "SUN");
+        assert_eq!(cron_weekdays("1-5"), "MON-FRI");
+        assert_eq!(cron_weekdays("0,6"), "SUN,SAT");
+        assert_eq!(cron_weekdays("1-5/2"), "MON-FRI/2");
+        assert_eq!(cron_weekdays("*"), "*");
+        assert_eq!(cron_weekdays("*/2"), "*/2");
+        assert_eq!(cron_weekdays("MON,wed"), "MON,wed");
+        assert_eq!(cron_weekdays("8"), "8");
+    }
+
+    #[test]
+    fn test_monthly_occurrences() {
+        let schedule = monthly(31, "09:00", "UTC");
+        assert_eq!(next(&schedule, "2025-01-15T00:00:00Z"), Some(at("2025-01-31T09:00:00Z")));
+        // Strictly after, and shorter months use their last day
+        assert_eq!(next(&schedule, "2025-01-31T09:00:00Z"), Some(at("2025-02-28T09:00:00Z")));
+        assert_eq!(next(&schedule, "2024-01-31T09:00:00Z"), Some(at("2024-02-29T09:00:00Z")));
+        assert_eq!(next(&schedule, "2025-12-31T10:00:00Z"), Some(at("2026-01-31T09:00:00Z")));
+
+        // Nothing before the start date
+        let schedule = ScheduledTransfer { start_date: date("2025-09-01"), ..monthly(1, "09:00", "UTC") };
+        assert_eq!(next(&schedule, "2025-06-15T00:00:00Z"), Some(at("2025-09-01T09:00:00Z")));
+
+        let schedule = monthly(1, "9am", "UTC");
+        assert!(occurrence_after(&schedule, at("2025-01-01T00:00:00Z")).is_err());
+    }
+
+    #[test]
+    fn test_occurrences_follow_the_time_zone() {
+        let schedule = monthly(15, "09:00", "Europe/London");
+        assert_eq!(next(&schedule, "2025-01-01T00:00:00Z"), Some(at("2025-01-15T09:00:00Z")));
+        // Summer time
+        assert_eq!(next(&schedule, "2025-07-01T00:00:00Z"), Some(at("2025-07-15T08:00:00Z")));
+
+        // 02:30 doesn't exist on the night clocks go forward; it moves to 03:30
+        let schedule = monthly(9, "02:30", "America/New_York");
+        assert_eq!(next(&schedule, "2025-03-01T00:00:00Z"), Some(at("2025-03-09T07:30:00Z")));
+        // 01:30 happens twice on the night clocks go back; the first one counts
+        let schedule = monthly(2, "01:30", "America/New_York");
+        assert_eq!(next(&schedule, "2025-11-01T00:00:00Z"), Some(at("2025-11-02T05:30:00Z")));
+
+        // The date is the local one: 00:30 in Nairobi is still the day before in UTC
+        let schedule = monthly(1, "00:30", "Africa/Nairobi");
+        assert_eq!(next(&schedule, "2025-04-15T00:00:00Z"), Some(at("2025-04-30T21:30:00Z")));
+
+        let schedule = monthly(1, "09:00", "Mars/Olympus");
+        assert!(occurrence_after(&schedule, at("2025-01-01T00:00:00Z")).is_err());
+    }
+
+    #[test]
+    fn test_end_date() {
+        let schedule = ScheduledTransfer { end_date: Some(date("2025-03-28")), ..monthly(28, "09:00", "UTC") };
+        // The end date itself is included
+        assert_eq!(next(&schedule, "2025-02-28T10:00:00Z"), Some(at("2025-03-28T09:00:00Z")));
+        assert_eq!(next(&schedule, "2025-03-28T09:00:00Z"), None);
+
+        // Compared in the schedule's time zone: 23:00 UTC on the 27th is the
+        // 28th in Nairobi, after the end date
+        let schedule = ScheduledTransfer { end_date: Some(date("2025-03-27")), ..cron("0 2 * * *", "Africa/Nairobi") };
+        assert_eq!(next(&schedule, "2025-03-25T12:00:00Z"), Some(at("2025-03-25T23:00:00Z")));
+        assert_eq!(next(&schedule, "2025-03-25T23:00:00Z"), Some(at("2025-03-26T23:00:00Z")));
+        assert_eq!(next(&schedule, "2025-03-26T23:00:00Z"), None);
+    }
+
+    #[test]
+    fn test_cron_occurrences() {
+        // Wednesday 2 July 2025; Monday is 1, Sunday 0 or 7
+        let monday = cron("0 9 * * 1", "Europe/London");
+        assert_eq!(next(&monday, "2025-07-02T12:00:00Z"), Some(at("2025-07-07T08:00:00Z")));
+        for sunday in ["0 9 * * 0", "0 9 * * 7"] {
+            assert_eq!(next(&cron(sunday, "UTC"), "2025-07-02T12:00:00Z"), Some(at("2025-07-06T09:00:00Z")));
+        }
+        let weekdays = cron("30 17 * * 1-5", "UTC");
+        assert_eq!(next(&weekdays, "2025-07-04T18:00:00Z"), Some(at("2025-07-07T17:30:00Z")));
+        let first_of_month = cron("0 6 1 * *", "America/New_York");
+        assert_eq!(next(&first_of_month, "2025-03-05T00:00:00Z"), Some(at("2025-04-01T10:00:00Z")));
+
+        assert!(occurrence_after(&cron("0 9 * *", "UTC"), at("2025-01-01T00:00:00Z")).is_err());
+        assert!(occurrence_after(&cron("0 25 * * *", "UTC"), at("2025-01-01T00:00:00Z")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_runs_cannot_spend_the_same_balance() {
+        let pool = memory_pool().await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+        sqlx::query("INSERT INTO wallets (id, user_id, stellar_public_key, stellar_secret_key, balance) VALUES ('w1', 'u1', 'G', 'S', 50)")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let schedule = monthly(1, "09:00", "UTC");
+        let now = Utc::now();
+        sqlx::query(
+            r#"
+            INSERT INTO scheduled_transfers (id, user_id, recipient_email, amount, currency, target_currency, frequency, day_of_month, time_of_day, timezone, start_date, created_at, updated_at)
+            VALUES ('s1', 'u1', 'mama@example.com', 40, 'USD', 'KES', 'monthly', 1, '09:00', 'UTC', '2024-01-01', ?, ?)
+            "#,
+        )
+        .bind(now)
+        .bind(now)
+        .execute(&pool)
+        .await
+        .unwrap();
+        for (id, due_at) in [("r1", "2025-01-01T09:00:00Z"), ("r2", "2025-02-01T09:00:00Z")] {
+            sqlx::query("INSERT INTO scheduled_transfer_runs (id, schedule_id, due_at, created_at, updated_at) VALUES (?, 's1', ?, ?, ?)")
+                .bind(id)
+                .bind(at(due_at))
+                .bind(now)
+                .bind(now)
+                .execute(&pool)
+                .await
+                .unwrap();
+        }
+        let r1 = ScheduledTransferService::find_run(&pool, "r1").await.unwrap().unwrap();
+        let r2 = ScheduledTransferService::find_run(&pool, "r2").await.unwrap().unwrap();
+
+        let RunStart::Started(transaction_id) = ScheduledTransferService::start_run(&pool, &schedule, &r1).await.unwrap() else {
+            panic!("the first run should start");
+        };
+        assert!(matches!(
+            ScheduledTransferService::start_run(&pool, &schedule, &r2).await.unwrap(),
+            RunStart::Underfunded
+        ));
+        // A second claim of the same run changes nothing
+        assert!(matches!(ScheduledTransferService::start_run(&pool, &schedule, &r1).await.unwrap(), RunStart::Taken));
+        let balance: f64 = sqlx::query_scalar("SELECT balance FROM wallets WHERE user_id = 'u1'").fetch_one(&pool).await.unwrap();
+        assert_eq!(balance, 10.0);
+        let r2 = ScheduledTransferService::find_run(&pool, "r2").await.unwrap().unwrap();
+        assert_eq!((r2.status.as_str(), r2.transaction_id), ("pending", None));
+
+        // A payment known not to have gone out is credited back, once
+        ScheduledTransferService::fail_run(&pool, "r1", &transaction_id, "tx_failed").await.unwrap();
+        ScheduledTransferService::fail_run(&pool, "r1", &transaction_id, "tx_failed").await.unwrap();
+        let balance: f64 = sqlx::query_scalar("SELECT balance FROM wallets WHERE user_id = 'u1'").fetch_one(&pool).await.unwrap();
+        assert_eq!(balance, 50.0);
+        let r1 = ScheduledTransferService::find_run(&pool, "r1").await.unwrap().unwrap();
+        assert_eq!(r1.status, "failed");
+    }
+}
@@ -0,0 +1,545 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use ed25519_dalek::{Signer, SigningKey};
+use rand::RngCore;
+use sqlx::SqlitePool;
+use std::collections::HashSet;
+use std::env;
+use stellar_xdr::curr::{
+    DataValue, DecoratedSignature, Limits, ManageDataOp, Memo, MuxedAccount, Operation, OperationBody, Preconditions,
+    SequenceNumber, Signature, SignatureHint, String64, TimeBounds, TimePoint, Transaction, TransactionEnvelope,
+    TransactionExt, TransactionV1Envelope, Uint256, WriteXdr,
+};
+use thiserror::Error;
+
+use crate::models::{Sep10ChallengeResponse, User};
+use crate::services::stellar_tx::{AccountSigners, StellarNetwork, StellarTxBuilder, StellarTxError};
+
+/// Random bytes in a challenge's nonce; base64 makes them the 64-byte value
+/// the ManageData op can hold.
+const NONCE_LEN: usize = 48;
+
+#[derive(Error, Debug)]
+pub enum Sep10Error {
+    #[error("Unknown home domain")]
+    WrongHomeDomain,
+    #[error("Invalid challenge: {0}")]
+    InvalidChallenge(&'static str),
+    #[error("Challenge expired")]
+    Expired,
+    #[error("Challenge was already used")]
+    AlreadyUsed,
+    #[error("Signatures do not meet the account's medium threshold")]
+    NotAuthorized,
+    #[error("No user has registered this account")]
+    UnknownAccount,
+    #[error("Account is registered to more than one user")]
+    AmbiguousAccount,
+    #[error(transparent)]
+    Stellar(#[from] StellarTxError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// SEP-10 settings: `SEP10_HOME_DOMAIN` names this service in the challenge,
+/// `SEP10_WEB_AUTH_DOMAIN` (defaults to the home domain) is where the
+/// endpoint is served, and `SEP10_SIGNING_KEY` is the `S...` seed challenges
+/// are signed with; it must match `SIGNING_KEY` in `stellar.toml`.
+struct Sep10Config {
+    home_domain: String,
+    web_auth_domain: String,
+    signing_key: SigningKey,
+    timeout_secs: i64,
+}
+
+impl Sep10Config {
+    fn from_env() -> Self {
+        let home_domain = env::var("SEP10_HOME_DOMAIN").unwrap_or_else(|_| "localhost".to_string());
+        let signing_key = Sep10Service::signing_key().expect("SEP10_SIGNING_KEY is checked at startup");
+
+        Self {
+            web_auth_domain: env::var("SEP10_WEB_AUTH_DOMAIN").unwrap_or_else(|_| home_domain.clone()),
+            home_domain,
+            signing_key,
+            timeout_secs: env::var("SEP10_CHALLENGE_TIMEOUT_SECS")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .unwrap_or(300),
+        }
+    }
+
+    fn server_account(&self) -> MuxedAccount {
+        MuxedAccount::Ed25519(Uint256(self.signing_key.verifying_key().to_bytes()))
+    }
+}
+
+/// Stellar Web Authentication: the server signs a challenge transaction that
+/// can never be submitted (sequence number 0), the client adds signatures
+/// from the account's signers, and a valid round trip proves control of the
+/// account.
+pub struct Sep10Service;
+
+impl Sep10Service {
+    /// Reads `SEP10_SIGNING_KEY`. Checked at startup: challenges and SEP-7
+    /// URIs signed with a guessable key would prove nothing.
+    pub fn signing_key() -> Result<SigningKey, String> {
+        let seed = env::var("SEP10_SIGNING_KEY").map_err(|_| "SEP10_SIGNING_KEY must be set".to_string())?;
+        stellar_strkey::ed25519::PrivateKey::from_string(seed.trim())
+            .map(|key| SigningKey::from_bytes(&key.0))
+            .map_err(|_| "SEP10_SIGNING_KEY must be an S... Stellar secret seed".to_string())
+    }
+
+    /// `G...` address of the key challenges are signed with.
+    pub fn signing_public_key() -> String {
+        let key = Sep10Config::from_env().signing_key.verifying_key().to_bytes();
+        stellar_strkey::ed25519::PublicKey(key).to_string()
+    }
+
+    /// Domain whose `stellar.toml` publishes the signing key.
+    pub fn home_domain() -> String {
+        Sep10Config::from_env().home_domain
+    }
+
+    /// Signs `message` with the same key, which is also how SEP-7 URIs from
+    /// this domain are signed.
+    pub fn sign(message: &[u8]) -> [u8; 64] {
+        Sep10Config::from_env().signing_key.sign(message).to_bytes()
+    }
+
+    pub fn web_auth_endpoint() -> String {
+        format!("https://{}/auth/sep10", Sep10Config::from_env().web_auth_domain)
+    }
+
+    pub async fn challenge(
+        pool: &SqlitePool,
+        account: &str,
+        home_domain: Option<&str>,
+    ) -> Result<Sep10ChallengeResponse, Sep10Error> {
+        let config = Sep10Config::from_env();
+        if home_domain.is_some_and(|domain| domain != config.home_domain) {
+            return Err(Sep10Error::WrongHomeDomain);
+        }
+        let client_key = StellarTxBuilder::public_key_bytes(account.trim())?;
+
+        let now = chrono::Utc::now();
+        let expires_at = now + chrono::Duration::seconds(config.timeout_secs);
+        let mut nonce = [0u8; NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let nonce = STANDARD.encode(nonce);
+
+        let operations = vec![
+            Operation {
+                source_account: Some(MuxedAccount::Ed25519(Uint256(client_key))),
+                body: OperationBody::ManageData(ManageDataOp {
+                    data_name: data_name(&format!("{} auth", config.home_domain))?,
+                    data_value: Some(data_value(nonce.as_bytes())?),
+                }),
+            },
+            Operation {
+                source_account: Some(config.server_account()),
+                body: OperationBody::ManageData(ManageDataOp {
+                    data_name: data_name("web_auth_domain")?,
+                    data_value: Some(data_value(config.web_auth_domain.as_bytes())?),
+                }),
+            },
+        ];
+        let base_fee: u32 = env::var("STELLAR_BASE_FEE").ok().and_then(|v| v.parse().ok()).unwrap_or(100);
+        let tx = Transaction {
+            source_account: config.server_account(),
+            fee: base_fee * operations.len() as u32,
+            seq_num: SequenceNumber(0),
+            cond: Preconditions::Time(TimeBounds {
+                min_time: TimePoint(now.timestamp() as u64),
+                max_time: TimePoint(expires_at.timestamp() as u64),
+            }),
+            memo: Memo::None,
+            operations: operations.try_into().map_err(|_| StellarTxError::MalformedXdr)?,
+            ext: TransactionExt::V0,
+        };
+
+        let network = StellarNetwork::from_env();
+        let hash = network.hash(&tx)?;
+        let signature = DecoratedSignature {
+            hint: SignatureHint(config.signing_key.verifying_key().to_bytes()[28..].try_into().unwrap_or_default()),
+            signature: Signature(
+                config
+                    .signing_key
+                    .sign(&hash)
+                    .to_bytes()
+                    .to_vec()
+                    .try_into()
+                    .map_err(|_| StellarTxError::MalformedXdr)?,
+            ),
+        };
+        let transaction = TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx,
+            signatures: vec![signature].try_into().map_err(|_| StellarTxError::MalformedXdr)?,
+        })
+        .to_xdr_base64(Limits::none())
+        .map_err(|_| StellarTxError::MalformedXdr)?;
+
+        sqlx::query("INSERT INTO sep10_challenges (nonce, account, expires_at, created_at) VALUES (?, ?, ?, ?)")
+            .bind(&nonce)
+            .bind(account.trim())
+            .bind(expires_at)
+            .bind(now)
+            .execute(pool)
+            .await?;
+
+        Ok(Sep10ChallengeResponse {
+            transaction,
+            network_passphrase: network.passphrase.to_string(),
+        })
+    }
+
+    /// Checks a signed challenge and returns the user who registered the
+    /// account. Each challenge can be redeemed once.
+    pub async fn verify(pool: &SqlitePool, envelope_xdr: &str) -> Result<User, Sep10Error> {
+        let account = Self::verify_challenge(pool, &StellarNetwork::from_env(), envelope_xdr).await?;
+
+        let mut users = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE id IN (SELECT user_id FROM external_accounts WHERE public_key = ?)",
+        )
+        .bind(&account)
+        .fetch_all(pool)
+        .await?;
+        match users.len() {
+            0 => Err(Sep10Error::UnknownAccount),
+            1 => Ok(users.remove(0)),
+            _ => Err(Sep10Error::AmbiguousAccount),
+        }
+    }
+
+    /// Checks a signed challenge, consumes it and returns the `G...` account
+    /// it proves control of. The signatures must add up to the account's
+    /// medium threshold; an account that isn't on the network yet must be
+    /// signed by its own key. Signers are looked up on `network`.
+    pub async fn verify_challenge(
+        pool: &SqlitePool,
+        network: &StellarNetwork,
+        envelope_xdr: &str,
+    ) -> Result<String, Sep10Error> {
+        let config = Sep10Config::from_env();
+        let server_key = Self::signing_public_key();
+        let (tx, signatures) = StellarTxBuilder::parse_envelope(envelope_xdr)?;
+
+        if tx.source_account != config.server_account() {
+            return Err(Sep10Error::InvalidChallenge("source is not the server account"));
+        }
+        if tx.seq_num.0 != 0 {
+            return Err(Sep10Error::InvalidChallenge("sequence number must be 0"));
+        }
+        let now = chrono::Utc::now().timestamp() as u64;
+        match &tx.cond {
+            Preconditions::Time(bounds) if bounds.max_time.0 != 0 => {
+                if now < bounds.min_time.0 || now > bounds.max_time.0 {
+                    return Err(Sep10Error::Expired);
+                }
+            }
+            _ => return Err(Sep10Error::InvalidChallenge("missing time bounds")),
+        }
+
+        let (first, rest) = tx
+            .operations
+            .split_first()
+            .ok_or(Sep10Error::InvalidChallenge("no operations"))?;
+        let (account, nonce) = match (&first.source_account, &first.body) {
+            (Some(MuxedAccount::Ed25519(key)), OperationBody::ManageData(op))
+                if op.data_name.to_utf8_string_lossy() == format!("{} auth", config.home_domain) =>
+            {
+                let nonce = op
+                    .data_value
+                    .as_ref()
+                    .and_then(|value| String::from_utf8(value.to_vec()).ok())
+                    .filter(|nonce| nonce.len() == 64)
+                    .ok_or(Sep10Error::InvalidChallenge("bad nonce"))?;
+                (stellar_strkey::ed25519::PublicKey(key.0).to_string(), nonce)
+            }
+            _ => return Err(Sep10Error::InvalidChallenge("first operation must be the client's ManageData")),
+        };
+        for op in rest {
+            let OperationBody::ManageData(data) = &op.body else {
+                return Err(Sep10Error::InvalidChallenge("only ManageData operations are allowed"));
+            };
+            if op.source_account.as_ref() != Some(&config.server_account()) {
+                return Err(Sep10Error::InvalidChallenge("later operations must come from the server"));
+            }
+            if data.data_name.to_utf8_string_lossy() == "web_auth_domain"
+                && data.data_value.as_ref().map(|value| value.to_vec()) != Some(config.web_auth_domain.clone().into_bytes())
+            {
+                return Err(Sep10Error::InvalidChallenge("wrong web_auth_domain"));
+            }
+        }
+
+        let hash = network.hash(&tx)?;
+        if !StellarTxBuilder::signed_by(&server_key, &hash, &signatures) {
+            return Err(Sep10Error::InvalidChallenge("not signed by the server"));
+        }
+
+        let signers = match network.signers(&account).await {
+            Ok(signers) => signers,
+            Err(StellarTxError::AccountNotFound(_)) => AccountSigners {
+                signers: vec![(account.clone(), 1)],
+                med_threshold: 0,
+                high_threshold: 0,
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        // Every signature has to belong to the server or one of the
+        // account's signers; each signer counts once
+        let mut signed = HashSet::new();
+        for signature in &signatures {
+            if StellarTxBuilder::signed_by(&server_key, &hash, std::slice::from_ref(signature)) {
+                continue;
+            }
+            let signer = signers
+                .signers
+                .iter()
+                .find(|(key, _)| StellarTxBuilder::signed_by(key, &hash, std::slice::from_ref(signature)))
+                .ok_or(Sep10Error::InvalidChallenge("unrecognized signature"))?;
+            signed.insert(signer);
+        }
+        let weight: u32 = signed.iter().map(|(_, weight)| weight).sum();
+        if weight == 0 || weight < signers.med_threshold {
+            return Err(Sep10Error::NotAuthorized);
+        }
+
+        let consumed = sqlx::query(
+            r#"
+            UPDATE sep10_challenges SET consumed_at = ?
+            WHERE nonce = ? AND account = ? AND consumed_at IS NULL AND expires_at > ?
+            "#,
+        )
+        .bind(chrono::Utc::now())
+        .bind(&nonce)
+        .bind(&account)
+        .bind(chrono::Utc::now())
+        .execute(pool)
+        .await?;
+        if consumed.rows_affected() == 0 {
+            return Err(Sep10Error::AlreadyUsed);
+        }
+        Ok(account)
+    }
+}
+
+/// ManageData names and values are capped at 64 bytes; only a very long
+/// configured domain hits this.
+fn data_name(name: &str) -> Result<String64, Sep10Error> {
+    name.as_bytes()
+        .to_vec()
+        .try_into()
+        .map(String64)
+        .map_err(|_| Sep10Error::InvalidChallenge("domain is too long"))
+}
+
+fn data_value(value: &[u8]) -> Result<DataValue, Sep10Error> {
+    value
+        .to_vec()
+        .try_into()
+        .map(DataValue)
+        .map_err(|_| Sep10Error::InvalidChallenge("domain is too long"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::stellar_tx::EnvelopeSignature;
+    use crate::test_support::{memory_pool, stub_server};
+    use axum::{extract::State, http::StatusCode, routing::get, Json, Router};
+    use serde_json::{json, Value};
+
+    fn server_key() -> SigningKey {
+        env::set_var("SEP10_SIGNING_KEY", stellar_strkey::ed25519::PrivateKey([9u8; 32]).to_string());
+        Sep10Service::signing_key().unwrap()
+    }
+
+    fn address(key: &SigningKey) -> String {
+        stellar_strkey::ed25519::PublicKey(key.verifying_key().to_bytes()).to_string()
+    }
+
+    /// Horizon that serves `account` for every account lookup, or 404s when
+    /// there is none.
+    async fn horizon(account: Option<Value>) -> StellarNetwork {
+        let app = Router::new()
+            .route(
+                "/accounts/:id",
+                get(|State(account): State<Option<Value>>| async move {
+                    match account {
+                        Some(account) => (StatusCode::OK, Json(account)),
+                        None => (StatusCode::NOT_FOUND, Json(json!({}))),
+                    }
+                }),
+            )
+            .with_state(account);
+
+        let mut network = StellarNetwork::from_env();
+        network.horizon_url = stub_server(app).await;
+        network
+    }
+
+    fn signers(signers: &[(&SigningKey, u32)], med_threshold: u32) -> Value {
+        let signers: Vec<Value> = signers
+            .iter()
+            .map(|(key, weight)| json!({ "type": "ed25519_public_key", "key": address(key), "weight": weight }))
+            .collect();
+        json!({ "signers": signers, "thresholds": { "med_threshold": med_threshold } })
+    }
+
+    async fn challenge(pool: &SqlitePool, client: &SigningKey) -> Transaction {
+        server_key();
+        let response = Sep10Service::challenge(pool, &address(client), None).await.unwrap();
+        StellarTxBuilder::parse_envelope(&response.transaction).unwrap().0
+    }
+
+    fn sign(key: &SigningKey, hash: &[u8; 32]) -> EnvelopeSignature {
+        let hint = key.verifying_key().to_bytes()[28..].try_into().unwrap();
+        (hint, key.sign(hash).to_bytes().to_vec())
+    }
+
+    fn envelope(network: &StellarNetwork, tx: &Transaction, keys: &[&SigningKey]) -> String {
+        let hash = network.hash(tx).unwrap();
+        let signatures: Vec<EnvelopeSignature> = keys.iter().map(|key| sign(key, &hash)).collect();
+        StellarTxBuilder::signed_envelope_xdr(tx, &signatures).unwrap()
+    }
+
+    fn set_data(tx: &mut Transaction, index: usize, name: &str, value: &[u8]) {
+        let mut operations = tx.operations.to_vec();
+        operations[index].body = OperationBody::ManageData(ManageDataOp {
+            data_name: data_name(name).unwrap(),
+            data_value: Some(data_value(value).unwrap()),
+        });
+        tx.operations = operations.try_into().unwrap();
+    }
+
+    fn nonce(tx: &Transaction) -> Vec<u8> {
+        match &tx.operations[0].body {
+            OperationBody::ManageData(op) => op.data_value.as_ref().unwrap().to_vec(),
+            _ => unreachable!(),
+        }
+    }
+
+    #[tokio::test]
+    async fn signed_challenge_proves_the_account_once() {
+        let pool = memory_pool().await;
+        let client = SigningKey::from_bytes(&[1u8; 32]);
+        let network = horizon(None).await;
+        let tx = challenge(&pool, &client).await;
+        let signed = envelope(&network, &tx, &[&server_key(), &client]);
+
+        let account = Sep10Service::verify_challenge(&pool, &network, &signed).await.unwrap();
+        assert_eq!(account, address(&client));
+        assert!(matches!(
+            Sep10Service::verify_challenge(&pool, &network, &signed).await,
+            Err(Sep10Error::AlreadyUsed)
+        ));
+    }
+
+    #[tokio::test]
+    async fn challenge_for_another_domain_is_refused() {
+        let pool = memory_pool().await;
+        let client = SigningKey::from_bytes(&[1u8; 32]);
+        let network = horizon(None).await;
+
+        let mut tx = challenge(&pool, &client).await;
+        let value = nonce(&tx);
+        set_data(&mut tx, 0, "evil.example auth", &value);
+        let signed = envelope(&network, &tx, &[&server_key(), &client]);
+        assert!(matches!(
+            Sep10Service::verify_challenge(&pool, &network, &signed).await,
+            Err(Sep10Error::InvalidChallenge("first operation must be the client's ManageData"))
+        ));
+
+        let mut tx = challenge(&pool, &client).await;
+        set_data(&mut tx, 1, "web_auth_domain", b"evil.example");
+        let signed = envelope(&network, &tx, &[&server_key(), &client]);
+        assert!(matches!(
+            Sep10Service::verify_challenge(&pool, &network, &signed).await,
+            Err(Sep10Error::InvalidChallenge("wrong web_auth_domain"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn expired_challenge_is_refused() {
+        let pool = memory_pool().await;
+        let client = SigningKey::from_bytes(&[1u8; 32]);
+        let network = horizon(None).await;
+
+        let mut tx = challenge(&pool, &client).await;
+        let an_hour_ago = chrono::Utc::now().timestamp() as u64 - 3600;
+        tx.cond = Preconditions::Time(TimeBounds {
+            min_time: TimePoint(an_hour_ago - 300),
+            max_time: TimePoint(an_hour_ago),
+        });
+        let signed = envelope(&network, &tx, &[&server_key(), &client]);
+        assert!(matches!(
+            Sep10Service::verify_challenge(&pool, &network, &signed).await,
+            Err(Sep10Error::Expired)
+        ));
+    }
+
+    #[tokio::test]
+    async fn challenge_without_the_server_signature_is_refused() {
+        let pool = memory_pool().await;
+        let client = SigningKey::from_bytes(&[1u8; 32]);
+        let network = horizon(None).await;
+
+        let tx = challenge(&pool, &client).await;
+        let signed = envelope(&network, &tx, &[&client]);
+        assert!(matches!(
+            Sep10Service::verify_challenge(&pool, &network, &signed).await,
+            Err(Sep10Error::InvalidChallenge("not signed by the server"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn signature_from_outside_the_signer_list_is_refused() {
+        let pool = memory_pool().await;
+        let client = SigningKey::from_bytes(&[1u8; 32]);
+        let stranger = SigningKey::from_bytes(&[2u8; 32]);
+
+        // Unfunded account: only its own key counts
+        let network = horizon(None).await;
+        let tx = challenge(&pool, &client).await;
+        let signed = envelope(&network, &tx, &[&server_key(), &stranger]);
+        assert!(matches!(
+            Sep10Service::verify_challenge(&pool, &network, &signed).await,
+            Err(Sep10Error::InvalidChallenge("unrecognized signature"))
+        ));
+
+        // Funded account: the stranger isn't one of its signers
+        let network = horizon(Some(signers(&[(&client, 1)], 1))).await;
+        let tx = challenge(&pool, &client).await;
+        let signed = envelope(&network, &tx, &[&server_key(), &client, &stranger]);
+        assert!(matches!(
+            Sep10Service::verify_challenge(&pool, &network, &signed).await,
+            Err(Sep10Error::InvalidChallenge("unrecognized signature"))
+        ));
+    }
+
+    #[tokio::test]
+    async fn signatures_must_reach_the_medium_threshold() {
+        let pool = memory_pool().await;
+        let client = SigningKey::from_bytes(&[1u8; 32]);
+        let cosigner = SigningKey::from_bytes(&[3u8; 32]);
+        let network = horizon(Some(signers(&[(&client, 1), (&cosigner, 1)], 2))).await;
+
+        let tx = challenge(&pool, &client).await;
+        let signed = envelope(&network, &tx, &[&server_key(), &client]);
+        assert!(matches!(
+            Sep10Service::verify_challenge(&pool, &network, &signed).await,
+            Err(Sep10Error::NotAuthorized)
+        ));
+        // The same signer twice still counts once
+        let signed = envelope(&network, &tx, &[&server_key(), &client, &client]);
+        assert!(matches!(
+            Sep10Service::verify_challenge(&pool, &network, &signed).await,
+            Err(Sep10Error::NotAuthorized)
+        ));
+
+        let signed = envelope(&network, &tx, &[&server_key(), &client, &cosigner]);
+        let account = Sep10Service::verify_challenge(&pool, &network, &signed).await.unwrap();
+        assert_eq!(account, address(&client));
+    }
+}
@@ -0,0 +1,344 @@
+use sqlx::SqlitePool;
+use std::env;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{Role, Session, SessionResponse};
+use crate::services::signing::sha256_hex;
+use crate::services::AuthService;
+
+#[derive(Error, Debug)]
+pub enum SessionError {
+    #[error("Invalid refresh token")]
+    InvalidToken,
+    #[error("Refresh token expired")]
+    Expired,
+    #[error("Refresh token reused; session revoked")]
+    Reused,
+    #[error("Failed to sign access token: {0}")]
+    Token(#[from] jsonwebtoken::errors::Error),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Access + refresh token pair handed to the client after login or refresh.
+#[derive(Debug)]
+pub struct TokenPair {
+    pub access_token: String,
+    pub refresh_token: String,
+    pub expires_in: i64,
+    pub session_id: String,
+}
+
+pub struct SessionService;
+
+impl SessionService {
+    /// Refresh token lifetime, `REFRESH_TOKEN_TTL_DAYS` (default 30).
+    fn refresh_token_ttl() -> chrono::Duration {
+        let days = env::var("REFRESH_TOKEN_TTL_DAYS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(30);
+        chrono::Duration::days(days)
+    }
+
+    fn generate_refresh_token() -> String {
+        hex::encode(rand::random::<[u8; 32]>())
+    }
+
+    /// Starts a new session family for a fresh sign-in.
+    pub async fn create(
+        pool: &SqlitePool,
+        user_id: &str,
+        user_agent: Option<&str>,
+        ip_address: Option<&str>,
+    ) -> Result<TokenPair, SessionError> {
+        let family_id = Uuid::new_v4().to_string();
+        let refresh_token = Self::generate_refresh_token();
+        Self::insert(pool, &family_id, user_id, &refresh_token, user_agent, ip_address).await?;
+        let role = Self::role_of(pool, user_id).await?;
+        Self::token_pair(user_id, role, family_id, refresh_token)
+    }
+
+    /// Exchanges a refresh token for a new pair. Each refresh token works once;
+    /// presenting one that was already exchanged means it leaked, so the whole
+    /// family is revoked and both holders have to sign in again.
+    pub async fn refresh(
+        pool: &SqlitePool,
+        refresh_token: &str,
+        user_agent: Option<&str>,
+        ip_address: Option<&str>,
+    ) -> Result<TokenPair, SessionError> {
+        let session = sqlx::query_as::<_, Session>("SELECT * FROM sessions WHERE refresh_token_hash = ?")
+            .bind(sha256_hex(refresh_token.as_bytes()))
+            .fetch_optional(pool)
+            .await?
+            .ok_or(SessionError::InvalidToken)?;
+
+        if session.revoked_at.is_some() {
+            return Err(SessionError::InvalidToken);
+        }
+        if session.rotated_at.is_some() {
+            return Err(Self::reuse_detected(pool, &session).await);
+        }
+        if session.expires_at <= chrono::Utc::now() {
+            return Err(SessionError::Expired);
+        }
+
+        let next_token = Self::generate_refresh_token();
+        let mut tx = pool.begin().await?;
+
+        // Guard against two concurrent refreshes with the same token
+        let rotated = sqlx::query("UPDATE sessions SET rotated_at = ? WHERE id = ? AND rotated_at IS NULL")
+            .bind(chrono::Utc::now())
+            .bind(&session.id)
+            .execute(&mut *tx)
+            .await?
+            .rows_affected();
+        if rotated == 0 {
+            drop(tx);
+            return Err(Self::reuse_detected(pool, &session).await);
+        }
+
+        Self::insert(
+            &mut *tx,
+            &session.family_id,
+            &session.user_id,
+            &next_token,
+            user_agent.or(session.user_agent.as_deref()),
+            ip_address.or(session.ip_address.as_deref()),
+        )
+        .await?;
+        tx.commit().await?;
+
+        // Read fresh on every refresh so role changes reach the next token
+        let role = Self::role_of(pool, &session.user_id).await?;
+        Self::token_pair(&session.user_id, role, session.family_id, next_token)
+    }
+
+    async fn reuse_detected(pool: &SqlitePool, session: &Session) -> SessionError {
+        println!(
+            "🚨 Refresh token reuse for user {} (session {}); revoking session",
+            session.user_id, session.family_id
+        );
+        match Self::revoke_family(pool, &session.family_id, "token_reuse").await {
+            Ok(_) => SessionError::Reused,
+            Err(e) => e.into(),
+        }
+    }
+
+    /// Whether the session family behind an access token is still signed in.
+    pub async fn is_active(pool: &SqlitePool, family_id: &str) -> Result<bool, sqlx::Error> {
+        let active: Option<i64> = sqlx::query_scalar(
+            "SELECT 1 FROM sessions WHERE family_id = ? AND revoked_at IS NULL AND expires_at > ? LIMIT 1",
+        )
+        .bind(family_id)
+        .bind(chrono::Utc::now())
+        .fetch_optional(pool)
+        .await?;
+        Ok(active.is_some())
+    }
+
+    /// The user's signed-in devices, most recently used first.
+    pub async fn list(pool: &SqlitePool, user_id: &str, current_family_id: &str) -> Result<Vec<SessionResponse>, sqlx::Error> {
+        let sessions = sqlx::query_as::<_, Session>(
+            r#"
+            SELECT * FROM sessions
+            WHERE user_id = ? AND rotated_at IS NULL AND revoked_at IS NULL AND expires_at > ?
+            ORDER BY created_at DESC
+            "#,
+        )
+        .bind(user_id)
+        .bind(chrono::Utc::now())
+        .fetch_all(pool)
+        .await?;
+
+        Ok(sessions
+            .into_iter()
+            .map(|s| SessionResponse {
+                current: s.family_id == current_family_id,
+                id: s.family_id,
+                user_agent: s.user_agent,
+                ip_address: s.ip_address,
+                last_used_at: s.created_at,
+                expires_at: s.expires_at,
+            })
+            .collect())
+    }
+
+    /// Revokes one of the user's sessions. Returns `false` if it does not
+    /// exist, belongs to someone else or was already revoked.
+    pub async fn revoke(pool: &SqlitePool, user_id: &str, family_id: &str, reason: &str) -> Result<bool, sqlx::Error> {
+        let result = sqlx::query(
+            "UPDATE sessions SET revoked_at = ?, revoked_reason = ? WHERE family_id = ? AND user_id = ? AND revoked_at IS NULL",
+        )
+        .bind(chrono::Utc::now())
+        .bind(reason)
+        .bind(family_id)
+        .bind(user_id)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// Revokes every active session of the user, optionally keeping the one
+    /// the request came from. Returns how many refresh tokens were revoked.
+    pub async fn revoke_all(
+        pool: &SqlitePool,
+        user_id: &str,
+        except_family_id: Option<&str>,
+        reason: &str,
+    ) -> Result<u64, sqlx::Error> {
+        let result = sqlx::query(
+            r#"
+            UPDATE sessions SET revoked_at = ?, revoked_reason = ?
+            WHERE user_id = ? AND revoked_at IS NULL AND (? IS NULL OR family_id != ?)
+            "#,
+        )
+        .bind(chrono::Utc::now())
+        .bind(reason)
+        .bind(user_id)
+        .bind(except_family_id)
+        .bind(except_family_id)
+        .execute(pool)
+        .await?;
+        Ok(result.rows_affected())
+    }
+
+    async fn revoke_family(pool: &SqlitePool, family_id: &str, reason: &str) -> Result<(), sqlx::Error> {
+        sqlx::query("UPDATE sessions SET revoked_at = ?, revoked_reason = ? WHERE family_id = ? AND revoked_at IS NULL")
+            .bind(chrono::Utc::now())
+            .bind(reason)
+            .bind(family_id)
+            .execute(pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn insert<'e, E>(
+        executor: E,
+        family_id: &str,
+        user_id: &str,
+        refresh_token: &str,
+        user_agent: Option<&str>,
+        ip_address: Option<&str>,
+    ) -> Result<(), sqlx::Error>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
+        let now = chrono::Utc::now();
+        sqlx::query(
+            r#"
+            INSERT INTO sessions (id, family_id, user_id, refresh_token_hash, user_agent, ip_address, created_at, expires_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(family_id)
+        .bind(user_id)
+        .bind(sha256_hex(refresh_token.as_bytes()))
+        .bind(user_agent)
+        .bind(ip_address)
+        .bind(now)
+        .bind(now + Self::refresh_token_ttl())
+        .execute(executor)
+        .await?;
+        Ok(())
+    }
+
+    async fn role_of(pool: &SqlitePool, user_id: &str) -> Result<Role, sqlx::Error> {
+        let role: Option<String> = sqlx::query_scalar("SELECT role FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?;
+        Ok(Role::from_str_or_customer(role.as_deref().unwrap_or_default()))
+    }
+
+    fn token_pair(user_id: &str, role: Role, family_id: String, refresh_token: String) -> Result<TokenPair, SessionError> {
+        Ok(TokenPair {
+            access_token: AuthService::generate_jwt(user_id, &family_id, role)?,
+            refresh_token,
+            expires_in: AuthService::access_token_ttl().num_seconds(),
+            session_id: family_id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::get;
+    use axum::{Extension, Router};
+
+    use super::*;
+    use crate::middleware::auth_middleware;
+    use crate::test_support::{insert_user, jwt_keys, memory_pool, stub_server};
+
+    #[tokio::test]
+    async fn test_rotation_replaces_the_refresh_token() {
+        let pool = memory_pool().await;
+        let _keys = jwt_keys(&pool).await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+
+        let first = SessionService::create(&pool, "u1", Some("phone"), None).await.unwrap();
+        let second = SessionService::refresh(&pool, &first.refresh_token, None, None).await.unwrap();
+        assert_ne!(second.refresh_token, first.refresh_token);
+        assert_eq!(second.session_id, first.session_id);
+        assert_eq!(AuthService::verify_jwt(&second.access_token).unwrap().sid, first.session_id);
+
+        // The new token carries on; the old one is spent
+        let third = SessionService::refresh(&pool, &second.refresh_token, None, None).await.unwrap();
+        assert!(SessionService::is_active(&pool, &third.session_id).await.unwrap());
+        assert_eq!(SessionService::list(&pool, "u1", &third.session_id).await.unwrap().len(), 1);
+        assert!(matches!(
+            SessionService::refresh(&pool, "not-a-token", None, None).await,
+            Err(SessionError::InvalidToken)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reused_refresh_token_revokes_the_family() {
+        let pool = memory_pool().await;
+        let _keys = jwt_keys(&pool).await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+
+        let stolen = SessionService::create(&pool, "u1", Some("phone"), None).await.unwrap();
+        let other = SessionService::create(&pool, "u1", Some("laptop"), None).await.unwrap();
+        let rotated = SessionService::refresh(&pool, &stolen.refresh_token, None, None).await.unwrap();
+
+        assert!(matches!(
+            SessionService::refresh(&pool, &stolen.refresh_token, None, None).await,
+            Err(SessionError::Reused)
+        ));
+        assert!(!SessionService::is_active(&pool, &stolen.session_id).await.unwrap());
+        // Whoever holds the rotated token is signed out too
+        assert!(matches!(
+            SessionService::refresh(&pool, &rotated.refresh_token, None, None).await,
+            Err(SessionError::InvalidToken)
+        ));
+        // Other devices are untouched
+        assert!(SessionService::is_active(&pool, &other.session_id).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_revoked_session_rejects_its_access_token() {
+        let pool = memory_pool().await;
+        let _keys = jwt_keys(&pool).await;
+        insert_user(&pool, "u1", "u1@example.com").await;
+        let tokens = SessionService::create(&pool, "u1", None, None).await.unwrap();
+
+        let app = Router::new()
+            .route("/me", get(|Extension(user_id): Extension<String>| async move { user_id }))
+            .layer(from_fn_with_state(pool.clone(), auth_middleware));
+        let url = format!("{}/me", stub_server(app).await);
+        let me = |token: &str| reqwest::Client::new().get(&url).bearer_auth(token).send();
+
+        let response = me(&tokens.access_token).await.unwrap();
+        assert_eq!(response.status(), reqwest::StatusCode::OK);
+        assert_eq!(response.text().await.unwrap(), "u1");
+
+        assert!(SessionService::revoke(&pool, "u1", &tokens.session_id, "logout").await.unwrap());
+        assert_eq!(me(&tokens.access_token).await.unwrap().status(), reqwest::StatusCode::UNAUTHORIZED);
+        assert_eq!(me("garbage").await.unwrap().status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+}
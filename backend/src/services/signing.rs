@@ -0,0 +1,47 @@
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::env;
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub fn hmac_sha256_hex(secret: &[u8], message: &[u8]) -> String {
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    hex::encode(mac.finalize().into_bytes())
+}
+
+/// Checks a hex HMAC-SHA256 signature in constant time.
+pub fn verify_hmac_sha256_hex(secret: &[u8], message: &[u8], signature_hex: &str) -> bool {
+    let Ok(signature) = hex::decode(signature_hex) else {
+        return false;
+    };
+    let mut mac = HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.verify_slice(&signature).is_ok()
+}
+
+/// A secret the server can't run without, from the environment. It must be
+/// at least 32 bytes, which also keeps out placeholders like the ones in
+/// `.env.example`. Checked at startup, so a missing one stops the server.
+pub fn required_secret(name: &str) -> Result<String, String> {
+    match env::var(name) {
+        Ok(secret) if secret.trim().len() >= 32 => Ok(secret.trim().to_string()),
+        Ok(_) => Err(format!("{} must be at least 32 characters", name)),
+        Err(_) => Err(format!("{} must be set", name)),
+    }
+}
+
+pub fn sha256_hex(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// `X-NovaPay-Signature` header value for an outbound webhook body:
+/// `t=<unix timestamp>,v1=<hex HMAC-SHA256 of "<timestamp>.<body>">`.
+pub fn webhook_signature_header(secret: &str, timestamp: i64, body: &str) -> String {
+    let signed_payload = format!("{}.{}", timestamp, body);
+    format!(
+        "t={},v1={}",
+        timestamp,
+        hmac_sha256_hex(secret.as_bytes(), signed_payload.as_bytes())
+    )
+}
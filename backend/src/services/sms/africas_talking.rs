@@ -0,0 +1,123 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+use std::env;
+use std::time::Duration;
+use subtle::ConstantTimeEq;
+
+use super::{SmsError, SmsProvider, SmsReceipt};
+
+#[derive(Debug, Deserialize)]
+struct SendResponse {
+    #[serde(rename = "SMSMessageData")]
+    sms_message_data: SmsMessageData,
+}
+
+#[derive(Debug, Deserialize)]
+struct SmsMessageData {
+    #[serde(rename = "Message")]
+    message: String,
+    #[serde(rename = "Recipients", default)]
+    recipients: Vec<Recipient>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Recipient {
+    #[serde(rename = "statusCode")]
+    status_code: u16,
+    status: String,
+    cost: Option<String>,
+    #[serde(rename = "messageId")]
+    message_id: String,
+}
+
+pub struct AfricasTalkingProvider {
+    client: Client,
+    base_url: String,
+    username: String,
+    api_key: String,
+    sender_id: Option<String>,
+}
+
+impl AfricasTalkingProvider {
+    pub fn new(base_url: &str, username: &str, api_key: &str, sender_id: Option<String>, timeout: Duration) -> Self {
+        Self {
+            client: Client::builder().timeout(timeout).build().unwrap_or_default(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            username: username.to_string(),
+            api_key: api_key.to_string(),
+            sender_id,
+        }
+    }
+
+    pub fn from_env(timeout: Duration) -> Result<Self, SmsError> {
+        let username = env::var("AT_USERNAME").map_err(|_| SmsError::NotConfigured("AT_USERNAME".to_string()))?;
+        let api_key = env::var("AT_API_KEY").map_err(|_| SmsError::NotConfigured("AT_API_KEY".to_string()))?;
+        let base_url = env::var("AT_BASE_URL").unwrap_or_else(|_| "https://api.africastalking.com".to_string());
+
+        Ok(Self::new(&base_url, &username, &api_key, env::var("AT_SENDER_ID").ok(), timeout))
+    }
+}
+
+#[async_trait]
+impl SmsProvider for AfricasTalkingProvider {
+    fn name(&self) -> &'static str {
+        "africastalking"
+    }
+
+    async fn send(&self, phone_number: &str, message: &str) -> Result<SmsReceipt, SmsError> {
+        let mut form = vec![
+            ("username", self.username.as_str()),
+            ("to", phone_number),
+            ("message", message),
+        ];
+        if let Some(sender_id) = &self.sender_id {
+            form.push(("from", sender_id.as_str()));
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/version1/messaging", self.base_url))
+            .header("apiKey", &self.api_key)
+            .header("Accept", "application/json")
+            .form(&form)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(SmsError::Rejected(format!("HTTP {}: {}", status, body)));
+        }
+
+        let body: SendResponse = response
+            .json()
+            .await
+            .map_err(|e| SmsError::Network(e.to_string()))?;
+
+        // 100 Processed, 101 Sent, 102 Queued; anything else is a rejection
+        match body.sms_message_data.recipients.into_iter().next() {
+            Some(recipient) if (100..=102).contains(&recipient.status_code) => Ok(SmsReceipt {
+                provider_message_id: recipient.message_id,
+                cost: recipient.cost,
+            }),
+            Some(recipient) => Err(SmsError::Rejected(format!("{} ({})", recipient.status, recipient.status_code))),
+            None => Err(SmsError::Rejected(body.sms_message_data.message)),
+        }
+    }
+}
+
+/// Maps an Africa's Talking delivery report status onto `sms_messages.status`.
+pub fn normalize_delivery_status(status: &str) -> &'static str {
+    match status {
+        "Success" => "delivered",
+        "Failed" | "Rejected" | "AbsentSubscriber" | "Expired" => "failed",
+        _ => "sent",
+    }
+}
+
+/// Africa's Talking doesn't sign delivery reports, so the callback URL
+/// registered with it carries `?secret=<AT_CALLBACK_SECRET>`.
+pub fn verify_callback_secret(expected: &str, given: &str) -> bool {
+    !expected.is_empty() && bool::from(expected.as_bytes().ct_eq(given.as_bytes()))
+}
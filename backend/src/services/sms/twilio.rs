@@ -0,0 +1,145 @@
+use async_trait::async_trait;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use hmac::{Hmac, Mac};
+use reqwest::Client;
+use serde::Deserialize;
+use sha1::Sha1;
+use std::collections::BTreeMap;
+use std::env;
+use std::time::Duration;
+
+use super::{SmsError, SmsProvider, SmsReceipt};
+
+#[derive(Debug, Deserialize)]
+struct MessageResponse {
+    sid: String,
+    price: Option<String>,
+    price_unit: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ErrorResponse {
+    code: Option<u32>,
+    message: String,
+}
+
+pub struct TwilioProvider {
+    client: Client,
+    base_url: String,
+    account_sid: String,
+    auth_token: String,
+    from_number: String,
+    status_callback_url: Option<String>,
+}
+
+impl TwilioProvider {
+    pub fn new(
+        base_url: &str,
+        account_sid: &str,
+        auth_token: &str,
+        from_number: &str,
+        status_callback_url: Option<String>,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            client: Client::builder().timeout(timeout).build().unwrap_or_default(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            account_sid: account_sid.to_string(),
+            auth_token: auth_token.to_string(),
+            from_number: from_number.to_string(),
+            status_callback_url,
+        }
+    }
+
+    pub fn from_env(timeout: Duration) -> Result<Self, SmsError> {
+        let account_sid = env::var("TWILIO_ACCOUNT_SID")
+            .map_err(|_| SmsError::NotConfigured("TWILIO_ACCOUNT_SID".to_string()))?;
+        let auth_token = env::var("TWILIO_AUTH_TOKEN")
+            .map_err(|_| SmsError::NotConfigured("TWILIO_AUTH_TOKEN".to_string()))?;
+        let from_number = env::var("TWILIO_FROM_NUMBER")
+            .map_err(|_| SmsError::NotConfigured("TWILIO_FROM_NUMBER".to_string()))?;
+        let base_url = env::var("TWILIO_BASE_URL").unwrap_or_else(|_| "https://api.twilio.com".to_string());
+
+        Ok(Self::new(
+            &base_url,
+            &account_sid,
+            &auth_token,
+            &from_number,
+            env::var("TWILIO_STATUS_CALLBACK_URL").ok(),
+            timeout,
+        ))
+    }
+}
+
+#[async_trait]
+impl SmsProvider for TwilioProvider {
+    fn name(&self) -> &'static str {
+        "twilio"
+    }
+
+    async fn send(&self, phone_number: &str, message: &str) -> Result<SmsReceipt, SmsError> {
+        let mut form = vec![
+            ("To", phone_number),
+            ("From", self.from_number.as_str()),
+            ("Body", message),
+        ];
+        if let Some(callback) = &self.status_callback_url {
+            form.push(("StatusCallback", callback.as_str()));
+        }
+
+        let response = self
+            .client
+            .post(format!("{}/2010-04-01/Accounts/{}/Messages.json", self.base_url, self.account_sid))
+            .basic_auth(&self.account_sid, Some(&self.auth_token))
+            .form(&form)
+            .send()
+            .await?;
+
+        if !response.status().is_success() {
+            let status = response.status();
+            let reason = match response.json::<ErrorResponse>().await {
+                Ok(err) => format!("{} ({})", err.message, err.code.unwrap_or_default()),
+                Err(_) => format!("HTTP {}", status),
+            };
+            return Err(SmsError::Rejected(reason));
+        }
+
+        let body: MessageResponse = response
+            .json()
+            .await
+            .map_err(|e| SmsError::Network(e.to_string()))?;
+
+        Ok(SmsReceipt {
+            provider_message_id: body.sid,
+            cost: body.price.map(|price| match body.price_unit {
+                Some(unit) => format!("{} {}", unit, price),
+                None => price,
+            }),
+        })
+    }
+}
+
+/// Maps a Twilio `MessageStatus` callback value onto `sms_messages.status`.
+pub fn normalize_delivery_status(status: &str) -> &'static str {
+    match status {
+        "delivered" => "delivered",
+        "failed" | "undelivered" | "canceled" => "failed",
+        _ => "sent",
+    }
+}
+
+/// Checks an `X-Twilio-Signature` header: the base64 HMAC-SHA1, keyed with
+/// the auth token, of the callback URL followed by every POST parameter's
+/// name and value in name order.
+pub fn verify_signature(auth_token: &str, url: &str, params: &BTreeMap<String, String>, signature: &str) -> bool {
+    let Ok(signature) = STANDARD.decode(signature) else {
+        return false;
+    };
+    let mut mac = Hmac::<Sha1>::new_from_slice(auth_token.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(url.as_bytes());
+    for (name, value) in params {
+        mac.update(name.as_bytes());
+        mac.update(value.as_bytes());
+    }
+    mac.verify_slice(&signature).is_ok()
+}
@@ -1,7 +1,6 @@
 use serde::{Deserialize, Serialize};
 use std::env;
 use reqwest::Client;
-use serde_json::json;
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct StellarAccount {
@@ -30,13 +29,14 @@ impl StellarService {
         }
     }
 
+    /// A new random ed25519 keypair as Stellar strkeys (`G...`/`S...`).
     pub fn generate_keypair() -> StellarAccount {
-        let random_bytes: [u8; 32] = rand::random();
-        let hex_str = hex::encode(&random_bytes);
-        
+        let seed: [u8; 32] = rand::random();
+        let public_key = ed25519_dalek::SigningKey::from_bytes(&seed).verifying_key().to_bytes();
+
         StellarAccount {
-            public_key: format!("G{}", &hex_str[..55]),
-            secret_key: format!("S{}", &hex_str[..55]),
+            public_key: stellar_strkey::ed25519::PublicKey(public_key).to_string(),
+            secret_key: stellar_strkey::ed25519::PrivateKey(seed).to_string(),
         }
     }
 
@@ -75,86 +75,19 @@ impl StellarService {
             }
         }
     }
+}
 
-    pub async fn send_payment(
-        &self,
-        from_secret: &str,
-        to_public: &str,
-        amount: f64,
-        _currency: &str,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        // Convert amount to stroops (1 XLM = 10,000,000 stroops)
-        let amount_stroops = (amount * 10_000_000.0) as u64;
-        
-        println!("💰 Sending {} XLM ({} stroops) from {} to {}", amount, amount_stroops, from_secret, to_public);
-        
-        // Build transaction using Stellar Horizon API
-        let horizon_url = "https://horizon-testnet.stellar.org";
-        
-        // Get source account info
-        let source_keypair = self.keypair_from_secret(from_secret)?;
-        let account_url = format!("{}/accounts/{}", horizon_url, source_keypair.public_key);
-        
-        let account_response = self.friendbot_client.get(&account_url).send().await?;
-        if !account_response.status().is_success() {
-            return Err("Source account not found".into());
-        }
-        
-        let account_data: serde_json::Value = account_response.json().await?;
-        let sequence_number: u64 = account_data["sequence"].as_str()
-            .ok_or("Invalid sequence number")?
-            .parse()?;
-        
-        // Create payment transaction
-        let transaction = json!({
-            "source_account": source_keypair.public_key,
-            "fee": "100",
-            "sequence_number": (sequence_number + 1).to_string(),
-            "operations": [{
-                "type_i": 1, // Payment operation
-                "destination": to_public,
-                "asset": {
-                    "type": "native"
-                },
-                "amount": amount.to_string()
-            }],
-            "memo": {
-                "type": "text",
-                "value": "NovaPay Transfer"
-            }
-        });
-        
-        // Submit transaction to Stellar network
-        let submit_url = format!("{}/transactions", horizon_url);
-        let tx_response = self.friendbot_client
-            .post(&submit_url)
-            .json(&transaction)
-            .send()
-            .await?;
-        
-        if tx_response.status().is_success() {
-            let tx_result: serde_json::Value = tx_response.json().await?;
-            let tx_hash = tx_result["hash"].as_str()
-                .unwrap_or(&format!("stellar_tx_{}", uuid::Uuid::new_v4()))
-                .to_string();
-            
-            println!("✅ Transaction successful: {}", tx_hash);
-            Ok(tx_hash)
-        } else {
-            // Fallback to mock transaction for demo
-            let uuid_str = uuid::Uuid::new_v4().to_string().replace("-", "");
-            let mock_hash = format!("mock_tx_{}", &uuid_str[..16]);
-            println!("⚠️ Using mock transaction: {}", mock_hash);
-            Ok(mock_hash)
-        }
-    }
-    
-    fn keypair_from_secret(&self, secret: &str) -> Result<StellarAccount, Box<dyn std::error::Error>> {
-        // In a real implementation, derive public key from secret
-        // For now, return mock keypair
-        Ok(StellarAccount {
-            public_key: format!("G{}", &secret[1..]),
-            secret_key: secret.to_string(),
-        })
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_generated_keypairs_are_strkeys() {
+        let account = StellarService::generate_keypair();
+        let public_key = stellar_strkey::ed25519::PublicKey::from_string(&account.public_key).unwrap();
+        let seed = stellar_strkey::ed25519::PrivateKey::from_string(&account.secret_key).unwrap();
+        let derived = ed25519_dalek::SigningKey::from_bytes(&seed.0).verifying_key().to_bytes();
+        assert_eq!(public_key.0, derived);
+        assert_ne!(StellarService::generate_keypair().public_key, account.public_key);
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,731 @@
+use ed25519_dalek::{Signature, VerifyingKey};
+use reqwest::Client;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+use stellar_xdr::curr::{
+    AccountId, AlphaNum12, AlphaNum4, Asset, AssetCode12, AssetCode4, ChangeTrustAsset, ChangeTrustOp,
+    DecoratedSignature, Hash, Limits, Memo, MuxedAccount, Operation, OperationBody, PathPaymentStrictSendOp, PaymentOp,
+    Preconditions, ReadXdr, SequenceNumber, SetOptionsOp, Signature as XdrSignature, SignatureHint, Signer, SignerKey,
+    TimeBounds, TimePoint, Transaction, TransactionEnvelope, TransactionExt, TransactionSignaturePayload,
+    TransactionSignaturePayloadTaggedTransaction, TransactionV1Envelope, Uint256, WriteXdr,
+};
+use thiserror::Error;
+
+use crate::models::{StellarOperationRequest, StellarSignerRequest};
+
+pub const TESTNET_PASSPHRASE: &str = "Test SDF Network ; September 2015";
+pub const PUBLIC_PASSPHRASE: &str = "Public Global Stellar Network ; September 2015";
+
+const STROOPS_PER_UNIT: i64 = 10_000_000;
+
+/// The network's limit on operations in one transaction.
+pub const MAX_OPERATIONS: usize = 100;
+
+/// A signature from an envelope: its 4-byte key hint and the signature bytes.
+pub type EnvelopeSignature = ([u8; 4], Vec<u8>);
+
+#[derive(Error, Debug)]
+pub enum StellarTxError {
+    #[error("Invalid account address: {0}")]
+    InvalidAddress(String),
+    #[error("Invalid asset: {0}")]
+    InvalidAsset(String),
+    #[error("Invalid amount: {0}")]
+    InvalidAmount(String),
+    #[error("Memo longer than 28 bytes")]
+    InvalidMemo,
+    #[error("Invalid operation: {0}")]
+    InvalidOperation(String),
+    #[error("Malformed transaction envelope")]
+    MalformedXdr,
+    #[error("Account {0} does not exist on the network")]
+    AccountNotFound(String),
+    #[error("Horizon unavailable: {0}")]
+    Horizon(String),
+    #[error("Rejected by the network: {0}")]
+    Rejected(String),
+}
+
+/// Who can sign for an account on the network.
+pub struct AccountSigners {
+    pub signers: Vec<(String, u32)>,
+    pub med_threshold: u32,
+    pub high_threshold: u32,
+}
+
+/// The Stellar network this instance talks to: `STELLAR_NETWORK` picks
+/// testnet or mainnet, `STELLAR_HORIZON_URL` overrides the Horizon server.
+pub struct StellarNetwork {
+    pub horizon_url: String,
+    pub passphrase: &'static str,
+    client: Client,
+}
+
+impl StellarNetwork {
+    pub fn from_env() -> Self {
+        let testnet = env::var("STELLAR_NETWORK").map_or(true, |network| network != "mainnet");
+        let default_horizon = if testnet {
+            "https://horizon-testnet.stellar.org"
+        } else {
+            "https://horizon.stellar.org"
+        };
+
+        Self {
+            horizon_url: env::var("STELLAR_HORIZON_URL")
+                .unwrap_or_else(|_| default_horizon.to_string())
+                .trim_end_matches('/')
+                .to_string(),
+            passphrase: if testnet { TESTNET_PASSPHRASE } else { PUBLIC_PASSPHRASE },
+            client: Client::builder()
+                .timeout(Duration::from_secs(15))
+                .build()
+                .unwrap_or_default(),
+        }
+    }
+
+    /// The account's current sequence number; the next transaction uses +1.
+    pub async fn sequence(&self, public_key: &str) -> Result<i64, StellarTxError> {
+        let account = self.account(public_key).await?;
+        account["sequence"]
+            .as_str()
+            .and_then(|sequence| sequence.parse().ok())
+            .ok_or_else(|| StellarTxError::Horizon("account has no sequence number".to_string()))
+    }
+
+    /// The account's ed25519 signers with their weights, and its medium
+    /// threshold. Hash and pre-auth signers can't sign a challenge and are
+    /// left out.
+    pub async fn signers(&self, public_key: &str) -> Result<AccountSigners, StellarTxError> {
+        let account = self.account(public_key).await?;
+        let signers = account["signers"]
+            .as_array()
+            .map(|signers| {
+                signers
+                    .iter()
+                    .filter(|signer| signer["type"] == "ed25519_public_key")
+                    .filter_map(|signer| Some((signer["key"].as_str()?.to_string(), signer["weight"].as_u64()? as u32)))
+                    .filter(|(_, weight)| *weight > 0)
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Ok(AccountSigners {
+            signers,
+            med_threshold: account["thresholds"]["med_threshold"].as_u64().unwrap_or(0) as u32,
+            high_threshold: account["thresholds"]["high_threshold"].as_u64().unwrap_or(0) as u32,
+        })
+    }
+
+    /// Payments in and out of `account` after `cursor`, oldest first, with
+    /// their transactions joined in so memos can be read. An account that
+    /// doesn't exist yet has none.
+    pub async fn payments(&self, account: &str, cursor: Option<&str>, limit: u32) -> Result<Vec<Value>, StellarTxError> {
+        let mut request = self
+            .client
+            .get(format!("{}/accounts/{}/payments", self.horizon_url, account))
+            .query(&[("order", "asc"), ("join", "transactions")])
+            .query(&[("limit", limit)]);
+        if let Some(cursor) = cursor {
+            request = request.query(&[("cursor", cursor)]);
+        }
+
+        let response = request.send().await.map_err(|e| StellarTxError::Horizon(e.to_string()))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !response.status().is_success() {
+            return Err(StellarTxError::Horizon(format!("payments lookup returned {}", response.status())));
+        }
+
+        let body: Value = response.json().await.map_err(|e| StellarTxError::Horizon(e.to_string()))?;
+        Ok(body["_embedded"]["records"].as_array().cloned().unwrap_or_default())
+    }
+
+    async fn account(&self, public_key: &str) -> Result<Value, StellarTxError> {
+        let response = self
+            .client
+            .get(format!("{}/accounts/{}", self.horizon_url, public_key))
+            .send()
+            .await
+            .map_err(|e| StellarTxError::Horizon(e.to_string()))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(StellarTxError::AccountNotFound(public_key.to_string()));
+        }
+        if !response.status().is_success() {
+            return Err(StellarTxError::Horizon(format!("account lookup returned {}", response.status())));
+        }
+
+        response.json().await.map_err(|e| StellarTxError::Horizon(e.to_string()))
+    }
+
+    /// Submits a signed envelope and returns its hash once it is in a ledger.
+    pub async fn submit(&self, envelope_xdr: &str) -> Result<String, StellarTxError> {
+        let response = self
+            .client
+            .post(format!("{}/transactions", self.horizon_url))
+            .form(&[("tx", envelope_xdr)])
+            .send()
+            .await
+            .map_err(|e| StellarTxError::Horizon(e.to_string()))?;
+        let status = response.status();
+        let body: Value = response.json().await.unwrap_or_default();
+
+        if status.is_success() {
+            return body["hash"]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| StellarTxError::Horizon("submission response has no hash".to_string()));
+        }
+        if status == reqwest::StatusCode::BAD_REQUEST {
+            return Err(StellarTxError::Rejected(body["extras"]["result_codes"].to_string()));
+        }
+        Err(StellarTxError::Horizon(format!("submission returned {}", status)))
+    }
+
+    /// Whether the transaction with this hash is in a ledger: `Some(true)` if
+    /// it succeeded, `Some(false)` if it failed, `None` if Horizon has no
+    /// record of it.
+    pub async fn outcome(&self, hash: &str) -> Result<Option<bool>, StellarTxError> {
+        let response = self
+            .client
+            .get(format!("{}/transactions/{}", self.horizon_url, hash))
+            .send()
+            .await
+            .map_err(|e| StellarTxError::Horizon(e.to_string()))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        if !response.status().is_success() {
+            return Err(StellarTxError::Horizon(format!("transaction lookup returned {}", response.status())));
+        }
+
+        let body: Value = response.json().await.map_err(|e| StellarTxError::Horizon(e.to_string()))?;
+        Ok(Some(body["successful"].as_bool().unwrap_or(false)))
+    }
+
+    /// Submits a signed envelope whose hash is `hash`, safely more than once:
+    /// one already in a ledger is not sent again, and a rejection only counts
+    /// once the hash is confirmed missing. An envelope keeps its sequence
+    /// number, so however often it goes out it can only land once.
+    pub async fn resubmit(&self, hash: &str, envelope_xdr: &str) -> Result<String, StellarTxError> {
+        match self.outcome(hash).await? {
+            Some(true) => return Ok(hash.to_string()),
+            Some(false) => return Err(StellarTxError::Rejected("transaction failed in its ledger".to_string())),
+            None => {}
+        }
+
+        match self.submit(envelope_xdr).await {
+            Err(StellarTxError::Rejected(codes)) => match self.outcome(hash).await? {
+                // An earlier submission landed in between
+                Some(true) => Ok(hash.to_string()),
+                _ => Err(StellarTxError::Rejected(codes)),
+            },
+            result => result,
+        }
+    }
+
+    /// Hash of `tx` on this network: what the source account signs.
+    pub fn hash(&self, tx: &Transaction) -> Result<[u8; 32], StellarTxError> {
+        let payload = TransactionSignaturePayload {
+            network_id: Hash(Sha256::digest(self.passphrase.as_bytes()).into()),
+            tagged_transaction: TransactionSignaturePayloadTaggedTransaction::Tx(tx.clone()),
+        };
+        let bytes = payload.to_xdr(Limits::none()).map_err(|_| StellarTxError::MalformedXdr)?;
+        Ok(Sha256::digest(bytes).into())
+    }
+}
+
+/// Builds unsigned transactions from requested operations and checks the
+/// signatures that come back. Nothing here holds or sees a secret key.
+pub struct StellarTxBuilder;
+
+impl StellarTxBuilder {
+    /// `sequence` is the account's current one; the transaction uses the next.
+    /// It is only valid until `max_time` (unix seconds).
+    pub fn build(
+        source: &str,
+        sequence: i64,
+        operation: &StellarOperationRequest,
+        memo: Option<&str>,
+        max_time: u64,
+    ) -> Result<Transaction, StellarTxError> {
+        Self::build_many(source, sequence, std::slice::from_ref(operation), memo, max_time)
+    }
+
+    /// Like `build`, with all of `operations` in one transaction: they succeed
+    /// or fail together. A transaction holds at most `MAX_OPERATIONS`.
+    pub fn build_many(
+        source: &str,
+        sequence: i64,
+        operations: &[StellarOperationRequest],
+        memo: Option<&str>,
+        max_time: u64,
+    ) -> Result<Transaction, StellarTxError> {
+        let base_fee: u32 = env::var("STELLAR_BASE_FEE").ok().and_then(|v| v.parse().ok()).unwrap_or(100);
+        let memo = match memo {
+            Some(text) => Memo::Text(text.try_into().map_err(|_| StellarTxError::InvalidMemo)?),
+            None => Memo::None,
+        };
+        let mut built = Vec::new();
+        for operation in operations {
+            built.extend(Self::operations(operation)?);
+        }
+        if built.is_empty() || built.len() > MAX_OPERATIONS {
+            return Err(StellarTxError::InvalidOperation(format!(
+                "a transaction holds 1 to {} operations",
+                MAX_OPERATIONS
+            )));
+        }
+
+        Ok(Transaction {
+            source_account: MuxedAccount::Ed25519(Uint256(Self::public_key_bytes(source)?)),
+            // The base fee is charged per operation
+            fee: base_fee.saturating_mul(built.len() as u32),
+            seq_num: SequenceNumber(sequence + 1),
+            cond: Preconditions::Time(TimeBounds {
+                min_time: TimePoint(0),
+                max_time: TimePoint(max_time),
+            }),
+            memo,
+            operations: built.try_into().map_err(|_| StellarTxError::MalformedXdr)?,
+            ext: TransactionExt::V0,
+        })
+    }
+
+    pub fn unsigned_envelope_xdr(tx: &Transaction) -> Result<String, StellarTxError> {
+        TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: tx.clone(),
+            signatures: Default::default(),
+        })
+        .to_xdr_base64(Limits::none())
+        .map_err(|_| StellarTxError::MalformedXdr)
+    }
+
+    /// `tx` as a base64 envelope carrying `signatures`.
+    pub fn signed_envelope_xdr(tx: &Transaction, signatures: &[EnvelopeSignature]) -> Result<String, StellarTxError> {
+        let signatures = signatures
+            .iter()
+            .map(|(hint, signature)| {
+                Ok(DecoratedSignature {
+                    hint: SignatureHint(*hint),
+                    signature: XdrSignature(signature.clone().try_into().map_err(|_| StellarTxError::MalformedXdr)?),
+                })
+            })
+            .collect::<Result<Vec<_>, StellarTxError>>()?;
+
+        TransactionEnvelope::Tx(TransactionV1Envelope {
+            tx: tx.clone(),
+            signatures: signatures.try_into().map_err(|_| StellarTxError::MalformedXdr)?,
+        })
+        .to_xdr_base64(Limits::none())
+        .map_err(|_| StellarTxError::MalformedXdr)
+    }
+
+    /// The transaction inside a base64 v1 envelope, and its signatures.
+    pub fn parse_envelope(envelope_xdr: &str) -> Result<(Transaction, Vec<EnvelopeSignature>), StellarTxError> {
+        match TransactionEnvelope::from_xdr_base64(envelope_xdr.trim(), Limits::len(64 * 1024)) {
+            Ok(TransactionEnvelope::Tx(envelope)) => {
+                let signatures = envelope
+                    .signatures
+                    .iter()
+                    .map(|signature| (signature.hint.0, signature.signature.to_vec()))
+                    .collect();
+                Ok((envelope.tx, signatures))
+            }
+            _ => Err(StellarTxError::MalformedXdr),
+        }
+    }
+
+    /// Whether any of `signatures` is `public_key`'s signature over `hash`.
+    pub fn signed_by(public_key: &str, hash: &[u8; 32], signatures: &[EnvelopeSignature]) -> bool {
+        Self::signature_by(public_key, hash, signatures).is_some()
+    }
+
+    /// `public_key`'s signature over `hash` among `signatures`, if it is there.
+    pub fn signature_by<'a>(
+        public_key: &str,
+        hash: &[u8; 32],
+        signatures: &'a [EnvelopeSignature],
+    ) -> Option<&'a EnvelopeSignature> {
+        let key_bytes = Self::public_key_bytes(public_key).ok()?;
+        let key = VerifyingKey::from_bytes(&key_bytes).ok()?;
+        let hint = &key_bytes[28..];
+
+        signatures.iter().filter(|(signature_hint, _)| signature_hint == hint).find(|(_, signature)| {
+            Signature::from_slice(signature).is_ok_and(|signature| key.verify_strict(hash, &signature).is_ok())
+        })
+    }
+
+    pub fn public_key_bytes(public_key: &str) -> Result<[u8; 32], StellarTxError> {
+        stellar_strkey::ed25519::PublicKey::from_string(public_key)
+            .map(|key| key.0)
+            .map_err(|_| StellarTxError::InvalidAddress(public_key.to_string()))
+    }
+
+    /// One operation, except that `SetOptions` takes one per signer.
+    fn operations(request: &StellarOperationRequest) -> Result<Vec<Operation>, StellarTxError> {
+        let body = match request {
+            StellarOperationRequest::Payment {
+                destination,
+                asset,
+                amount,
+            } => OperationBody::Payment(PaymentOp {
+                destination: Self::account(destination)?,
+                asset: Self::asset(asset)?,
+                amount: Self::amount(amount, false)?,
+            }),
+            StellarOperationRequest::Trustline { asset, limit } => {
+                let line = match Self::asset(asset)? {
+                    Asset::CreditAlphanum4(asset) => ChangeTrustAsset::CreditAlphanum4(asset),
+                    Asset::CreditAlphanum12(asset) => ChangeTrustAsset::CreditAlphanum12(asset),
+                    Asset::Native => return Err(StellarTxError::InvalidAsset("XLM needs no trustline".to_string())),
+                };
+                let limit = match limit {
+                    Some(limit) => Self::amount(limit, true)?,
+                    None => i64::MAX,
+                };
+                OperationBody::ChangeTrust(ChangeTrustOp { line, limit })
+            }
+            StellarOperationRequest::PathPayment {
+                destination,
+                send_asset,
+                send_amount,
+                dest_asset,
+                dest_min,
+                path,
+            } => OperationBody::PathPaymentStrictSend(PathPaymentStrictSendOp {
+                send_asset: Self::asset(send_asset)?,
+                send_amount: Self::amount(send_amount, false)?,
+                destination: Self::account(destination)?,
+                dest_asset: Self::asset(dest_asset)?,
+                dest_min: Self::amount(dest_min, false)?,
+                path: path
+                    .iter()
+                    .map(|asset| Self::asset(asset))
+                    .collect::<Result<Vec<_>, _>>()?
+                    .try_into()
+                    .map_err(|_| StellarTxError::InvalidAsset("a path has at most 5 assets".to_string()))?,
+            }),
+            StellarOperationRequest::SetOptions {
+                signers,
+                master_weight,
+                low_threshold,
+                med_threshold,
+                high_threshold,
+            } => {
+                let settings = SetOptionsOp {
+                    inflation_dest: None,
+                    clear_flags: None,
+                    set_flags: None,
+                    master_weight: master_weight.map(u32::from),
+                    low_threshold: low_threshold.map(u32::from),
+                    med_threshold: med_threshold.map(u32::from),
+                    high_threshold: high_threshold.map(u32::from),
+                    home_domain: None,
+                    signer: None,
+                };
+                return Self::set_options(settings, signers);
+            }
+        };
+
+        Ok(vec![Operation {
+            source_account: None,
+            body,
+        }])
+    }
+
+    /// The weights and thresholds ride on the first operation, and each
+    /// signer gets one of its own.
+    fn set_options(settings: SetOptionsOp, signers: &[StellarSignerRequest]) -> Result<Vec<Operation>, StellarTxError> {
+        let changes_settings = settings.master_weight.is_some()
+            || settings.low_threshold.is_some()
+            || settings.med_threshold.is_some()
+            || settings.high_threshold.is_some();
+        if signers.is_empty() && !changes_settings {
+            return Err(StellarTxError::InvalidOperation("nothing to change".to_string()));
+        }
+        if signers.len() > 20 {
+            return Err(StellarTxError::InvalidOperation("an account has at most 20 signers".to_string()));
+        }
+
+        let mut operations = vec![settings.clone()];
+        for (i, signer) in signers.iter().enumerate() {
+            let signer = Signer {
+                key: SignerKey::Ed25519(Uint256(Self::public_key_bytes(signer.key.trim())?)),
+                weight: signer.weight.into(),
+            };
+            if i == 0 {
+                operations[0].signer = Some(signer);
+            } else {
+                operations.push(SetOptionsOp {
+                    inflation_dest: None,
+                    clear_flags: None,
+                    set_flags: None,
+                    master_weight: None,
+                    low_threshold: None,
+                    med_threshold: None,
+                    high_threshold: None,
+                    home_domain: None,
+                    signer: Some(signer),
+                });
+            }
+        }
+
+        Ok(operations
+            .into_iter()
+            .map(|options| Operation {
+                source_account: None,
+                body: OperationBody::SetOptions(options),
+            })
+            .collect())
+    }
+
+    fn account(address: &str) -> Result<MuxedAccount, StellarTxError> {
+        MuxedAccount::from_str(address.trim()).map_err(|_| StellarTxError::InvalidAddress(address.to_string()))
+    }
+
+    /// `XLM` (or `native`) or `CODE:ISSUER`.
+    pub fn asset(value: &str) -> Result<Asset, StellarTxError> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("xlm") || value == "native" {
+            return Ok(Asset::Native);
+        }
+
+        let invalid = || StellarTxError::InvalidAsset(value.to_string());
+        let (code, issuer) = value.split_once(':').ok_or_else(invalid)?;
+        let issuer = AccountId::from_str(issuer).map_err(|_| invalid())?;
+        if code.is_empty() || code.len() > 12 || !code.bytes().all(|b| b.is_ascii_alphanumeric()) {
+            return Err(invalid());
+        }
+
+        if code.len() <= 4 {
+            let mut asset_code = [0u8; 4];
+            asset_code[..code.len()].copy_from_slice(code.as_bytes());
+            Ok(Asset::CreditAlphanum4(AlphaNum4 {
+                asset_code: AssetCode4(asset_code),
+                issuer,
+            }))
+        } else {
+            let mut asset_code = [0u8; 12];
+            asset_code[..code.len()].copy_from_slice(code.as_bytes());
+            Ok(Asset::CreditAlphanum12(AlphaNum12 {
+                asset_code: AssetCode12(asset_code),
+                issuer,
+            }))
+        }
+    }
+
+    /// Decimal string with up to 7 places, in stroops.
+    pub fn amount(value: &str, allow_zero: bool) -> Result<i64, StellarTxError> {
+        let invalid = || StellarTxError::InvalidAmount(value.to_string());
+        let (whole, fraction) = value.trim().split_once('.').unwrap_or((value.trim(), ""));
+        let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty() || fraction.len() > 7 || !digits(whole) || !digits(fraction) {
+            return Err(invalid());
+        }
+
+        let whole: i64 = whole.parse().map_err(|_| invalid())?;
+        let fraction: i64 = format!("{:0<7}", fraction).parse().map_err(|_| invalid())?;
+        let stroops = whole
+            .checked_mul(STROOPS_PER_UNIT)
+            .and_then(|stroops| stroops.checked_add(fraction))
+            .ok_or_else(invalid)?;
+        if stroops == 0 && !allow_zero {
+            return Err(invalid());
+        }
+        Ok(stroops)
+    }
+
+    /// Parses a stored unsigned envelope back into its transaction.
+    pub fn stored_transaction(unsigned_xdr: &str) -> Result<Transaction, StellarTxError> {
+        Self::parse_envelope(unsigned_xdr).map(|(tx, _)| tx)
+    }
+}
+
+pub fn stroops_to_units(stroops: i64) -> f64 {
+    stroops as f64 / STROOPS_PER_UNIT as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::stub_server;
+    use axum::{extract::State, http::StatusCode, routing::{get, post}, Json, Router};
+    use serde_json::json;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    #[derive(Clone, Default)]
+    struct Horizon {
+        landed: Arc<AtomicBool>,
+        submissions: Arc<AtomicUsize>,
+        reject: bool,
+    }
+
+    async fn horizon(state: Horizon) -> StellarNetwork {
+        let app = Router::new()
+            .route(
+                "/transactions/:hash",
+                get(|State(horizon): State<Horizon>| async move {
+                    if horizon.landed.load(Ordering::SeqCst) {
+                        (StatusCode::OK, Json(json!({ "successful": true })))
+                    } else {
+                        (StatusCode::NOT_FOUND, Json(json!({})))
+                    }
+                }),
+            )
+            .route(
+                "/transactions",
+                post(|State(horizon): State<Horizon>| async move {
+                    horizon.submissions.fetch_add(1, Ordering::SeqCst);
+                    horizon.landed.store(true, Ordering::SeqCst);
+                    if horizon.reject {
+                        // As if an earlier submission landed first
+                        let codes = json!({ "extras": { "result_codes": { "transaction": "tx_bad_seq" } } });
+                        (StatusCode::BAD_REQUEST, Json(codes))
+                    } else {
+                        (StatusCode::OK, Json(json!({ "hash": "abc123" })))
+                    }
+                }),
+            )
+            .with_state(state);
+
+        StellarNetwork {
+            horizon_url: stub_server(app).await,
+            passphrase: TESTNET_PASSPHRASE,
+            client: Client::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_resubmit_sends_once() {
+        let state = Horizon::default();
+        let network = horizon(state.clone()).await;
+
+        assert_eq!(network.outcome("abc123").await.unwrap(), None);
+        assert_eq!(network.resubmit("abc123", "AAAA").await.unwrap(), "abc123");
+        assert_eq!(network.outcome("abc123").await.unwrap(), Some(true));
+        // A retry finds the landed transaction and doesn't submit it again
+        assert_eq!(network.resubmit("abc123", "AAAA").await.unwrap(), "abc123");
+        assert_eq!(state.submissions.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_resubmit_rejection_after_landing() {
+        let state = Horizon { reject: true, ..Default::default() };
+        let network = horizon(state.clone()).await;
+
+        assert_eq!(network.resubmit("abc123", "AAAA").await.unwrap(), "abc123");
+        assert_eq!(state.submissions.load(Ordering::SeqCst), 1);
+    }
+
+    fn keypair(seed: u8) -> (ed25519_dalek::SigningKey, String) {
+        let key = ed25519_dalek::SigningKey::from_bytes(&[seed; 32]);
+        let public_key = stellar_strkey::ed25519::PublicKey(key.verifying_key().to_bytes()).to_string();
+        (key, public_key)
+    }
+
+    fn payment(destination: &str, amount: &str) -> StellarOperationRequest {
+        StellarOperationRequest::Payment {
+            destination: destination.to_string(),
+            asset: "XLM".to_string(),
+            amount: amount.to_string(),
+        }
+    }
+
+    fn sign(key: &ed25519_dalek::SigningKey, hash: &[u8; 32]) -> EnvelopeSignature {
+        use ed25519_dalek::Signer;
+        let hint = key.verifying_key().to_bytes()[28..].try_into().unwrap();
+        (hint, key.sign(hash).to_bytes().to_vec())
+    }
+
+    #[test]
+    fn test_amounts() {
+        assert_eq!(StellarTxBuilder::amount("12.5", false).unwrap(), 125_000_000);
+        assert_eq!(StellarTxBuilder::amount("0.0000001", false).unwrap(), 1);
+        assert_eq!(StellarTxBuilder::amount(" 3 ", false).unwrap(), 30_000_000);
+        assert_eq!(StellarTxBuilder::amount("1.", false).unwrap(), 10_000_000);
+        assert_eq!(StellarTxBuilder::amount("0", true).unwrap(), 0);
+        for value in ["0", "0.0", "", ".5", "-1", "1.12345678", "1e3", "1,5", "922337203685.4775808"] {
+            assert!(StellarTxBuilder::amount(value, false).is_err(), "{:?} should be refused", value);
+        }
+        assert_eq!(stroops_to_units(125_000_000), 12.5);
+    }
+
+    #[test]
+    fn test_assets() {
+        let (_, issuer) = keypair(1);
+        assert_eq!(StellarTxBuilder::asset("xlm").unwrap(), Asset::Native);
+        assert_eq!(StellarTxBuilder::asset("native").unwrap(), Asset::Native);
+        assert!(matches!(StellarTxBuilder::asset(&format!("USDC:{}", issuer)).unwrap(), Asset::CreditAlphanum4(_)));
+        assert!(matches!(StellarTxBuilder::asset(&format!("LONGCODE123:{}", issuer)).unwrap(), Asset::CreditAlphanum12(_)));
+        for value in [
+            "USDC".to_string(),
+            format!(":{}", issuer),
+            format!("TOOLONGCODE13:{}", issuer),
+            format!("US-D:{}", issuer),
+            "USDC:GNOTANACCOUNT".to_string(),
+        ] {
+            assert!(StellarTxBuilder::asset(&value).is_err(), "{:?} should be refused", value);
+        }
+    }
+
+    #[test]
+    fn test_envelope_round_trip_and_signatures() {
+        let network = StellarNetwork::from_env();
+        let (source_key, source) = keypair(1);
+        let (other_key, _) = keypair(2);
+        let (_, destination) = keypair(3);
+
+        let tx = StellarTxBuilder::build(&source, 41, &payment(&destination, "12.5"), Some("rent"), 1_900_000_000).unwrap();
+        assert_eq!(tx.seq_num.0, 42);
+        assert_eq!(tx.fee, 100);
+        let unsigned = StellarTxBuilder::unsigned_envelope_xdr(&tx).unwrap();
+        let (parsed, signatures) = StellarTxBuilder::parse_envelope(&unsigned).unwrap();
+        assert_eq!(parsed, tx);
+        assert!(signatures.is_empty());
+        assert_eq!(StellarTxBuilder::stored_transaction(&unsigned).unwrap(), tx);
+
+        let hash = network.hash(&tx).unwrap();
+        let signed = StellarTxBuilder::signed_envelope_xdr(&tx, &[sign(&source_key, &hash)]).unwrap();
+        let (parsed, signatures) = StellarTxBuilder::parse_envelope(&signed).unwrap();
+        assert_eq!(parsed, tx);
+        assert!(StellarTxBuilder::signed_by(&source, &hash, &signatures));
+
+        // Someone else's key, a signature over another transaction, or a
+        // tampered signature don't count
+        assert!(!StellarTxBuilder::signed_by(&source, &hash, &[sign(&other_key, &hash)]));
+        let other_tx = StellarTxBuilder::build(&source, 41, &payment(&destination, "99"), None, 1_900_000_000).unwrap();
+        let other_hash = network.hash(&other_tx).unwrap();
+        assert!(!StellarTxBuilder::signed_by(&source, &hash, &[sign(&source_key, &other_hash)]));
+        let (hint, mut signature) = sign(&source_key, &hash);
+        signature[0] ^= 1;
+        assert!(!StellarTxBuilder::signed_by(&source, &hash, &[(hint, signature)]));
+        assert!(!StellarTxBuilder::signed_by("GNOTANACCOUNT", &hash, &signatures));
+    }
+
+    #[test]
+    fn test_malformed_envelopes_and_operations() {
+        assert!(matches!(StellarTxBuilder::parse_envelope("not base64!"), Err(StellarTxError::MalformedXdr)));
+        assert!(matches!(StellarTxBuilder::parse_envelope("AAAAAA=="), Err(StellarTxError::MalformedXdr)));
+
+        let (_, source) = keypair(1);
+        let (_, destination) = keypair(3);
+        let too_many = vec![payment(&destination, "1"); MAX_OPERATIONS + 1];
+        assert!(matches!(
+            StellarTxBuilder::build_many(&source, 1, &too_many, None, 1_900_000_000),
+            Err(StellarTxError::InvalidOperation(_))
+        ));
+        assert!(matches!(
+            StellarTxBuilder::build(&source, 1, &payment("GBAD", "1"), None, 1_900_000_000),
+            Err(StellarTxError::InvalidAddress(_))
+        ));
+        assert!(matches!(
+            StellarTxBuilder::build(&source, 1, &payment(&destination, "1"), Some(&"m".repeat(29)), 1_900_000_000),
+            Err(StellarTxError::InvalidMemo)
+        ));
+    }
+}
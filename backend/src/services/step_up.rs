@@ -0,0 +1,100 @@
+use sqlx::SqlitePool;
+use std::env;
+use uuid::Uuid;
+
+use crate::models::{StepUpAction, StepUpKind};
+use crate::services::signing::sha256_hex;
+
+const PROOF_TTL_MINUTES: i64 = 5;
+
+/// Fresh second-factor proofs for high-value operations. A proof is single
+/// use, expires after five minutes and is bound to one exact operation.
+pub struct StepUpService;
+
+impl StepUpService {
+    /// Secret exports always need a proof; transfers and withdrawals only at
+    /// or above the threshold for their currency.
+    pub fn required(action: &StepUpAction) -> bool {
+        match action.action {
+            StepUpKind::SecretExport => true,
+            StepUpKind::Transfer | StepUpKind::Withdrawal => {
+                let currency = action.currency.as_deref().unwrap_or("XLM");
+                action.amount.unwrap_or(0.0) >= Self::threshold(currency)
+            }
+        }
+    }
+
+    /// From `STEP_UP_THRESHOLDS` (`XLM:1000,USD:100,...`), falling back to
+    /// `STEP_UP_DEFAULT_THRESHOLD` (default 1000).
+    pub fn threshold(currency: &str) -> f64 {
+        let configured = env::var("STEP_UP_THRESHOLDS").unwrap_or_default();
+        configured
+            .split(',')
+            .filter_map(|entry| entry.split_once(':'))
+            .find(|(code, _)| code.trim().eq_ignore_ascii_case(currency))
+            .and_then(|(_, amount)| amount.trim().parse().ok())
+            .unwrap_or_else(|| {
+                env::var("STEP_UP_DEFAULT_THRESHOLD")
+                    .ok()
+                    .and_then(|v| v.parse().ok())
+                    .unwrap_or(1000.0)
+            })
+    }
+
+    fn binding_hash(user_id: &str, action: &StepUpAction) -> String {
+        let binding = format!(
+            "{}|{}|{:.7}|{}|{}",
+            user_id,
+            action.action.as_str(),
+            action.amount.unwrap_or(0.0),
+            action.currency.as_deref().unwrap_or("").to_uppercase(),
+            action.destination.as_deref().unwrap_or("").trim(),
+        );
+        sha256_hex(binding.as_bytes())
+    }
+
+    /// Records a proof for `action` once the caller has checked a second
+    /// factor, returning the token to send as `X-Step-Up-Token`.
+    pub async fn issue(pool: &SqlitePool, user_id: &str, action: &StepUpAction) -> Result<String, sqlx::Error> {
+        let token = hex::encode(rand::random::<[u8; 32]>());
+        let now = chrono::Utc::now();
+
+        sqlx::query(
+            r#"
+            INSERT INTO step_up_proofs (id, token_hash, user_id, action, binding_hash, expires_at, created_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?)
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(sha256_hex(token.as_bytes()))
+        .bind(user_id)
+        .bind(action.action.as_str())
+        .bind(Self::binding_hash(user_id, action))
+        .bind(now + chrono::Duration::minutes(PROOF_TTL_MINUTES))
+        .bind(now)
+        .execute(pool)
+        .await?;
+
+        Ok(token)
+    }
+
+    /// Uses up the proof if it was issued to this user for exactly `action`.
+    pub async fn consume(pool: &SqlitePool, user_id: &str, token: &str, action: &StepUpAction) -> Result<bool, sqlx::Error> {
+        let now = chrono::Utc::now();
+        let result = sqlx::query(
+            r#"
+            UPDATE step_up_proofs SET consumed_at = ?
+            WHERE token_hash = ? AND user_id = ? AND binding_hash = ? AND consumed_at IS NULL AND expires_at > ?
+            "#,
+        )
+        .bind(now)
+        .bind(sha256_hex(token.as_bytes()))
+        .bind(user_id)
+        .bind(Self::binding_hash(user_id, action))
+        .bind(now)
+        .execute(pool)
+        .await?;
+
+        Ok(result.rows_affected() > 0)
+    }
+}
@@ -1,27 +1,50 @@
-use crate::models::{Transaction, CreateTransaction};
-use crate::services::{StellarService, SmsService};
+use crate::models::{Transaction, CreateTransaction, Wallet};
+use crate::services::payouts::receiving_account;
+use crate::services::{DomainEvent, JobPayload, Outbox, WalletService};
+use crate::wallet_sdk::{NovaPayWallet, WalletError};
+use crate::wallet_sdk_service::WalletSDKService;
 use sqlx::SqlitePool;
+use thiserror::Error;
 use uuid::Uuid;
 
-pub struct TransactionService {
-    stellar_service: StellarService,
-    sms_service: SmsService,
+#[derive(Error, Debug)]
+pub enum WalletPaymentError {
+    #[error("Insufficient balance")]
+    InsufficientFunds,
+    #[error("Payment failed: {0}")]
+    Failed(String),
+    #[error("Stellar network unavailable: {0}")]
+    Unavailable(String),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
 }
 
+/// How a payment submission ended. `Unknown` means Horizon couldn't be
+/// reached to tell; the transaction stays `submitting` with its signed
+/// envelope, and submitting it again can't pay twice.
+#[derive(Debug)]
+pub enum PaymentOutcome {
+    Completed(String),
+    Failed(String),
+    Unknown(String),
+}
+
+pub struct TransactionService;
+
 impl TransactionService {
     pub fn new() -> Self {
-        Self {
-            stellar_service: StellarService::new(),
-            sms_service: SmsService::new(),
-        }
+        Self
     }
 
-    pub async fn create_transaction(
+    pub async fn create_transaction<'e, E>(
         &self,
-        pool: &SqlitePool,
+        executor: E,
         user_id: &str,
         create_tx: CreateTransaction,
-    ) -> Result<Transaction, sqlx::Error> {
+    ) -> Result<Transaction, sqlx::Error>
+    where
+        E: sqlx::Executor<'e, Database = sqlx::Sqlite>,
+    {
         let tx_id = Uuid::new_v4().to_string();
         let currency = create_tx.currency.unwrap_or_else(|| "USD".to_string());
         let target_currency = create_tx.target_currency.unwrap_or_else(|| "KES".to_string());
@@ -39,55 +62,201 @@ impl TransactionService {
         .bind(create_tx.amount)
         .bind(&currency)
         .bind(&target_currency)
-        .fetch_one(pool)
+        .fetch_one(executor)
         .await?;
 
         Ok(transaction)
     }
 
-    pub async fn process_payment(
+    /// Signs the payment of a pending transaction and stores it, moving the
+    /// transaction to `submitting`. Nothing is sent yet. Returns false if the
+    /// transaction wasn't pending, i.e. it was already prepared.
+    pub async fn prepare(
         &self,
         pool: &SqlitePool,
         transaction_id: &str,
         from_secret: &str,
         to_public: &str,
-    ) -> Result<String, Box<dyn std::error::Error>> {
-        // Get transaction details
-        let transaction = sqlx::query_as::<_, Transaction>(
-            "SELECT * FROM transactions WHERE id = ?"
+    ) -> Result<bool, WalletPaymentError> {
+        let transaction = sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = ?")
+            .bind(transaction_id)
+            .fetch_one(pool)
+            .await?;
+        if transaction.status != "pending" {
+            return Ok(false);
+        }
+
+        // Payments go out in XLM whatever the transaction's currency
+        let wallet = NovaPayWallet::new(from_secret, WalletSDKService::new().config()).map_err(payment_error)?;
+        let (hash, envelope) = wallet
+            .sign_payments("XLM", &[(to_public.to_string(), format!("{:.7}", transaction.amount))], None)
+            .await
+            .map_err(payment_error)?;
+
+        let prepared = sqlx::query(
+            r#"
+            UPDATE transactions
+            SET status = 'submitting', submission_hash = ?, submission_envelope = ?, submitted_at = ?
+            WHERE id = ? AND status = 'pending'
+            "#,
         )
+        .bind(&hash)
+        .bind(&envelope)
+        .bind(chrono::Utc::now())
         .bind(transaction_id)
-        .fetch_one(pool)
-        .await?;
+        .execute(pool)
+        .await?
+        .rows_affected();
+        Ok(prepared == 1)
+    }
+
+    /// Submits a prepared transaction, or finds out what became of an earlier
+    /// submission, and completes the transaction if the payment landed. The
+    /// stored envelope is what goes out, so calling this again is safe.
+    pub async fn submit(&self, pool: &SqlitePool, transaction_id: &str) -> Result<PaymentOutcome, sqlx::Error> {
+        let transaction = sqlx::query_as::<_, Transaction>("SELECT * FROM transactions WHERE id = ?")
+            .bind(transaction_id)
+            .fetch_one(pool)
+            .await?;
+        let (hash, envelope) = match (transaction.status.as_str(), &transaction.submission_hash, &transaction.submission_envelope) {
+            ("completed", _, _) => {
+                return Ok(PaymentOutcome::Completed(transaction.stellar_tx_hash.unwrap_or_default()));
+            }
+            ("submitting", Some(hash), Some(envelope)) => (hash, envelope),
+            (status, _, _) => return Ok(PaymentOutcome::Failed(format!("transaction is {}", status))),
+        };
 
-        // Send payment via Stellar
-        let tx_hash = self.stellar_service.send_payment(
-            from_secret,
-            to_public,
-            transaction.amount,
-            &transaction.target_currency,
-        ).await?;
-
-        // Update transaction with hash and status
-        sqlx::query(
-            "UPDATE transactions SET stellar_tx_hash = ?, status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?"
+        let tx_hash = match NovaPayWallet::submit_signed(hash, envelope).await {
+            Ok(tx_hash) => tx_hash,
+            Err(WalletError::NetworkError(e)) => return Ok(PaymentOutcome::Unknown(e)),
+            Err(e) => return Ok(PaymentOutcome::Failed(e.to_string())),
+        };
+
+        // Update transaction with hash and status, queueing notifications in
+        // the same database transaction
+        let mut db_tx = pool.begin().await?;
+        let completed = sqlx::query(
+            "UPDATE transactions SET stellar_tx_hash = ?, status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ? AND status = 'submitting'"
         )
         .bind(&tx_hash)
         .bind(transaction_id)
-        .execute(pool)
+        .execute(&mut *db_tx)
+        .await?
+        .rows_affected();
+
+        if completed == 1 {
+            Outbox::enqueue(&mut *db_tx, &JobPayload::Notify {
+                event: DomainEvent::TransferCompleted {
+                    transaction_id: transaction.id.clone(),
+                    sender_id: transaction.user_id.clone(),
+                    recipient: transaction.recipient_email.clone(),
+                    amount: transaction.amount,
+                    currency: transaction.target_currency.clone(),
+                    tx_hash: tx_hash.clone(),
+                },
+            }).await?;
+        }
+        db_tx.commit().await?;
+
+        Ok(PaymentOutcome::Completed(tx_hash))
+    }
+
+    /// Fails a transaction whose payment is known not to have gone out, with
+    /// `also` queued alongside `TransferFailed`. Only `pending` and
+    /// `submitting` transactions fail; returns false for any other.
+    pub async fn fail(
+        pool: &SqlitePool,
+        transaction_id: &str,
+        reason: &str,
+        also: Option<DomainEvent>,
+    ) -> Result<bool, sqlx::Error> {
+        let mut db_tx = pool.begin().await?;
+        let failed = sqlx::query_as::<_, Transaction>(
+            "UPDATE transactions SET status = 'failed' WHERE id = ? AND status IN ('pending', 'submitting') RETURNING *",
+        )
+        .bind(transaction_id)
+        .fetch_optional(&mut *db_tx)
         .await?;
+        let Some(transaction) = failed else {
+            return Ok(false);
+        };
+
+        Outbox::enqueue(&mut *db_tx, &JobPayload::Notify {
+            event: DomainEvent::TransferFailed {
+                transaction_id: transaction.id.clone(),
+                sender_id: transaction.user_id.clone(),
+                recipient: transaction.recipient_email.clone(),
+                amount: transaction.amount,
+                currency: transaction.target_currency.clone(),
+                reason: reason.to_string(),
+            },
+        }).await?;
+        if let Some(event) = also {
+            Outbox::enqueue(&mut *db_tx, &JobPayload::Notify { event }).await?;
+        }
+        db_tx.commit().await?;
+        Ok(true)
+    }
 
-        // Send SMS notification to recipient
-        if let Err(e) = self.sms_service.send_notification(
-            &transaction.recipient_email, // This is actually phone number
-            transaction.amount,
-            &transaction.target_currency,
-            "NovaPay User"
-        ).await {
-            println!("Failed to send SMS: {}", e);
+    /// Checks the user's wallet holds at least `amount`, before a payment
+    /// from it is recorded.
+    pub async fn ensure_funds(&self, pool: &SqlitePool, user_id: &str, amount: f64) -> Result<(), WalletPaymentError> {
+        let balance = WalletService::new()
+            .get_wallet_balance(pool, user_id)
+            .await
+            .map_err(|e| WalletPaymentError::Failed(e.to_string()))?;
+        if balance < amount {
+            return Err(WalletPaymentError::InsufficientFunds);
         }
+        Ok(())
+    }
 
-        Ok(tx_hash)
+    /// The Stellar account payments to the user with this email go to: their
+    /// wallet, or failing that the account they receive payouts on.
+    pub async fn recipient_account(&self, pool: &SqlitePool, email: &str) -> Result<Option<String>, sqlx::Error> {
+        let recipient: Option<(String, Option<String>)> = sqlx::query_as(
+            "SELECT u.id, w.stellar_public_key FROM users u LEFT JOIN wallets w ON w.user_id = u.id WHERE u.email = ?",
+        )
+        .bind(email)
+        .fetch_optional(pool)
+        .await?;
+        match recipient {
+            Some((_, Some(public_key))) => Ok(Some(public_key)),
+            Some((user_id, None)) => receiving_account(pool, &user_id).await,
+            None => Ok(None),
+        }
+    }
+
+    /// Sends a pending transaction from its sender's wallet to a Stellar
+    /// account. A payment that definitely didn't go out fails the
+    /// transaction and comes back as `Failed`; `Unknown` leaves it
+    /// `submitting`, and calling this again finds out what became of it.
+    pub async fn pay_from_wallet(
+        &self,
+        pool: &SqlitePool,
+        transaction_id: &str,
+        destination: &str,
+    ) -> Result<PaymentOutcome, sqlx::Error> {
+        let wallet = sqlx::query_as::<_, Wallet>(
+            "SELECT w.* FROM wallets w JOIN transactions t ON t.user_id = w.user_id WHERE t.id = ?",
+        )
+        .bind(transaction_id)
+        .fetch_optional(pool)
+        .await?;
+        let prepared = match wallet {
+            Some(wallet) => self.prepare(pool, transaction_id, &wallet.stellar_secret_key, destination).await,
+            None => Err(WalletPaymentError::Failed("no wallet".to_string())),
+        };
+        // Nothing was sent if the payment couldn't be signed
+        let outcome = match prepared {
+            Ok(_) => self.submit(pool, transaction_id).await?,
+            Err(WalletPaymentError::Database(e)) => return Err(e),
+            Err(e) => PaymentOutcome::Failed(e.to_string()),
+        };
+        if let PaymentOutcome::Failed(reason) = &outcome {
+            Self::fail(pool, transaction_id, reason, None).await?;
+        }
+        Ok(outcome)
     }
 
     pub async fn get_user_transactions(
@@ -102,4 +271,12 @@ impl TransactionService {
         .fetch_all(pool)
         .await
     }
-}
\ No newline at end of file
+}
+
+/// Signing needs the sequence number from Horizon; nothing was sent either way.
+fn payment_error(error: WalletError) -> WalletPaymentError {
+    match error {
+        WalletError::NetworkError(e) => WalletPaymentError::Unavailable(e),
+        e => WalletPaymentError::Failed(e.to_string()),
+    }
+}
@@ -1,11 +1,10 @@
 use crate::models::Wallet;
-use crate::services::{StellarService, stellar_sdk::StellarSDK};
+use crate::services::{DomainEvent, JobPayload, Ledger, Outbox, stellar_sdk::StellarSDK};
 use sqlx::SqlitePool;
 use uuid::Uuid;
 use std::collections::HashMap;
 
 pub struct WalletService {
-    stellar_service: StellarService,
     stellar_sdk: StellarSDK,
     // In-memory balance tracking for demo
     balances: HashMap<String, f64>,
@@ -19,7 +18,6 @@ impl WalletService {
         balances.insert("demo_wallet_2".to_string(), 500.0);
         
         Self {
-            stellar_service: StellarService::new(),
             stellar_sdk: StellarSDK::new(),
             balances,
         }
@@ -48,11 +46,11 @@ impl WalletService {
             .fetch_one(pool)
             .await?;
 
-        // Fund wallet with XLM (using friendbot for testnet)
-        self.stellar_service.fund_test_account(&wallet.stellar_public_key).await?;
         let tx_hash = format!("deposit_{}", uuid::Uuid::new_v4());
-        
-        // Record deposit transaction
+        let balance = self.get_wallet_balance(pool, user_id).await?;
+
+        // Record the deposit and queue its side effects atomically
+        let mut db_tx = pool.begin().await?;
         let deposit_id = Uuid::new_v4().to_string();
         sqlx::query(
             "INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, stellar_tx_hash, status) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
@@ -65,29 +63,53 @@ impl WalletService {
         .bind("XLM")
         .bind(&tx_hash)
         .bind("completed")
-        .execute(pool)
+        .execute(&mut *db_tx)
         .await?;
 
+        // Fund wallet with XLM (using friendbot for testnet)
+        Outbox::enqueue(&mut *db_tx, &JobPayload::FriendbotFund {
+            public_key: wallet.stellar_public_key.clone(),
+        }).await?;
+        Outbox::enqueue(&mut *db_tx, &JobPayload::Notify {
+            event: DomainEvent::DepositCredited {
+                user_id: user_id.to_string(),
+                amount: kes_amount,
+                currency: "KES".to_string(),
+                balance,
+                reference: mpesa_ref.to_string(),
+            },
+        }).await?;
+        db_tx.commit().await?;
+
         println!("💰 Deposit: {} KES → {} XLM (Ref: {})", kes_amount, xlm_amount, mpesa_ref);
         Ok(tx_hash)
     }
 
+    /// The Stellar account withdrawals are paid into before the M-Pesa
+    /// payout, from `MPESA_WITHDRAWAL_ACCOUNT`. `None` if it isn't set to a
+    /// valid public key.
+    pub fn withdrawal_account() -> Option<String> {
+        std::env::var("MPESA_WITHDRAWAL_ACCOUNT")
+            .ok()
+            .filter(|account| stellar_strkey::ed25519::PublicKey::from_string(account).is_ok())
+    }
+
     pub async fn withdraw_to_mpesa(&self, pool: &SqlitePool, user_id: &str, xlm_amount: f64, mpesa_number: &str) -> Result<String, Box<dyn std::error::Error>> {
+        let destination = Self::withdrawal_account().ok_or("MPESA_WITHDRAWAL_ACCOUNT is not configured")?;
         let wallet = sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE user_id = ?")
             .bind(user_id)
             .fetch_one(pool)
             .await?;
 
-        // Send XLM from wallet (mock withdrawal)
-        let tx_hash = self.stellar_service.send_payment(&wallet.stellar_secret_key, "WITHDRAWAL_ACCOUNT", xlm_amount, "XLM").await?;
-        
         // Convert XLM to KES for M-Pesa
         let kes_amount = xlm_amount * 120.0;
-        
-        // Record withdrawal transaction
+
+        // Record the withdrawal as pending; the Stellar submission runs in the
+        // background and completes (or fails) the transaction
         let withdrawal_id = Uuid::new_v4().to_string();
+        let mut db_tx = pool.begin().await?;
         sqlx::query(
-            "INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, stellar_tx_hash, status) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            "INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, status) VALUES (?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&withdrawal_id)
         .bind(user_id)
@@ -95,41 +117,37 @@ impl WalletService {
         .bind(xlm_amount)
         .bind("XLM")
         .bind("KES")
-        .bind(&tx_hash)
-        .bind("completed")
-        .execute(pool)
+        .bind("pending")
+        .execute(&mut *db_tx)
         .await?;
 
-        println!("💸 Withdrawal: {} XLM → {} KES to {}", xlm_amount, kes_amount, mpesa_number);
-        Ok(tx_hash)
+        Outbox::enqueue(&mut *db_tx, &JobPayload::StellarPayment {
+            transaction_id: withdrawal_id.clone(),
+            user_id: wallet.user_id.clone(),
+            destination,
+            amount: xlm_amount,
+            currency: "XLM".to_string(),
+        }).await?;
+        db_tx.commit().await?;
+
+        println!("💸 Withdrawal queued: {} XLM → {} KES to {}", xlm_amount, kes_amount, mpesa_number);
+        Ok(withdrawal_id)
     }
 
+    /// Debits the sender before the payment goes out, so two transfers can't
+    /// spend the same balance, and credits the debit back if it fails.
     pub async fn transfer_to_wallet(&self, pool: &SqlitePool, from_user_id: &str, to_wallet_id: &str, xlm_amount: f64) -> Result<String, Box<dyn std::error::Error>> {
         let from_wallet = sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE user_id = ?")
             .bind(from_user_id)
             .fetch_one(pool)
             .await?;
 
-        // Check if sender has sufficient balance
-        let current_balance = self.get_wallet_balance(pool, from_user_id).await?;
-        if current_balance < xlm_amount {
-            return Err("Insufficient balance".into());
-        }
-
-        // Send XLM using Stellar SDK
-        let tx_hash = self.stellar_sdk.send_xlm_payment(
-            &from_wallet.stellar_secret_key, 
-            to_wallet_id, 
-            xlm_amount
-        ).await?;
-        
-        // Update balances in database (simulate balance changes)
-        self.update_balance_after_transfer(pool, from_user_id, to_wallet_id, xlm_amount).await?;
-        
-        // Record transfer transaction
+        // Record the transfer and book the debit together, so an admin
+        // reversal can undo exactly what was applied
         let transfer_id = Uuid::new_v4().to_string();
+        let mut db_tx = pool.begin().await?;
         sqlx::query(
-            "INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, stellar_tx_hash, status) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+            "INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, status) VALUES (?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(&transfer_id)
         .bind(from_user_id)
@@ -137,47 +155,67 @@ impl WalletService {
         .bind(xlm_amount)
         .bind("XLM")
         .bind("XLM")
-        .bind(&tx_hash)
-        .bind("completed")
-        .execute(pool)
+        .bind("submitting")
+        .execute(&mut *db_tx)
         .await?;
 
-        println!("🔄 Transfer: {} XLM from {} to {} (Hash: {})", xlm_amount, from_user_id, to_wallet_id, tx_hash);
-        Ok(tx_hash)
-    }
-    
-    async fn update_balance_after_transfer(&self, pool: &SqlitePool, from_user_id: &str, to_wallet_id: &str, amount: f64) -> Result<(), Box<dyn std::error::Error>> {
-        // Update sender's balance (decrease)
-        let sender_balance = self.get_wallet_balance(pool, from_user_id).await?;
-        let new_sender_balance = sender_balance - amount;
-        
-        sqlx::query("UPDATE wallets SET balance = ? WHERE user_id = ?")
-            .bind(new_sender_balance)
-            .bind(from_user_id)
-            .execute(pool)
+        let sender_balance = Ledger::post(&mut db_tx, &transfer_id, from_user_id, -xlm_amount)
+            .await?
+            .ok_or("Insufficient balance")?;
+        db_tx.commit().await?;
+
+        // Send XLM using Stellar SDK
+        let tx_hash = match self.stellar_sdk.send_xlm_payment(
+            &from_wallet.stellar_secret_key,
+            to_wallet_id,
+            xlm_amount
+        ).await.map_err(|e| e.to_string()) {
+            Ok(tx_hash) => tx_hash,
+            Err(e) => {
+                Self::reverse_transfer(pool, &transfer_id).await?;
+                return Err(e.into());
+            }
+        };
+
+        let mut db_tx = pool.begin().await?;
+        sqlx::query("UPDATE transactions SET stellar_tx_hash = ?, status = 'completed', completed_at = CURRENT_TIMESTAMP WHERE id = ?")
+            .bind(&tx_hash)
+            .bind(&transfer_id)
+            .execute(&mut *db_tx)
             .await?;
-            
-        // Find recipient by wallet ID and update their balance (increase)
-        if let Some(recipient) = sqlx::query_as::<_, Wallet>("SELECT * FROM wallets WHERE stellar_public_key = ?")
+
+        // Find recipient by wallet ID and credit them
+        let recipient: Option<String> = sqlx::query_scalar("SELECT user_id FROM wallets WHERE stellar_public_key = ?")
             .bind(to_wallet_id)
-            .fetch_optional(pool)
-            .await? {
-            
-            let recipient_balance = recipient.balance;
-            let new_recipient_balance = recipient_balance + amount;
-            
-            sqlx::query("UPDATE wallets SET balance = ? WHERE user_id = ?")
-                .bind(new_recipient_balance)
-                .bind(&recipient.user_id)
-                .execute(pool)
-                .await?;
-                
+            .fetch_optional(&mut *db_tx)
+            .await?;
+        if let Some(recipient) = recipient {
+            let recipient_balance = Ledger::post(&mut db_tx, &transfer_id, &recipient, xlm_amount).await?.unwrap_or_default();
             println!("💰 Transfer complete:");
-            println!("   Sender {}: {} XLM → {} XLM", from_user_id, sender_balance, new_sender_balance);
-            println!("   Recipient {}: {} XLM → {} XLM", recipient.user_id, recipient_balance, new_recipient_balance);
+            println!("   Sender {}: {} XLM", from_user_id, sender_balance);
+            println!("   Recipient {}: {} XLM", recipient, recipient_balance);
         }
-        
-        Ok(())
+        db_tx.commit().await?;
+
+        println!("🔄 Transfer: {} XLM from {} to {} (Hash: {})", xlm_amount, from_user_id, to_wallet_id, tx_hash);
+        Ok(tx_hash)
+    }
+
+    /// Fails a transfer whose payment didn't go out and credits back what
+    /// was booked against it.
+    async fn reverse_transfer(pool: &SqlitePool, transfer_id: &str) -> Result<(), sqlx::Error> {
+        let mut db_tx = pool.begin().await?;
+        let failed = sqlx::query("UPDATE transactions SET status = 'failed' WHERE id = ? AND status = 'submitting'")
+            .bind(transfer_id)
+            .execute(&mut *db_tx)
+            .await?
+            .rows_affected();
+        if failed == 1 {
+            for (user_id, amount) in Ledger::entries(&mut db_tx, transfer_id).await? {
+                Ledger::post(&mut db_tx, transfer_id, &user_id, -amount).await?;
+            }
+        }
+        db_tx.commit().await
     }
 
     pub fn convert_xlm_to_kes(&self, xlm_amount: f64) -> f64 {
@@ -187,4 +225,73 @@ impl WalletService {
     pub fn convert_kes_to_xlm(&self, kes_amount: f64) -> f64 {
         kes_amount / 120.0
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{insert_user, memory_pool};
+
+    async fn wallet(pool: &SqlitePool, user_id: &str, public_key: &str, balance: f64) {
+        insert_user(pool, user_id, &format!("{}@example.com", user_id)).await;
+        sqlx::query("INSERT INTO wallets (id, user_id, stellar_public_key, stellar_secret_key, balance) VALUES (?, ?, ?, 'SSECRETKEY', ?)")
+            .bind(format!("w-{}", user_id))
+            .bind(user_id)
+            .bind(public_key)
+            .bind(balance)
+            .execute(pool)
+            .await
+            .unwrap();
+    }
+
+    async fn balance(pool: &SqlitePool, user_id: &str) -> f64 {
+        WalletService::new().get_wallet_balance(pool, user_id).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_transfer_moves_the_balance() {
+        let pool = memory_pool().await;
+        wallet(&pool, "u1", "GSENDER", 50.0).await;
+        wallet(&pool, "u2", "GRECIPIENT", 5.0).await;
+
+        let tx_hash = WalletService::new().transfer_to_wallet(&pool, "u1", "GRECIPIENT", 20.0).await.unwrap();
+        assert_eq!(balance(&pool, "u1").await, 30.0);
+        assert_eq!(balance(&pool, "u2").await, 25.0);
+        let (status, hash): (String, Option<String>) =
+            sqlx::query_as("SELECT status, stellar_tx_hash FROM transactions").fetch_one(&pool).await.unwrap();
+        assert_eq!((status.as_str(), hash), ("completed", Some(tx_hash)));
+    }
+
+    #[tokio::test]
+    async fn test_transfer_beyond_the_balance_is_refused() {
+        let pool = memory_pool().await;
+        wallet(&pool, "u1", "GSENDER", 10.0).await;
+
+        assert!(WalletService::new().transfer_to_wallet(&pool, "u1", "GRECIPIENT", 20.0).await.is_err());
+        assert_eq!(balance(&pool, "u1").await, 10.0);
+        let transactions: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM transactions").fetch_one(&pool).await.unwrap();
+        assert_eq!(transactions, 0);
+    }
+
+    #[tokio::test]
+    async fn test_failed_transfer_is_credited_back() {
+        let pool = memory_pool().await;
+        wallet(&pool, "u1", "GSENDER", 50.0).await;
+        sqlx::query("INSERT INTO transactions (id, user_id, recipient_email, amount, currency, target_currency, status) VALUES ('t1', 'u1', 'GRECIPIENT', 20, 'XLM', 'XLM', 'submitting')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        let mut conn = pool.acquire().await.unwrap();
+        Ledger::post(&mut conn, "t1", "u1", -20.0).await.unwrap().unwrap();
+        drop(conn);
+
+        WalletService::reverse_transfer(&pool, "t1").await.unwrap();
+        assert_eq!(balance(&pool, "u1").await, 50.0);
+        let status: String = sqlx::query_scalar("SELECT status FROM transactions").fetch_one(&pool).await.unwrap();
+        assert_eq!(status, "failed");
+
+        // Only once
+        WalletService::reverse_transfer(&pool, "t1").await.unwrap();
+        assert_eq!(balance(&pool, "u1").await, 50.0);
+    }
+}
@@ -0,0 +1,700 @@
+use chrono::Utc;
+use serde_json::{json, Value};
+use sqlx::{SqliteConnection, SqlitePool};
+use std::env;
+use std::time::Duration;
+use thiserror::Error;
+use uuid::Uuid;
+
+use crate::models::{
+    CreateWebhookEndpointRequest, WebhookDelivery, WebhookDeliveryListQuery, WebhookEndpoint, WebhookEndpointWithSecret,
+};
+use crate::services::outbound::{allowed_hosts, check_url, webhook_client, OutboundUrlError};
+use crate::services::signing::webhook_signature_header;
+use crate::services::{DomainEvent, JobPayload, Outbox};
+
+/// Events sent to a user's endpoints.
+pub const USER_WEBHOOK_EVENTS: &[&str] = &["transaction.completed", "transaction.failed", "deposit.credited"];
+
+/// Events sent to a merchant's endpoints.
+pub const MERCHANT_WEBHOOK_EVENTS: &[&str] = &["invoice.underpaid", "invoice.paid", "invoice.overpaid", "invoice.expired"];
+
+#[derive(Error, Debug)]
+pub enum WebhookError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Unknown event type: {0}")]
+    UnknownEventType(String),
+    #[error("Register a merchant first")]
+    NoMerchant,
+    #[error("At most {0} webhook endpoints")]
+    TooManyEndpoints(i64),
+    #[error("Unsafe webhook URL: {0}")]
+    UnsafeUrl(#[from] OutboundUrlError),
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+}
+
+/// Whose endpoints an event goes to.
+pub enum WebhookOwner<'a> {
+    User(&'a str),
+    Merchant(&'a str),
+}
+
+/// Partner webhooks. An event is published by writing one delivery per
+/// subscribed endpoint, each with a `WebhookDelivery` job, in the caller's
+/// transaction; the job posts the body signed with the endpoint's secret and
+/// is retried with backoff until the endpoint answers 2xx. Endpoint URLs
+/// must be https and resolve to public addresses, unless the host is in
+/// `WEBHOOK_ALLOWED_HOSTS`; they are checked again on every delivery.
+pub struct WebhookService;
+
+impl WebhookService {
+    pub async fn register(
+        pool: &SqlitePool,
+        user_id: &str,
+        merchant_id: Option<&str>,
+        request: &CreateWebhookEndpointRequest,
+    ) -> Result<WebhookEndpointWithSecret, WebhookError> {
+        check_url(&request.url, &allowed_hosts()).await?;
+        let known = if merchant_id.is_some() { MERCHANT_WEBHOOK_EVENTS } else { USER_WEBHOOK_EVENTS };
+        let mut event_types: Vec<&str> = Vec::new();
+        for event_type in &request.event_types {
+            let event_type = event_type.trim();
+            if !known.contains(&event_type) {
+                return Err(WebhookError::UnknownEventType(event_type.to_string()));
+            }
+            if !event_types.contains(&event_type) {
+                event_types.push(event_type);
+            }
+        }
+
+        let max_endpoints: i64 = env::var("WEBHOOK_MAX_ENDPOINTS")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(10);
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM webhook_endpoints WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await?;
+        if count >= max_endpoints {
+            return Err(WebhookError::TooManyEndpoints(max_endpoints));
+        }
+
+        let secret = request
+            .secret
+            .clone()
+            .unwrap_or_else(|| format!("whsec_{}", hex::encode(rand::random::<[u8; 24]>())));
+        let now = Utc::now();
+        let endpoint = sqlx::query_as::<_, WebhookEndpoint>(
+            r#"
+            INSERT INTO webhook_endpoints (id, user_id, merchant_id, url, secret, event_types, description, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(user_id)
+        .bind(merchant_id)
+        .bind(&request.url)
+        .bind(&secret)
+        .bind(event_types.join(","))
+        .bind(&request.description)
+        .bind(now)
+        .bind(now)
+        .fetch_one(pool)
+        .await?;
+
+        println!("🪝 Webhook endpoint {} registered for user {}", endpoint.id, user_id);
+        Ok(WebhookEndpointWithSecret { endpoint, secret })
+    }
+
+    pub async fn list(pool: &SqlitePool, user_id: &str) -> Result<Vec<WebhookEndpoint>, WebhookError> {
+        Ok(sqlx::query_as::<_, WebhookEndpoint>(
+            "SELECT * FROM webhook_endpoints WHERE user_id = ? ORDER BY created_at",
+        )
+        .bind(user_id)
+        .fetch_all(pool)
+        .await?)
+    }
+
+    /// Removes the endpoint and its delivery log. Deliveries still queued
+    /// are dropped.
+    pub async fn delete(pool: &SqlitePool, user_id: &str, id: &str) -> Result<WebhookEndpoint, WebhookError> {
+        let endpoint = Self::endpoint(pool, user_id, id).await?;
+        let mut db_tx = pool.begin().await?;
+        sqlx::query("DELETE FROM webhook_deliveries WHERE endpoint_id = ?")
+            .bind(&endpoint.id)
+            .execute(&mut *db_tx)
+            .await?;
+        sqlx::query("DELETE FROM webhook_endpoints WHERE id = ?")
+            .bind(&endpoint.id)
+            .execute(&mut *db_tx)
+            .await?;
+        db_tx.commit().await?;
+        Ok(endpoint)
+    }
+
+    /// The endpoint's delivery log, newest first.
+    pub async fn deliveries(
+        pool: &SqlitePool,
+        user_id: &str,
+        endpoint_id: &str,
+        query: &WebhookDeliveryListQuery,
+    ) -> Result<Vec<WebhookDelivery>, WebhookError> {
+        let endpoint = Self::endpoint(pool, user_id, endpoint_id).await?;
+        Ok(sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            SELECT * FROM webhook_deliveries
+            WHERE endpoint_id = ? AND (? IS NULL OR status = ?)
+            ORDER BY created_at DESC
+            LIMIT ?
+            "#,
+        )
+        .bind(&endpoint.id)
+        .bind(&query.status)
+        .bind(&query.status)
+        .bind(query.limit.unwrap_or(50).clamp(1, 500))
+        .fetch_all(pool)
+        .await?)
+    }
+
+    /// Sends a delivery's body again as a new delivery, whatever became of
+    /// the original. The event keeps its id so the receiver can tell.
+    pub async fn replay(pool: &SqlitePool, user_id: &str, delivery_id: &str) -> Result<WebhookDelivery, WebhookError> {
+        let original = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            SELECT d.* FROM webhook_deliveries d
+            JOIN webhook_endpoints e ON e.id = d.endpoint_id
+            WHERE d.id = ? AND e.user_id = ?
+            "#,
+        )
+        .bind(delivery_id)
+        .bind(user_id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or(WebhookError::NotFound)?;
+
+        let now = Utc::now();
+        let mut db_tx = pool.begin().await?;
+        let replay = sqlx::query_as::<_, WebhookDelivery>(
+            r#"
+            INSERT INTO webhook_deliveries (id, endpoint_id, event_id, event_type, payload, replay_of, created_at, updated_at)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+            RETURNING *
+            "#,
+        )
+        .bind(Uuid::new_v4().to_string())
+        .bind(&original.endpoint_id)
+        .bind(&original.event_id)
+        .bind(&original.event_type)
+        .bind(&original.payload)
+        .bind(&original.id)
+        .bind(now)
+        .bind(now)
+        .fetch_one(&mut *db_tx)
+        .await?;
+        Outbox::enqueue(&mut *db_tx, &JobPayload::WebhookDelivery { delivery_id: replay.id.clone() }).await?;
+        db_tx.commit().await?;
+        Ok(replay)
+    }
+
+    /// Queues `body` for every endpoint of `owner` that subscribes to its
+    /// `type`. An event already published to an endpoint, as told by its
+    /// `id`, is not queued again. Returns the number of deliveries queued.
+    pub async fn publish(conn: &mut SqliteConnection, owner: WebhookOwner<'_>, body: &Value) -> Result<usize, sqlx::Error> {
+        let event_id = body["id"].as_str().unwrap_or_default();
+        let event_type = body["type"].as_str().unwrap_or_default();
+        let endpoints = match owner {
+            WebhookOwner::User(user_id) => {
+                sqlx::query_as::<_, WebhookEndpoint>(
+                    "SELECT * FROM webhook_endpoints WHERE user_id = ? AND merchant_id IS NULL",
+                )
+                .bind(user_id)
+                .fetch_all(&mut *conn)
+                .await?
+            }
+            WebhookOwner::Merchant(merchant_id) => {
+                sqlx::query_as::<_, WebhookEndpoint>("SELECT * FROM webhook_endpoints WHERE merchant_id = ?")
+                    .bind(merchant_id)
+                    .fetch_all(&mut *conn)
+                    .await?
+            }
+        };
+
+        let now = Utc::now();
+        let mut queued = 0;
+        for endpoint in endpoints.iter().filter(|e| subscribes(e, event_type)) {
+            let delivery_id = Uuid::new_v4().to_string();
+            let inserted = sqlx::query(
+                r#"
+                INSERT OR IGNORE INTO webhook_deliveries (id, endpoint_id, event_id, event_type, payload, created_at, updated_at)
+                VALUES (?, ?, ?, ?, ?, ?, ?)
+                "#,
+            )
+            .bind(&delivery_id)
+            .bind(&endpoint.id)
+            .bind(event_id)
+            .bind(event_type)
+            .bind(body.to_string())
+            .bind(now)
+            .bind(now)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+            if inserted == 1 {
+                Outbox::enqueue(&mut *conn, &JobPayload::WebhookDelivery { delivery_id }).await?;
+                queued += 1;
+            }
+        }
+        Ok(queued)
+    }
+
+    /// Publishes the partner event for a domain event, if there is one.
+    /// `event_id` must be the same each time the same event is handled.
+    pub async fn publish_event(pool: &SqlitePool, event_id: &str, event: &DomainEvent) -> Result<(), sqlx::Error> {
+        let Some((user_id, event_type, data)) = partner_event(event) else {
+            return Ok(());
+        };
+        let mut db_tx = pool.begin().await?;
+        Self::publish(&mut db_tx, WebhookOwner::User(user_id), &event_body(event_id, event_type, data)).await?;
+        db_tx.commit().await
+    }
+
+    /// Posts a delivery to its endpoint and records the outcome. Any
+    /// response other than 2xx is an error so the job is retried.
+    pub async fn deliver(pool: &SqlitePool, delivery_id: &str) -> Result<(), String> {
+        let delivery = sqlx::query_as::<_, WebhookDelivery>("SELECT * FROM webhook_deliveries WHERE id = ?")
+            .bind(delivery_id)
+            .fetch_optional(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+        // Gone with its endpoint, or already delivered by an earlier attempt
+        let Some(delivery) = delivery.filter(|d| d.status == "pending") else {
+            return Ok(());
+        };
+        let endpoint = sqlx::query_as::<_, WebhookEndpoint>("SELECT * FROM webhook_endpoints WHERE id = ?")
+            .bind(&delivery.endpoint_id)
+            .fetch_one(pool)
+            .await
+            .map_err(|e| e.to_string())?;
+
+        let timestamp = Utc::now().timestamp();
+        // A host that has since been pointed at a private address fails the
+        // attempt like an unreachable one
+        let response = match webhook_client(&endpoint.url, Duration::from_secs(10)).await {
+            Ok(client) => client
+                .post(&endpoint.url)
+                .header("Content-Type", "application/json")
+                .header("X-NovaPay-Event", &delivery.event_type)
+                .header("X-NovaPay-Delivery", &delivery.id)
+                .header("X-NovaPay-Signature", webhook_signature_header(&endpoint.secret, timestamp, &delivery.payload))
+                .body(delivery.payload.clone())
+                .send()
+                .await
+                .map_err(|e| e.to_string()),
+            Err(e) => Err(e.to_string()),
+        };
+        let (response_status, error) = match response {
+            Ok(response) if response.status().is_success() => (Some(response.status().as_u16() as i64), None),
+            Ok(response) => (
+                Some(response.status().as_u16() as i64),
+                Some(format!("endpoint returned {}", response.status())),
+            ),
+            Err(e) => (None, Some(e)),
+        };
+
+        let now = Utc::now();
+        sqlx::query(
+            r#"
+            UPDATE webhook_deliveries
+            SET attempts = attempts + 1, response_status = ?, last_error = ?,
+                status = CASE WHEN ? IS NULL THEN 'delivered' ELSE status END,
+                delivered_at = CASE WHEN ? IS NULL THEN ? ELSE delivered_at END,
+                updated_at = ?
+            WHERE id = ?
+            "#,
+        )
+        .bind(response_status)
+        .bind(&error)
+        .bind(&error)
+        .bind(&error)
+        .bind(now)
+        .bind(now)
+        .bind(&delivery.id)
+        .execute(pool)
+        .await
+        .map_err(|e| e.to_string())?;
+
+        match error {
+            Some(error) => Err(error),
+            None => {
+                println!("🪝 Delivered {} {} to endpoint {}", delivery.event_type, delivery.event_id, endpoint.id);
+                Ok(())
+            }
+        }
+    }
+
+    /// Marks a delivery failed once its job has used all its attempts.
+    pub async fn abandon(pool: &SqlitePool, delivery_id: &str, error: &str) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "UPDATE webhook_deliveries SET status = 'failed', last_error = ?, updated_at = ? WHERE id = ? AND status = 'pending'",
+        )
+        .bind(error)
+        .bind(Utc::now())
+        .bind(delivery_id)
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn endpoint(pool: &SqlitePool, user_id: &str, id: &str) -> Result<WebhookEndpoint, WebhookError> {
+        sqlx::query_as::<_, WebhookEndpoint>("SELECT * FROM webhook_endpoints WHERE id = ? AND user_id = ?")
+            .bind(id)
+            .bind(user_id)
+            .fetch_optional(pool)
+            .await?
+            .ok_or(WebhookError::NotFound)
+    }
+}
+
+/// The JSON body posted for an event.
+pub fn event_body(event_id: &str, event_type: &str, data: Value) -> Value {
+    json!({
+        "id": event_id,
+        "type": event_type,
+        "created_at": Utc::now(),
+        "data": data,
+    })
+}
+
+fn subscribes(endpoint: &WebhookEndpoint, event_type: &str) -> bool {
+    endpoint.event_types.is_empty() || endpoint.event_types.split(',').any(|t| t == event_type)
+}
+
+/// The user an event is about, with the type and data partners see.
+fn partner_event(event: &DomainEvent) -> Option<(&str, &'static str, Value)> {
+    match event {
+        DomainEvent::TransferCompleted { transaction_id, sender_id, recipient, amount, currency, tx_hash } => Some((
+            sender_id,
+            "transaction.completed",
+            json!({
+                "transaction_id": transaction_id,
+                "recipient": recipient,
+                "amount": amount,
+                "currency": currency,
+                "status": "completed",
+                "tx_hash": tx_hash,
+            }),
+        )),
+        DomainEvent::TransferFailed { transaction_id, sender_id, recipient, amount, currency, reason } => Some((
+            sender_id,
+            "transaction.failed",
+            json!({
+                "transaction_id": transaction_id,
+                "recipient": recipient,
+                "amount": amount,
+                "currency": currency,
+                "status": "failed",
+                "reason": reason,
+            }),
+        )),
+        DomainEvent::DepositCredited { user_id, amount, currency, balance, reference } => Some((
+            user_id,
+            "deposit.credited",
+            json!({
+                "amount": amount,
+                "currency": currency,
+                "balance": balance,
+                "reference": reference,
+            }),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Bytes, extract::State, http::HeaderMap, http::StatusCode, routing::post, Router};
+    use sqlx::sqlite::SqlitePoolOptions;
+    use std::sync::{Arc, Mutex};
+
+    use crate::services::signing::verify_hmac_sha256_hex;
+
+    const SECRET: &str = "whsec_test_secret_0123456789";
+
+    /// Requests received by the stub, and how many more to fail with 500.
+    #[derive(Clone, Default)]
+    struct Receiver {
+        received: Arc<Mutex<Vec<(HeaderMap, String)>>>,
+        failures: Arc<Mutex<usize>>,
+    }
+
+    async fn receive(State(receiver): State<Receiver>, headers: HeaderMap, body: Bytes) -> StatusCode {
+        receiver
+            .received
+            .lock()
+            .unwrap()
+            .push((headers, String::from_utf8_lossy(&body).to_string()));
+        let mut failures = receiver.failures.lock().unwrap();
+        if *failures > 0 {
+            *failures -= 1;
+            return StatusCode::INTERNAL_SERVER_ERROR;
+        }
+        StatusCode::OK
+    }
+
+    /// Starts a receiver on a free local port and returns its URL.
+    async fn receiver_stub(receiver: Receiver) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hooks", listener.local_addr().unwrap());
+        let app = Router::new().route("/hooks", post(receive)).with_state(receiver);
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        url
+    }
+
+    async fn setup() -> SqlitePool {
+        // The stub receivers listen on loopback
+        env::set_var("WEBHOOK_ALLOWED_HOSTS", "127.0.0.1");
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect("sqlite::memory:")
+            .await
+            .unwrap();
+        sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+        sqlx::query("INSERT INTO users (id, email, password_hash, full_name) VALUES ('user-1', 'partner@example.com', 'x', 'Partner')")
+            .execute(&pool)
+            .await
+            .unwrap();
+        pool
+    }
+
+    async fn register(pool: &SqlitePool, url: &str, event_types: &[&str]) -> WebhookEndpoint {
+        let request = CreateWebhookEndpointRequest {
+            url: url.to_string(),
+            event_types: event_types.iter().map(|t| t.to_string()).collect(),
+            secret: Some(SECRET.to_string()),
+            description: None,
+            merchant: false,
+        };
+        WebhookService::register(pool, "user-1", None, &request).await.unwrap().endpoint
+    }
+
+    fn completed() -> DomainEvent {
+        DomainEvent::TransferCompleted {
+            transaction_id: "tx-1".to_string(),
+            sender_id: "user-1".to_string(),
+            recipient: "friend@example.com".to_string(),
+            amount: 25.0,
+            currency: "XLM".to_string(),
+            tx_hash: "abc123".to_string(),
+        }
+    }
+
+    /// The deliveries queued for the worker, oldest first.
+    async fn queued(pool: &SqlitePool) -> Vec<String> {
+        let jobs: Vec<(String,)> = sqlx::query_as("SELECT payload FROM jobs WHERE kind = 'webhook_delivery' ORDER BY rowid")
+            .fetch_all(pool)
+            .await
+            .unwrap();
+        jobs.into_iter()
+            .map(|(payload,)| match serde_json::from_str(&payload).unwrap() {
+                JobPayload::WebhookDelivery { delivery_id } => delivery_id,
+                other => panic!("unexpected job {:?}", other),
+            })
+            .collect()
+    }
+
+    async fn delivery(pool: &SqlitePool, id: &str) -> WebhookDelivery {
+        sqlx::query_as::<_, WebhookDelivery>("SELECT * FROM webhook_deliveries WHERE id = ?")
+            .bind(id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_delivers_signed_event() {
+        let pool = setup().await;
+        let receiver = Receiver::default();
+        let url = receiver_stub(receiver.clone()).await;
+        register(&pool, &url, &[]).await;
+
+        WebhookService::publish_event(&pool, "evt_1", &completed()).await.unwrap();
+        let queued = queued(&pool).await;
+        assert_eq!(queued.len(), 1);
+        WebhookService::deliver(&pool, &queued[0]).await.unwrap();
+
+        let received = receiver.received.lock().unwrap().clone();
+        assert_eq!(received.len(), 1);
+        let (headers, body) = &received[0];
+        assert_eq!(headers["x-novapay-event"], "transaction.completed");
+        assert_eq!(headers["x-novapay-delivery"], queued[0].as_str());
+        let signature = headers["x-novapay-signature"].to_str().unwrap();
+        let (timestamp, v1) = signature.split_once(',').unwrap();
+        let timestamp = timestamp.strip_prefix("t=").unwrap();
+        let v1 = v1.strip_prefix("v1=").unwrap();
+        assert!(verify_hmac_sha256_hex(SECRET.as_bytes(), format!("{}.{}", timestamp, body).as_bytes(), v1));
+
+        let body: Value = serde_json::from_str(body).unwrap();
+        assert_eq!(body["id"], "evt_1");
+        assert_eq!(body["type"], "transaction.completed");
+        assert_eq!(body["data"]["transaction_id"], "tx-1");
+        assert!(body["created_at"].is_string());
+
+        let delivered = delivery(&pool, &queued[0]).await;
+        assert_eq!(delivered.status, "delivered");
+        assert_eq!(delivered.attempts, 1);
+        assert_eq!(delivered.response_status, Some(200));
+        assert!(delivered.delivered_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_failed_attempt_is_retried_with_same_body() {
+        let pool = setup().await;
+        let receiver = Receiver::default();
+        *receiver.failures.lock().unwrap() = 1;
+        let url = receiver_stub(receiver.clone()).await;
+        register(&pool, &url, &["transaction.completed"]).await;
+
+        WebhookService::publish_event(&pool, "evt_1", &completed()).await.unwrap();
+        let id = queued(&pool).await.remove(0);
+        let error = WebhookService::deliver(&pool, &id).await.unwrap_err();
+        assert!(error.contains("500"));
+        let failed = delivery(&pool, &id).await;
+        assert_eq!(failed.status, "pending");
+        assert_eq!(failed.response_status, Some(500));
+
+        WebhookService::deliver(&pool, &id).await.unwrap();
+        let delivered = delivery(&pool, &id).await;
+        assert_eq!(delivered.status, "delivered");
+        assert_eq!(delivered.attempts, 2);
+        assert_eq!(delivered.last_error, None);
+
+        // A delivered event is not posted again if its job runs once more
+        WebhookService::deliver(&pool, &id).await.unwrap();
+        let received = receiver.received.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].1, received[1].1);
+    }
+
+    #[tokio::test]
+    async fn test_filters_and_deduplicates_events() {
+        let pool = setup().await;
+        let receiver = Receiver::default();
+        let url = receiver_stub(receiver.clone()).await;
+        register(&pool, &url, &["deposit.credited"]).await;
+        register(&pool, &url, &[]).await;
+
+        let deposit = DomainEvent::DepositCredited {
+            user_id: "user-1".to_string(),
+            amount: 10.0,
+            currency: "XLM".to_string(),
+            balance: 35.0,
+            reference: "dep-1".to_string(),
+        };
+        WebhookService::publish_event(&pool, "evt_1", &completed()).await.unwrap();
+        WebhookService::publish_event(&pool, "evt_2", &deposit).await.unwrap();
+        // Handling the same event again queues nothing new
+        WebhookService::publish_event(&pool, "evt_2", &deposit).await.unwrap();
+        let login = DomainEvent::LoginFromNewDevice {
+            user_id: "user-1".to_string(),
+            device: "Firefox".to_string(),
+            ip_address: None,
+        };
+        WebhookService::publish_event(&pool, "evt_3", &login).await.unwrap();
+
+        let queued = queued(&pool).await;
+        assert_eq!(queued.len(), 3);
+        let mut types = Vec::new();
+        for id in &queued {
+            types.push(delivery(&pool, id).await.event_type);
+        }
+        assert_eq!(types, ["transaction.completed", "deposit.credited", "deposit.credited"]);
+
+        let request = CreateWebhookEndpointRequest {
+            url,
+            event_types: vec!["invoice.paid".to_string()],
+            secret: None,
+            description: None,
+            merchant: false,
+        };
+        assert!(matches!(
+            WebhookService::register(&pool, "user-1", None, &request).await,
+            Err(WebhookError::UnknownEventType(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_replay_posts_event_again() {
+        let pool = setup().await;
+        let receiver = Receiver::default();
+        let url = receiver_stub(receiver.clone()).await;
+        let endpoint = register(&pool, &url, &[]).await;
+
+        WebhookService::publish_event(&pool, "evt_1", &completed()).await.unwrap();
+        let original = queued(&pool).await.remove(0);
+        WebhookService::deliver(&pool, &original).await.unwrap();
+
+        assert!(matches!(
+            WebhookService::replay(&pool, "someone-else", &original).await,
+            Err(WebhookError::NotFound)
+        ));
+        let replay = WebhookService::replay(&pool, "user-1", &original).await.unwrap();
+        assert_eq!(replay.replay_of.as_deref(), Some(original.as_str()));
+        assert_eq!(replay.event_id, "evt_1");
+        assert_eq!(replay.status, "pending");
+        assert_eq!(queued(&pool).await, [original.clone(), replay.id.clone()]);
+        WebhookService::deliver(&pool, &replay.id).await.unwrap();
+
+        let received = receiver.received.lock().unwrap().clone();
+        assert_eq!(received.len(), 2);
+        assert_eq!(received[0].1, received[1].1);
+        assert_eq!(received[1].0["x-novapay-delivery"], replay.id.as_str());
+
+        let query = WebhookDeliveryListQuery { status: Some("delivered".to_string()), limit: None };
+        let log = WebhookService::deliveries(&pool, "user-1", &endpoint.id, &query).await.unwrap();
+        assert_eq!(log.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_internal_urls_are_refused() {
+        let pool = setup().await;
+        for (url, expected) in [
+            ("http://partner.example/hooks", OutboundUrlError::NotHttps),
+            ("https://10.0.0.1/hooks", OutboundUrlError::PrivateAddress("10.0.0.1".parse().unwrap())),
+            ("https://169.254.169.254/latest", OutboundUrlError::PrivateAddress("169.254.169.254".parse().unwrap())),
+            ("https://[::1]/hooks", OutboundUrlError::PrivateAddress("::1".parse().unwrap())),
+        ] {
+            let request = CreateWebhookEndpointRequest {
+                url: url.to_string(),
+                event_types: Vec::new(),
+                secret: None,
+                description: None,
+                merchant: false,
+            };
+            match WebhookService::register(&pool, "user-1", None, &request).await {
+                Err(WebhookError::UnsafeUrl(error)) => assert_eq!(error, expected, "{}", url),
+                other => panic!("{} was accepted: {:?}", url, other.map(|e| e.endpoint.id)),
+            }
+        }
+
+        // An endpoint that reaches an internal address by the time it is
+        // delivered to is not posted to
+        let now = Utc::now();
+        sqlx::query("INSERT INTO webhook_endpoints (id, user_id, url, secret, created_at, updated_at) VALUES ('we-1', 'user-1', 'https://10.0.0.1/hooks', ?, ?, ?)")
+            .bind(SECRET)
+            .bind(now)
+            .bind(now)
+            .execute(&pool)
+            .await
+            .unwrap();
+        WebhookService::publish_event(&pool, "evt_1", &completed()).await.unwrap();
+        let id = queued(&pool).await.remove(0);
+        let error = WebhookService::deliver(&pool, &id).await.unwrap_err();
+        assert!(error.contains("private address"), "{}", error);
+        let failed = delivery(&pool, &id).await;
+        assert_eq!((failed.status.as_str(), failed.attempts, failed.response_status), ("pending", 1, None));
+    }
+}
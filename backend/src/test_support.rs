@@ -0,0 +1,50 @@
+//! Shared setup for unit tests: a migrated in-memory database and local HTTP
+//! stubs standing in for outside services.
+use axum::Router;
+use sqlx::sqlite::SqlitePoolOptions;
+use sqlx::SqlitePool;
+use tokio::sync::{Mutex, MutexGuard};
+
+use crate::services::KeyManager;
+
+/// JWT keys are process-wide, so tests that sign or verify tokens take turns.
+static JWT_KEYS: Mutex<()> = Mutex::const_new(());
+
+/// A fresh database with every migration applied. One connection, so every
+/// query sees the same in-memory database.
+pub async fn memory_pool() -> SqlitePool {
+    let pool = SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect("sqlite::memory:")
+        .await
+        .unwrap();
+    sqlx::migrate!("./migrations").run(&pool).await.unwrap();
+    pool
+}
+
+/// Serves `app` on a free local port and returns its base URL.
+pub async fn stub_server(app: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    url
+}
+
+/// A user with the given id and email, for rows that reference `users`.
+pub async fn insert_user(pool: &SqlitePool, id: &str, email: &str) {
+    sqlx::query("INSERT INTO users (id, email, password_hash, full_name) VALUES (?, ?, 'x', 'Test User')")
+        .bind(id)
+        .bind(email)
+        .execute(pool)
+        .await
+        .unwrap();
+}
+
+/// Loads `pool`'s JWT keys, creating the first one, and holds them as the
+/// process-wide set until the guard is dropped.
+pub async fn jwt_keys(pool: &SqlitePool) -> MutexGuard<'static, ()> {
+    let guard = JWT_KEYS.lock().await;
+    std::env::set_var("WALLET_ENCRYPTION_KEY", "000102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f");
+    KeyManager::init(pool).await.unwrap();
+    guard
+}
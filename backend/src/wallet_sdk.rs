@@ -1,7 +1,18 @@
+use bip39::Mnemonic;
+use ed25519_dalek::{Signer, SigningKey};
+use hmac::{Hmac, Mac};
 use serde::{Deserialize, Serialize};
+use sha2::Sha512;
 use std::collections::HashMap;
+use std::env;
 use thiserror::Error;
-use rand::rngs::OsRng;
+
+use crate::models::StellarOperationRequest;
+use crate::services::stellar_tx::{StellarNetwork, StellarTxBuilder, StellarTxError};
+
+/// Hardened SLIP-0010 indexes: SEP-0005 accounts live at m/44'/148'/n'.
+const HARDENED: u32 = 0x8000_0000;
+const SEP5_PATH: [u32; 2] = [44, 148];
 
 #[derive(Error, Debug)]
 pub enum WalletError {
@@ -13,6 +24,8 @@ pub enum WalletError {
     InsufficientBalance,
     #[error("Transaction failed: {0}")]
     TransactionFailed(String),
+    #[error("Invalid mnemonic: {0}")]
+    InvalidMnemonic(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -49,8 +62,12 @@ impl NovaPayWallet {
             return Err(WalletError::InvalidKeypair("Invalid secret key format".to_string()));
         }
         
-        // Generate corresponding public key (simplified)
-        let public_key = format!("G{}", &secret_key[1..]);
+        // Real seeds map to their account; older placeholder keys keep the
+        // simplified mapping
+        let public_key = match stellar_strkey::ed25519::PrivateKey::from_string(secret_key) {
+            Ok(seed) => public_key_for(&seed.0),
+            Err(_) => format!("G{}", &secret_key[1..]),
+        };
         
         Ok(Self {
             public_key,
@@ -60,21 +77,49 @@ impl NovaPayWallet {
     }
 
     pub fn generate() -> (String, String) {
-        let mut rng = OsRng;
-        let random_bytes: [u8; 32] = rand::random();
-        
-        // Simplified Stellar key format
-        let hex_str = hex::encode(&random_bytes);
-        let public_key = format!("G{}", &hex_str[..55]);
-        let secret_key = format!("S{}", &hex_str[..55]);
-        
-        (public_key, secret_key)
+        let seed: [u8; 32] = rand::random();
+        (public_key_for(&seed), secret_key_for(&seed))
+    }
+
+    /// A new BIP-39 backup phrase of 12 or 24 English words.
+    pub fn generate_mnemonic(word_count: usize) -> Result<String, WalletError> {
+        let entropy = match word_count {
+            12 => rand::random::<[u8; 16]>().to_vec(),
+            24 => rand::random::<[u8; 32]>().to_vec(),
+            _ => return Err(WalletError::InvalidMnemonic("word count must be 12 or 24".to_string())),
+        };
+        Mnemonic::from_entropy(&entropy)
+            .map(|mnemonic| mnemonic.to_string())
+            .map_err(|e| WalletError::InvalidMnemonic(e.to_string()))
+    }
+
+    /// Checks the words and the checksum of a backup phrase.
+    pub fn validate_mnemonic(phrase: &str) -> Result<(), WalletError> {
+        parse_mnemonic(phrase).map(|_| ())
+    }
+
+    /// Restores account `account_index` of a backup phrase (SEP-0005, path
+    /// m/44'/148'/account_index'). The passphrase is the optional BIP-39 one;
+    /// use "" for none.
+    pub fn from_mnemonic(
+        phrase: &str,
+        passphrase: &str,
+        account_index: u32,
+        config: WalletConfig,
+    ) -> Result<Self, WalletError> {
+        let seed = mnemonic_seed(phrase, passphrase)?;
+        let secret_key = derive_secret_key(&seed, account_index)?;
+        Self::new(&secret_key, config)
     }
 
     pub fn public_key(&self) -> String {
         self.public_key.clone()
     }
 
+    pub fn secret_key(&self) -> String {
+        self.secret_key.clone()
+    }
+
     pub async fn get_balances(&self) -> Result<Vec<Balance>, WalletError> {
         let client = reqwest::Client::new();
         let url = format!("{}/accounts/{}", self.config.horizon_url, self.public_key);
@@ -117,6 +162,58 @@ impl NovaPayWallet {
         })
     }
 
+    /// Builds and signs one transaction paying every `(destination, amount)`
+    /// in `asset` (`XLM` or `CODE:ISSUER`), so they all land or none do, but
+    /// doesn't submit it. Returns its hash (hex) and envelope, which can be
+    /// kept and passed to `submit_signed` as often as needed.
+    pub async fn sign_payments(
+        &self,
+        asset: &str,
+        payments: &[(String, String)],
+        memo: Option<&str>,
+    ) -> Result<(String, String), WalletError> {
+        let seed = stellar_strkey::ed25519::PrivateKey::from_string(&self.secret_key)
+            .map_err(|_| WalletError::InvalidKeypair("Placeholder keys can't sign transactions".to_string()))?;
+        let operations: Vec<StellarOperationRequest> = payments
+            .iter()
+            .map(|(destination, amount)| StellarOperationRequest::Payment {
+                destination: destination.clone(),
+                asset: asset.to_string(),
+                amount: amount.clone(),
+            })
+            .collect();
+
+        let network = StellarNetwork::from_env();
+        let timeout: i64 = env::var("STELLAR_TX_TIMEOUT_SECS").ok().and_then(|v| v.parse().ok()).unwrap_or(300);
+        let max_time = (chrono::Utc::now() + chrono::Duration::seconds(timeout)).timestamp() as u64;
+
+        let sequence = network.sequence(&self.public_key).await.map_err(submission_error)?;
+        let tx = StellarTxBuilder::build_many(&self.public_key, sequence, &operations, memo, max_time)
+            .map_err(submission_error)?;
+        let hash = network.hash(&tx).map_err(submission_error)?;
+        let signing_key = SigningKey::from_bytes(&seed.0);
+        let signature = signing_key.sign(&hash);
+        let key = signing_key.verifying_key().to_bytes();
+        let envelope = StellarTxBuilder::signed_envelope_xdr(
+            &tx,
+            &[([key[28], key[29], key[30], key[31]], signature.to_bytes().to_vec())],
+        )
+        .map_err(submission_error)?;
+
+        Ok((hex::encode(hash), envelope))
+    }
+
+    /// Submits a transaction from `sign_payments`, or returns its hash if an
+    /// earlier submission already landed. A `NetworkError` leaves the outcome
+    /// unknown; calling this again with the same envelope can't pay twice. A
+    /// rejection carries Horizon's result codes as JSON.
+    pub async fn submit_signed(hash: &str, envelope: &str) -> Result<String, WalletError> {
+        StellarNetwork::from_env()
+            .resubmit(hash, envelope)
+            .await
+            .map_err(submission_error)
+    }
+
     pub async fn fund_testnet(&self) -> Result<bool, WalletError> {
         let client = reqwest::Client::new();
         let url = format!("https://friendbot.stellar.org?addr={}", self.public_key);
@@ -163,20 +260,135 @@ impl WalletManager {
     pub fn get_wallet(&self, id: &str) -> Option<&NovaPayWallet> {
         self.wallets.get(id)
     }
+}
 
-    pub async fn bulk_payment(
-        &self,
-        wallet_id: &str,
-        payments: Vec<(String, String)>,
-    ) -> Result<Vec<PaymentResult>, WalletError> {
-        let wallet = self.wallets.get(wallet_id)
-            .ok_or_else(|| WalletError::TransactionFailed("Wallet not found".to_string()))?;
-
-        let mut results = Vec::new();
-        for (destination, amount) in payments {
-            let result = wallet.send_payment(&destination, &amount, None).await?;
-            results.push(result);
+/// Network trouble is worth retrying; anything else failed the transaction.
+fn submission_error(error: StellarTxError) -> WalletError {
+    match error {
+        StellarTxError::Horizon(message) => WalletError::NetworkError(message),
+        StellarTxError::Rejected(codes) => WalletError::TransactionFailed(codes),
+        e => WalletError::TransactionFailed(e.to_string()),
+    }
+}
+
+fn parse_mnemonic(phrase: &str) -> Result<Mnemonic, WalletError> {
+    Mnemonic::parse(phrase.trim().to_lowercase()).map_err(|e| WalletError::InvalidMnemonic(e.to_string()))
+}
+
+/// The 64-byte BIP-39 seed of a backup phrase.
+fn mnemonic_seed(phrase: &str, passphrase: &str) -> Result<[u8; 64], WalletError> {
+    Ok(parse_mnemonic(phrase)?.to_seed(passphrase))
+}
+
+/// SLIP-0010 ed25519 derivation of m/44'/148'/account_index', as an `S...`
+/// secret key.
+fn derive_secret_key(seed: &[u8; 64], account_index: u32) -> Result<String, WalletError> {
+    if account_index >= HARDENED {
+        return Err(WalletError::InvalidKeypair("Account index out of range".to_string()));
+    }
+
+    let mut node = hmac_sha512(b"ed25519 seed", &[seed]);
+    for index in SEP5_PATH.into_iter().chain([account_index]) {
+        node = hmac_sha512(&node[32..], &[&[0], &node[..32], &(index | HARDENED).to_be_bytes()]);
+    }
+
+    let mut key = [0u8; 32];
+    key.copy_from_slice(&node[..32]);
+    Ok(secret_key_for(&key))
+}
+
+fn hmac_sha512(key: &[u8], parts: &[&[u8]]) -> [u8; 64] {
+    let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("HMAC takes keys of any length");
+    for part in parts {
+        mac.update(part);
+    }
+    mac.finalize().into_bytes().into()
+}
+
+fn public_key_for(seed: &[u8; 32]) -> String {
+    let public_key = SigningKey::from_bytes(seed).verifying_key().to_bytes();
+    stellar_strkey::ed25519::PublicKey(public_key).to_string()
+}
+
+fn secret_key_for(seed: &[u8; 32]) -> String {
+    stellar_strkey::ed25519::PrivateKey(*seed).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> WalletConfig {
+        WalletConfig {
+            network: "testnet".to_string(),
+            horizon_url: "https://horizon-testnet.stellar.org".to_string(),
         }
-        Ok(results)
     }
-}
\ No newline at end of file
+
+    // Test vectors from SEP-0005
+    const TWELVE_WORDS: &str = "illness spike retreat truth genius clock brain pass fit cave bargain toe";
+    const TWENTY_FOUR_WORDS: &str = "bench hurt jump file august wise shallow faculty impulse spring exact slush \
+        thunder author capable act festival slice deposit sauce coconut afford frown better";
+    const WITH_PASSPHRASE: &str = "cable spray genius state float twenty onion head street palace net private \
+        method loan turn phrase state blanket interest dry amazing dress blast tube";
+
+    #[test]
+    fn test_sep5_twelve_words() {
+        let seed = mnemonic_seed(TWELVE_WORDS, "").unwrap();
+        assert_eq!(
+            hex::encode(seed),
+            "e4a5a632e70943ae7f07659df1332160937fad82587216a4c64315a0fb39497ee4a01f76ddab4cba68147977f3a147b6ad584c41808e8238a07f6cc4b582f186"
+        );
+
+        let expected = [
+            ("GDRXE2BQUC3AZNPVFSCEZ76NJ3WWL25FYFK6RGZGIEKWE4SOOHSUJUJ6", "SBGWSG6BTNCKCOB3DIFBGCVMUPQFYPA2G4O34RMTB343OYPXU5DJDVMN"),
+            ("GBAW5XGWORWVFE2XTJYDTLDHXTY2Q2MO73HYCGB3XMFMQ562Q2W2GJQX", "SCEPFFWGAG5P2VX5DHIYK3XEMZYLTYWIPWYEKXFHSK25RVMIUNJ7CTIS"),
+            ("GAY5PRAHJ2HIYBYCLZXTHID6SPVELOOYH2LBPH3LD4RUMXUW3DOYTLXW", "SDAILLEZCSA67DUEP3XUPZJ7NYG7KGVRM46XA7K5QWWUIGADUZCZWTJP"),
+        ];
+        for (index, (public_key, secret_key)) in expected.iter().enumerate() {
+            assert_eq!(derive_secret_key(&seed, index as u32).unwrap(), *secret_key);
+            let wallet = NovaPayWallet::from_mnemonic(TWELVE_WORDS, "", index as u32, config()).unwrap();
+            assert_eq!(wallet.public_key(), *public_key);
+        }
+    }
+
+    #[test]
+    fn test_sep5_twenty_four_words() {
+        let wallet = NovaPayWallet::from_mnemonic(TWENTY_FOUR_WORDS, "", 0, config()).unwrap();
+        assert_eq!(wallet.public_key(), "GC3MMSXBWHL6CPOAVERSJITX7BH76YU252WGLUOM5CJX3E7UCYZBTPJQ");
+        let wallet = NovaPayWallet::from_mnemonic(TWENTY_FOUR_WORDS, "", 1, config()).unwrap();
+        assert_eq!(wallet.public_key(), "GB3MTYFXPBZBUINVG72XR7AQ6P2I32CYSXWNRKJ2PV5H5C7EAM5YYISO");
+    }
+
+    #[test]
+    fn test_sep5_passphrase() {
+        let seed = mnemonic_seed(WITH_PASSPHRASE, "p4ssphr4se").unwrap();
+        assert_eq!(
+            derive_secret_key(&seed, 0).unwrap(),
+            "SAFWTGXVS7ELMNCXELFWCFZOPMHUZ5LXNBGUVRCY3FHLFPXK4QPXYP2X"
+        );
+        let wallet = NovaPayWallet::from_mnemonic(WITH_PASSPHRASE, "p4ssphr4se", 0, config()).unwrap();
+        assert_eq!(wallet.public_key(), "GDAHPZ2NSYIIHZXM56Y36SBVTV5QKFIZGYMMBHOU53ETUSWTP62B63EQ");
+    }
+
+    #[test]
+    fn test_mnemonic_validation() {
+        let phrase = NovaPayWallet::generate_mnemonic(24).unwrap();
+        assert_eq!(phrase.split_whitespace().count(), 24);
+        assert!(NovaPayWallet::validate_mnemonic(&phrase).is_ok());
+        assert!(NovaPayWallet::validate_mnemonic(&TWELVE_WORDS.to_uppercase()).is_ok());
+
+        // Last word swapped, so the checksum no longer matches
+        let bad_checksum = TWELVE_WORDS.replace("toe", "zoo");
+        assert!(NovaPayWallet::validate_mnemonic(&bad_checksum).is_err());
+        assert!(NovaPayWallet::validate_mnemonic("illness spike retreat").is_err());
+        assert!(NovaPayWallet::generate_mnemonic(15).is_err());
+    }
+
+    #[test]
+    fn test_generated_keys_round_trip() {
+        let (public_key, secret_key) = NovaPayWallet::generate();
+        let wallet = NovaPayWallet::new(&secret_key, config()).unwrap();
+        assert_eq!(wallet.public_key(), public_key);
+    }
+}
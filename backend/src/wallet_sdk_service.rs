@@ -49,6 +49,10 @@ impl WalletSDKService {
         }
     }
 
+    pub fn config(&self) -> WalletConfig {
+        self.config.clone()
+    }
+
     pub fn create_wallet(&self) -> WalletCreateResponse {
         let (public_key, secret_key) = NovaPayWallet::generate();
         let wallet_id = uuid::Uuid::new_v4().to_string();
@@ -60,6 +64,31 @@ impl WalletSDKService {
         }
     }
 
+    /// A wallet backed by a new 12-word backup phrase, which is returned
+    /// alongside the keys. The wallet is account 0 of the phrase.
+    pub fn create_wallet_with_mnemonic(&self) -> Result<(WalletCreateResponse, String), WalletError> {
+        let mnemonic = NovaPayWallet::generate_mnemonic(12)?;
+        let wallet = self.restore_wallet(&mnemonic, "", 0)?;
+        Ok((wallet, mnemonic))
+    }
+
+    /// Recreates the keys of account `account_index` of a backup phrase, under
+    /// a new wallet id.
+    pub fn restore_wallet(
+        &self,
+        mnemonic: &str,
+        passphrase: &str,
+        account_index: u32,
+    ) -> Result<WalletCreateResponse, WalletError> {
+        let wallet = NovaPayWallet::from_mnemonic(mnemonic, passphrase, account_index, self.config.clone())?;
+
+        Ok(WalletCreateResponse {
+            public_key: wallet.public_key(),
+            secret_key: wallet.secret_key(),
+            wallet_id: uuid::Uuid::new_v4().to_string(),
+        })
+    }
+
     pub async fn get_wallet_balance(&self, secret_key: &str) -> Result<WalletBalanceResponse, WalletError> {
         let wallet = NovaPayWallet::new(secret_key, self.config.clone())?;
         let balances = wallet.get_balances().await?;
@@ -23,10 +23,10 @@ WALLET_RESPONSE=$(curl -s -X POST "$BASE_URL/sdk/wallet/create" \
 
 echo "Wallet Response: $WALLET_RESPONSE"
 
-SECRET_KEY=$(echo $WALLET_RESPONSE | grep -o '"secret_key":"[^"]*' | cut -d'"' -f4)
+WALLET_ID=$(echo $WALLET_RESPONSE | grep -o '"wallet_id":"[^"]*' | cut -d'"' -f4)
 PUBLIC_KEY=$(echo $WALLET_RESPONSE | grep -o '"public_key":"[^"]*' | cut -d'"' -f4)
 
-echo "Secret Key: $SECRET_KEY"
+echo "Wallet ID: $WALLET_ID"
 echo "Public Key: $PUBLIC_KEY"
 
 # 3. Fund testnet account
@@ -34,7 +34,7 @@ echo -e "\n3. Funding testnet account..."
 FUND_RESPONSE=$(curl -s -X POST "$BASE_URL/sdk/wallet/fund-testnet" \
   -H "Content-Type: application/json" \
   -H "Authorization: Bearer $TOKEN" \
-  -d "{\"secret_key\": \"$SECRET_KEY\"}")
+  -d "{\"wallet_id\": \"$WALLET_ID\"}")
 
 echo "Fund Response: $FUND_RESPONSE"
 
@@ -43,20 +43,26 @@ echo -e "\n4. Getting wallet balance..."
 BALANCE_RESPONSE=$(curl -s -X POST "$BASE_URL/sdk/wallet/balance" \
   -H "Content-Type: application/json" \
   -H "Authorization: Bearer $TOKEN" \
-  -d "{\"secret_key\": \"$SECRET_KEY\"}")
+  -d "{\"wallet_id\": \"$WALLET_ID\"}")
 
 echo "Balance Response: $BALANCE_RESPONSE"
 
-# 5. Send payment (to self for testing)
+# 5. Send payment (to self for testing); payments need a transaction PIN
 echo -e "\n5. Sending test payment..."
+curl -s -X POST "$BASE_URL/user/pin" \
+  -H "Content-Type: application/json" \
+  -H "Authorization: Bearer $TOKEN" \
+  -d '{"pin": "4821"}' > /dev/null
+
 PAYMENT_RESPONSE=$(curl -s -X POST "$BASE_URL/sdk/wallet/send" \
   -H "Content-Type: application/json" \
   -H "Authorization: Bearer $TOKEN" \
   -d "{
-    \"secret_key\": \"$SECRET_KEY\",
+    \"wallet_id\": \"$WALLET_ID\",
     \"destination\": \"$PUBLIC_KEY\",
     \"amount\": \"1.0\",
-    \"memo\": \"Test payment\"
+    \"memo\": \"Test payment\",
+    \"pin\": \"4821\"
   }")
 
 echo "Payment Response: $PAYMENT_RESPONSE"
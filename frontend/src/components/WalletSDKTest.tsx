@@ -8,6 +8,7 @@ const WalletSDKTest: React.FC = () => {
   const [balance, setBalance] = useState<any>(null);
   const [loading, setLoading] = useState(false);
   const [logs, setLogs] = useState<string[]>([]);
+  const [pin, setPin] = useState('');
 
   const addLog = (message: string) => {
     setLogs(prev => [...prev, `${new Date().toLocaleTimeString()}: ${message}`]);
@@ -29,7 +30,7 @@ const WalletSDKTest: React.FC = () => {
     if (!wallet) return;
     setLoading(true);
     try {
-      const result = await walletSDK.fundTestnetAccount(wallet.secret_key);
+      const result = await walletSDK.fundTestnetAccount(wallet.wallet_id);
       addLog(`✅ Testnet funding: ${result.success ? 'Success' : 'Failed'}`);
     } catch (error) {
       addLog(`❌ Failed to fund account: ${error}`);
@@ -41,7 +42,7 @@ const WalletSDKTest: React.FC = () => {
     if (!wallet) return;
     setLoading(true);
     try {
-      const balanceData = await walletSDK.getWalletBalance(wallet.secret_key);
+      const balanceData = await walletSDK.getWalletBalance(wallet.wallet_id);
       setBalance(balanceData);
       addLog(`✅ Balance loaded: ${balanceData.balances[0]?.balance || '0'} XLM`);
     } catch (error) {
@@ -51,13 +52,14 @@ const WalletSDKTest: React.FC = () => {
   };
 
   const testSendPayment = async () => {
-    if (!wallet) return;
+    if (!wallet || !pin) return;
     setLoading(true);
     try {
-      const result = await walletSDK.sendPayment(wallet.secret_key, {
+      const result = await walletSDK.sendPayment(wallet.wallet_id, {
         destination: wallet.public_key, // Send to self
         amount: '1.0',
-        memo: 'SDK Test Payment'
+        memo: 'SDK Test Payment',
+        pin
       });
       addLog(`✅ Payment sent: ${result.transaction_hash.substring(0, 10)}...`);
     } catch (error) {
@@ -81,15 +83,24 @@ const WalletSDKTest: React.FC = () => {
           <Button onClick={testGetBalance} disabled={loading || !wallet}>
             3. Get Balance
           </Button>
-          <Button onClick={testSendPayment} disabled={loading || !wallet}>
+          <Button onClick={testSendPayment} disabled={loading || !wallet || !pin}>
             4. Send Payment
           </Button>
         </div>
 
+        <input
+          type="password"
+          inputMode="numeric"
+          placeholder="Transaction PIN (for payments)"
+          value={pin}
+          onChange={e => setPin(e.target.value)}
+          className="w-full border rounded p-2 text-sm"
+        />
+
         {wallet && (
           <div className="bg-gray-50 p-3 rounded text-sm">
             <div><strong>Public Key:</strong> {wallet.public_key}</div>
-            <div><strong>Secret Key:</strong> {wallet.secret_key.substring(0, 10)}...</div>
+            <div><strong>Wallet ID:</strong> {wallet.wallet_id}</div>
           </div>
         )}
 
@@ -1,9 +1,32 @@
 const API_BASE_URL = 'http://localhost:3000';
 
-interface WalletCreateResponse {
+interface CustodialWallet {
+  wallet_id: string;
   public_key: string;
-  secret_key: string;
+  label: string | null;
+  exportable: boolean;
+  exported_at: string | null;
+  created_at: string;
+  backup_phrase?: string;
+}
+
+interface CreateWalletOptions {
+  label?: string;
+  exportable?: boolean;
+  backup_phrase?: boolean;
+}
+
+interface RestoreWalletOptions {
+  passphrase?: string;
+  account_index?: number;
+  label?: string;
+  exportable?: boolean;
+}
+
+interface ExportedWalletKey {
   wallet_id: string;
+  public_key: string;
+  secret_key: string;
 }
 
 interface Balance {
@@ -13,6 +36,7 @@ interface Balance {
 }
 
 interface WalletBalanceResponse {
+  wallet_id: string;
   balances: Balance[];
   public_key: string;
 }
@@ -29,6 +53,7 @@ interface SendPaymentRequest {
   amount: string;
   asset_code?: string;
   memo?: string;
+  pin: string;
 }
 
 class WalletSDK {
@@ -37,34 +62,58 @@ class WalletSDK {
     return token ? { 'Authorization': `Bearer ${token}` } : {};
   }
 
-  async createWallet(): Promise<WalletCreateResponse> {
+  async createWallet(options: CreateWalletOptions = {}): Promise<CustodialWallet> {
     const response = await fetch(`${API_BASE_URL}/sdk/wallet/create`, {
       method: 'POST',
       headers: {
         'Content-Type': 'application/json',
         ...this.getAuthHeaders()
-      }
+      },
+      body: JSON.stringify(options)
     });
     
     if (!response.ok) throw new Error('Failed to create wallet');
     return response.json();
   }
 
-  async getWalletBalance(secretKey: string): Promise<WalletBalanceResponse> {
+  async restoreWallet(mnemonic: string, options: RestoreWalletOptions = {}): Promise<CustodialWallet> {
+    const response = await fetch(`${API_BASE_URL}/sdk/wallet/restore`, {
+      method: 'POST',
+      headers: {
+        'Content-Type': 'application/json',
+        ...this.getAuthHeaders()
+      },
+      body: JSON.stringify({ mnemonic, ...options })
+    });
+    
+    if (!response.ok) throw new Error('Failed to restore wallet');
+    return response.json();
+  }
+
+  async listWallets(): Promise<CustodialWallet[]> {
+    const response = await fetch(`${API_BASE_URL}/sdk/wallet`, {
+      headers: this.getAuthHeaders()
+    });
+    
+    if (!response.ok) throw new Error('Failed to list wallets');
+    return response.json();
+  }
+
+  async getWalletBalance(walletId: string): Promise<WalletBalanceResponse> {
     const response = await fetch(`${API_BASE_URL}/sdk/wallet/balance`, {
       method: 'POST',
       headers: {
         'Content-Type': 'application/json',
         ...this.getAuthHeaders()
       },
-      body: JSON.stringify({ secret_key: secretKey })
+      body: JSON.stringify({ wallet_id: walletId })
     });
     
     if (!response.ok) throw new Error('Failed to get wallet balance');
     return response.json();
   }
 
-  async sendPayment(secretKey: string, payment: SendPaymentRequest): Promise<PaymentResult> {
+  async sendPayment(walletId: string, payment: SendPaymentRequest): Promise<PaymentResult> {
     const response = await fetch(`${API_BASE_URL}/sdk/wallet/send`, {
       method: 'POST',
       headers: {
@@ -72,7 +121,7 @@ class WalletSDK {
         ...this.getAuthHeaders()
       },
       body: JSON.stringify({
-        secret_key: secretKey,
+        wallet_id: walletId,
         ...payment
       })
     });
@@ -81,14 +130,14 @@ class WalletSDK {
     return response.json();
   }
 
-  async fundTestnetAccount(secretKey: string): Promise<{ success: boolean; message: string }> {
+  async fundTestnetAccount(walletId: string): Promise<{ success: boolean; message: string }> {
     const response = await fetch(`${API_BASE_URL}/sdk/wallet/fund-testnet`, {
       method: 'POST',
       headers: {
         'Content-Type': 'application/json',
         ...this.getAuthHeaders()
       },
-      body: JSON.stringify({ secret_key: secretKey })
+      body: JSON.stringify({ wallet_id: walletId })
     });
     
     if (!response.ok) throw new Error('Failed to fund testnet account');
@@ -96,7 +145,7 @@ class WalletSDK {
   }
 
   async createTrustline(
-    secretKey: string,
+    walletId: string,
     assetCode: string,
     issuer: string,
     limit?: string
@@ -108,7 +157,7 @@ class WalletSDK {
         ...this.getAuthHeaders()
       },
       body: JSON.stringify({
-        secret_key: secretKey,
+        wallet_id: walletId,
         asset_code: assetCode,
         issuer,
         limit
@@ -119,6 +168,23 @@ class WalletSDK {
     return response.json();
   }
 
+  // Only for wallets created with `exportable: true`; needs a
+  // `secret_export` step-up token for this wallet id
+  async exportWalletKey(walletId: string, pin: string, stepUpToken: string): Promise<ExportedWalletKey> {
+    const response = await fetch(`${API_BASE_URL}/sdk/wallet/export`, {
+      method: 'POST',
+      headers: {
+        'Content-Type': 'application/json',
+        'X-Step-Up-Token': stepUpToken,
+        ...this.getAuthHeaders()
+      },
+      body: JSON.stringify({ wallet_id: walletId, pin })
+    });
+    
+    if (!response.ok) throw new Error('Failed to export wallet key');
+    return response.json();
+  }
+
   validateStellarAddress(address: string): boolean {
     return address.length === 56 && address.startsWith('G');
   }
@@ -141,4 +207,4 @@ class WalletSDK {
 }
 
 export const walletSDK = new WalletSDK();
-export type { WalletCreateResponse, Balance, WalletBalanceResponse, PaymentResult, SendPaymentRequest };
\ No newline at end of file
+export type { CustodialWallet, CreateWalletOptions, ExportedWalletKey, Balance, WalletBalanceResponse, PaymentResult, SendPaymentRequest };
\ No newline at end of file
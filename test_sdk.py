@@ -36,7 +36,7 @@ def test_wallet_sdk():
         if response.status_code == 200:
             wallet = response.json()
             print(f"✅ Wallet created: {wallet['public_key'][:10]}...")
-            secret_key = wallet["secret_key"]
+            wallet_id = wallet["wallet_id"]
         else:
             print(f"❌ Wallet creation failed: {response.status_code}")
             return
@@ -50,7 +50,7 @@ def test_wallet_sdk():
         response = requests.post(
             f"{BASE_URL}/sdk/wallet/fund-testnet",
             headers=headers,
-            json={"secret_key": secret_key}
+            json={"wallet_id": wallet_id}
         )
         if response.status_code == 200:
             result = response.json()
@@ -66,7 +66,7 @@ def test_wallet_sdk():
         response = requests.post(
             f"{BASE_URL}/sdk/wallet/balance",
             headers=headers,
-            json={"secret_key": secret_key}
+            json={"wallet_id": wallet_id}
         )
         if response.status_code == 200:
             balance = response.json()